@@ -23,6 +23,8 @@ pub struct Client {
 
     create_time: SystemTime,
     last_interaction: SystemTime,
+    // protocol version selected by HELLO
+    resp: u8,
 
     kill_tx: Sender<()>,
 }
@@ -39,6 +41,7 @@ impl Client {
             peer_addr: socket.peer_addr().unwrap().to_string(),
             create_time: now,
             last_interaction: now,
+            resp: 2,
             kill_tx,
         }
     }
@@ -78,6 +81,10 @@ impl Client {
     pub fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
     }
+
+    pub fn set_resp(&mut self, resp: u8) {
+        self.resp = resp;
+    }
 }
 
 impl fmt::Display for Client {
@@ -86,7 +93,7 @@ impl fmt::Display for Client {
             f,
             "id={} addr={} laddr={} fd={} name={} age={} idle={} flags=N \
             db=0 sub=0 psub=0 multi=-1 qbuf=0 qbuf-free=0 argv-mem=10 obl=0 oll=0 omem=0 \
-            tot-mem=0 events=r cmd={} user=default redir=-1",
+            tot-mem=0 events=r cmd={} user=default redir=-1 resp={}",
             self.id,
             self.peer_addr,
             self.local_addr,
@@ -94,7 +101,8 @@ impl fmt::Display for Client {
             self.name,
             self.age(),
             self.idle(),
-            self.cmd
+            self.cmd,
+            self.resp
         )
     }
 }
@@ -1,16 +1,49 @@
-use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex, RwLock};
 
+use async_std::net::TcpStream;
+use bytes::Bytes;
+use crc::{Crc, CRC_16_XMODEM};
+use futures::future::join_all;
 use hex::ToHex;
 use sha1::{Digest, Sha1};
+use slog::warn;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
 
+use crate::config::LOGGER;
 use crate::{
+    config_password,
     utils::{resp_array, resp_bulk, resp_int},
-    Frame,
+    Connection, Frame,
 };
 
+const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+
+// PUBLISH is not held up by a slow or unreachable instance for longer than this
+const PEER_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// connection to another instance, it is reconnected if the last request failed
+type PeerConnection = Arc<Mutex<Option<Connection>>>;
+
+/// Hash slot of the key as redis cluster computes it, only the part inside the
+/// first `{...}` is hashed if it is not empty
+pub fn key_slot(key: &[u8]) -> usize {
+    let hashed = match key.iter().position(|c| *c == b'{') {
+        Some(start) => match key[start + 1..].iter().position(|c| *c == b'}') {
+            Some(len) if len > 0 => &key[start + 1..start + 1 + len],
+            _ => key,
+        },
+        None => key,
+    };
+    (CRC16.checksum(hashed) & 0x3FFF) as usize
+}
+
 #[derive(Debug, Clone)]
 pub struct Cluster {
     nodes: Arc<RwLock<Vec<Node>>>,
+    /// connections to the other instances by address, used to fan out PUBLISH
+    peers: Arc<StdMutex<HashMap<String, PeerConnection>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -28,6 +61,7 @@ impl Cluster {
     pub fn new(nodes: &[Node]) -> Cluster {
         Cluster {
             nodes: Arc::new(RwLock::new(nodes.to_owned().to_vec())),
+            peers: Arc::new(StdMutex::new(HashMap::new())),
         }
     }
 
@@ -94,6 +128,12 @@ impl Cluster {
         let mut nodes_guard = self.nodes.write().unwrap();
         nodes_guard.clear();
         nodes_guard.append(&mut nodes);
+
+        // close the connections to the instances which left the cluster
+        self.peers
+            .lock()
+            .unwrap()
+            .retain(|addr, _| addrs.contains(addr));
     }
 
     pub fn cluster_member_changed(&self, addrs: &[String]) -> bool {
@@ -187,4 +227,114 @@ impl Cluster {
             .unwrap();
         (myself.slot_start, myself.slot_end)
     }
+
+    pub fn owns_slot(&self, slot: usize) -> bool {
+        let (slot_start, slot_end) = self.myself_owned_slots();
+        slot >= slot_start && slot <= slot_end
+    }
+
+    /// Returns the MOVED error redirecting to the instance owning the slot, or None
+    /// if the slot is owned by this instance
+    pub fn moved_error(&self, slot: usize) -> Option<Frame> {
+        let nodes_guard = self.nodes.read().unwrap();
+        nodes_guard
+            .iter()
+            .find(|node| node.slot_start <= slot && slot <= node.slot_end)
+            .filter(|node| node.flags.is_none())
+            .map(|node| Frame::ErrorOwned(format!("MOVED {} {}:{}", slot, node.ip, node.port)))
+    }
+
+    fn peer_addrs(&self) -> Vec<String> {
+        let nodes_guard = self.nodes.read().unwrap();
+        nodes_guard
+            .iter()
+            .filter(|node| node.flags.is_none())
+            .map(|node| format!("{}:{}", node.ip, node.port))
+            .collect()
+    }
+
+    async fn connect_peer(addr: &str) -> crate::Result<Connection> {
+        let socket = TcpStream::connect(addr).await?;
+        let mut conn = Connection::new(socket);
+        // instances of a cluster share the same password
+        if let Some(password) = config_password() {
+            let auth = resp_array(vec![
+                resp_bulk(b"auth".to_vec()),
+                resp_bulk(password.into_bytes()),
+            ]);
+            conn.write_frame(&auth).await?;
+            match conn.read_frame().await? {
+                Some(Frame::Simple(_)) => {}
+                reply => return Err(format!("auth failed, {:?}", reply).into()),
+            }
+        }
+        Ok(conn)
+    }
+
+    async fn request_peer(&self, addr: &str, request: &Frame) -> crate::Result<usize> {
+        let peer = self
+            .peers
+            .lock()
+            .unwrap()
+            .entry(addr.to_owned())
+            .or_default()
+            .clone();
+        let mut peer = peer.lock().await;
+        // the connection is only put back after a complete request, so it is dropped
+        // if the request fails or times out in the middle
+        let mut conn = match peer.take() {
+            Some(conn) => conn,
+            None => Self::connect_peer(addr).await?,
+        };
+        conn.write_frame(request).await?;
+        match conn.read_frame().await? {
+            Some(Frame::Integer(n)) => {
+                *peer = Some(conn);
+                Ok(n as usize)
+            }
+            reply => Err(format!("unexpected reply {:?}", reply).into()),
+        }
+    }
+
+    /// Publish the message on all other instances by `CLUSTER PUBLISH`, returns the
+    /// number of clients that received it there. Unreachable instances are skipped.
+    pub async fn publish_to_peers(&self, channel: &str, message: &Bytes) -> usize {
+        let request = resp_array(vec![
+            resp_bulk(b"cluster".to_vec()),
+            resp_bulk(b"publish".to_vec()),
+            resp_bulk(channel.as_bytes().to_vec()),
+            Frame::Bulk(message.clone()),
+        ]);
+        self.request_peers(&request).await
+    }
+
+    /// Invalidate the modified keys for the tracking clients of all other instances
+    /// by `CLUSTER INVALIDATE`, returns the number of clients notified there.
+    pub async fn invalidate_on_peers(&self, keys: &[String]) -> usize {
+        let mut args = vec![
+            resp_bulk(b"cluster".to_vec()),
+            resp_bulk(b"invalidate".to_vec()),
+        ];
+        args.extend(keys.iter().map(|key| resp_bulk(key.as_bytes().to_vec())));
+        self.request_peers(&resp_array(args)).await
+    }
+
+    /// Send the request to all other instances and sum up their integer replies,
+    /// unreachable instances are skipped.
+    async fn request_peers(&self, request: &Frame) -> usize {
+        let requests = self.peer_addrs().into_iter().map(|addr| async move {
+            match time::timeout(PEER_TIMEOUT, self.request_peer(&addr, request)).await {
+                Ok(Ok(n)) => n,
+                Ok(Err(e)) => {
+                    warn!(LOGGER, "request to {} failed, {}", addr, e);
+                    0
+                }
+                Err(_) => {
+                    warn!(LOGGER, "request to {} timed out", addr);
+                    0
+                }
+            }
+        });
+        join_all(requests).await.into_iter().sum()
+    }
 }
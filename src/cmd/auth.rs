@@ -19,10 +19,6 @@ impl Auth {
         &self.passwd
     }
 
-    pub fn valid(&self) -> bool {
-        self.valid
-    }
-
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Auth> {
         let passwd = parse.next_string()?;
 
@@ -40,4 +36,8 @@ impl Invalid for Auth {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse, ParseError};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_INVALID_CAS_EXPIRE_TIME_ERR, REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{now_timestamp_in_millis, resp_err, resp_invalid_arguments, timestamp_from_ttl};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use bytes::Bytes;
+use slog::debug;
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+
+/// Compare the value of `key` with `expected`, set it to `value` if they are equal,
+/// or delete the key if `value` is None.
+///
+/// ```text
+/// CAS key expected value [EX seconds|PX milliseconds]
+/// CAD key expected
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cas {
+    key: String,
+    expected: Bytes,
+    value: Option<Bytes>,
+    /// expire in milliseconds, 0 means no ttl, negative if the time is invalid
+    expire: i64,
+    valid: bool,
+}
+
+impl Cas {
+    pub fn new(key: impl ToString, expected: Bytes, value: Option<Bytes>, expire: i64) -> Cas {
+        Cas {
+            key: key.to_string(),
+            expected,
+            value,
+            expire,
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    // convert the EX or PX option to milliseconds, None on syntax error
+    fn expire_ms(flag: &str, v: i64) -> Option<i64> {
+        let ms = match flag.to_uppercase().as_str() {
+            "EX" => v.checked_mul(1000).unwrap_or(-1),
+            "PX" => v,
+            _ => return None,
+        };
+        Some(if ms > 0 { ms } else { -1 })
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, delete: bool) -> crate::Result<Cas> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let expected = parse.next_bytes()?;
+        if delete {
+            return Ok(Cas::new(key, expected, None, 0));
+        }
+
+        let value = parse.next_bytes()?;
+        let expire = match parse.next_string() {
+            Ok(flag) => match parse.next_int().ok().and_then(|v| Cas::expire_ms(&flag, v)) {
+                Some(ms) => ms,
+                None => return Ok(Cas::new_invalid()),
+            },
+            Err(EndOfStream) => 0,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Cas::new(key, expected, Some(value), expire))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>, delete: bool) -> crate::Result<Cas> {
+        if delete {
+            if argv.len() != 2 {
+                return Ok(Cas::new_invalid());
+            }
+            let key = String::from_utf8_lossy(&argv[0]).to_string();
+            return Ok(Cas::new(key, argv[1].clone(), None, 0));
+        }
+
+        if argv.len() != 3 && argv.len() != 5 {
+            return Ok(Cas::new_invalid());
+        }
+        let key = String::from_utf8_lossy(&argv[0]).to_string();
+        let mut expire = 0;
+        if argv.len() == 5 {
+            let flag = String::from_utf8_lossy(&argv[3]);
+            expire = match String::from_utf8_lossy(&argv[4])
+                .parse::<i64>()
+                .ok()
+                .and_then(|v| Cas::expire_ms(&flag, v))
+            {
+                Some(ms) => ms,
+                None => return Ok(Cas::new_invalid()),
+            };
+        }
+
+        Ok(Cas::new(
+            key,
+            argv[1].clone(),
+            Some(argv[2].clone()),
+            expire,
+        ))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.cas(None).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn cas(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        // the expire time must be positive and not overflow when added to now
+        if self.expire < 0 || self.expire > i64::MAX - now_timestamp_in_millis() as i64 {
+            return Ok(resp_err(REDIS_INVALID_CAS_EXPIRE_TIME_ERR));
+        }
+        if is_use_txn_api() {
+            let ts = if self.expire > 0 {
+                timestamp_from_ttl(self.expire as u64)
+            } else {
+                0
+            };
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_compare_and_swap(&self.key, &self.expected, self.value.as_ref(), ts)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Cas {
+    fn new_invalid() -> Cas {
+        Cas {
+            key: "".to_owned(),
+            expected: Bytes::new(),
+            value: None,
+            expire: 0,
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
@@ -2,13 +2,16 @@ use crate::cluster::Cluster as Topo;
 use crate::cmd::Invalid;
 use crate::config::LOGGER;
 use crate::tikv::errors::REDIS_UNKNOWN_SUBCOMMAND;
-use crate::utils::{resp_err, resp_invalid_arguments};
-use crate::{Connection, Parse};
+use crate::tracking::invalidate_keys;
+use crate::utils::{resp_err, resp_int, resp_invalid_arguments};
+use crate::{Connection, Db, Parse};
+use bytes::Bytes;
 use slog::debug;
 
 #[derive(Debug, Clone)]
 pub struct Cluster {
     subcommand: String,
+    args: Vec<Bytes>,
     valid: bool,
 }
 
@@ -16,17 +19,27 @@ impl Cluster {
     pub fn new(subcommand: impl ToString) -> Cluster {
         Cluster {
             subcommand: subcommand.to_string(),
+            args: vec![],
             valid: true,
         }
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Cluster> {
         let subcommand = parse.next_string()?;
+        let mut cluster = Cluster::new(subcommand);
+        while let Ok(arg) = parse.next_bytes() {
+            cluster.args.push(arg);
+        }
 
-        Ok(Cluster::new(subcommand))
+        Ok(cluster)
     }
 
-    pub(crate) async fn apply(self, topo: &Topo, dst: &mut Connection) -> crate::Result<()> {
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        topo: &Topo,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
         if !self.valid {
             dst.write_frame(&resp_invalid_arguments()).await?;
             return Ok(());
@@ -36,6 +49,21 @@ impl Cluster {
             "INFO" => topo.cluster_info(),
             "SLOTS" => topo.cluster_slots(),
             "NODES" => topo.cluster_nodes(),
+            // sent by the other instances to fan out PUBLISH, only delivered to the
+            // subscribers of this instance
+            "PUBLISH" if self.args.len() == 2 => {
+                let channel = String::from_utf8_lossy(&self.args[0]);
+                resp_int(db.publish(&channel, self.args[1].clone()) as i64)
+            }
+            // sent by the other instances after they modify the keys
+            "INVALIDATE" => {
+                let keys: Vec<String> = self
+                    .args
+                    .iter()
+                    .map(|key| String::from_utf8_lossy(key).into_owned())
+                    .collect();
+                resp_int(invalidate_keys(&keys, None) as i64)
+            }
             _ => resp_err(REDIS_UNKNOWN_SUBCOMMAND),
         };
 
@@ -57,7 +85,12 @@ impl Invalid for Cluster {
     fn new_invalid() -> Cluster {
         Cluster {
             subcommand: "".to_owned(),
+            args: vec![],
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use crate::cmd::Invalid;
+use crate::config::is_use_txn_api;
+use crate::config::LOGGER;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use slog::debug;
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Copy {
+    src: String,
+    dst: String,
+    replace: bool,
+    valid: bool,
+}
+
+impl Copy {
+    pub fn new(src: impl ToString, dst: impl ToString, replace: bool) -> Copy {
+        Copy {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            replace,
+            valid: true,
+        }
+    }
+
+    /// Get the source key
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// Get the destination key
+    pub fn dst(&self) -> &str {
+        &self.dst
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Copy> {
+        let src = parse.next_string()?;
+        let dst = parse.next_string()?;
+        let mut replace = false;
+
+        while let Ok(flag) = parse.next_string() {
+            match flag.to_uppercase().as_str() {
+                "REPLACE" => replace = true,
+                // only the default db is available
+                "DB" => {
+                    if parse.next_int()? != 0 {
+                        return Ok(Copy::new_invalid());
+                    }
+                }
+                _ => return Ok(Copy::new_invalid()),
+            }
+        }
+
+        Ok(Copy::new(src, dst, replace))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Copy> {
+        if argv.len() < 2 {
+            return Ok(Copy::new_invalid());
+        }
+        let mut replace = false;
+        let mut idx = 2;
+        while idx < argv.len() {
+            match argv[idx].to_ascii_uppercase().as_slice() {
+                b"REPLACE" => replace = true,
+                b"DB" => {
+                    idx += 1;
+                    if idx >= argv.len() || argv[idx] != "0" {
+                        return Ok(Copy::new_invalid());
+                    }
+                }
+                _ => return Ok(Copy::new_invalid()),
+            }
+            idx += 1;
+        }
+        Ok(Copy::new(
+            String::from_utf8_lossy(&argv[0]),
+            String::from_utf8_lossy(&argv[1]),
+            replace,
+        ))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.copy(None).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn copy(self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_copy(&self.src, &self.dst, self.replace)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Copy {
+    fn new_invalid() -> Copy {
+        Copy {
+            src: "".to_owned(),
+            dst: "".to_owned(),
+            replace: false,
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
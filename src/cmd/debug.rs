@@ -1,6 +1,7 @@
 use crate::cmd::Invalid;
-use crate::config::LOGGER;
+use crate::config::{is_use_txn_api, LOGGER};
 use crate::tikv::errors::REDIS_NOT_SUPPORTED_DEBUG_SUB_COMMAND_ERR;
+use crate::tikv::string::StringCommandCtx;
 use crate::tikv::{start_profiler, stop_profiler};
 use crate::utils::{resp_err, resp_invalid_arguments, resp_ok};
 use crate::{Connection, Parse};
@@ -9,6 +10,7 @@ use slog::debug;
 #[derive(Debug, Clone)]
 pub struct Debug {
     subcommand: String,
+    args: Vec<String>,
     valid: bool,
 }
 
@@ -16,14 +18,19 @@ impl Debug {
     pub fn new(subcommand: impl ToString) -> Debug {
         Debug {
             subcommand: subcommand.to_string(),
+            args: vec![],
             valid: true,
         }
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Debug> {
         let subcommand = parse.next_string()?;
+        let mut debug = Debug::new(subcommand);
+        while let Ok(arg) = parse.next_string() {
+            debug.args.push(arg);
+        }
 
-        Ok(Debug::new(subcommand))
+        Ok(debug)
     }
 
     pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
@@ -41,6 +48,11 @@ impl Debug {
                 stop_profiler();
                 resp_ok()
             }
+            // number of keys stored in tikv for the user key, including the expired ones
+            "rawkeys" if self.args.len() == 1 && is_use_txn_api() => StringCommandCtx::new(None)
+                .do_async_txnkv_raw_key_count(&self.args[0])
+                .await
+                .unwrap_or_else(Into::into),
             _ => resp_err(REDIS_NOT_SUPPORTED_DEBUG_SUB_COMMAND_ERR),
         };
 
@@ -62,7 +74,12 @@ impl Invalid for Debug {
     fn new_invalid() -> Debug {
         Debug {
             subcommand: "".to_owned(),
+            args: vec![],
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
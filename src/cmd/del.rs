@@ -101,4 +101,8 @@ impl Invalid for Del {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
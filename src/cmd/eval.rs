@@ -2,14 +2,17 @@ use std::sync::Arc;
 
 use crate::config::is_use_txn_api;
 use crate::db::Db;
+use crate::notify::PendingEvents;
 use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
 use crate::tikv::get_txn_client;
+use crate::tikv::ldb::{run_session, LdbMode};
 use crate::tikv::lua::LuaCommandCtx;
 use crate::utils::{resp_err, resp_invalid_arguments};
 use crate::{Connection, Frame, Parse};
 
 use bytes::Bytes;
 use mlua::Lua;
+use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 use crate::cmd::Invalid;
@@ -79,7 +82,7 @@ impl Eval {
         db: &Db,
         lua: &Option<Lua>,
     ) -> crate::Result<()> {
-        let response = self.eval(is_sha, db, lua).await?;
+        let response = self.eval(is_sha, db, lua, None).await?;
 
         debug!(
             LOGGER,
@@ -94,7 +97,30 @@ impl Eval {
         Ok(())
     }
 
-    async fn eval(&self, is_sha: bool, db: &Db, lua: &Option<Lua>) -> AsyncResult<Frame> {
+    /// Run the script in the debugging session of SCRIPT DEBUG
+    pub(crate) async fn debug(&self, dst: &mut Connection, mode: LdbMode) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        if !is_use_txn_api() {
+            dst.write_frame(&resp_err(REDIS_NOT_SUPPORTED_ERR)).await?;
+            return Ok(());
+        }
+
+        run_session(dst, &self.script, &self.keys, &self.args, mode).await
+    }
+
+    /// Run the script in `txn` if given, such as in MULTI, otherwise in a new txn which is
+    /// committed if the script succeeds.
+    pub(crate) async fn eval(
+        &self,
+        is_sha: bool,
+        db: &Db,
+        lua: &Option<Lua>,
+        txn: Option<Arc<Mutex<Transaction>>>,
+    ) -> AsyncResult<Frame> {
         if !self.valid {
             return Ok(resp_invalid_arguments());
         }
@@ -103,6 +129,19 @@ impl Eval {
             return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
         }
 
+        // the error of the script is the reply, the outer txn goes on
+        if let Some(txn_rc) = txn {
+            let ctx = LuaCommandCtx::new(Some(txn_rc), lua);
+            let resp = if is_sha {
+                ctx.do_async_evalsha(&self.script, db, &self.keys, &self.args)
+                    .await
+            } else {
+                ctx.do_async_eval(&self.script, db, &self.keys, &self.args)
+                    .await
+            };
+            return Ok(resp.unwrap_or_else(resp_err));
+        }
+
         // create new txn
         let client = get_txn_client()?;
         let txn = client.begin().await?;
@@ -110,16 +149,21 @@ impl Eval {
 
         let ctx = LuaCommandCtx::new(Some(txn_rc.clone()), lua);
 
+        // keyspace events are published only if the txn commits
+        let events = PendingEvents::default();
         let resp = if is_sha {
-            ctx.do_async_evalsha(&self.script, db, &self.keys, &self.args)
+            events
+                .defer(ctx.do_async_evalsha(&self.script, db, &self.keys, &self.args))
                 .await
         } else {
-            ctx.do_async_eval(&self.script, db, &self.keys, &self.args)
+            events
+                .defer(ctx.do_async_eval(&self.script, db, &self.keys, &self.args))
                 .await
         };
         match resp {
             Ok(r) => {
                 txn_rc.lock().await.commit().await?;
+                events.publish();
                 Ok(r)
             }
             Err(e) => {
@@ -140,4 +184,8 @@ impl Invalid for Eval {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
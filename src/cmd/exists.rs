@@ -103,4 +103,8 @@ impl Invalid for Exists {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
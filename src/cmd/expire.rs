@@ -116,4 +116,8 @@ impl Invalid for Expire {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -5,10 +5,14 @@ use tokio::sync::Mutex;
 
 use crate::client::Client;
 use crate::cmd::Invalid;
+use crate::tikv::client::READ_OPTIONS;
 use crate::tikv::errors::{
     REDIS_INVALID_CLIENT_ID_ERR, REDIS_NOT_SUPPORTED_ERR, REDIS_NO_SUCH_CLIENT_ERR,
     REDIS_VALUE_IS_NOT_INTEGER_ERR,
 };
+use crate::tracking::{
+    disable_tracking, enable_tracking, get_redirect, set_caching, TrackingOptions,
+};
 use crate::{
     config::LOGGER,
     tikv::errors::REDIS_UNKNOWN_SUBCOMMAND,
@@ -69,6 +73,8 @@ impl Fake {
             return resp_invalid_arguments();
         }
         let response = match command.to_uppercase().as_str() {
+            // the tikv client has no replica read option, reads are always served by the
+            // region leaders and READONLY only keeps cluster clients working
             "READWRITE" => resp_ok(),
             "READONLY" => resp_ok(),
             "CLIENT" => {
@@ -210,6 +216,40 @@ impl Fake {
                         w_cur_client.set_name(&self.args[1]);
                         resp_ok()
                     }
+                    "TRACKING" => {
+                        let id = cur_client.lock().await.id();
+                        self.client_tracking(id)
+                    }
+                    "CACHING" if self.args.len() == 2 => {
+                        let yes = match self.args[1].to_uppercase().as_str() {
+                            "YES" => true,
+                            "NO" => false,
+                            _ => return resp_invalid_arguments(),
+                        };
+                        match set_caching(cur_client.lock().await.id(), yes) {
+                            Ok(()) => resp_ok(),
+                            Err(e) => resp_err(e),
+                        }
+                    }
+                    "GETREDIR" if self.args.len() == 1 => {
+                        resp_int(get_redirect(cur_client.lock().await.id()))
+                    }
+                    "STALENESS" => {
+                        // get or set the staleness in ms allowed for reads of this connection
+                        match self.args.len() {
+                            1 => resp_int(
+                                READ_OPTIONS.try_with(|o| o.staleness_ms.get()).unwrap_or(0) as i64,
+                            ),
+                            2 => match self.args[1].parse::<u64>() {
+                                Ok(ms) => {
+                                    let _ = READ_OPTIONS.try_with(|o| o.staleness_ms.set(ms));
+                                    resp_ok()
+                                }
+                                Err(_) => resp_err(REDIS_VALUE_IS_NOT_INTEGER_ERR),
+                            },
+                            _ => resp_invalid_arguments(),
+                        }
+                    }
                     "GETNAME" => {
                         let r_cur_client = cur_client.lock().await;
                         let name = r_cur_client.name().to_owned();
@@ -237,6 +277,52 @@ impl Fake {
         };
         response
     }
+
+    /// Returns the subcommand of CLIENT
+    pub fn subcommand(&self) -> &str {
+        self.args.first().map_or("", |arg| arg.as_str())
+    }
+
+    /// Handle `CLIENT TRACKING ON|OFF [REDIRECT id] [BCAST] [PREFIX prefix ...]
+    /// [OPTIN] [OPTOUT] [NOLOOP]` of client `id`.
+    fn client_tracking(&self, id: u64) -> Frame {
+        if self.args.len() < 2 {
+            return resp_invalid_arguments();
+        }
+
+        let mut options = TrackingOptions::default();
+        let mut args = self.args[2..].iter();
+        while let Some(arg) = args.next() {
+            match arg.to_uppercase().as_str() {
+                "REDIRECT" => match args.next().map(|id| id.parse::<u64>()) {
+                    Some(Ok(redirect)) => options.redirect = Some(redirect),
+                    Some(Err(_)) => return resp_err(REDIS_INVALID_CLIENT_ID_ERR),
+                    None => return resp_invalid_arguments(),
+                },
+                "BCAST" => options.bcast = true,
+                "PREFIX" => match args.next() {
+                    Some(prefix) => options.prefixes.push(prefix.clone()),
+                    None => return resp_invalid_arguments(),
+                },
+                "OPTIN" => options.optin = true,
+                "OPTOUT" => options.optout = true,
+                "NOLOOP" => options.noloop = true,
+                _ => return resp_invalid_arguments(),
+            }
+        }
+
+        match self.args[1].to_uppercase().as_str() {
+            "ON" => match enable_tracking(id, options) {
+                Ok(()) => resp_ok(),
+                Err(e) => resp_err(e),
+            },
+            "OFF" => {
+                disable_tracking(id);
+                resp_ok()
+            }
+            _ => resp_invalid_arguments(),
+        }
+    }
 }
 
 #[inline]
@@ -258,4 +344,8 @@ impl Invalid for Fake {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
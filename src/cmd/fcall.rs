@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use crate::cmd::Invalid;
+use crate::config::{is_use_txn_api, LOGGER};
+use crate::notify::PendingEvents;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_FUNCTION_NOT_FOUND_ERR, REDIS_FUNCTION_WRITE_ON_RO_ERR,
+    REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::tikv::function::FunctionCommandCtx;
+use crate::tikv::get_txn_client;
+use crate::tikv::lua::LuaCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame, Parse};
+
+use bytes::Bytes;
+use mlua::Lua;
+use slog::debug;
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+
+/// Call a function loaded by FUNCTION LOAD.
+///
+/// ```text
+/// FCALL function numkeys [key ...] [arg ...]
+/// FCALL_RO function numkeys [key ...] [arg ...]
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fcall {
+    name: String,
+    keys: Vec<String>,
+    args: Vec<Bytes>,
+    valid: bool,
+}
+
+impl Fcall {
+    pub fn new(name: &str) -> Fcall {
+        Fcall {
+            name: name.to_owned(),
+            keys: vec![],
+            args: vec![],
+            valid: true,
+        }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Fcall> {
+        let name = parse.next_string()?;
+        let numkeys = parse.next_int()?;
+        if numkeys < 0 {
+            return Ok(Self::new_invalid());
+        }
+        let mut fcall = Fcall::new(&name);
+
+        for _ in 0..numkeys {
+            match parse.next_string() {
+                Ok(key) => fcall.keys.push(key),
+                Err(_) => return Ok(Self::new_invalid()),
+            }
+        }
+
+        while let Ok(arg) = parse.next_bytes() {
+            fcall.args.push(arg);
+        }
+
+        Ok(fcall)
+    }
+
+    pub(crate) async fn apply(
+        self,
+        dst: &mut Connection,
+        read_only: bool,
+        lua: &Option<Lua>,
+    ) -> crate::Result<()> {
+        let response = self
+            .fcall(read_only, lua, None)
+            .await
+            .unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Call the function in `txn` if given, such as in MULTI, otherwise in a snapshot or a
+    /// new txn which is committed if the function succeeds.
+    pub(crate) async fn fcall(
+        &self,
+        read_only: bool,
+        lua: &Option<Lua>,
+        txn: Option<Arc<Mutex<Transaction>>>,
+    ) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+
+        let (code, no_writes) = match FunctionCommandCtx::new(None)
+            .do_async_txnkv_function_get(&self.name)
+            .await?
+        {
+            Some(f) => f,
+            None => return Ok(resp_err(REDIS_FUNCTION_NOT_FOUND_ERR)),
+        };
+        if read_only && !no_writes {
+            return Ok(resp_err(REDIS_FUNCTION_WRITE_ON_RO_ERR));
+        }
+
+        // the error of the function is the reply, the outer txn goes on
+        if let Some(txn_rc) = txn {
+            let ctx = LuaCommandCtx::new(Some(txn_rc), lua);
+            return Ok(ctx
+                .do_async_fcall(
+                    &code,
+                    &self.name,
+                    &self.keys,
+                    &self.args,
+                    read_only || no_writes,
+                )
+                .await
+                .unwrap_or_else(resp_err));
+        }
+
+        let client = get_txn_client()?;
+        // read only calls run on a snapshot, which needs no commit
+        if read_only || no_writes {
+            let txn_rc = Arc::new(Mutex::new(client.snapshot().await?));
+            let ctx = LuaCommandCtx::new(Some(txn_rc), lua);
+            return Ok(ctx
+                .do_async_fcall(&code, &self.name, &self.keys, &self.args, true)
+                .await
+                .unwrap_or_else(resp_err));
+        }
+
+        let txn_rc = Arc::new(Mutex::new(client.begin().await?));
+        let ctx = LuaCommandCtx::new(Some(txn_rc.clone()), lua);
+        // keyspace events are published only if the txn commits
+        let events = PendingEvents::default();
+        match events
+            .defer(ctx.do_async_fcall(&code, &self.name, &self.keys, &self.args, false))
+            .await
+        {
+            Ok(r) => {
+                txn_rc.lock().await.commit().await?;
+                events.publish();
+                Ok(r)
+            }
+            Err(e) => {
+                txn_rc.lock().await.rollback().await?;
+                Ok(resp_err(e))
+            }
+        }
+    }
+}
+
+impl Invalid for Fcall {
+    fn new_invalid() -> Fcall {
+        Fcall {
+            name: "".to_owned(),
+            keys: vec![],
+            args: vec![],
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
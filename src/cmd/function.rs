@@ -0,0 +1,247 @@
+use crate::cmd::{script_kill, Invalid, Parse};
+use crate::config::{is_use_txn_api, LOGGER};
+use crate::rdb;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR, REDIS_UNKNOWN_SUBCOMMAND};
+use crate::tikv::function::{FunctionCommandCtx, FunctionLibrary};
+use crate::tikv::lua::parse_library;
+use crate::utils::{
+    glob_match, resp_array, resp_bulk, resp_err, resp_invalid_arguments, resp_nil, resp_ok,
+};
+use crate::{Connection, Frame};
+use bytes::Bytes;
+use slog::debug;
+
+/// Manage the function libraries shared by all instances.
+///
+/// ```text
+/// FUNCTION LOAD [REPLACE] code
+/// FUNCTION LIST [LIBRARYNAME pattern] [WITHCODE]
+/// FUNCTION DELETE library
+/// FUNCTION DUMP
+/// FUNCTION RESTORE payload [FLUSH|APPEND|REPLACE]
+/// FUNCTION FLUSH [ASYNC|SYNC]
+/// FUNCTION KILL
+/// ```
+#[derive(Debug, Clone)]
+pub struct Function {
+    subcommand: String,
+    /// library code of LOAD, or payload of RESTORE
+    payload: Bytes,
+    /// library name of DELETE, or library name pattern of LIST
+    library: Option<String>,
+    replace: bool,
+    flush: bool,
+    with_code: bool,
+    valid: bool,
+}
+
+impl Function {
+    pub fn new(subcommand: impl ToString) -> Function {
+        Function {
+            subcommand: subcommand.to_string().to_uppercase(),
+            payload: Bytes::new(),
+            library: None,
+            replace: false,
+            flush: false,
+            with_code: false,
+            valid: true,
+        }
+    }
+
+    pub fn is_kill(&self) -> bool {
+        self.valid && self.subcommand == "KILL"
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Function> {
+        let subcommand = parse.next_string()?;
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+
+        Ok(Function::parse_args(&subcommand, args))
+    }
+
+    fn parse_args(subcommand: &str, args: Vec<Bytes>) -> Function {
+        let mut cmd = Function::new(subcommand);
+        let flag = |idx: usize| String::from_utf8_lossy(&args[idx]).to_uppercase();
+        match cmd.subcommand.as_str() {
+            "LOAD" => match args.len() {
+                1 => cmd.payload = args[0].clone(),
+                2 if flag(0) == "REPLACE" => {
+                    cmd.replace = true;
+                    cmd.payload = args[1].clone();
+                }
+                _ => return Function::new_invalid(),
+            },
+            "DELETE" if args.len() == 1 => {
+                cmd.library = Some(String::from_utf8_lossy(&args[0]).to_string());
+            }
+            "DUMP" | "KILL" if args.is_empty() => {}
+            "FLUSH" => match args.len() {
+                0 => {}
+                1 if matches!(flag(0).as_str(), "ASYNC" | "SYNC") => {}
+                _ => return Function::new_invalid(),
+            },
+            "LIST" => {
+                let mut idx = 0;
+                while idx < args.len() {
+                    match flag(idx).as_str() {
+                        "WITHCODE" => cmd.with_code = true,
+                        "LIBRARYNAME" if idx + 1 < args.len() => {
+                            idx += 1;
+                            cmd.library = Some(String::from_utf8_lossy(&args[idx]).to_string());
+                        }
+                        _ => return Function::new_invalid(),
+                    }
+                    idx += 1;
+                }
+            }
+            "RESTORE" if !args.is_empty() && args.len() <= 2 => {
+                cmd.payload = args[0].clone();
+                if args.len() == 2 {
+                    match flag(1).as_str() {
+                        "FLUSH" => cmd.flush = true,
+                        "REPLACE" => cmd.replace = true,
+                        "APPEND" => {}
+                        _ => return Function::new_invalid(),
+                    }
+                }
+            }
+            "DELETE" | "DUMP" | "KILL" | "RESTORE" => return Function::new_invalid(),
+            _ => {}
+        }
+        cmd
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.function().await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    fn library_to_frame(&self, lib: FunctionLibrary) -> Frame {
+        let functions = lib
+            .functions
+            .into_iter()
+            .map(|f| {
+                resp_array(vec![
+                    resp_bulk(b"name".to_vec()),
+                    resp_bulk(f.name.into_bytes()),
+                    resp_bulk(b"description".to_vec()),
+                    f.description
+                        .map(|d| resp_bulk(d.into_bytes()))
+                        .unwrap_or_else(resp_nil),
+                    resp_bulk(b"flags".to_vec()),
+                    resp_array(
+                        f.flags
+                            .into_iter()
+                            .map(|flag| resp_bulk(flag.into_bytes()))
+                            .collect(),
+                    ),
+                ])
+            })
+            .collect();
+
+        let mut resp = vec![
+            resp_bulk(b"library_name".to_vec()),
+            resp_bulk(lib.name.into_bytes()),
+            resp_bulk(b"engine".to_vec()),
+            resp_bulk(b"LUA".to_vec()),
+            resp_bulk(b"functions".to_vec()),
+            resp_array(functions),
+        ];
+        if self.with_code {
+            resp.push(resp_bulk(b"library_code".to_vec()));
+            resp.push(resp_bulk(lib.code.to_vec()));
+        }
+        resp_array(resp)
+    }
+
+    async fn function(&self) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.is_kill() {
+            return Ok(script_kill(true));
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+
+        let ctx = FunctionCommandCtx::new(None);
+        match self.subcommand.as_str() {
+            "LOAD" => {
+                let lib = parse_library(&self.payload)?;
+                let name = lib.name.clone();
+                ctx.do_async_txnkv_function_load(vec![lib], self.replace, false)
+                    .await?;
+                Ok(resp_bulk(name.into_bytes()))
+            }
+            "DELETE" => {
+                let lib = self.library.as_deref().unwrap_or_default();
+                ctx.do_async_txnkv_function_delete(lib).await?;
+                Ok(resp_ok())
+            }
+            "FLUSH" => {
+                ctx.do_async_txnkv_function_flush().await?;
+                Ok(resp_ok())
+            }
+            "LIST" => {
+                let mut resp = vec![];
+                for code in ctx.do_async_txnkv_function_libraries().await? {
+                    let lib = parse_library(&code)?;
+                    if let Some(pattern) = &self.library {
+                        if !glob_match(pattern.as_bytes(), lib.name.as_bytes()) {
+                            continue;
+                        }
+                    }
+                    resp.push(self.library_to_frame(lib));
+                }
+                Ok(resp_array(resp))
+            }
+            "DUMP" => {
+                let libs = ctx.do_async_txnkv_function_libraries().await?;
+                Ok(resp_bulk(rdb::dump_functions(&libs)))
+            }
+            "RESTORE" => {
+                let libs = rdb::restore_functions(&self.payload)?
+                    .into_iter()
+                    .map(|code| parse_library(&Bytes::from(code)))
+                    .collect::<AsyncResult<Vec<_>>>()?;
+                ctx.do_async_txnkv_function_load(libs, self.replace, self.flush)
+                    .await?;
+                Ok(resp_ok())
+            }
+            _ => Ok(resp_err(REDIS_UNKNOWN_SUBCOMMAND)),
+        }
+    }
+}
+
+impl Invalid for Function {
+    fn new_invalid() -> Function {
+        Function {
+            subcommand: "".to_owned(),
+            payload: Bytes::new(),
+            library: None,
+            replace: false,
+            flush: false,
+            with_code: false,
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
@@ -122,4 +122,8 @@ impl Invalid for Get {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::hash::HashCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use bytes::Bytes;
+use slog::debug;
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+
+/// Set `field` of hash `key` to `value` if its current value equals `expected`
+#[derive(Debug, Clone)]
+pub struct Hcas {
+    key: String,
+    field: String,
+    expected: Bytes,
+    value: Bytes,
+    valid: bool,
+}
+
+impl Hcas {
+    pub fn new(key: &str, field: &str, expected: Bytes, value: Bytes) -> Hcas {
+        Hcas {
+            key: key.to_owned(),
+            field: field.to_owned(),
+            expected,
+            value,
+            valid: true,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hcas> {
+        let key = parse.next_string()?;
+        let field = parse.next_string()?;
+        let expected = parse.next_bytes()?;
+        let value = parse.next_bytes()?;
+        Ok(Hcas::new(&key, &field, expected, value))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Hcas> {
+        if argv.len() != 4 {
+            return Ok(Hcas::new_invalid());
+        }
+        Ok(Hcas::new(
+            &String::from_utf8_lossy(&argv[0]),
+            &String::from_utf8_lossy(&argv[1]),
+            argv[2].clone(),
+            argv[3].clone(),
+        ))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.hcas(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn hcas(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            HashCommandCtx::new(txn)
+                .do_async_txnkv_hcas(&self.key, &self.field, &self.expected, &self.value)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Hcas {
+    fn new_invalid() -> Hcas {
+        Hcas {
+            key: "".to_owned(),
+            field: "".to_owned(),
+            expected: Bytes::new(),
+            value: Bytes::new(),
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
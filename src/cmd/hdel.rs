@@ -93,4 +93,8 @@ impl Invalid for Hdel {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+use crate::client::Client;
+use crate::cmd::Invalid;
+use crate::config::LOGGER;
+use crate::frame::RESP_VERSION;
+use crate::tikv::errors::{
+    REDIS_HELLO_AUTH_REQUIRED_ERR, REDIS_NO_PROTO_ERR, REDIS_PROTOCOL_VERSION_ERR,
+    REDIS_WRONG_PASS_ERR,
+};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{is_auth_matched, Connection, Frame, Parse, ParseError};
+
+/// Switches the protocol of the connection and replies with the server info.
+///
+/// ```text
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+/// ```
+///
+/// Only the `default` user exists, its password is the `requirepass` of the server.
+#[derive(Debug, Clone)]
+pub struct Hello {
+    protover: Option<String>,
+    auth: Option<(String, String)>,
+    setname: Option<String>,
+    valid: bool,
+}
+
+impl Hello {
+    pub fn new(protover: Option<String>) -> Hello {
+        Hello {
+            protover,
+            auth: None,
+            setname: None,
+            valid: true,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hello> {
+        let mut hello = match parse.next_string() {
+            Ok(protover) => Hello::new(Some(protover)),
+            Err(ParseError::EndOfStream) => return Ok(Hello::new(None)),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Ok(option) = parse.next_string() {
+            match option.to_uppercase().as_str() {
+                "AUTH" => {
+                    let user = parse.next_string()?;
+                    let passwd = parse.next_string()?;
+                    hello.auth = Some((user, passwd));
+                }
+                "SETNAME" => {
+                    hello.setname = Some(parse.next_string()?);
+                }
+                _ => return Ok(Hello::new_invalid()),
+            }
+        }
+
+        Ok(hello)
+    }
+
+    /// Apply the `Hello` command, it is accepted before the client is authorized
+    /// as it may authenticate the client by the `AUTH` option.
+    pub(crate) async fn apply(
+        self,
+        cur_client: &Arc<Mutex<Client>>,
+        authorized: &mut bool,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.hello(cur_client, authorized, dst).await;
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    async fn hello(
+        self,
+        cur_client: &Arc<Mutex<Client>>,
+        authorized: &mut bool,
+        dst: &mut Connection,
+    ) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+
+        let version = match self.protover {
+            None => dst.resp_version(),
+            Some(protover) => match protover.parse::<i64>() {
+                Ok(v @ 2..=3) => v as u8,
+                Ok(_) => return resp_err(REDIS_NO_PROTO_ERR),
+                Err(_) => return resp_err(REDIS_PROTOCOL_VERSION_ERR),
+            },
+        };
+
+        match self.auth {
+            Some((user, passwd)) => {
+                if user != "default" || !is_auth_matched(&passwd) {
+                    return resp_err(REDIS_WRONG_PASS_ERR);
+                }
+                *authorized = true;
+            }
+            None if !*authorized => return resp_err(REDIS_HELLO_AUTH_REQUIRED_ERR),
+            None => {}
+        }
+
+        let mut w_client = cur_client.lock().await;
+        if let Some(name) = self.setname {
+            w_client.set_name(&name);
+        }
+        w_client.set_resp(version);
+        dst.set_resp_version(version);
+        let _ = RESP_VERSION.try_with(|v| v.set(version));
+
+        let field = |name: &'static str| Frame::Bulk(Bytes::from_static(name.as_bytes()));
+        Frame::Map(vec![
+            (field("server"), field("redis")),
+            (field("version"), field(env!("CARGO_PKG_VERSION"))),
+            (field("proto"), Frame::Integer(version as i64)),
+            (field("id"), Frame::Integer(w_client.id() as i64)),
+            (field("mode"), field("cluster")),
+            (field("role"), field("master")),
+            (field("modules"), Frame::Array(vec![])),
+        ])
+    }
+}
+
+impl Invalid for Hello {
+    fn new_invalid() -> Hello {
+        Hello {
+            protover: None,
+            auth: None,
+            setname: None,
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
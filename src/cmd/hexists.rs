@@ -89,4 +89,8 @@ impl Invalid for Hexists {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
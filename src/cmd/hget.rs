@@ -88,4 +88,8 @@ impl Invalid for Hget {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
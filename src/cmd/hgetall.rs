@@ -84,4 +84,8 @@ impl Invalid for Hgetall {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -110,4 +110,8 @@ impl Invalid for Hincrby {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
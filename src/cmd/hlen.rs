@@ -84,4 +84,8 @@ impl Invalid for Hlen {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
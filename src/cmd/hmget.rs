@@ -98,4 +98,8 @@ impl Invalid for Hmget {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
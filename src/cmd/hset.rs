@@ -130,4 +130,8 @@ impl Invalid for Hset {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -97,4 +97,8 @@ impl Invalid for Hstrlen {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -84,4 +84,8 @@ impl Invalid for Hvals {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
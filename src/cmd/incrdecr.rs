@@ -112,4 +112,8 @@ impl Invalid for IncrDecr {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
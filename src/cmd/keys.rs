@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use crate::cmd::Invalid;
+use crate::config::is_use_txn_api;
+use crate::config::LOGGER;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use slog::debug;
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Keys {
+    pattern: Bytes,
+    valid: bool,
+}
+
+impl Keys {
+    pub fn new(pattern: Bytes) -> Keys {
+        Keys {
+            pattern,
+            valid: true,
+        }
+    }
+
+    /// Get the glob-style pattern
+    pub fn pattern(&self) -> &[u8] {
+        &self.pattern
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Keys> {
+        let pattern = parse.next_bytes()?;
+
+        Ok(Keys::new(pattern))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Keys> {
+        if argv.len() != 1 {
+            return Ok(Keys::new_invalid());
+        }
+        Ok(Keys::new(argv[0].clone()))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.keys(None).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn keys(self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_keys(&self.pattern)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Keys {
+    fn new_invalid() -> Keys {
+        Keys {
+            pattern: Bytes::new(),
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
@@ -92,4 +92,8 @@ impl Invalid for Lindex {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
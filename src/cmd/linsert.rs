@@ -35,6 +35,11 @@ impl Linsert {
         }
     }
 
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Linsert> {
         let key = parse.next_string()?;
         let pos = parse.next_string()?;
@@ -113,4 +118,8 @@ impl Invalid for Linsert {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -80,4 +80,8 @@ impl Invalid for Llen {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
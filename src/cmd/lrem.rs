@@ -33,6 +33,11 @@ impl Lrem {
         }
     }
 
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Lrem> {
         let key = parse.next_string()?;
         let count = parse.next_int()?;
@@ -100,4 +105,8 @@ impl Invalid for Lrem {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
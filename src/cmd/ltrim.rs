@@ -102,4 +102,8 @@ impl Invalid for Ltrim {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -108,4 +108,8 @@ impl Invalid for Mget {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -31,6 +31,9 @@ pub use setnx::SetNX;
 mod setex;
 pub use setex::SetEX;
 
+mod cas;
+pub use cas::Cas;
+
 mod ttl;
 pub use ttl::TTL;
 
@@ -38,7 +41,10 @@ mod cmdtype;
 pub use cmdtype::Type;
 
 mod subscribe;
-pub use subscribe::{Subscribe, Unsubscribe};
+pub use subscribe::{Psubscribe, Punsubscribe, Subscribe, Unsubscribe};
+
+mod pubsub;
+pub use pubsub::Pubsub;
 
 mod ping;
 pub use ping::Ping;
@@ -61,6 +67,9 @@ pub use hset::Hset;
 mod hget;
 pub use hget::Hget;
 
+mod hcas;
+pub use hcas::Hcas;
+
 mod hmget;
 pub use hmget::Hmget;
 
@@ -118,6 +127,12 @@ pub use linsert::Linsert;
 mod eval;
 pub use eval::Eval;
 
+mod function;
+pub use function::Function;
+
+mod fcall;
+pub use fcall::Fcall;
+
 mod sadd;
 pub use sadd::Sadd;
 
@@ -182,10 +197,11 @@ mod zincrby;
 pub use zincrby::Zincrby;
 
 mod script;
-pub use script::script_clear_killed;
-pub use script::script_interuptted;
-pub use script::script_set_killed;
+pub use script::script_busy;
+pub use script::script_kill;
+pub use script::script_set_written;
 pub use script::Script;
+pub use script::ScriptRunGuard;
 
 mod unknown;
 pub use unknown::Unknown;
@@ -193,6 +209,9 @@ pub use unknown::Unknown;
 mod auth;
 pub use auth::Auth;
 
+mod hello;
+pub use hello::Hello;
+
 mod debug;
 pub use debug::Debug;
 
@@ -205,15 +224,41 @@ pub use fake::Fake;
 mod multi;
 pub use multi::Multi;
 
+mod watch;
+pub use watch::{Watch, WatchedKeys};
+
+mod txn;
+pub use txn::{InteractiveTxn, Txn};
+
 mod scan;
 pub use scan::Scan;
 
+mod keys;
+pub use keys::Keys;
+
+mod snapshot;
+pub use snapshot::Snapshot;
+
+mod rename;
+pub use rename::Rename;
+
+mod copy;
+pub use copy::Copy;
+
+mod dump;
+pub use dump::Dump;
+
+mod restore;
+pub use restore::Restore;
+
 use crate::client::Client;
-use crate::{cluster::Cluster as Topo, Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::{cluster::Cluster as Topo, Connection, Db, Frame, Parse, ParseError};
 
 /// All commands should be implement new_invalid() for invalid check
 pub trait Invalid {
     fn new_invalid() -> Self;
+
+    fn valid(&self) -> bool;
 }
 
 fn transform_parse<T: Invalid>(parse_res: crate::Result<T>, parse: &mut Parse) -> T {
@@ -241,9 +286,17 @@ pub enum Command {
     Set(Set),
     SetNX(SetNX),
     SetEX(SetEX),
+    Cas(Cas),
+    Cad(Cas),
     Mset(Mset),
     Subscribe(Subscribe),
     Unsubscribe(Unsubscribe),
+    Psubscribe(Psubscribe),
+    Ssubscribe(Subscribe),
+    Sunsubscribe(Unsubscribe),
+    Spublish(Publish),
+    Punsubscribe(Punsubscribe),
+    Pubsub(Pubsub),
     Ping(Ping),
     Type(Type),
     TTL(TTL),
@@ -259,12 +312,18 @@ pub enum Command {
     IncrBy(IncrDecr),
     DecrBy(IncrDecr),
     Strlen(Strlen),
+    Rename(Rename),
+    RenameNX(Rename),
+    Copy(Copy),
+    Dump(Dump),
+    Restore(Restore),
 
     // hash
     Hset(Hset),
     Hmset(Hset),
     Hsetnx(Hset),
     Hget(Hget),
+    Hcas(Hcas),
     Hmget(Hmget),
     Hlen(Hlen),
     Hgetall(Hgetall),
@@ -316,8 +375,12 @@ pub enum Command {
     Eval(Eval),
     Evalsha(Eval),
     Script(Script),
+    Function(Function),
+    Fcall(Fcall),
+    FcallRo(Fcall),
 
     Auth(Auth),
+    Hello(Hello),
     Debug(Debug),
 
     Cluster(Cluster),
@@ -330,10 +393,18 @@ pub enum Command {
     Multi(Multi),
     Exec(Multi),
     Discard(Multi),
+    Reset(Multi),
+    Quit(Multi),
+    Watch(Watch),
+    Unwatch(Watch),
+    // interactive txn
+    Txn(Txn),
 
     Scan(Scan),
     // Xscan command is same as scan, for testing purpose, avoid some client decoding the response
     Xscan(Scan),
+    Keys(Keys),
+    Snapshot(Snapshot),
 
     Unknown(Unknown),
 }
@@ -370,6 +441,14 @@ impl Command {
             )),
             "set" => Command::Set(transform_parse(Set::parse_frames(&mut parse), &mut parse)),
             "setnx" => Command::SetNX(transform_parse(SetNX::parse_frames(&mut parse), &mut parse)),
+            "cas" => Command::Cas(transform_parse(
+                Cas::parse_frames(&mut parse, false),
+                &mut parse,
+            )),
+            "cad" => Command::Cad(transform_parse(
+                Cas::parse_frames(&mut parse, true),
+                &mut parse,
+            )),
             "setex" => Command::SetEX(transform_parse(SetEX::parse_frames(&mut parse), &mut parse)),
             "subscribe" => Command::Subscribe(transform_parse(
                 Subscribe::parse_frames(&mut parse),
@@ -379,6 +458,30 @@ impl Command {
                 Unsubscribe::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "psubscribe" => Command::Psubscribe(transform_parse(
+                Psubscribe::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "punsubscribe" => Command::Punsubscribe(transform_parse(
+                Punsubscribe::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "ssubscribe" => Command::Ssubscribe(transform_parse(
+                Subscribe::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "sunsubscribe" => Command::Sunsubscribe(transform_parse(
+                Unsubscribe::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "spublish" => Command::Spublish(transform_parse(
+                Publish::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "pubsub" => Command::Pubsub(transform_parse(
+                Pubsub::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "ping" => Command::Ping(transform_parse(Ping::parse_frames(&mut parse), &mut parse)),
             "type" => Command::Type(transform_parse(Type::parse_frames(&mut parse), &mut parse)),
             "mget" => Command::Mget(transform_parse(Mget::parse_frames(&mut parse), &mut parse)),
@@ -429,12 +532,27 @@ impl Command {
                 Strlen::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "rename" => Command::Rename(transform_parse(
+                Rename::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "renamenx" => Command::RenameNX(transform_parse(
+                Rename::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "copy" => Command::Copy(transform_parse(Copy::parse_frames(&mut parse), &mut parse)),
+            "dump" => Command::Dump(transform_parse(Dump::parse_frames(&mut parse), &mut parse)),
+            "restore" => Command::Restore(transform_parse(
+                Restore::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "hset" => Command::Hset(transform_parse(Hset::parse_frames(&mut parse), &mut parse)),
             "hsetnx" => {
                 Command::Hsetnx(transform_parse(Hset::parse_frames(&mut parse), &mut parse))
             }
             "hmset" => Command::Hmset(transform_parse(Hset::parse_frames(&mut parse), &mut parse)),
             "hget" => Command::Hget(transform_parse(Hget::parse_frames(&mut parse), &mut parse)),
+            "hcas" => Command::Hcas(transform_parse(Hcas::parse_frames(&mut parse), &mut parse)),
             "hmget" => Command::Hmget(transform_parse(Hmget::parse_frames(&mut parse), &mut parse)),
             "hlen" => Command::Hlen(transform_parse(Hlen::parse_frames(&mut parse), &mut parse)),
             "hgetall" => Command::Hgetall(transform_parse(
@@ -484,6 +602,14 @@ impl Command {
                 Script::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "function" => Command::Function(transform_parse(
+                Function::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "fcall" => Command::Fcall(transform_parse(Fcall::parse_frames(&mut parse), &mut parse)),
+            "fcall_ro" => {
+                Command::FcallRo(transform_parse(Fcall::parse_frames(&mut parse), &mut parse))
+            }
             "sadd" => Command::Sadd(transform_parse(Sadd::parse_frames(&mut parse), &mut parse)),
             "scard" => Command::Scard(transform_parse(Scard::parse_frames(&mut parse), &mut parse)),
             "sismember" => Command::Sismember(transform_parse(
@@ -551,6 +677,7 @@ impl Command {
                 &mut parse,
             )),
             "auth" => Command::Auth(transform_parse(Auth::parse_frames(&mut parse), &mut parse)),
+            "hello" => Command::Hello(transform_parse(Hello::parse_frames(&mut parse), &mut parse)),
             "debug" => Command::Debug(transform_parse(Debug::parse_frames(&mut parse), &mut parse)),
             "cluster" => Command::Cluster(transform_parse(
                 Cluster::parse_frames(&mut parse),
@@ -575,8 +702,18 @@ impl Command {
             "multi" => Command::Multi(Multi::new()),
             "exec" => Command::Exec(Multi::new()),
             "discard" => Command::Discard(Multi::new()),
+            "reset" => Command::Reset(Multi::new()),
+            "quit" => Command::Quit(Multi::new()),
+            "watch" => Command::Watch(transform_parse(Watch::parse_frames(&mut parse), &mut parse)),
+            "unwatch" => Command::Unwatch(Watch::new(vec![])),
+            "txn" => Command::Txn(transform_parse(Txn::parse_frames(&mut parse), &mut parse)),
             "scan" => Command::Scan(transform_parse(Scan::parse_frames(&mut parse), &mut parse)),
             "xscan" => Command::Scan(transform_parse(Scan::parse_frames(&mut parse), &mut parse)),
+            "keys" => Command::Keys(transform_parse(Keys::parse_frames(&mut parse), &mut parse)),
+            "snapshot" => Command::Snapshot(transform_parse(
+                Snapshot::parse_frames(&mut parse),
+                &mut parse,
+            )),
             _ => {
                 // The command is not recognized and an Unknown command is
                 // returned.
@@ -610,6 +747,8 @@ impl Command {
             "get" => Command::Get(Get::parse_argv(argv)?),
             "set" => Command::Set(Set::parse_argv(argv)?),
             "setnx" => Command::SetNX(SetNX::parse_argv(argv)?),
+            "cas" => Command::Cas(Cas::parse_argv(argv, false)?),
+            "cad" => Command::Cad(Cas::parse_argv(argv, true)?),
             "setex" => Command::SetEX(SetEX::parse_argv(argv)?),
             "mget" => Command::Mget(Mget::parse_argv(argv)?),
             "mset" => Command::Mset(Mset::parse_argv(argv)?),
@@ -620,10 +759,16 @@ impl Command {
             "pexpire" => Command::Pexpire(Expire::parse_argv(argv)?),
             "pexpireat" => Command::PexpireAt(Expire::parse_argv(argv)?),
             "persist" => Command::Persist(Persist::parse_argv(argv)?),
+            "rename" => Command::Rename(Rename::parse_argv(argv)?),
+            "renamenx" => Command::RenameNX(Rename::parse_argv(argv)?),
+            "copy" => Command::Copy(Copy::parse_argv(argv)?),
+            "dump" => Command::Dump(Dump::parse_argv(argv)?),
+            "restore" => Command::Restore(Restore::parse_argv(argv)?),
             "hset" => Command::Hset(Hset::parse_argv(argv)?),
             "hsetnx" => Command::Hsetnx(Hset::parse_argv(argv)?),
             "hmset" => Command::Hmset(Hset::parse_argv(argv)?),
             "hget" => Command::Hget(Hget::parse_argv(argv)?),
+            "hcas" => Command::Hcas(Hcas::parse_argv(argv)?),
             "hmget" => Command::Hmget(Hmget::parse_argv(argv)?),
             "hlen" => Command::Hlen(Hlen::parse_argv(argv)?),
             "hgetall" => Command::Hgetall(Hgetall::parse_argv(argv)?),
@@ -669,6 +814,7 @@ impl Command {
             "zincrby" => Command::Zincryby(Zincrby::parse_argv(argv)?),
             "scan" => Command::Scan(Scan::parse_argv(argv)?),
             "xscan" => Command::Scan(Scan::parse_argv(argv)?),
+            "keys" => Command::Keys(Keys::parse_argv(argv)?),
             _ => {
                 // The command is not recognized and an Unknown command is
                 // returned.
@@ -683,7 +829,6 @@ impl Command {
     ///
     /// The response is written to `dst`. This is called by the server in order
     /// to execute a received command.
-    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn apply(
         self,
         db: &Db,
@@ -692,18 +837,20 @@ impl Command {
         cur_client: Arc<Mutex<Client>>,
         clients: Arc<Mutex<HashMap<u64, Arc<Mutex<Client>>>>>,
         lua: &mut Option<Lua>,
-        shutdown: &mut Shutdown,
     ) -> crate::Result<()> {
         use Command::*;
 
         match self {
             Del(cmd) => cmd.apply(dst).await,
             Get(cmd) => cmd.apply(dst).await,
-            Publish(cmd) => cmd.apply(db, dst).await,
+            Publish(cmd) => cmd.apply(db, topo, dst).await,
             Set(cmd) => cmd.apply(dst).await,
             SetNX(cmd) => cmd.apply(dst).await,
+            Cas(cmd) => cmd.apply(dst).await,
+            Cad(cmd) => cmd.apply(dst).await,
             SetEX(cmd) => cmd.apply(dst).await,
-            Subscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            Spublish(cmd) => cmd.apply_sharded(db, topo, dst).await,
+            Pubsub(cmd) => cmd.apply(db, topo, dst).await,
             Ping(cmd) => cmd.apply(dst).await,
             Type(cmd) => cmd.apply(dst).await,
             Mget(cmd) => cmd.apply(dst).await,
@@ -721,10 +868,16 @@ impl Command {
             IncrBy(cmd) => cmd.apply(dst, true).await,
             DecrBy(cmd) => cmd.apply(dst, false).await,
             Strlen(cmd) => cmd.apply(dst).await,
+            Rename(cmd) => cmd.apply(dst, false).await,
+            RenameNX(cmd) => cmd.apply(dst, true).await,
+            Copy(cmd) => cmd.apply(dst).await,
+            Dump(cmd) => cmd.apply(dst).await,
+            Restore(cmd) => cmd.apply(dst).await,
             Hset(cmd) => cmd.apply(dst, false, false).await,
             Hmset(cmd) => cmd.apply(dst, true, false).await,
             Hsetnx(cmd) => cmd.apply(dst, false, true).await,
             Hget(cmd) => cmd.apply(dst).await,
+            Hcas(cmd) => cmd.apply(dst).await,
             Hmget(cmd) => cmd.apply(dst).await,
             Hlen(cmd) => cmd.apply(dst).await,
             Hgetall(cmd) => cmd.apply(dst).await,
@@ -748,6 +901,9 @@ impl Command {
             Eval(cmd) => cmd.apply(dst, false, db, lua).await,
             Evalsha(cmd) => cmd.apply(dst, true, db, lua).await,
             Script(cmd) => cmd.apply(dst, db).await,
+            Function(cmd) => cmd.apply(dst).await,
+            Fcall(cmd) => cmd.apply(dst, false, lua).await,
+            FcallRo(cmd) => cmd.apply(dst, true, lua).await,
             Sadd(cmd) => cmd.apply(dst).await,
             Scard(cmd) => cmd.apply(dst).await,
             Sismember(cmd) => cmd.apply(dst).await,
@@ -774,7 +930,7 @@ impl Command {
 
             Debug(cmd) => cmd.apply(dst).await,
 
-            Cluster(cmd) => cmd.apply(topo, dst).await,
+            Cluster(cmd) => cmd.apply(db, topo, dst).await,
             ReadWrite(cmd) => cmd.apply("readwrite", dst, cur_client, clients).await,
             ReadOnly(cmd) => cmd.apply("readonly", dst, cur_client, clients).await,
             Client(cmd) => cmd.apply("client", dst, cur_client, clients).await,
@@ -782,11 +938,14 @@ impl Command {
 
             Scan(cmd) => cmd.apply(dst).await,
             Xscan(cmd) => cmd.apply(dst).await,
+            Keys(cmd) => cmd.apply(dst).await,
+            Snapshot(cmd) => cmd.apply(dst).await,
 
             Unknown(cmd) => cmd.apply(dst).await,
-            // `Unsubscribe` cannot be applied. It may only be received from the
-            // context of a `Subscribe` command.
-            Unsubscribe(_) => Err("`Unsubscribe` is unsupported in this context".into()),
+            // the client is not subscribed to anything out of the subscribed state
+            Unsubscribe(cmd) => cmd.apply("unsubscribe", dst).await,
+            Sunsubscribe(cmd) => cmd.apply("sunsubscribe", dst).await,
+            Punsubscribe(cmd) => cmd.apply(dst).await,
 
             _ => Ok(()),
         }
@@ -800,9 +959,17 @@ impl Command {
             Command::Publish(_) => "pub",
             Command::Set(_) => "set",
             Command::SetNX(_) => "setnx",
+            Command::Cas(_) => "cas",
+            Command::Cad(_) => "cad",
             Command::SetEX(_) => "setex",
             Command::Subscribe(_) => "subscribe",
             Command::Unsubscribe(_) => "unsubscribe",
+            Command::Psubscribe(_) => "psubscribe",
+            Command::Ssubscribe(_) => "ssubscribe",
+            Command::Sunsubscribe(_) => "sunsubscribe",
+            Command::Spublish(_) => "spublish",
+            Command::Punsubscribe(_) => "punsubscribe",
+            Command::Pubsub(_) => "pubsub",
             Command::Ping(_) => "ping",
             Command::Type(_) => "type",
             Command::Mget(_) => "mget",
@@ -820,10 +987,16 @@ impl Command {
             Command::IncrBy(_) => "incrby",
             Command::DecrBy(_) => "decrby",
             Command::Strlen(_) => "strlen",
+            Command::Rename(_) => "rename",
+            Command::RenameNX(_) => "renamenx",
+            Command::Copy(_) => "copy",
+            Command::Dump(_) => "dump",
+            Command::Restore(_) => "restore",
             Command::Hset(_) => "hset",
             Command::Hmset(_) => "hmset",
             Command::Hsetnx(_) => "hsetnx",
             Command::Hget(_) => "hget",
+            Command::Hcas(_) => "hcas",
             Command::Hmget(_) => "hmget",
             Command::Hlen(_) => "hlen",
             Command::Hgetall(_) => "hgetall",
@@ -847,6 +1020,9 @@ impl Command {
             Command::Eval(_) => "eval",
             Command::Evalsha(_) => "evalsha",
             Command::Script(_) => "script",
+            Command::Function(_) => "function",
+            Command::Fcall(_) => "fcall",
+            Command::FcallRo(_) => "fcall_ro",
             Command::Sadd(_) => "sadd",
             Command::Scard(_) => "scard",
             Command::Sismember(_) => "sismember",
@@ -871,6 +1047,7 @@ impl Command {
             Command::Zrank(_) => "zrank",
             Command::Zincryby(_) => "zincrby",
             Command::Auth(_) => "auth",
+            Command::Hello(_) => "hello",
             Command::Debug(_) => "debug",
             Command::Cluster(_) => "cluster",
             Command::ReadWrite(_) => "readwrite",
@@ -880,9 +1057,297 @@ impl Command {
             Command::Multi(_) => "multi",
             Command::Exec(_) => "exec",
             Command::Discard(_) => "discard",
+            Command::Reset(_) => "reset",
+            Command::Quit(_) => "quit",
+            Command::Watch(_) => "watch",
+            Command::Unwatch(_) => "unwatch",
+            Command::Txn(_) => "txn",
             Command::Scan(_) => "scan",
             Command::Xscan(_) => "xscan",
+            Command::Keys(_) => "keys",
+            Command::Snapshot(_) => "snapshot",
             Command::Unknown(cmd) => cmd.get_name(),
         }
     }
+
+    /// Returns false if the command has invalid arguments
+    pub(crate) fn valid(&self) -> bool {
+        match self {
+            Command::Del(cmd) => cmd.valid(),
+            Command::Get(cmd) => cmd.valid(),
+            Command::Mget(cmd) => cmd.valid(),
+            Command::Publish(cmd) => cmd.valid(),
+            Command::Set(cmd) => cmd.valid(),
+            Command::SetNX(cmd) => cmd.valid(),
+            Command::Cas(cmd) => cmd.valid(),
+            Command::Cad(cmd) => cmd.valid(),
+            Command::SetEX(cmd) => cmd.valid(),
+            Command::Mset(cmd) => cmd.valid(),
+            Command::Subscribe(cmd) => cmd.valid(),
+            Command::Unsubscribe(cmd) => cmd.valid(),
+            Command::Psubscribe(cmd) => cmd.valid(),
+            Command::Ssubscribe(cmd) => cmd.valid(),
+            Command::Sunsubscribe(cmd) => cmd.valid(),
+            Command::Spublish(cmd) => cmd.valid(),
+            Command::Punsubscribe(cmd) => cmd.valid(),
+            Command::Pubsub(cmd) => cmd.valid(),
+            Command::Ping(cmd) => cmd.valid(),
+            Command::Type(cmd) => cmd.valid(),
+            Command::TTL(cmd) => cmd.valid(),
+            Command::PTTL(cmd) => cmd.valid(),
+            Command::Expire(cmd) => cmd.valid(),
+            Command::ExpireAt(cmd) => cmd.valid(),
+            Command::Pexpire(cmd) => cmd.valid(),
+            Command::PexpireAt(cmd) => cmd.valid(),
+            Command::Persist(cmd) => cmd.valid(),
+            Command::Exists(cmd) => cmd.valid(),
+            Command::Incr(cmd) => cmd.valid(),
+            Command::Decr(cmd) => cmd.valid(),
+            Command::IncrBy(cmd) => cmd.valid(),
+            Command::DecrBy(cmd) => cmd.valid(),
+            Command::Strlen(cmd) => cmd.valid(),
+            Command::Rename(cmd) => cmd.valid(),
+            Command::RenameNX(cmd) => cmd.valid(),
+            Command::Copy(cmd) => cmd.valid(),
+            Command::Dump(cmd) => cmd.valid(),
+            Command::Restore(cmd) => cmd.valid(),
+            Command::Hset(cmd) => cmd.valid(),
+            Command::Hmset(cmd) => cmd.valid(),
+            Command::Hsetnx(cmd) => cmd.valid(),
+            Command::Hget(cmd) => cmd.valid(),
+            Command::Hcas(cmd) => cmd.valid(),
+            Command::Hmget(cmd) => cmd.valid(),
+            Command::Hlen(cmd) => cmd.valid(),
+            Command::Hgetall(cmd) => cmd.valid(),
+            Command::Hdel(cmd) => cmd.valid(),
+            Command::Hkeys(cmd) => cmd.valid(),
+            Command::Hvals(cmd) => cmd.valid(),
+            Command::Hincrby(cmd) => cmd.valid(),
+            Command::Hexists(cmd) => cmd.valid(),
+            Command::Hstrlen(cmd) => cmd.valid(),
+            Command::Lpush(cmd) => cmd.valid(),
+            Command::Rpush(cmd) => cmd.valid(),
+            Command::Lpop(cmd) => cmd.valid(),
+            Command::Rpop(cmd) => cmd.valid(),
+            Command::Lrange(cmd) => cmd.valid(),
+            Command::Llen(cmd) => cmd.valid(),
+            Command::Lindex(cmd) => cmd.valid(),
+            Command::Lset(cmd) => cmd.valid(),
+            Command::Ltrim(cmd) => cmd.valid(),
+            Command::Lrem(cmd) => cmd.valid(),
+            Command::Linsert(cmd) => cmd.valid(),
+            Command::Sadd(cmd) => cmd.valid(),
+            Command::Scard(cmd) => cmd.valid(),
+            Command::Sismember(cmd) => cmd.valid(),
+            Command::Smismember(cmd) => cmd.valid(),
+            Command::Smembers(cmd) => cmd.valid(),
+            Command::Srandmember(cmd) => cmd.valid(),
+            Command::Spop(cmd) => cmd.valid(),
+            Command::Srem(cmd) => cmd.valid(),
+            Command::Zadd(cmd) => cmd.valid(),
+            Command::Zcard(cmd) => cmd.valid(),
+            Command::Zscore(cmd) => cmd.valid(),
+            Command::Zrem(cmd) => cmd.valid(),
+            Command::Zremrangebyscore(cmd) => cmd.valid(),
+            Command::Zremrangebyrank(cmd) => cmd.valid(),
+            Command::Zrange(cmd) => cmd.valid(),
+            Command::Zrevrange(cmd) => cmd.valid(),
+            Command::Zrangebyscore(cmd) => cmd.valid(),
+            Command::Zrevrangebyscore(cmd) => cmd.valid(),
+            Command::Zcount(cmd) => cmd.valid(),
+            Command::Zpopmin(cmd) => cmd.valid(),
+            Command::Zpopmax(cmd) => cmd.valid(),
+            Command::Zrank(cmd) => cmd.valid(),
+            Command::Zincryby(cmd) => cmd.valid(),
+            Command::Eval(cmd) => cmd.valid(),
+            Command::Evalsha(cmd) => cmd.valid(),
+            Command::Script(cmd) => cmd.valid(),
+            Command::Function(cmd) => cmd.valid(),
+            Command::Fcall(cmd) => cmd.valid(),
+            Command::FcallRo(cmd) => cmd.valid(),
+            Command::Auth(cmd) => cmd.valid(),
+            Command::Hello(cmd) => cmd.valid(),
+            Command::Debug(cmd) => cmd.valid(),
+            Command::Cluster(cmd) => cmd.valid(),
+            Command::ReadWrite(cmd) => cmd.valid(),
+            Command::ReadOnly(cmd) => cmd.valid(),
+            Command::Client(cmd) => cmd.valid(),
+            Command::Info(cmd) => cmd.valid(),
+            Command::Watch(cmd) => cmd.valid(),
+            Command::Unwatch(cmd) => cmd.valid(),
+            Command::Txn(cmd) => cmd.valid(),
+            Command::Scan(cmd) => cmd.valid(),
+            Command::Xscan(cmd) => cmd.valid(),
+            Command::Keys(cmd) => cmd.valid(),
+            Command::Snapshot(cmd) => cmd.valid(),
+            Command::Multi(_)
+            | Command::Exec(_)
+            | Command::Discard(_)
+            | Command::Reset(_)
+            | Command::Quit(_)
+            | Command::Unknown(_) => true,
+        }
+    }
+
+    /// Returns true if the command is served while a script of other connection
+    /// runs longer than lua-time-limit
+    pub(crate) fn is_allowed_when_busy(&self) -> bool {
+        match self {
+            Command::Script(c) => c.is_kill(),
+            Command::Function(c) => c.is_kill(),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the command runs a lua script or function
+    pub(crate) fn is_script(&self) -> bool {
+        matches!(
+            self,
+            Command::Eval(_) | Command::Evalsha(_) | Command::Fcall(_) | Command::FcallRo(_)
+        )
+    }
+
+    /// Returns the keys declared by the script, or the keys of other commands as `keys`
+    pub(crate) fn declared_keys(&self) -> Option<Vec<String>> {
+        match self {
+            Command::Eval(cmd) | Command::Evalsha(cmd) => Some(cmd.keys().clone()),
+            Command::Fcall(cmd) | Command::FcallRo(cmd) => Some(cmd.keys().clone()),
+            _ => self.keys(),
+        }
+    }
+
+    /// Returns the keys read by the command, which are tracked for the clients
+    /// with CLIENT TRACKING on
+    pub(crate) fn read_keys(&self) -> Vec<String> {
+        let key = match self {
+            Command::Mget(cmd) => return cmd.keys().clone(),
+            Command::Exists(cmd) => return cmd.keys().clone(),
+            Command::Get(cmd) => cmd.key(),
+            Command::Type(cmd) => cmd.key(),
+            Command::TTL(cmd) | Command::PTTL(cmd) => cmd.key(),
+            Command::Strlen(cmd) => cmd.key(),
+            Command::Dump(cmd) => cmd.key(),
+            Command::Hget(cmd) => cmd.key(),
+            Command::Hmget(cmd) => cmd.key(),
+            Command::Hlen(cmd) => cmd.key(),
+            Command::Hgetall(cmd) => cmd.key(),
+            Command::Hkeys(cmd) => cmd.key(),
+            Command::Hvals(cmd) => cmd.key(),
+            Command::Hexists(cmd) => cmd.key(),
+            Command::Hstrlen(cmd) => cmd.key(),
+            Command::Lrange(cmd) => cmd.key(),
+            Command::Llen(cmd) => cmd.key(),
+            Command::Lindex(cmd) => cmd.key(),
+            Command::Scard(cmd) => cmd.key(),
+            Command::Sismember(cmd) => cmd.key(),
+            Command::Smismember(cmd) => cmd.key(),
+            Command::Smembers(cmd) => cmd.key(),
+            Command::Srandmember(cmd) => cmd.key(),
+            Command::Zcard(cmd) => cmd.key(),
+            Command::Zscore(cmd) => cmd.key(),
+            Command::Zrange(cmd) => cmd.key(),
+            Command::Zrevrange(cmd) => cmd.key(),
+            Command::Zrangebyscore(cmd) | Command::Zrevrangebyscore(cmd) => cmd.key(),
+            Command::Zcount(cmd) => cmd.key(),
+            Command::Zrank(cmd) => cmd.key(),
+            _ => return vec![],
+        };
+        vec![key.to_owned()]
+    }
+
+    /// Returns the keys the command reads or writes, None if the command is not
+    /// on known keys, such commands are never applied concurrently in a pipeline.
+    pub(crate) fn keys(&self) -> Option<Vec<String>> {
+        let key = match self {
+            Command::Del(cmd) => return Some(cmd.keys().clone()),
+            Command::Mset(cmd) => return Some(cmd.keys().clone()),
+            Command::Set(cmd) => cmd.key(),
+            Command::SetNX(cmd) => cmd.key(),
+            Command::SetEX(cmd) => cmd.key(),
+            Command::Cas(cmd) | Command::Cad(cmd) => cmd.key(),
+            Command::Expire(cmd)
+            | Command::ExpireAt(cmd)
+            | Command::Pexpire(cmd)
+            | Command::PexpireAt(cmd) => cmd.key(),
+            Command::Persist(cmd) => cmd.key(),
+            Command::Incr(cmd) | Command::Decr(cmd) => cmd.key(),
+            Command::IncrBy(cmd) | Command::DecrBy(cmd) => cmd.key(),
+            Command::Restore(cmd) => cmd.key(),
+            Command::Hset(cmd) | Command::Hmset(cmd) | Command::Hsetnx(cmd) => cmd.key(),
+            Command::Hcas(cmd) => cmd.key(),
+            Command::Hdel(cmd) => cmd.key(),
+            Command::Hincrby(cmd) => cmd.key(),
+            Command::Lpush(cmd) | Command::Rpush(cmd) => cmd.key(),
+            Command::Lpop(cmd) | Command::Rpop(cmd) => cmd.key(),
+            Command::Lset(cmd) => cmd.key(),
+            Command::Ltrim(cmd) => cmd.key(),
+            Command::Lrem(cmd) => cmd.key(),
+            Command::Linsert(cmd) => cmd.key(),
+            Command::Sadd(cmd) => cmd.key(),
+            Command::Spop(cmd) => cmd.key(),
+            Command::Srem(cmd) => cmd.key(),
+            Command::Zadd(cmd) => cmd.key(),
+            Command::Zrem(cmd) => cmd.key(),
+            Command::Zremrangebyscore(cmd) => cmd.key(),
+            Command::Zremrangebyrank(cmd) => cmd.key(),
+            Command::Zpopmin(cmd) | Command::Zpopmax(cmd) => cmd.key(),
+            Command::Zincryby(cmd) => cmd.key(),
+            _ => {
+                let keys = self.read_keys();
+                return if keys.is_empty() { None } else { Some(keys) };
+            }
+        };
+        Some(vec![key.to_owned()])
+    }
+
+    /// Returns true if the command may modify the keyspace
+    pub(crate) fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Command::Del(_)
+                | Command::Set(_)
+                | Command::SetNX(_)
+                | Command::SetEX(_)
+                | Command::Cas(_)
+                | Command::Cad(_)
+                | Command::Mset(_)
+                | Command::Incr(_)
+                | Command::Decr(_)
+                | Command::IncrBy(_)
+                | Command::DecrBy(_)
+                | Command::Expire(_)
+                | Command::ExpireAt(_)
+                | Command::Pexpire(_)
+                | Command::PexpireAt(_)
+                | Command::Persist(_)
+                | Command::Rename(_)
+                | Command::RenameNX(_)
+                | Command::Copy(_)
+                | Command::Restore(_)
+                | Command::Hset(_)
+                | Command::Hmset(_)
+                | Command::Hsetnx(_)
+                | Command::Hcas(_)
+                | Command::Hdel(_)
+                | Command::Hincrby(_)
+                | Command::Lpush(_)
+                | Command::Rpush(_)
+                | Command::Lpop(_)
+                | Command::Rpop(_)
+                | Command::Lset(_)
+                | Command::Ltrim(_)
+                | Command::Lrem(_)
+                | Command::Linsert(_)
+                | Command::Sadd(_)
+                | Command::Spop(_)
+                | Command::Srem(_)
+                | Command::Zadd(_)
+                | Command::Zrem(_)
+                | Command::Zremrangebyscore(_)
+                | Command::Zremrangebyrank(_)
+                | Command::Zpopmin(_)
+                | Command::Zpopmax(_)
+                | Command::Zincryby(_)
+        )
+    }
 }
@@ -139,4 +139,8 @@ impl Invalid for Mset {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -1,12 +1,22 @@
 use std::sync::Arc;
 
+use mlua::Lua;
 use slog::{debug, error};
+use tikv_client::{Timestamp, TimestampExt, Transaction};
 use tokio::sync::Mutex;
 
 use crate::{
+    cmd::WatchedKeys,
     config::LOGGER,
-    tikv::{errors::REDIS_EXEC_ERR, get_txn_client},
-    utils::{resp_array, resp_err, resp_invalid_arguments, resp_nil},
+    db::Db,
+    notify::PendingEvents,
+    tikv::{
+        client::{gc_safepoint, is_write_conflict},
+        errors::{AsyncResult, RTError, REDIS_EXEC_ERR, REDIS_NOT_SUPPORTED_ERR},
+        get_txn_client,
+        string::StringCommandCtx,
+    },
+    utils::{resp_array, resp_err, resp_nil, resp_ok},
     Command, Connection, Frame,
 };
 
@@ -18,115 +28,89 @@ impl Multi {
         Multi {}
     }
 
-    pub async fn exec(self, dst: &mut Connection, cmds: Vec<Command>) -> crate::Result<()> {
+    pub async fn exec(
+        self,
+        dst: &mut Connection,
+        cmds: Vec<Command>,
+        watched_keys: WatchedKeys,
+        db: &Db,
+        lua: &Option<Lua>,
+    ) -> crate::Result<()> {
         let mut resp_arr = Vec::with_capacity(cmds.len());
 
-        // create new txn
+        // create new txn, if any key is watched it begins right after the watched keys are
+        // checked, and locks them so that the writes to them during EXEC conflict with it
         let client = get_txn_client()?;
-        let txn = client.begin().await?;
+        let (keys, watch_ts) = watched_keys.into_parts();
+        let watching = watch_ts.is_some();
+        let txn = match watch_ts {
+            // the versions since WATCH may be collected already, abort with a null reply
+            Some(ts) if ts.version() < gc_safepoint() => {
+                dst.write_frame(&resp_nil()).await?;
+                return Ok(());
+            }
+            Some(ts) => match Multi::check_watched_keys(&keys, ts).await {
+                Ok(Some(checked_ts)) => client.begin_at(checked_ts),
+                Ok(None) => client.begin().await?,
+                Err(RTError::TikvClient(e)) if is_write_conflict(&e) => {
+                    debug!(LOGGER, "watched keys are modified since WATCH, {}", e);
+                    dst.write_frame(&resp_nil()).await?;
+                    return Ok(());
+                }
+                Err(e) => {
+                    dst.write_frame(&resp_err(e)).await?;
+                    return Ok(());
+                }
+            },
+            None => client.begin().await?,
+        };
         let txn_rc = Some(Arc::new(Mutex::new(txn)));
 
+        if watching {
+            if let Err(e) = StringCommandCtx::new(txn_rc.clone())
+                .do_async_txnkv_lock_keys(&keys)
+                .await
+            {
+                txn_rc.unwrap().lock().await.rollback().await?;
+                dst.write_frame(&resp_err(e)).await?;
+                return Ok(());
+            }
+        }
+
         let mut response = resp_nil();
         let mut abort_on_error = false;
+        // keyspace events are published only if the txn commits
+        let events = PendingEvents::default();
 
         for cmd in cmds {
-            let result = match cmd {
-                Command::Incr(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
-                Command::IncrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
-                Command::Decr(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
-                Command::DecrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
-                Command::Strlen(cmd) => cmd.strlen(txn_rc.clone()).await,
-                Command::Del(cmd) => cmd.del(txn_rc.clone()).await,
-                Command::Exists(cmd) => cmd.exists(txn_rc.clone()).await,
-                Command::Get(cmd) => cmd.get(txn_rc.clone()).await,
-                Command::Set(cmd) => cmd.set(txn_rc.clone()).await,
-                Command::SetNX(cmd) => cmd.put_not_exists(txn_rc.clone()).await,
-                Command::SetEX(cmd) => cmd.setex(txn_rc.clone()).await,
-                Command::Mget(cmd) => cmd.batch_get(txn_rc.clone()).await,
-                Command::Mset(cmd) => cmd.batch_put(txn_rc.clone()).await,
-                Command::Type(cmd) => cmd.cmd_type(txn_rc.clone()).await,
-                Command::TTL(cmd) => cmd.ttl(false, txn_rc.clone()).await,
-                Command::PTTL(cmd) => cmd.ttl(true, txn_rc.clone()).await,
-                Command::Expire(cmd) => cmd.expire(false, false, txn_rc.clone()).await,
-                Command::ExpireAt(cmd) => cmd.expire(false, true, txn_rc.clone()).await,
-                Command::Pexpire(cmd) => cmd.expire(true, false, txn_rc.clone()).await,
-                Command::PexpireAt(cmd) => cmd.expire(true, true, txn_rc.clone()).await,
-                Command::Persist(cmd) => cmd.persist(txn_rc.clone()).await,
-                Command::Hset(cmd) => cmd.hset(txn_rc.clone(), false, false).await,
-                Command::Hmset(cmd) => cmd.hset(txn_rc.clone(), true, false).await,
-                Command::Hsetnx(cmd) => cmd.hset(txn_rc.clone(), false, true).await,
-                Command::Hget(cmd) => cmd.hget(txn_rc.clone()).await,
-                Command::Hmget(cmd) => cmd.hmget(txn_rc.clone()).await,
-                Command::Hlen(cmd) => cmd.hlen(txn_rc.clone()).await,
-                Command::Hgetall(cmd) => cmd.hgetall(txn_rc.clone()).await,
-                Command::Hdel(cmd) => cmd.hdel(txn_rc.clone()).await,
-                Command::Hkeys(cmd) => cmd.hkeys(txn_rc.clone()).await,
-                Command::Hvals(cmd) => cmd.hvals(txn_rc.clone()).await,
-                Command::Hincrby(cmd) => cmd.hincrby(txn_rc.clone()).await,
-                Command::Hexists(cmd) => cmd.hexists(txn_rc.clone()).await,
-                Command::Hstrlen(cmd) => cmd.hstrlen(txn_rc.clone()).await,
-                Command::Lpush(cmd) => cmd.push(txn_rc.clone(), true).await,
-                Command::Rpush(cmd) => cmd.push(txn_rc.clone(), false).await,
-                Command::Lpop(cmd) => cmd.pop(txn_rc.clone(), true).await,
-                Command::Rpop(cmd) => cmd.pop(txn_rc.clone(), false).await,
-                Command::Lrange(cmd) => cmd.lrange(txn_rc.clone()).await,
-                Command::Llen(cmd) => cmd.llen(txn_rc.clone()).await,
-                Command::Lindex(cmd) => cmd.lindex(txn_rc.clone()).await,
-                Command::Lset(cmd) => cmd.lset(txn_rc.clone()).await,
-                Command::Ltrim(cmd) => cmd.ltrim(txn_rc.clone()).await,
-                Command::Lrem(cmd) => cmd.lrem(txn_rc.clone()).await,
-                Command::Linsert(cmd) => cmd.linsert(txn_rc.clone()).await,
-                Command::Sadd(cmd) => cmd.sadd(txn_rc.clone()).await,
-                Command::Scard(cmd) => cmd.scard(txn_rc.clone()).await,
-                Command::Sismember(cmd) => cmd.sismember(txn_rc.clone()).await,
-                Command::Smismember(cmd) => cmd.smismember(txn_rc.clone()).await,
-                Command::Smembers(cmd) => cmd.smembers(txn_rc.clone()).await,
-                Command::Srandmember(cmd) => cmd.srandmember(txn_rc.clone()).await,
-                Command::Spop(cmd) => cmd.spop(txn_rc.clone()).await,
-                Command::Srem(cmd) => cmd.srem(txn_rc.clone()).await,
-                Command::Zadd(cmd) => cmd.zadd(txn_rc.clone()).await,
-                Command::Zcard(cmd) => cmd.zcard(txn_rc.clone()).await,
-                Command::Zscore(cmd) => cmd.zscore(txn_rc.clone()).await,
-                Command::Zrem(cmd) => cmd.zrem(txn_rc.clone()).await,
-                Command::Zremrangebyscore(cmd) => cmd.zremrangebyscore(txn_rc.clone()).await,
-                Command::Zremrangebyrank(cmd) => cmd.zremrangebyrank(txn_rc.clone()).await,
-                Command::Zrange(cmd) => cmd.zrange(txn_rc.clone()).await,
-                Command::Zrevrange(cmd) => cmd.zrevrange(txn_rc.clone()).await,
-                Command::Zrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), false).await,
-                Command::Zrevrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), true).await,
-                Command::Zcount(cmd) => cmd.zcount(txn_rc.clone()).await,
-                Command::Zpopmin(cmd) => cmd.zpop(txn_rc.clone(), true).await,
-                Command::Zpopmax(cmd) => cmd.zpop(txn_rc.clone(), false).await,
-                Command::Zrank(cmd) => cmd.zrank(txn_rc.clone()).await,
-                Command::Zincryby(cmd) => cmd.zincrby(txn_rc.clone()).await,
-                Command::Scan(cmd) => cmd.scan(txn_rc.clone()).await,
-                Command::Xscan(cmd) => cmd.scan(txn_rc.clone()).await,
-                _ => Ok(resp_invalid_arguments()),
-            };
+            let result = events
+                .defer(Multi::exec_queued_cmd(cmd, txn_rc.clone(), db, lua))
+                .await;
             match result {
-                Ok(resp) => {
-                    // check response error
-                    match resp {
-                        Frame::ErrorOwned(_) | Frame::ErrorString(_) => {
-                            response = resp_err(REDIS_EXEC_ERR);
-                            abort_on_error = true;
-                            break;
-                        }
-                        _ => resp_arr.push(resp),
-                    }
-                }
-                Err(e) => {
+                // like redis, an error of single command does not abort the others
+                Ok(resp) => resp_arr.push(resp),
+                // the txn can not be committed after a backend error
+                Err(RTError::TikvClient(e)) => {
                     error!(LOGGER, "EXECABORT {}", e);
                     response = resp_err(REDIS_EXEC_ERR);
                     abort_on_error = true;
                     break;
                 }
+                Err(e) => resp_arr.push(resp_err(e)),
             }
         }
 
         if !abort_on_error {
             response = resp_array(resp_arr);
-            txn_rc.unwrap().lock().await.commit().await?;
+            match txn_rc.unwrap().lock().await.commit().await {
+                Ok(_) => events.publish(),
+                // watched keys are modified during exec
+                Err(e) if watching && is_write_conflict(&e) => {
+                    debug!(LOGGER, "EXEC with watched keys failed to commit, {}", e);
+                    response = resp_nil();
+                }
+                Err(e) => return Err(e.into()),
+            }
         } else {
             txn_rc.unwrap().lock().await.rollback().await?;
         }
@@ -141,4 +125,130 @@ impl Multi {
         dst.write_frame(&response).await?;
         Ok(())
     }
+
+    // Lock the watched keys in a txn begun at `watch_ts` and commit it, which fails with a
+    // write conflict if any of them is modified since WATCH. Returns the commit timestamp,
+    // the keys are not modified before it.
+    async fn check_watched_keys(
+        keys: &[String],
+        watch_ts: Timestamp,
+    ) -> AsyncResult<Option<Timestamp>> {
+        let txn_rc = Arc::new(Mutex::new(get_txn_client()?.begin_at(watch_ts)));
+        if let Err(e) = StringCommandCtx::new(Some(txn_rc.clone()))
+            .do_async_txnkv_lock_keys(keys)
+            .await
+        {
+            txn_rc.lock().await.rollback().await?;
+            return Err(e);
+        }
+        let commit_ts = txn_rc.lock().await.commit().await?;
+        Ok(commit_ts)
+    }
+
+    /// Execute a command queued by MULTI or sent in an interactive txn, scripts are run in
+    /// the txn as well
+    pub(crate) async fn exec_queued_cmd(
+        cmd: Command,
+        txn_rc: Option<Arc<Mutex<Transaction>>>,
+        db: &Db,
+        lua: &Option<Lua>,
+    ) -> AsyncResult<Frame> {
+        match cmd {
+            Command::Eval(cmd) => cmd.eval(false, db, lua, txn_rc).await,
+            Command::Evalsha(cmd) => cmd.eval(true, db, lua, txn_rc).await,
+            Command::Fcall(cmd) => cmd.fcall(false, lua, txn_rc).await,
+            Command::FcallRo(cmd) => cmd.fcall(true, lua, txn_rc).await,
+            cmd => Multi::exec_cmd(cmd, txn_rc).await,
+        }
+    }
+
+    /// Execute a single command in the given txn, used by EXEC, interactive txn and scripts
+    pub(crate) async fn exec_cmd(
+        cmd: Command,
+        txn_rc: Option<Arc<Mutex<Transaction>>>,
+    ) -> AsyncResult<Frame> {
+        match cmd {
+            Command::Incr(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
+            Command::IncrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
+            Command::Decr(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
+            Command::DecrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
+            Command::Strlen(cmd) => cmd.strlen(txn_rc.clone()).await,
+            Command::Del(cmd) => cmd.del(txn_rc.clone()).await,
+            Command::Exists(cmd) => cmd.exists(txn_rc.clone()).await,
+            Command::Get(cmd) => cmd.get(txn_rc.clone()).await,
+            Command::Set(cmd) => cmd.set(txn_rc.clone()).await,
+            Command::SetNX(cmd) => cmd.put_not_exists(txn_rc.clone()).await,
+            Command::Cas(cmd) => cmd.cas(txn_rc.clone()).await,
+            Command::Cad(cmd) => cmd.cas(txn_rc.clone()).await,
+            Command::SetEX(cmd) => cmd.setex(txn_rc.clone()).await,
+            Command::Mget(cmd) => cmd.batch_get(txn_rc.clone()).await,
+            Command::Mset(cmd) => cmd.batch_put(txn_rc.clone()).await,
+            Command::Type(cmd) => cmd.cmd_type(txn_rc.clone()).await,
+            Command::TTL(cmd) => cmd.ttl(false, txn_rc.clone()).await,
+            Command::PTTL(cmd) => cmd.ttl(true, txn_rc.clone()).await,
+            Command::Expire(cmd) => cmd.expire(false, false, txn_rc.clone()).await,
+            Command::ExpireAt(cmd) => cmd.expire(false, true, txn_rc.clone()).await,
+            Command::Pexpire(cmd) => cmd.expire(true, false, txn_rc.clone()).await,
+            Command::PexpireAt(cmd) => cmd.expire(true, true, txn_rc.clone()).await,
+            Command::Persist(cmd) => cmd.persist(txn_rc.clone()).await,
+            Command::Rename(cmd) => cmd.rename(txn_rc.clone(), false).await,
+            Command::RenameNX(cmd) => cmd.rename(txn_rc.clone(), true).await,
+            Command::Copy(cmd) => cmd.copy(txn_rc.clone()).await,
+            Command::Dump(cmd) => cmd.dump(txn_rc.clone()).await,
+            Command::Restore(cmd) => cmd.restore(txn_rc.clone()).await,
+            Command::Hset(cmd) => cmd.hset(txn_rc.clone(), false, false).await,
+            Command::Hmset(cmd) => cmd.hset(txn_rc.clone(), true, false).await,
+            Command::Hsetnx(cmd) => cmd.hset(txn_rc.clone(), false, true).await,
+            Command::Hget(cmd) => cmd.hget(txn_rc.clone()).await,
+            Command::Hcas(cmd) => cmd.hcas(txn_rc.clone()).await,
+            Command::Hmget(cmd) => cmd.hmget(txn_rc.clone()).await,
+            Command::Hlen(cmd) => cmd.hlen(txn_rc.clone()).await,
+            Command::Hgetall(cmd) => cmd.hgetall(txn_rc.clone()).await,
+            Command::Hdel(cmd) => cmd.hdel(txn_rc.clone()).await,
+            Command::Hkeys(cmd) => cmd.hkeys(txn_rc.clone()).await,
+            Command::Hvals(cmd) => cmd.hvals(txn_rc.clone()).await,
+            Command::Hincrby(cmd) => cmd.hincrby(txn_rc.clone()).await,
+            Command::Hexists(cmd) => cmd.hexists(txn_rc.clone()).await,
+            Command::Hstrlen(cmd) => cmd.hstrlen(txn_rc.clone()).await,
+            Command::Lpush(cmd) => cmd.push(txn_rc.clone(), true).await,
+            Command::Rpush(cmd) => cmd.push(txn_rc.clone(), false).await,
+            Command::Lpop(cmd) => cmd.pop(txn_rc.clone(), true).await,
+            Command::Rpop(cmd) => cmd.pop(txn_rc.clone(), false).await,
+            Command::Lrange(cmd) => cmd.lrange(txn_rc.clone()).await,
+            Command::Llen(cmd) => cmd.llen(txn_rc.clone()).await,
+            Command::Lindex(cmd) => cmd.lindex(txn_rc.clone()).await,
+            Command::Lset(cmd) => cmd.lset(txn_rc.clone()).await,
+            Command::Ltrim(cmd) => cmd.ltrim(txn_rc.clone()).await,
+            Command::Lrem(cmd) => cmd.lrem(txn_rc.clone()).await,
+            Command::Linsert(cmd) => cmd.linsert(txn_rc.clone()).await,
+            Command::Sadd(cmd) => cmd.sadd(txn_rc.clone()).await,
+            Command::Scard(cmd) => cmd.scard(txn_rc.clone()).await,
+            Command::Sismember(cmd) => cmd.sismember(txn_rc.clone()).await,
+            Command::Smismember(cmd) => cmd.smismember(txn_rc.clone()).await,
+            Command::Smembers(cmd) => cmd.smembers(txn_rc.clone()).await,
+            Command::Srandmember(cmd) => cmd.srandmember(txn_rc.clone()).await,
+            Command::Spop(cmd) => cmd.spop(txn_rc.clone()).await,
+            Command::Srem(cmd) => cmd.srem(txn_rc.clone()).await,
+            Command::Zadd(cmd) => cmd.zadd(txn_rc.clone()).await,
+            Command::Zcard(cmd) => cmd.zcard(txn_rc.clone()).await,
+            Command::Zscore(cmd) => cmd.zscore(txn_rc.clone()).await,
+            Command::Zrem(cmd) => cmd.zrem(txn_rc.clone()).await,
+            Command::Zremrangebyscore(cmd) => cmd.zremrangebyscore(txn_rc.clone()).await,
+            Command::Zremrangebyrank(cmd) => cmd.zremrangebyrank(txn_rc.clone()).await,
+            Command::Zrange(cmd) => cmd.zrange(txn_rc.clone()).await,
+            Command::Zrevrange(cmd) => cmd.zrevrange(txn_rc.clone()).await,
+            Command::Zrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), false).await,
+            Command::Zrevrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), true).await,
+            Command::Zcount(cmd) => cmd.zcount(txn_rc.clone()).await,
+            Command::Zpopmin(cmd) => cmd.zpop(txn_rc.clone(), true).await,
+            Command::Zpopmax(cmd) => cmd.zpop(txn_rc.clone(), false).await,
+            Command::Zrank(cmd) => cmd.zrank(txn_rc.clone()).await,
+            Command::Zincryby(cmd) => cmd.zincrby(txn_rc.clone()).await,
+            Command::Scan(cmd) => cmd.scan(txn_rc.clone()).await,
+            Command::Xscan(cmd) => cmd.scan(txn_rc.clone()).await,
+            Command::Keys(cmd) => cmd.keys(txn_rc.clone()).await,
+            Command::Unwatch(_) => Ok(resp_ok()),
+            _ => Ok(resp_err(REDIS_NOT_SUPPORTED_ERR)),
+        }
+    }
 }
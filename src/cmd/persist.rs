@@ -84,4 +84,8 @@ impl Invalid for Persist {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
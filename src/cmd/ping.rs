@@ -79,6 +79,31 @@ impl Ping {
 
         Ok(())
     }
+
+    /// Apply the `Ping` command received in the subscribed state, the reply is a
+    /// `pong` message along with the argument, or an empty bulk without one.
+    pub(crate) async fn apply_subscribed(self, dst: &mut Connection) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        let mut response = Frame::array();
+        response.push_bulk(Bytes::from_static(b"pong"));
+        response.push_bulk(Bytes::from(self.msg.unwrap_or_default()));
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
 }
 
 impl Default for Ping {
@@ -97,4 +122,8 @@ impl Invalid for Ping {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
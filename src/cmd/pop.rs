@@ -103,4 +103,8 @@ impl Invalid for Pop {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -1,3 +1,4 @@
+use crate::cluster::{key_slot, Cluster};
 use crate::{Connection, Db, Frame, Parse};
 
 use bytes::Bytes;
@@ -74,7 +75,12 @@ impl Publish {
     ///
     /// The response is written to `dst`. This is called by the server in order
     /// to execute a received command.
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        topo: &Cluster,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
         if !self.valid {
             dst.write_frame(&resp_invalid_arguments()).await?;
             return Ok(());
@@ -89,7 +95,11 @@ impl Publish {
         // receive the message. Subscribers may drop before receiving the
         // message. Given this, `num_subscribers` should only be used as a
         // "hint".
-        let num_subscribers = db.publish(&self.channel, self.message);
+        let mut num_subscribers = db.publish(&self.channel, self.message.clone());
+
+        // The message is also published on the other instances of the cluster, so
+        // the subscribers connected to them receive it as well.
+        num_subscribers += topo.publish_to_peers(&self.channel, &self.message).await;
 
         // The number of subscribers is returned as the response to the publish
         // request.
@@ -101,6 +111,29 @@ impl Publish {
         Ok(())
     }
 
+    /// Apply the `SPUBLISH` command, the message is only delivered by the instance
+    /// owning the slot of the shard channel, others reply with a MOVED error.
+    pub(crate) async fn apply_sharded(
+        self,
+        db: &Db,
+        topo: &Cluster,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        let response = match topo.moved_error(key_slot(self.channel.as_bytes())) {
+            Some(moved) => moved,
+            None => Frame::Integer(db.spublish(&self.channel, self.message) as i64),
+        };
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
     #[allow(dead_code)]
     /// Converts the command into an equivalent `Frame`.
     ///
@@ -124,4 +157,8 @@ impl Invalid for Publish {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
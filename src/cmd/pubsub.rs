@@ -0,0 +1,113 @@
+use crate::cluster::{key_slot, Cluster};
+use crate::cmd::Invalid;
+use crate::config::LOGGER;
+use crate::tikv::errors::REDIS_UNKNOWN_SUBCOMMAND;
+use crate::utils::{resp_array, resp_bulk, resp_err, resp_int, resp_invalid_arguments};
+use crate::{Connection, Db, Parse};
+use slog::debug;
+
+/// Introspects the state of the pub/sub subsystem of this instance.
+///
+/// ```text
+/// PUBSUB CHANNELS [pattern]
+/// PUBSUB NUMSUB [channel [channel ...]]
+/// PUBSUB NUMPAT
+/// PUBSUB SHARDCHANNELS [pattern]
+/// PUBSUB SHARDNUMSUB [shardchannel [shardchannel ...]]
+/// ```
+///
+/// Shard channels in the slots not owned by this instance are not reported.
+#[derive(Debug, Clone)]
+pub struct Pubsub {
+    subcommand: String,
+    args: Vec<String>,
+    valid: bool,
+}
+
+impl Pubsub {
+    pub fn new(subcommand: impl ToString) -> Pubsub {
+        Pubsub {
+            subcommand: subcommand.to_string(),
+            args: vec![],
+            valid: true,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Pubsub> {
+        let subcommand = parse.next_string()?;
+        let mut pubsub = Pubsub::new(subcommand);
+        while let Ok(arg) = parse.next_string() {
+            pubsub.args.push(arg);
+        }
+
+        Ok(pubsub)
+    }
+
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        topo: &Cluster,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        let response = match self.subcommand.to_uppercase().as_str() {
+            "CHANNELS" | "SHARDCHANNELS" if self.args.len() <= 1 => {
+                let sharded = self.subcommand.eq_ignore_ascii_case("SHARDCHANNELS");
+                let channels = db.pubsub_channels(self.args.first().map(|p| p.as_str()), sharded);
+                resp_array(
+                    channels
+                        .into_iter()
+                        .filter(|channel| !sharded || topo.owns_slot(key_slot(channel.as_bytes())))
+                        .map(|channel| resp_bulk(channel.into_bytes()))
+                        .collect(),
+                )
+            }
+            "NUMSUB" | "SHARDNUMSUB" => {
+                let sharded = self.subcommand.eq_ignore_ascii_case("SHARDNUMSUB");
+                let mut counts = Vec::with_capacity(self.args.len() * 2);
+                for channel in self.args {
+                    let numsub = if sharded && !topo.owns_slot(key_slot(channel.as_bytes())) {
+                        0
+                    } else {
+                        db.pubsub_numsub(&channel, sharded) as i64
+                    };
+                    counts.push(resp_bulk(channel.into_bytes()));
+                    counts.push(resp_int(numsub));
+                }
+                resp_array(counts)
+            }
+            "NUMPAT" if self.args.is_empty() => resp_int(db.pubsub_numpat() as i64),
+            _ => resp_err(REDIS_UNKNOWN_SUBCOMMAND),
+        };
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Invalid for Pubsub {
+    fn new_invalid() -> Pubsub {
+        Pubsub {
+            subcommand: "".to_owned(),
+            args: vec![],
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
@@ -105,4 +105,8 @@ impl Invalid for Push {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
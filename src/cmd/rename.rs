@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use crate::cmd::Invalid;
+use crate::config::is_use_txn_api;
+use crate::config::LOGGER;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use slog::debug;
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Rename {
+    src: String,
+    dst: String,
+    valid: bool,
+}
+
+impl Rename {
+    pub fn new(src: impl ToString, dst: impl ToString) -> Rename {
+        Rename {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            valid: true,
+        }
+    }
+
+    /// Get the source key
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// Get the destination key
+    pub fn dst(&self) -> &str {
+        &self.dst
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Rename> {
+        let src = parse.next_string()?;
+        let dst = parse.next_string()?;
+
+        Ok(Rename::new(src, dst))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Rename> {
+        if argv.len() != 2 {
+            return Ok(Rename::new_invalid());
+        }
+        Ok(Rename::new(
+            String::from_utf8_lossy(&argv[0]),
+            String::from_utf8_lossy(&argv[1]),
+        ))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection, is_nx: bool) -> crate::Result<()> {
+        let response = self.rename(None, is_nx).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn rename(
+        self,
+        txn: Option<Arc<Mutex<Transaction>>>,
+        is_nx: bool,
+    ) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_rename(&self.src, &self.dst, is_nx)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Rename {
+    fn new_invalid() -> Rename {
+        Rename {
+            src: "".to_owned(),
+            dst: "".to_owned(),
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
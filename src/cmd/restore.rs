@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use crate::cmd::Invalid;
+use crate::config::is_use_txn_api;
+use crate::config::LOGGER;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_INVALID_FREQ_ERR, REDIS_INVALID_IDLETIME_ERR, REDIS_INVALID_TTL_ERR,
+    REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments, timestamp_from_ttl};
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use slog::debug;
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Restore {
+    key: String,
+    ttl: i64,
+    payload: Bytes,
+    replace: bool,
+    absttl: bool,
+    idletime: Option<i64>,
+    freq: Option<i64>,
+    valid: bool,
+}
+
+impl Restore {
+    pub fn new(key: impl ToString, ttl: i64, payload: Bytes) -> Restore {
+        Restore {
+            key: key.to_string(),
+            ttl,
+            payload,
+            replace: false,
+            absttl: false,
+            idletime: None,
+            freq: None,
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    // options are shared by frames and argv parsing, return false on syntax error
+    fn set_option(&mut self, flag: &str, arg: Option<i64>) -> bool {
+        match flag.to_uppercase().as_str() {
+            "REPLACE" => self.replace = true,
+            "ABSTTL" => self.absttl = true,
+            // IDLETIME and FREQ are exclusive, as the object uses either LRU or LFU
+            "IDLETIME" if self.freq.is_none() => match arg {
+                Some(v) => self.idletime = Some(v),
+                None => return false,
+            },
+            "FREQ" if self.idletime.is_none() => match arg {
+                Some(v) => self.freq = Some(v),
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn has_arg(flag: &str) -> bool {
+        matches!(flag.to_uppercase().as_str(), "IDLETIME" | "FREQ")
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Restore> {
+        let key = parse.next_string()?;
+        let ttl = parse.next_int()?;
+        let payload = parse.next_bytes()?;
+        let mut restore = Restore::new(key, ttl, payload);
+
+        while let Ok(flag) = parse.next_string() {
+            let arg = if Restore::has_arg(&flag) {
+                parse.next_int().ok()
+            } else {
+                None
+            };
+            if !restore.set_option(&flag, arg) {
+                return Ok(Restore::new_invalid());
+            }
+        }
+
+        Ok(restore)
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Restore> {
+        if argv.len() < 3 {
+            return Ok(Restore::new_invalid());
+        }
+        let ttl = match String::from_utf8_lossy(&argv[1]).parse::<i64>() {
+            Ok(v) => v,
+            Err(_) => return Ok(Restore::new_invalid()),
+        };
+        let mut restore = Restore::new(String::from_utf8_lossy(&argv[0]), ttl, argv[2].clone());
+
+        let mut idx = 3;
+        while idx < argv.len() {
+            let flag = String::from_utf8_lossy(&argv[idx]).to_string();
+            let arg = if Restore::has_arg(&flag) {
+                idx += 1;
+                argv.get(idx)
+                    .and_then(|v| String::from_utf8_lossy(v).parse::<i64>().ok())
+            } else {
+                None
+            };
+            if !restore.set_option(&flag, arg) {
+                return Ok(Restore::new_invalid());
+            }
+            idx += 1;
+        }
+
+        Ok(restore)
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.restore(None).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn restore(self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.ttl < 0 {
+            return Ok(resp_err(REDIS_INVALID_TTL_ERR));
+        }
+        // tidis does not track access time and frequency, the options are validated only
+        if matches!(self.idletime, Some(v) if v < 0) {
+            return Ok(resp_err(REDIS_INVALID_IDLETIME_ERR));
+        }
+        if matches!(self.freq, Some(v) if !(0..=255).contains(&v)) {
+            return Ok(resp_err(REDIS_INVALID_FREQ_ERR));
+        }
+
+        let ttl = if self.ttl == 0 || self.absttl {
+            self.ttl as u64
+        } else {
+            timestamp_from_ttl(self.ttl as u64)
+        };
+
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_restore(&self.key, &self.payload, ttl, self.replace)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Restore {
+    fn new_invalid() -> Restore {
+        Restore {
+            key: "".to_owned(),
+            ttl: 0,
+            payload: Bytes::new(),
+            replace: false,
+            absttl: false,
+            idletime: None,
+            freq: None,
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
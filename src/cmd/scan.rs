@@ -17,87 +17,63 @@ use crate::config::LOGGER;
 pub struct Scan {
     start: String,
     count: i64,
-    regex: String,
+    pattern: Bytes,
+    key_type: Option<String>,
     valid: bool,
 }
 
 impl Scan {
-    pub fn new(start: String, count: i64, regex: String) -> Scan {
+    pub fn new(start: String, count: i64, pattern: Bytes, key_type: Option<String>) -> Scan {
         Scan {
             start,
             count,
-            regex,
+            pattern,
+            key_type,
             valid: true,
         }
     }
 
-    pub fn valid(&self) -> bool {
-        self.valid
+    // apply the option and its argument, return false if it is invalid
+    fn set_option(&mut self, flag: &[u8], arg: Bytes) -> bool {
+        match flag.to_ascii_uppercase().as_slice() {
+            b"COUNT" => match String::from_utf8_lossy(&arg).parse::<i64>() {
+                Ok(c) if c >= 1 => self.count = c,
+                _ => return false,
+            },
+            b"MATCH" => self.pattern = arg,
+            b"TYPE" => self.key_type = Some(String::from_utf8_lossy(&arg).to_string()),
+            _ => return false,
+        }
+        true
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Scan> {
         let start = parse.next_string()?;
-        let mut count = 10;
-        let mut regex = ".*?".to_owned();
-        while let Ok(flag) = parse.next_string() {
-            if flag.to_uppercase().as_str() == "COUNT" {
-                if let Ok(c) = parse.next_int() {
-                    count = c;
-                };
-            } else if flag.to_uppercase().as_str() == "MATCH" {
-                regex = parse.next_string()?;
+        let mut scan = Scan::new(start, 10, Bytes::from_static(b"*"), None);
+        while let Ok(flag) = parse.next_bytes() {
+            let arg = parse.next_bytes()?;
+            if !scan.set_option(&flag, arg) {
+                return Ok(Scan::new_invalid());
             }
         }
 
-        Ok(Scan {
-            start,
-            count,
-            regex,
-            valid: true,
-        })
+        Ok(scan)
     }
 
     pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Scan> {
-        if argv.is_empty() || argv.len() > 5 {
+        if argv.is_empty() || argv.len() % 2 != 1 {
             return Ok(Scan::new_invalid());
         }
 
-        let mut count = 10;
-        let mut regex = ".*?".to_owned();
-        let start = String::from_utf8_lossy(&argv[0]);
-        if argv.len() >= 3 {
-            if argv[1].to_ascii_uppercase() == b"COUNT" {
-                if let Ok(c) = String::from_utf8_lossy(&argv[2]).parse::<i64>() {
-                    count = c;
-                } else {
-                    return Ok(Scan::new_invalid());
-                }
-            } else if argv[1].to_ascii_uppercase() == b"MATCH" {
-                regex = String::from_utf8_lossy(&argv[2]).to_string();
-            } else {
+        let start = String::from_utf8_lossy(&argv[0]).to_string();
+        let mut scan = Scan::new(start, 10, Bytes::from_static(b"*"), None);
+        for option in argv[1..].chunks(2) {
+            if !scan.set_option(&option[0], option[1].clone()) {
                 return Ok(Scan::new_invalid());
             }
-            if argv.len() == 5 {
-                if argv[3].to_ascii_uppercase() == b"COUNT" {
-                    if let Ok(c) = String::from_utf8_lossy(&argv[4]).parse::<i64>() {
-                        count = c;
-                    } else {
-                        return Ok(Scan::new_invalid());
-                    }
-                } else if argv[3].to_ascii_uppercase() == b"MATCH" {
-                    regex = String::from_utf8_lossy(&argv[4]).to_string();
-                } else {
-                    return Ok(Scan::new_invalid());
-                }
-            }
         }
 
-        Ok(Scan {
-            start: start.to_string(),
-            count,
-            regex,
-            valid: true,
-        })
+        Ok(scan)
     }
 
     pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
@@ -120,7 +96,12 @@ impl Scan {
         }
         if is_use_txn_api() {
             StringCommandCtx::new(txn)
-                .do_async_txnkv_scan(&self.start, self.count.try_into().unwrap(), &self.regex)
+                .do_async_txnkv_scan(
+                    &self.start,
+                    self.count.try_into().unwrap_or(u32::MAX),
+                    &self.pattern,
+                    self.key_type.as_deref(),
+                )
                 .await
         } else {
             Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
@@ -133,8 +114,13 @@ impl Invalid for Scan {
         Scan {
             start: "".to_owned(),
             count: 0,
-            regex: "".to_owned(),
+            pattern: Bytes::new(),
+            key_type: None,
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -83,4 +83,8 @@ impl Invalid for Scard {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
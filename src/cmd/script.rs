@@ -1,26 +1,182 @@
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 use crate::cmd::Invalid;
 use crate::config::LOGGER;
+use crate::config_lua_time_limit_or_default;
 use crate::db::Db;
-use crate::tikv::errors::AsyncResult;
-use crate::utils::{resp_array, resp_bulk, resp_int, resp_invalid_arguments, resp_ok, sha1hex};
+use crate::tikv::errors::{AsyncResult, REDIS_NOTBUSY_ERR, REDIS_UNKILLABLE_ERR};
+use crate::tikv::ldb::LdbMode;
+use crate::tikv::script::ScriptCommandCtx;
+use crate::utils::{
+    resp_array, resp_bulk, resp_err, resp_int, resp_invalid_arguments, resp_ok, sha1hex,
+};
 use crate::{Connection, Frame, Parse};
 use bytes::Bytes;
-use slog::debug;
+use mlua::{HookTriggers, Lua};
+use slog::{debug, warn};
 
-static SCRIPT_KILLED: AtomicBool = AtomicBool::new(false);
+/// Script running on a connection
+struct RunningScript {
+    started: Instant,
+    /// function called by FCALL, or script called by EVAL
+    function: bool,
+    /// scripts which called write commands can not be killed
+    written: AtomicBool,
+    killed: AtomicBool,
+    /// slow script is logged once
+    reported: AtomicBool,
+    /// keys declared by the script, None if unknown
+    keys: Option<Vec<String>>,
+}
+
+impl RunningScript {
+    // checked by the lua hook, returns true if the script is killed
+    fn check_killed(&self, client_id: u64) -> bool {
+        if let Some(limit) = lua_time_limit() {
+            if self.started.elapsed() > limit && !self.reported.swap(true, Ordering::Relaxed) {
+                warn!(
+                    LOGGER,
+                    "Slow script detected: still in execution after {} milliseconds, client id {}",
+                    limit.as_millis(),
+                    client_id
+                );
+            }
+        }
+        self.killed.load(Ordering::Relaxed)
+    }
+
+    // commands on unknown keys contend with every script
+    fn contends(&self, keys: Option<&[String]>) -> bool {
+        match (&self.keys, keys) {
+            (Some(script_keys), Some(keys)) => keys.iter().any(|k| script_keys.contains(k)),
+            _ => true,
+        }
+    }
+
+    // scripts running longer than lua-time-limit are seen by the other connections
+    fn is_slow(&self) -> bool {
+        match lua_time_limit() {
+            Some(limit) => self.started.elapsed() > limit,
+            None => true,
+        }
+    }
+}
 
-pub fn script_interuptted() -> bool {
-    SCRIPT_KILLED.load(Ordering::Relaxed)
+// the lua hook checks the running script every this many instructions
+const SCRIPT_HOOK_INSTRUCTIONS: u32 = 1000;
+
+lazy_static! {
+    // running scripts of this instance by client id
+    static ref RUNNING_SCRIPTS: RwLock<HashMap<u64, Arc<RunningScript>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Registers the script of the connection as running until it is dropped
+pub struct ScriptRunGuard {
+    client_id: u64,
+    script: Arc<RunningScript>,
+}
+
+impl ScriptRunGuard {
+    pub fn new(client_id: u64, function: bool, keys: Option<Vec<String>>) -> ScriptRunGuard {
+        let script = RunningScript {
+            started: Instant::now(),
+            function,
+            written: AtomicBool::new(false),
+            killed: AtomicBool::new(false),
+            reported: AtomicBool::new(false),
+            keys,
+        };
+        let script = Arc::new(script);
+        RUNNING_SCRIPTS
+            .write()
+            .unwrap()
+            .insert(client_id, script.clone());
+        ScriptRunGuard { client_id, script }
+    }
+
+    /// Abort the script run by `lua` once it is killed by SCRIPT KILL, the hook
+    /// holds the script so the running scripts are not looked up.
+    pub fn set_hook(&self, lua: &Lua) -> mlua::Result<()> {
+        let client_id = self.client_id;
+        let script = self.script.clone();
+        let triggers = HookTriggers {
+            every_nth_instruction: Some(SCRIPT_HOOK_INSTRUCTIONS),
+            ..Default::default()
+        };
+        lua.set_hook(triggers, move |_lua, _debug| {
+            if script.check_killed(client_id) {
+                warn!(LOGGER, "Script killed by user with SCRIPT KILL...");
+                return Err(mlua::Error::RuntimeError(
+                    "Script killed by user with SCRIPT KILL...".to_string(),
+                ));
+            }
+            Ok(())
+        })
+    }
 }
 
-pub fn script_set_killed() {
-    SCRIPT_KILLED.store(true, Ordering::Relaxed)
+impl Drop for ScriptRunGuard {
+    fn drop(&mut self) {
+        RUNNING_SCRIPTS.write().unwrap().remove(&self.client_id);
+    }
 }
 
-pub fn script_clear_killed() {
-    SCRIPT_KILLED.store(false, Ordering::Relaxed)
+fn lua_time_limit() -> Option<Duration> {
+    match config_lua_time_limit_or_default() {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+/// The script of the connection called a write command, it can not be killed any more
+pub fn script_set_written(client_id: u64) {
+    if let Some(script) = RUNNING_SCRIPTS.read().unwrap().get(&client_id) {
+        script.written.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Returns true if a script of other connections runs longer than lua-time-limit and
+/// declared any of `keys`, commands on them except SCRIPT KILL and FUNCTION KILL are
+/// replied with -BUSY then. Commands on unknown keys, given as None, contend with all
+/// the slow scripts.
+pub fn script_busy(client_id: u64, keys: Option<&[String]>) -> bool {
+    if lua_time_limit().is_none() {
+        return false;
+    }
+    RUNNING_SCRIPTS
+        .read()
+        .unwrap()
+        .iter()
+        .any(|(id, script)| *id != client_id && script.is_slow() && script.contends(keys))
+}
+
+/// Kill the scripts, or functions if `function` is set, which run longer than
+/// lua-time-limit and have not called any write commands. The scripts not slow yet
+/// are not seen as busy, so they are not killed.
+pub fn script_kill(function: bool) -> Frame {
+    let scripts = RUNNING_SCRIPTS.read().unwrap();
+    let mut running = scripts
+        .values()
+        .filter(|s| s.function == function && s.is_slow())
+        .peekable();
+    if running.peek().is_none() {
+        return resp_err(REDIS_NOTBUSY_ERR);
+    }
+
+    let mut killed = false;
+    for script in running.filter(|s| !s.written.load(Ordering::Relaxed)) {
+        script.killed.store(true, Ordering::Relaxed);
+        killed = true;
+    }
+    if killed {
+        resp_ok()
+    } else {
+        resp_err(REDIS_UNKILLABLE_ERR)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +187,8 @@ pub struct Script {
     is_exists: bool,
     is_flush: bool,
     is_kill: bool,
+    is_debug: bool,
+    debug_mode: Option<LdbMode>,
     valid: bool,
 }
 
@@ -40,6 +198,7 @@ impl Script {
         let mut is_exists = false;
         let mut is_flush = false;
         let mut is_kill = false;
+        let mut is_debug = false;
         match subcommand.to_uppercase().as_str() {
             "LOAD" => {
                 is_load = true;
@@ -53,6 +212,9 @@ impl Script {
             "KILL" => {
                 is_kill = true;
             }
+            "DEBUG" => {
+                is_debug = true;
+            }
             _ => {}
         }
         Script {
@@ -62,6 +224,8 @@ impl Script {
             is_exists,
             is_flush,
             is_kill,
+            is_debug,
+            debug_mode: None,
             valid: true,
         }
     }
@@ -74,6 +238,19 @@ impl Script {
         self.sha1_vec.push(sha1.to_owned());
     }
 
+    pub fn is_kill(&self) -> bool {
+        self.is_kill
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.is_debug
+    }
+
+    /// Mode of SCRIPT DEBUG, None for SCRIPT DEBUG NO
+    pub fn debug_mode(&self) -> Option<LdbMode> {
+        self.debug_mode
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Script> {
         let subcommand = parse.next_string()?;
         let mut cmd = Script::new(&subcommand);
@@ -88,6 +265,15 @@ impl Script {
                 }
             }
             "FLUSH" => {}
+            "DEBUG" => {
+                let mode = parse.next_string().unwrap_or_default();
+                match mode.to_uppercase().as_str() {
+                    "YES" => cmd.debug_mode = Some(LdbMode::Async),
+                    "SYNC" => cmd.debug_mode = Some(LdbMode::Sync),
+                    "NO" => {}
+                    _ => cmd.valid = false,
+                }
+            }
             _ => {}
         }
 
@@ -118,19 +304,28 @@ impl Script {
         if self.is_load {
             // calculate script sha1
             let sha1_str = sha1hex(&self.script);
-            db.set_script(sha1_str.clone(), Bytes::from(self.script.clone()));
+            let script = Bytes::from(self.script.clone());
+            // persist in tikv, so that it can be found by all instances
+            ScriptCommandCtx::new(None)
+                .do_async_txnkv_script_load(&sha1_str, &script)
+                .await?;
+            db.set_script(sha1_str.clone(), script);
             return Ok(resp_bulk(sha1_str.as_bytes().to_vec()));
         } else if self.is_flush {
-            db.flush_script();
+            let epoch = ScriptCommandCtx::new(None)
+                .do_async_txnkv_script_flush()
+                .await?;
+            db.sync_script_epoch(epoch);
             return Ok(resp_ok());
         } else if self.is_exists {
-            let mut resp = vec![];
-            for sha1 in &self.sha1_vec {
-                resp.push(resp_int(if db.get_script(sha1).is_some() { 1 } else { 0 }));
-            }
+            let sha1s: Vec<String> = self.sha1_vec.iter().map(|s| s.to_lowercase()).collect();
+            let exists = ScriptCommandCtx::new(None)
+                .do_async_txnkv_script_exists(&sha1s)
+                .await?;
+            let resp = exists.into_iter().map(|e| resp_int(e as i64)).collect();
             return Ok(resp_array(resp));
         } else if self.is_kill {
-            script_set_killed();
+            return Ok(script_kill(false));
         }
         Ok(resp_ok())
     }
@@ -145,7 +340,13 @@ impl Invalid for Script {
             is_exists: false,
             is_flush: false,
             is_kill: false,
+            is_debug: false,
+            debug_mode: None,
             valid: true,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
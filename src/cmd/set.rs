@@ -259,4 +259,8 @@ impl Invalid for Set {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
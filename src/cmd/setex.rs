@@ -136,4 +136,8 @@ impl Invalid for SetEX {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -134,4 +134,8 @@ impl Invalid for SetNX {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -29,6 +29,10 @@ impl Sismember {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Sismember> {
         let key = parse.next_string()?;
         let member = parse.next_string()?;
@@ -87,4 +91,8 @@ impl Invalid for Sismember {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -86,4 +86,8 @@ impl Invalid for Smembers {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -98,4 +98,8 @@ impl Invalid for Smismember {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -0,0 +1,97 @@
+use crate::cmd::Invalid;
+use crate::config::LOGGER;
+use crate::tikv::client::{gc_safepoint, tso_from_unix_ms_or_tso, READ_OPTIONS};
+use crate::tikv::errors::{
+    AsyncResult, REDIS_SNAPSHOT_IN_FUTURE_ERR, REDIS_SNAPSHOT_TOO_OLD_ERR, REDIS_UNKNOWN_SUBCOMMAND,
+};
+use crate::tikv::get_txn_client;
+use crate::utils::{resp_err, resp_invalid_arguments, resp_ok};
+use crate::{Connection, Frame, Parse};
+use slog::debug;
+use tikv_client::TimestampExt;
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    subcommand: String,
+    timestamp: Option<u64>,
+    valid: bool,
+}
+
+impl Snapshot {
+    pub fn new(subcommand: impl ToString, timestamp: Option<u64>) -> Snapshot {
+        Snapshot {
+            subcommand: subcommand.to_string(),
+            timestamp,
+            valid: true,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Snapshot> {
+        let subcommand = parse.next_string()?;
+        let timestamp = match subcommand.to_uppercase().as_str() {
+            "AT" => match parse.next_int() {
+                Ok(v) if v > 0 => Some(v as u64),
+                _ => return Ok(Snapshot::new_invalid()),
+            },
+            _ => None,
+        };
+
+        Ok(Snapshot::new(subcommand, timestamp))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.snapshot().await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    async fn snapshot(self) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+
+        match self.subcommand.to_uppercase().as_str() {
+            "AT" => {
+                let version = tso_from_unix_ms_or_tso(self.timestamp.unwrap());
+                if version < gc_safepoint() {
+                    return Ok(resp_err(REDIS_SNAPSHOT_TOO_OLD_ERR));
+                }
+                let current_ts = get_txn_client()?.current_timestamp().await?;
+                if version > current_ts.version() {
+                    return Ok(resp_err(REDIS_SNAPSHOT_IN_FUTURE_ERR));
+                }
+                let _ = READ_OPTIONS.try_with(|o| o.snapshot_ts.set(Some(version)));
+                Ok(resp_ok())
+            }
+            "RESET" => {
+                let _ = READ_OPTIONS.try_with(|o| o.snapshot_ts.set(None));
+                Ok(resp_ok())
+            }
+            _ => Ok(resp_err(REDIS_UNKNOWN_SUBCOMMAND)),
+        }
+    }
+}
+
+impl Invalid for Snapshot {
+    fn new_invalid() -> Snapshot {
+        Snapshot {
+            subcommand: "".to_owned(),
+            timestamp: None,
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
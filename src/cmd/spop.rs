@@ -29,6 +29,11 @@ impl Spop {
         }
     }
 
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Spop> {
         let key = parse.next_string()?;
 
@@ -93,4 +98,8 @@ impl Invalid for Spop {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
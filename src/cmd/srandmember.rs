@@ -29,6 +29,10 @@ impl Srandmember {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Srandmember> {
         let key = parse.next_string()?;
 
@@ -110,4 +114,8 @@ impl Invalid for Srandmember {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
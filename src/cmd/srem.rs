@@ -99,4 +99,8 @@ impl Invalid for Srem {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -85,4 +85,8 @@ impl Invalid for Strlen {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
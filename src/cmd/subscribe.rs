@@ -1,18 +1,23 @@
-use crate::cmd::{Invalid, Parse, ParseError, Unknown};
+use crate::cluster::{key_slot, Cluster};
+use crate::cmd::{Invalid, Parse, ParseError};
+use crate::config_cluster_topology_interval_or_default;
+use crate::tikv::errors::REDIS_CROSSSLOT_ERR;
+use crate::tracking::{Invalidation, INVALIDATE_CHANNEL};
+use crate::utils::{resp_err, resp_invalid_arguments};
 use crate::{Command, Connection, Db, Frame, Shutdown};
 
-use crate::utils::resp_invalid_arguments;
 use bytes::Bytes;
 use std::pin::Pin;
 use tokio::select;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{self, Duration};
 use tokio_stream::{Stream, StreamExt, StreamMap};
 
 /// Subscribes the client to one or more channels.
 ///
 /// Once the client enters the subscribed state, it is not supposed to issue any
-/// other commands, except for additional SUBSCRIBE, PSUBSCRIBE, UNSUBSCRIBE,
-/// PUNSUBSCRIBE, PING and QUIT commands.
+/// other commands, except for additional SUBSCRIBE, PSUBSCRIBE, SSUBSCRIBE,
+/// UNSUBSCRIBE, PUNSUBSCRIBE, SUNSUBSCRIBE, PING, RESET and QUIT commands.
 #[derive(Debug, Clone)]
 pub struct Subscribe {
     channels: Vec<String>,
@@ -29,12 +34,106 @@ pub struct Unsubscribe {
     valid: bool,
 }
 
+/// Subscribes the client to the channels matching the given glob-style patterns.
+#[derive(Debug, Clone)]
+pub struct Psubscribe {
+    patterns: Vec<String>,
+    valid: bool,
+}
+
+/// Unsubscribes the client from the given patterns, or from all the patterns
+/// when none is specified.
+#[derive(Debug, Clone)]
+pub struct Punsubscribe {
+    patterns: Vec<String>,
+    valid: bool,
+}
+
 /// Stream of messages. The stream receives messages from the
 /// `broadcast::Receiver`. We use `stream!` to create a `Stream` that consumes
 /// messages. Because `stream!` values cannot be named, we box the stream using
 /// a trait object.
 type Messages = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
 
+/// Stream of messages of a pattern subscription, along with the channels they
+/// are published to.
+type PatternMessages = Pin<Box<dyn Stream<Item = (String, Bytes)> + Send>>;
+
+/// Channel, pattern and shard channel subscriptions of a client in the
+/// subscribed state.
+struct Subscriptions {
+    db: Db,
+    channels: StreamMap<String, Messages>,
+    patterns: StreamMap<String, PatternMessages>,
+    shard_channels: StreamMap<String, Messages>,
+}
+
+impl Subscriptions {
+    fn new(db: &Db) -> Subscriptions {
+        Subscriptions {
+            db: db.clone(),
+            channels: StreamMap::new(),
+            patterns: StreamMap::new(),
+            shard_channels: StreamMap::new(),
+        }
+    }
+
+    fn remove_channel(&mut self, channel_name: &str) {
+        // the receiver is dropped along with the stream
+        self.channels.remove(channel_name);
+        self.db.unsubscribe(channel_name);
+    }
+
+    fn remove_pattern(&mut self, pattern: &str) {
+        self.patterns.remove(pattern);
+        self.db.punsubscribe(pattern);
+    }
+
+    fn remove_shard_channel(&mut self, channel_name: &str) {
+        self.shard_channels.remove(channel_name);
+        self.db.sunsubscribe(channel_name);
+    }
+
+    /// Number of subscriptions reported in the replies of the channel and
+    /// pattern commands, shard channels are counted apart as redis does.
+    fn len(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// The client leaves the subscribed state when there is no subscription left.
+    fn is_empty(&self) -> bool {
+        self.len() == 0 && self.shard_channels.is_empty()
+    }
+}
+
+// the subscriptions left are dropped when the client disconnects, resets or the
+// server shuts down
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        let channels: Vec<String> = self.channels.keys().cloned().collect();
+        for channel_name in channels {
+            self.remove_channel(&channel_name);
+        }
+        let patterns: Vec<String> = self.patterns.keys().cloned().collect();
+        for pattern in patterns {
+            self.remove_pattern(&pattern);
+        }
+        let shard_channels: Vec<String> = self.shard_channels.keys().cloned().collect();
+        for channel_name in shard_channels {
+            self.remove_shard_channel(&channel_name);
+        }
+    }
+}
+
+/// Subscriptions requested in the subscribed state, applied by the `subscribed`
+/// loop.
+#[derive(Default)]
+struct Pending {
+    channels: Vec<String>,
+    patterns: Vec<String>,
+    shard_channels: Vec<String>,
+}
+
 impl Subscribe {
     #[allow(dead_code)]
     /// Creates a new `Subscribe` command to listen on the specified channels.
@@ -106,66 +205,54 @@ impl Subscribe {
     /// commands may be received from the client and the list of subscriptions
     /// are updated accordingly.
     ///
+    /// Returns true if the client left the subscribed state with `RESET`, the
+    /// rest of the connection state is expected to be reset by the caller.
+    ///
     /// [here]: https://redis.io/topics/pubsub
     pub(crate) async fn apply(
-        mut self,
+        self,
         db: &Db,
+        topo: &Cluster,
         dst: &mut Connection,
         shutdown: &mut Shutdown,
-    ) -> crate::Result<()> {
+        invalidations: &mut mpsc::UnboundedReceiver<Invalidation>,
+    ) -> crate::Result<bool> {
         if !self.valid {
             dst.write_frame(&resp_invalid_arguments()).await?;
-            return Ok(());
+            return Ok(false);
         }
 
-        // Each individual channel subscription is handled using a
-        // `sync::broadcast` channel. Messages are then fanned out to all
-        // clients currently subscribed to the channels.
-        //
-        // An individual client may subscribe to multiple channels and may
-        // dynamically add and remove channels from its subscription set. To
-        // handle this, a `StreamMap` is used to track active subscriptions. The
-        // `StreamMap` merges messages from individual broadcast channels as
-        // they are received.
-        let mut subscriptions = StreamMap::new();
-
-        loop {
-            // `self.channels` is used to track additional channels to subscribe
-            // to. When new `SUBSCRIBE` commands are received during the
-            // execution of `apply`, the new channels are pushed onto this vec.
-            for channel_name in self.channels.drain(..) {
-                subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
-            }
+        let pending = Pending {
+            channels: self.channels,
+            ..Default::default()
+        };
+        subscribed(db, topo, dst, shutdown, invalidations, pending).await
+    }
 
-            // Wait for one of the following to happen:
-            //
-            // - Receive a message from one of the subscribed channels.
-            // - Receive a subscribe or unsubscribe command from the client.
-            // - A server shutdown signal.
-            select! {
-                // Receive messages from subscribed channels
-                Some((channel_name, msg)) = subscriptions.next() => {
-                    dst.write_frame(&make_message_frame(channel_name, msg)).await?;
-                }
-                res = dst.read_frame() => {
-                    let frame = match res? {
-                        Some(frame) => frame,
-                        // This happens if the remote client has disconnected.
-                        None => return Ok(())
-                    };
-
-                    handle_command(
-                        frame,
-                        &mut self.channels,
-                        &mut subscriptions,
-                        dst,
-                    ).await?;
-                }
-                _ = shutdown.recv() => {
-                    return Ok(());
-                }
-            };
+    /// Apply the `SSUBSCRIBE` command, the shard channels must be in one slot
+    /// owned by this instance.
+    pub(crate) async fn apply_sharded(
+        self,
+        db: &Db,
+        topo: &Cluster,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+        invalidations: &mut mpsc::UnboundedReceiver<Invalidation>,
+    ) -> crate::Result<bool> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(false);
+        }
+        if let Some(response) = check_shard_channels(topo, &self.channels) {
+            dst.write_frame(&response).await?;
+            return Ok(false);
         }
+
+        let pending = Pending {
+            shard_channels: self.channels,
+            ..Default::default()
+        };
+        subscribed(db, topo, dst, shutdown, invalidations, pending).await
     }
 
     #[allow(dead_code)]
@@ -183,9 +270,124 @@ impl Subscribe {
     }
 }
 
+/// Keep the client in the subscribed state until all the subscriptions are
+/// dropped, the client disconnects or the server shuts down.
+async fn subscribed(
+    db: &Db,
+    topo: &Cluster,
+    dst: &mut Connection,
+    shutdown: &mut Shutdown,
+    invalidations: &mut mpsc::UnboundedReceiver<Invalidation>,
+    mut pending: Pending,
+) -> crate::Result<bool> {
+    // Each individual channel subscription is handled using a
+    // `sync::broadcast` channel. Messages are then fanned out to all
+    // clients currently subscribed to the channels.
+    //
+    // An individual client may subscribe to multiple channels and may
+    // dynamically add and remove channels from its subscription set. To
+    // handle this, a `StreamMap` is used to track active subscriptions. The
+    // `StreamMap` merges messages from individual broadcast channels as
+    // they are received.
+    let mut subscriptions = Subscriptions::new(db);
+
+    // shard channels whose slots are moved to other instances are unsubscribed
+    // after the topology is updated
+    let mut slot_check = time::interval(Duration::from_millis(
+        config_cluster_topology_interval_or_default(),
+    ));
+
+    loop {
+        // `pending` is used to track additional subscriptions. When new
+        // subscribe commands are received in the subscribed state, the new
+        // channels and patterns are pushed onto it.
+        for channel_name in pending.channels.drain(..) {
+            subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
+        }
+        for pattern in pending.patterns.drain(..) {
+            subscribe_to_pattern(pattern, &mut subscriptions, db, dst).await?;
+        }
+        for channel_name in pending.shard_channels.drain(..) {
+            subscribe_to_shard_channel(channel_name, &mut subscriptions, db, dst).await?;
+        }
+
+        // The client leaves the subscribed state once it is unsubscribed from
+        // all the channels and patterns.
+        if subscriptions.is_empty() {
+            return Ok(false);
+        }
+
+        // Wait for one of the following to happen:
+        //
+        // - Receive a message from one of the subscribed channels or patterns.
+        // - Receive a command from the client.
+        // - A server shutdown signal.
+        select! {
+            // Receive messages from subscribed channels
+            Some((channel_name, msg)) = subscriptions.channels.next() => {
+                dst.write_frame(&make_message_frame("message", channel_name, msg)).await?;
+            }
+            // Receive messages from subscribed patterns
+            Some((pattern, (channel_name, msg))) = subscriptions.patterns.next() => {
+                dst.write_frame(&make_pmessage_frame(pattern, channel_name, msg)).await?;
+            }
+            // Receive messages from subscribed shard channels
+            Some((channel_name, msg)) = subscriptions.shard_channels.next() => {
+                dst.write_frame(&make_message_frame("smessage", channel_name, msg)).await?;
+            }
+            // keys tracked by this client or redirected to it are modified
+            Some(invalidation) = invalidations.recv() => {
+                let resp3 = dst.resp_version() == 3;
+                let subscribed = subscriptions.channels.contains_key(INVALIDATE_CHANNEL);
+                if let Some(frame) = invalidation.into_frame(resp3, subscribed) {
+                    dst.write_frame(&frame).await?;
+                }
+            }
+            _ = slot_check.tick(), if !subscriptions.shard_channels.is_empty() => {
+                let moved: Vec<String> = subscriptions
+                    .shard_channels
+                    .keys()
+                    .filter(|channel_name| !topo.owns_slot(key_slot(channel_name.as_bytes())))
+                    .cloned()
+                    .collect();
+                for channel_name in moved {
+                    subscriptions.remove_shard_channel(&channel_name);
+                    let response = make_unsubscribe_frame(
+                        "sunsubscribe",
+                        Some(channel_name),
+                        subscriptions.shard_channels.len(),
+                    );
+                    dst.write_frame(&response).await?;
+                }
+            }
+            res = dst.read_frame() => {
+                let frame = match res? {
+                    Some(frame) => frame,
+                    // This happens if the remote client has disconnected.
+                    None => return Ok(false)
+                };
+
+                let reset = handle_command(
+                    frame,
+                    &mut pending,
+                    &mut subscriptions,
+                    topo,
+                    dst,
+                ).await?;
+                if reset {
+                    return Ok(true);
+                }
+            }
+            _ = shutdown.recv() => {
+                return Ok(false);
+            }
+        };
+    }
+}
+
 async fn subscribe_to_channel(
     channel_name: String,
-    subscriptions: &mut StreamMap<String, Messages>,
+    subscriptions: &mut Subscriptions,
     db: &Db,
     dst: &mut Connection,
 ) -> crate::Result<()> {
@@ -204,94 +406,296 @@ async fn subscribe_to_channel(
     });
 
     // Track subscription in this client's subscription set.
-    subscriptions.insert(channel_name.clone(), rx);
+    subscriptions.channels.insert(channel_name.clone(), rx);
 
     // Respond with the successful subscription
-    let response = make_subscribe_frame(channel_name, subscriptions.len());
+    let response = make_subscribe_frame("subscribe", channel_name, subscriptions.len());
+    dst.write_frame(&response).await?;
+
+    Ok(())
+}
+
+async fn subscribe_to_shard_channel(
+    channel_name: String,
+    subscriptions: &mut Subscriptions,
+    db: &Db,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    let mut rx = db.ssubscribe(channel_name.clone());
+
+    let rx = Box::pin(async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => yield msg,
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+    });
+
+    subscriptions
+        .shard_channels
+        .insert(channel_name.clone(), rx);
+
+    let response = make_subscribe_frame(
+        "ssubscribe",
+        channel_name,
+        subscriptions.shard_channels.len(),
+    );
     dst.write_frame(&response).await?;
 
     Ok(())
 }
 
-/// Handle a command received while inside `Subscribe::apply`. Only subscribe
-/// and unsubscribe commands are permitted in this context.
+/// Returns the error reply if the shard channels are not in the same slot, or
+/// the slot is not owned by this instance.
+fn check_shard_channels(topo: &Cluster, channels: &[String]) -> Option<Frame> {
+    let slot = key_slot(channels.first()?.as_bytes());
+    if channels
+        .iter()
+        .any(|channel_name| key_slot(channel_name.as_bytes()) != slot)
+    {
+        return Some(resp_err(REDIS_CROSSSLOT_ERR));
+    }
+    topo.moved_error(slot)
+}
+
+async fn subscribe_to_pattern(
+    pattern: String,
+    subscriptions: &mut Subscriptions,
+    db: &Db,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    let mut rx = db.psubscribe(pattern.clone());
+
+    let rx = Box::pin(async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => yield msg,
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+    });
+
+    subscriptions.patterns.insert(pattern.clone(), rx);
+
+    let response = make_subscribe_frame("psubscribe", pattern, subscriptions.len());
+    dst.write_frame(&response).await?;
+
+    Ok(())
+}
+
+/// Handle a command received in the subscribed state. Only the subscribe and
+/// unsubscribe commands, `PING` and `RESET` are permitted in this context.
 ///
-/// Any new subscriptions are appended to `subscribe_to` instead of modifying
-/// `subscriptions`.
+/// Any new subscriptions are appended to `pending` instead of modifying
+/// `subscriptions`. Returns true if `RESET` is received.
 async fn handle_command(
     frame: Frame,
-    subscribe_to: &mut Vec<String>,
-    subscriptions: &mut StreamMap<String, Messages>,
+    pending: &mut Pending,
+    subscriptions: &mut Subscriptions,
+    topo: &Cluster,
     dst: &mut Connection,
-) -> crate::Result<()> {
+) -> crate::Result<bool> {
     // A command has been received from the client.
-    //
-    // Only `SUBSCRIBE` and `UNSUBSCRIBE` commands are permitted
-    // in this context.
     match Command::from_frame(frame)? {
         Command::Subscribe(subscribe) => {
-            // The `apply` method will subscribe to the channels we add to this
-            // vector.
-            subscribe_to.extend(subscribe.channels.into_iter());
+            if !subscribe.valid {
+                dst.write_frame(&resp_invalid_arguments()).await?;
+                return Ok(false);
+            }
+            // The `subscribed` loop will subscribe to the channels we add to
+            // this vector.
+            pending.channels.extend(subscribe.channels.into_iter());
+        }
+        Command::Ssubscribe(ssubscribe) => {
+            if !ssubscribe.valid {
+                dst.write_frame(&resp_invalid_arguments()).await?;
+                return Ok(false);
+            }
+            if let Some(response) = check_shard_channels(topo, &ssubscribe.channels) {
+                dst.write_frame(&response).await?;
+                return Ok(false);
+            }
+            pending
+                .shard_channels
+                .extend(ssubscribe.channels.into_iter());
+        }
+        Command::Psubscribe(psubscribe) => {
+            if !psubscribe.valid {
+                dst.write_frame(&resp_invalid_arguments()).await?;
+                return Ok(false);
+            }
+            pending.patterns.extend(psubscribe.patterns.into_iter());
         }
         Command::Unsubscribe(mut unsubscribe) => {
+            if !unsubscribe.valid {
+                dst.write_frame(&resp_invalid_arguments()).await?;
+                return Ok(false);
+            }
             // If no channels are specified, this requests unsubscribing from
             // **all** channels. To implement this, the `unsubscribe.channels`
             // vec is populated with the list of channels currently subscribed
             // to.
             if unsubscribe.channels.is_empty() {
                 unsubscribe.channels = subscriptions
+                    .channels
                     .keys()
                     .map(|channel_name| channel_name.to_string())
                     .collect();
+                if unsubscribe.channels.is_empty() {
+                    let response = make_unsubscribe_frame("unsubscribe", None, subscriptions.len());
+                    dst.write_frame(&response).await?;
+                }
             }
 
             for channel_name in unsubscribe.channels {
-                subscriptions.remove(&channel_name);
+                subscriptions.remove_channel(&channel_name);
+
+                let response =
+                    make_unsubscribe_frame("unsubscribe", Some(channel_name), subscriptions.len());
+                dst.write_frame(&response).await?;
+            }
+        }
+        Command::Punsubscribe(mut punsubscribe) => {
+            if !punsubscribe.valid {
+                dst.write_frame(&resp_invalid_arguments()).await?;
+                return Ok(false);
+            }
+            if punsubscribe.patterns.is_empty() {
+                punsubscribe.patterns = subscriptions
+                    .patterns
+                    .keys()
+                    .map(|pattern| pattern.to_string())
+                    .collect();
+                if punsubscribe.patterns.is_empty() {
+                    let response =
+                        make_unsubscribe_frame("punsubscribe", None, subscriptions.len());
+                    dst.write_frame(&response).await?;
+                }
+            }
+
+            for pattern in punsubscribe.patterns {
+                subscriptions.remove_pattern(&pattern);
 
-                let response = make_unsubscribe_frame(channel_name, subscriptions.len());
+                let response =
+                    make_unsubscribe_frame("punsubscribe", Some(pattern), subscriptions.len());
                 dst.write_frame(&response).await?;
             }
         }
+        Command::Sunsubscribe(mut sunsubscribe) => {
+            if !sunsubscribe.valid {
+                dst.write_frame(&resp_invalid_arguments()).await?;
+                return Ok(false);
+            }
+            if sunsubscribe.channels.is_empty() {
+                sunsubscribe.channels = subscriptions
+                    .shard_channels
+                    .keys()
+                    .map(|channel_name| channel_name.to_string())
+                    .collect();
+                if sunsubscribe.channels.is_empty() {
+                    let response = make_unsubscribe_frame("sunsubscribe", None, 0);
+                    dst.write_frame(&response).await?;
+                }
+            }
+
+            for channel_name in sunsubscribe.channels {
+                subscriptions.remove_shard_channel(&channel_name);
+
+                let response = make_unsubscribe_frame(
+                    "sunsubscribe",
+                    Some(channel_name),
+                    subscriptions.shard_channels.len(),
+                );
+                dst.write_frame(&response).await?;
+            }
+        }
+        Command::Ping(ping) => ping.apply_subscribed(dst).await?,
+        // all the subscriptions are dropped along with `subscriptions`
+        Command::Reset(_) => return Ok(true),
         command => {
-            let cmd = Unknown::new(command.get_name());
-            cmd.apply(dst).await?;
+            let response = Frame::ErrorOwned(format!(
+                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                command.get_name()
+            ));
+            dst.write_frame(&response).await?;
         }
     }
-    Ok(())
+    Ok(false)
 }
 
-/// Creates the response to a subcribe request.
+/// Creates the response to a subcribe or psubscribe request, pushed to the
+/// RESP3 clients out of band.
 ///
 /// All of these functions take the `channel_name` as a `String` instead of
 /// a `&str` since `Bytes::from` can reuse the allocation in the `String`, and
 /// taking a `&str` would require copying the data. This allows the caller to
 /// decide whether to clone the channel name or not.
-fn make_subscribe_frame(channel_name: String, num_subs: usize) -> Frame {
-    let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"subscribe"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_int(num_subs as i64);
-    response
+fn make_subscribe_frame(kind: &'static str, channel_name: String, num_subs: usize) -> Frame {
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(kind.as_bytes())),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Integer(num_subs as i64),
+    ])
+}
+
+/// Creates the response to an unsubcribe or punsubscribe request, the channel
+/// is nil if the client is not subscribed to any channel or pattern.
+fn make_unsubscribe_frame(
+    kind: &'static str,
+    channel_name: Option<String>,
+    num_subs: usize,
+) -> Frame {
+    let channel = match channel_name {
+        Some(channel_name) => Frame::Bulk(Bytes::from(channel_name)),
+        None => Frame::Null,
+    };
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(kind.as_bytes())),
+        channel,
+        Frame::Integer(num_subs as i64),
+    ])
 }
 
-/// Creates the response to an unsubcribe request.
-fn make_unsubscribe_frame(channel_name: String, num_subs: usize) -> Frame {
-    let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"unsubscribe"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_int(num_subs as i64);
-    response
+/// Reply to the unsubscribe commands received out of the subscribed state, the
+/// client is not subscribed to any channel or pattern.
+async fn unsubscribe_all(
+    kind: &'static str,
+    names: Vec<String>,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    if names.is_empty() {
+        dst.write_frame(&make_unsubscribe_frame(kind, None, 0))
+            .await?;
+    }
+    for name in names {
+        dst.write_frame(&make_unsubscribe_frame(kind, Some(name), 0))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Creates a message informing the client about a new message on a channel or
+/// shard channel that the client subscribes to.
+fn make_message_frame(kind: &'static str, channel_name: String, msg: Bytes) -> Frame {
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(kind.as_bytes())),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Bulk(msg),
+    ])
 }
 
-/// Creates a message informing the client about a new message on a channel that
-/// the client subscribes to.
-fn make_message_frame(channel_name: String, msg: Bytes) -> Frame {
-    let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"message"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_bulk(msg);
-    response
+/// Creates a message informing the client about a new message on a channel
+/// matching a pattern that the client subscribes to.
+fn make_pmessage_frame(pattern: String, channel_name: String, msg: Bytes) -> Frame {
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(b"pmessage")),
+        Frame::Bulk(Bytes::from(pattern)),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Bulk(msg),
+    ])
 }
 
 impl Unsubscribe {
@@ -353,6 +757,17 @@ impl Unsubscribe {
         })
     }
 
+    /// Apply the `Unsubscribe` command, or `SUNSUBSCRIBE` as `kind`, out of the
+    /// subscribed state.
+    pub(crate) async fn apply(self, kind: &'static str, dst: &mut Connection) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        unsubscribe_all(kind, self.channels, dst).await
+    }
+
     #[allow(dead_code)]
     /// Converts the command into an equivalent `Frame`.
     ///
@@ -381,6 +796,10 @@ impl Invalid for Subscribe {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
 
 impl Invalid for Unsubscribe {
@@ -390,4 +809,104 @@ impl Invalid for Unsubscribe {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
+
+impl Psubscribe {
+    /// Parse a `Psubscribe` instance from a received frame.
+    ///
+    /// ```text
+    /// PSUBSCRIBE pattern [pattern ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Psubscribe> {
+        let mut patterns = vec![parse.next_string()?];
+        while let Ok(pattern) = parse.next_string() {
+            patterns.push(pattern);
+        }
+
+        Ok(Psubscribe {
+            patterns,
+            valid: true,
+        })
+    }
+
+    /// Apply the `Psubscribe` command, entering the subscribed state as `Subscribe`
+    /// does. Returns true if the client left the subscribed state with `RESET`.
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        topo: &Cluster,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+        invalidations: &mut mpsc::UnboundedReceiver<Invalidation>,
+    ) -> crate::Result<bool> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(false);
+        }
+
+        let pending = Pending {
+            patterns: self.patterns,
+            ..Default::default()
+        };
+        subscribed(db, topo, dst, shutdown, invalidations, pending).await
+    }
+}
+
+impl Punsubscribe {
+    /// Parse a `Punsubscribe` instance from a received frame.
+    ///
+    /// ```text
+    /// PUNSUBSCRIBE [pattern [pattern ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Punsubscribe> {
+        let mut patterns = vec![];
+        while let Ok(pattern) = parse.next_string() {
+            patterns.push(pattern);
+        }
+
+        Ok(Punsubscribe {
+            patterns,
+            valid: true,
+        })
+    }
+
+    /// Apply the `Punsubscribe` command out of the subscribed state.
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        unsubscribe_all("punsubscribe", self.patterns, dst).await
+    }
+}
+
+impl Invalid for Psubscribe {
+    fn new_invalid() -> Psubscribe {
+        Psubscribe {
+            patterns: vec![],
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
+
+impl Invalid for Punsubscribe {
+    fn new_invalid() -> Punsubscribe {
+        Punsubscribe {
+            patterns: vec![],
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -88,4 +88,8 @@ impl Invalid for TTL {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -0,0 +1,207 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Multi};
+use crate::config::{is_use_pessimistic_txn, is_use_txn_api, LOGGER};
+use crate::db::Db;
+use crate::notify::PendingEvents;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_NOT_SUPPORTED_ERR, REDIS_TXN_NESTED_ERR, REDIS_TXN_TIMEOUT_ERR,
+    REDIS_TXN_WITHOUT_BEGIN_ERR, REDIS_UNKNOWN_SUBCOMMAND,
+};
+use crate::tikv::get_txn_client;
+use crate::txn_interactive_timeout_ms_or_default;
+use crate::utils::{resp_err, resp_invalid_arguments, resp_ok};
+use crate::{Command, Connection, Frame, Parse};
+use mlua::Lua;
+use slog::{debug, warn};
+use tikv_client::Transaction;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct Txn {
+    subcommand: String,
+    pessimistic: bool,
+    valid: bool,
+}
+
+/// Transaction started by TXN BEGIN, commands of the connection are executed in it
+/// until TXN COMMIT or TXN ROLLBACK
+pub struct InteractiveTxn {
+    // None if the txn has been rolled back on timeout
+    txn: Option<Arc<Mutex<Transaction>>>,
+    deadline: Instant,
+    // keyspace events of the commands, published once the txn commits
+    events: PendingEvents,
+}
+
+impl fmt::Debug for InteractiveTxn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InteractiveTxn")
+            .field("active", &self.txn.is_some())
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl InteractiveTxn {
+    /// Returns None if the txn has been rolled back
+    pub fn deadline(&self) -> Option<Instant> {
+        self.txn.as_ref().map(|_| self.deadline)
+    }
+
+    pub async fn exec(
+        &mut self,
+        cmd: Command,
+        dst: &mut Connection,
+        db: &Db,
+        lua: &Option<Lua>,
+    ) -> crate::Result<()> {
+        if Instant::now() >= self.deadline {
+            if let Err(e) = self.rollback().await {
+                warn!(LOGGER, "rollback timeout txn failed, {}", e);
+            }
+        }
+
+        let response = match &self.txn {
+            Some(txn) => self
+                .events
+                .defer(Multi::exec_queued_cmd(cmd, Some(txn.clone()), db, lua))
+                .await
+                .unwrap_or_else(Into::into),
+            None => resp_err(REDIS_TXN_TIMEOUT_ERR),
+        };
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Rollback the txn, commands are rejected until TXN COMMIT or TXN ROLLBACK
+    pub async fn rollback(&mut self) -> AsyncResult<()> {
+        self.events.discard();
+        if let Some(txn) = self.txn.take() {
+            txn.lock().await.rollback().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Txn {
+    pub fn new(subcommand: impl ToString, pessimistic: bool) -> Txn {
+        Txn {
+            subcommand: subcommand.to_string(),
+            pessimistic,
+            valid: true,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Txn> {
+        let subcommand = parse.next_string()?;
+        let mut pessimistic = false;
+        if subcommand.to_uppercase() == "BEGIN" {
+            match parse.next_string() {
+                Ok(mode) if mode.to_uppercase() == "PESSIMISTIC" => pessimistic = true,
+                Ok(_) => return Ok(Txn::new_invalid()),
+                Err(_) => {}
+            }
+        }
+
+        Ok(Txn::new(subcommand, pessimistic))
+    }
+
+    pub(crate) async fn apply(
+        &self,
+        dst: &mut Connection,
+        session: &mut Option<InteractiveTxn>,
+    ) -> crate::Result<()> {
+        let response = self.txn(session).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    async fn txn(&self, session: &mut Option<InteractiveTxn>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+
+        match self.subcommand.to_uppercase().as_str() {
+            "BEGIN" => {
+                if session.is_some() {
+                    return Ok(resp_err(REDIS_TXN_NESTED_ERR));
+                }
+                let txn = get_txn_client()?
+                    .begin_with_mode(self.pessimistic || is_use_pessimistic_txn())
+                    .await?;
+                let timeout = Duration::from_millis(txn_interactive_timeout_ms_or_default());
+                *session = Some(InteractiveTxn {
+                    txn: Some(Arc::new(Mutex::new(txn))),
+                    deadline: Instant::now() + timeout,
+                    events: PendingEvents::default(),
+                });
+                Ok(resp_ok())
+            }
+            "COMMIT" => match session.take() {
+                Some(mut s) if Instant::now() >= s.deadline => {
+                    s.rollback().await?;
+                    Ok(resp_err(REDIS_TXN_TIMEOUT_ERR))
+                }
+                Some(InteractiveTxn {
+                    txn: Some(txn),
+                    events,
+                    ..
+                }) => {
+                    txn.lock().await.commit().await?;
+                    events.publish();
+                    Ok(resp_ok())
+                }
+                Some(_) => Ok(resp_err(REDIS_TXN_TIMEOUT_ERR)),
+                None => Ok(resp_err(REDIS_TXN_WITHOUT_BEGIN_ERR)),
+            },
+            "ROLLBACK" => match session.take() {
+                Some(mut s) => {
+                    s.rollback().await?;
+                    Ok(resp_ok())
+                }
+                None => Ok(resp_err(REDIS_TXN_WITHOUT_BEGIN_ERR)),
+            },
+            _ => Ok(resp_err(REDIS_UNKNOWN_SUBCOMMAND)),
+        }
+    }
+}
+
+impl Invalid for Txn {
+    fn new_invalid() -> Txn {
+        Txn {
+            subcommand: "".to_owned(),
+            pessimistic: false,
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
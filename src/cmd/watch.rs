@@ -0,0 +1,85 @@
+use tikv_client::Timestamp;
+
+use crate::cmd::Invalid;
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::get_txn_client;
+use crate::Parse;
+
+/// Keys watched by WATCH, EXEC aborts if any of them is modified since the first WATCH, the
+/// other keys are read and written at a fresh timestamp.
+#[derive(Debug, Default)]
+pub struct WatchedKeys {
+    keys: Vec<String>,
+    start_ts: Option<Timestamp>,
+}
+
+impl WatchedKeys {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.start_ts = None;
+    }
+
+    /// Returns the keys and the timestamp they are watched since
+    pub fn into_parts(self) -> (Vec<String>, Option<Timestamp>) {
+        (self.keys, self.start_ts)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Watch {
+    keys: Vec<String>,
+    valid: bool,
+}
+
+impl Watch {
+    pub fn new(keys: Vec<String>) -> Watch {
+        Watch { keys, valid: true }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Watch> {
+        let mut keys = vec![];
+        while let Ok(key) = parse.next_string() {
+            keys.push(key);
+        }
+        if keys.is_empty() {
+            return Ok(Watch::new_invalid());
+        }
+
+        Ok(Watch::new(keys))
+    }
+
+    /// Add the keys to `watched`, the timestamp is taken by the first WATCH
+    pub async fn watch(&self, watched: &mut WatchedKeys) -> AsyncResult<()> {
+        if !is_use_txn_api() {
+            return Err(REDIS_NOT_SUPPORTED_ERR);
+        }
+        if watched.start_ts.is_none() {
+            watched.start_ts = Some(get_txn_client()?.current_timestamp().await?);
+        }
+        watched.keys.extend(self.keys.iter().cloned());
+        Ok(())
+    }
+}
+
+impl Invalid for Watch {
+    fn new_invalid() -> Watch {
+        Watch {
+            keys: vec![],
+            valid: false,
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
+}
@@ -260,4 +260,8 @@ impl Invalid for Zadd {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
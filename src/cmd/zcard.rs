@@ -83,4 +83,8 @@ impl Invalid for Zcard {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -35,6 +35,10 @@ impl Zcount {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zcount> {
         let key = parse.next_string()?;
         let mut min_inclusive = true;
@@ -141,4 +145,8 @@ impl Invalid for Zcount {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
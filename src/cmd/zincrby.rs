@@ -31,6 +31,11 @@ impl Zincrby {
         }
     }
 
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zincrby> {
         let key = parse.next_string()?;
         let step_byte = parse.next_bytes()?;
@@ -93,4 +98,8 @@ impl Invalid for Zincrby {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
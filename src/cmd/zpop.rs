@@ -29,6 +29,11 @@ impl Zpop {
         }
     }
 
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zpop> {
         let key = parse.next_string()?;
         // default count is 1
@@ -97,4 +102,8 @@ impl Invalid for Zpop {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -35,6 +35,10 @@ impl Zrange {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zrange> {
         let key = parse.next_string()?;
 
@@ -146,4 +150,8 @@ impl Invalid for Zrange {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
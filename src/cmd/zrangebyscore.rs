@@ -44,6 +44,10 @@ impl Zrangebyscore {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zrangebyscore> {
         let key = parse.next_string()?;
         let mut min_inclusive = true;
@@ -219,4 +223,8 @@ impl Invalid for Zrangebyscore {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -29,6 +29,10 @@ impl Zrank {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zrank> {
         let key = parse.next_string()?;
         let member = parse.next_string()?;
@@ -86,4 +90,8 @@ impl Invalid for Zrank {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -101,4 +101,8 @@ impl Invalid for Zrem {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -31,6 +31,11 @@ impl Zremrangebyrank {
         }
     }
 
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zremrangebyrank> {
         let key = parse.next_string()?;
 
@@ -103,4 +108,8 @@ impl Invalid for Zremrangebyrank {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
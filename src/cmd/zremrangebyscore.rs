@@ -31,6 +31,11 @@ impl Zremrangebyscore {
         }
     }
 
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zremrangebyscore> {
         let key = parse.next_string()?;
 
@@ -105,4 +110,8 @@ impl Invalid for Zremrangebyscore {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
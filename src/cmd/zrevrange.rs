@@ -33,6 +33,10 @@ impl Zrevrange {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zrevrange> {
         let key = parse.next_string()?;
 
@@ -116,4 +120,8 @@ impl Invalid for Zrevrange {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -29,6 +29,10 @@ impl Zscore {
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zscore> {
         let key = parse.next_string()?;
         let member = parse.next_string()?;
@@ -86,4 +90,8 @@ impl Invalid for Zscore {
             valid: false,
         }
     }
+
+    fn valid(&self) -> bool {
+        self.valid
+    }
 }
@@ -51,6 +51,12 @@ struct Server {
     cluster_topology_interval: Option<u64>,
     cluster_topology_expire: Option<u64>,
     meta_key_number: Option<u16>,
+    script_cache_size: Option<usize>,
+    lua_time_limit: Option<u64>,
+    lua_memory_limit: Option<usize>,
+    notify_keyspace_events: Option<String>,
+    pipeline_window: Option<usize>,
+    cluster_tracking_invalidation: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -81,6 +87,10 @@ struct Backend {
     txn_region_backoff_delay_attemps: Option<u32>,
     txn_lock_backoff_delay_ms: Option<u64>,
     txn_lock_backoff_delay_attemps: Option<u32>,
+    txn_read_staleness_ms: Option<u64>,
+    txn_gc_life_time_ms: Option<u64>,
+    txn_gc_safepoint_refresh_ms: Option<u64>,
+    txn_interactive_timeout_ms: Option<u64>,
 
     cmd_lrem_length_limit: Option<u32>,
     cmd_linsert_length_limit: Option<u32>,
@@ -128,6 +138,16 @@ pub fn is_auth_matched(password: &str) -> bool {
     true
 }
 
+// password of requirepass, used by the instances to connect to each other
+pub fn config_password() -> Option<String> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            return c.server.password.clone();
+        }
+    }
+    None
+}
+
 pub fn txn_retry_count() -> u32 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -347,6 +367,84 @@ pub fn config_meta_key_number_or_default() -> u16 {
     100
 }
 
+pub fn config_script_cache_size_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.script_cache_size {
+                return s;
+            }
+        }
+    }
+
+    // default number of scripts cached in local
+    1000
+}
+
+pub fn config_lua_time_limit_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.lua_time_limit {
+                return s;
+            }
+        }
+    }
+
+    // default milliseconds before a running script makes other clients busy
+    5000
+}
+
+pub fn config_lua_memory_limit_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.lua_memory_limit {
+                return s;
+            }
+        }
+    }
+
+    // default max bytes used by the lua state of a connection
+    256 * 1024 * 1024
+}
+
+pub fn config_notify_keyspace_events_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.notify_keyspace_events.clone() {
+                return s;
+            }
+        }
+    }
+
+    // keyspace notifications are disabled by default
+    "".to_owned()
+}
+
+pub fn config_pipeline_window_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.pipeline_window {
+                return s;
+            }
+        }
+    }
+
+    // default max pipelined commands of a connection applied concurrently
+    16
+}
+
+pub fn config_cluster_tracking_invalidation_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.server.cluster_tracking_invalidation {
+                return b;
+            }
+        }
+    }
+
+    // modified keys are only invalidated for the clients of the local instance by default
+    false
+}
+
 fn log_level_str() -> String {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -482,6 +580,54 @@ pub fn txn_lock_backoff_delay_attemps() -> u32 {
     2
 }
 
+pub fn txn_read_staleness_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.txn_read_staleness_ms {
+                return b;
+            }
+        }
+    }
+    // default read with a fresh timestamp
+    0
+}
+
+pub fn txn_gc_life_time_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.txn_gc_life_time_ms {
+                return b;
+            }
+        }
+    }
+    // default gc life time of tikv cluster, 10 minutes
+    600000
+}
+
+pub fn txn_gc_safepoint_refresh_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.txn_gc_safepoint_refresh_ms {
+                return b;
+            }
+        }
+    }
+    // default refresh the gc safepoint from pd every minute
+    60000
+}
+
+pub fn txn_interactive_timeout_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.txn_interactive_timeout_ms {
+                return b;
+            }
+        }
+    }
+    // default rollback the txn started by TXN BEGIN after 30 seconds
+    30000
+}
+
 pub fn cmd_lrem_length_limit_or_default() -> u32 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
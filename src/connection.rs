@@ -1,4 +1,4 @@
-use crate::frame::{self, Frame};
+use crate::frame::{self, format_double, Frame};
 use crate::metrics::{DATA_TRAFFIC_IN, DATA_TRAFFIC_OUT};
 
 use async_std::io::{BufReader, BufWriter, WriteExt};
@@ -36,6 +36,13 @@ pub struct Connection {
     local_addr: String,
     peer_addr: String,
 
+    // RESP version selected by HELLO, frames are written in RESP2 until then
+    resp_version: u8,
+
+    // frames written by a pipelined command, they are kept here until the
+    // replies of the commands before it are written to the socket
+    pending: Option<Vec<u8>>,
+
     // The buffer for reading frames.
     buffer: BytesMut,
 }
@@ -54,6 +61,8 @@ impl Connection {
 
             tls_w: None,
             tls_r: None,
+            resp_version: 2,
+            pending: None,
             // Default to a 4KB read buffer. For the use case of mini redis,
             // this is fine. However, real applications will want to tune this
             // value to their specific use case. There is a high likelihood that
@@ -78,10 +87,54 @@ impl Connection {
 
             tls_w: Some(BufWriter::new(tls_w)),
             tls_r: Some(BufReader::new(tls_r)),
+            resp_version: 2,
+            pending: None,
             buffer: BytesMut::with_capacity(32 * 1024),
         }
     }
 
+    /// Returns a connection to the same peer which keeps the frames written to it
+    /// in memory, a pipelined command is applied with it and its replies are taken
+    /// by `take_pending` later.
+    pub fn buffered(&self) -> Connection {
+        Connection {
+            tls: self.tls,
+            local_addr: self.local_addr.clone(),
+            peer_addr: self.peer_addr.clone(),
+            w: None,
+            r: None,
+            tls_w: None,
+            tls_r: None,
+            resp_version: self.resp_version,
+            pending: Some(Vec::new()),
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Take the frames kept by the connection returned by `buffered`
+    pub fn take_pending(&mut self) -> Vec<u8> {
+        self.pending
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Write the frames taken from a buffered connection, they are sent to the socket
+    /// by `flush_pending` or the next `write_frame`.
+    pub async fn write_pending(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_all(buf).await
+    }
+
+    /// Send the frames written by `write_pending` to the socket
+    pub async fn flush_pending(&mut self) -> io::Result<()> {
+        self.flush().await
+    }
+
+    /// Returns true if more requests are received but not read yet
+    pub fn has_buffered_input(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
     pub fn local_addr(&self) -> &str {
         &self.local_addr
     }
@@ -90,7 +143,19 @@ impl Connection {
         &self.peer_addr
     }
 
+    pub fn resp_version(&self) -> u8 {
+        self.resp_version
+    }
+
+    pub fn set_resp_version(&mut self, version: u8) {
+        self.resp_version = version;
+    }
+
     async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if let Some(pending) = self.pending.as_mut() {
+            pending.extend_from_slice(buf);
+            return Ok(());
+        }
         if self.tls {
             self.tls_w.as_mut().unwrap().write_all(buf).await?;
         } else {
@@ -101,6 +166,9 @@ impl Connection {
     }
 
     async fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_some() {
+            return Ok(());
+        }
         if self.tls {
             self.tls_w.as_mut().unwrap().flush().await?;
         } else {
@@ -110,7 +178,10 @@ impl Connection {
     }
 
     async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        Ok(if self.tls {
+        Ok(if self.pending.is_some() {
+            // nothing is read from a buffered connection
+            0
+        } else if self.tls {
             self.tls_r.as_mut().unwrap().read(buf).await?
         } else {
             self.r.as_mut().unwrap().read(buf).await?
@@ -132,9 +203,21 @@ impl Connection {
         loop {
             // Attempt to parse a frame from the buffered data. If enough data
             // has been buffered, the frame is returned.
-            if let (Some(frame), len) = self.parse_frame()? {
-                DATA_TRAFFIC_IN.inc_by(len as u64);
-                return Ok(Some(frame));
+            match self.parse_frame() {
+                Ok((Some(frame), len)) => {
+                    DATA_TRAFFIC_IN.inc_by(len as u64);
+                    return Ok(Some(frame));
+                }
+                Ok((None, _)) => {}
+                Err(e) => {
+                    // malformed inline requests are replied before the connection
+                    // is closed, as redis does
+                    if let Some(frame::Error::Protocol(_)) = e.downcast_ref::<frame::Error>() {
+                        self.write_frame(&Frame::ErrorOwned(format!("ERR {}", e)))
+                            .await?;
+                    }
+                    return Err(e);
+                }
             }
 
             // There is not enough buffered data to read a frame. Attempt to
@@ -168,6 +251,18 @@ impl Connection {
     fn parse_frame(&mut self) -> crate::Result<(Option<Frame>, usize)> {
         use frame::Error::Incomplete;
 
+        // requests not starting with a RESP type byte are inline commands
+        while let Some(&first) = self.buffer.first() {
+            if !Frame::is_inline(first) {
+                break;
+            }
+            match self.parse_inline_frame()? {
+                // blank lines are skipped as redis does
+                (Some(Frame::Array(args)), _) if args.is_empty() => continue,
+                parsed => return Ok(parsed),
+            }
+        }
+
         // Cursor is used to track the "current" location in the
         // buffer. Cursor also implements `Buf` from the `bytes` crate
         // which provides a number of helpful utilities for working
@@ -226,117 +321,104 @@ impl Connection {
         }
     }
 
+    /// Tries to parse an inline command from the buffer, the same as `parse_frame`.
+    fn parse_inline_frame(&mut self) -> crate::Result<(Option<Frame>, usize)> {
+        let mut buf = Cursor::new(&self.buffer[..]);
+
+        match Frame::check_inline(&mut buf) {
+            Ok(_) => {
+                let len = buf.position() as usize;
+                buf.set_position(0);
+                let frame = Frame::parse_inline(&mut buf)?;
+                self.buffer.advance(len);
+                Ok((Some(frame), len))
+            }
+            Err(frame::Error::Incomplete) => Ok((None, 0)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// Write a single `Frame` value to the underlying stream.
     ///
-    /// The `Frame` value is written to the socket using the various `write_*`
-    /// functions provided by `AsyncWrite`. Calling these functions directly on
-    /// a `TcpStream` is **not** advised, as this will result in a large number of
-    /// syscalls. However, it is fine to call these functions on a *buffered*
-    /// write stream. The data will be written to the buffer. Once the buffer is
-    /// full, it is flushed to the underlying socket.
+    /// The frame is encoded into a buffer first, in RESP3 or in RESP2 according to
+    /// the RESP version of the connection, then the buffer is written to the socket
+    /// and flushed.
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        // Arrays are encoded by encoding each entry. All other frame types are
-        // considered literals. For now, this is not able to encode
-        // recursive frame structures. See below for more details.
-        match frame {
-            Frame::Array(val) => {
-                // Encode the frame type prefix. For an array, it is `*`.
-                self.write_all(b"*").await?;
-
-                // Encode the length of the array.
-                self.write_decimal(val.len() as i64).await?;
-
-                // Iterate and encode each entry in the array.
-                for entry in &**val {
-                    // TODO make this to be recursive
-                    // we need nested array response only for `cluster slots` command for now
-                    match entry {
-                        Frame::Array(val) => {
-                            self.write_all(b"*").await?;
-                            self.write_decimal(val.len() as i64).await?;
-                            for entry in &**val {
-                                match entry {
-                                    Frame::Array(val) => {
-                                        self.write_all(b"*").await?;
-                                        self.write_decimal(val.len() as i64).await?;
-                                        for entry in &**val {
-                                            self.write_value(entry).await?;
-                                        }
-                                    }
-                                    _ => self.write_value(entry).await?,
-                                }
-                            }
-                        }
-                        _ => self.write_value(entry).await?,
-                    }
-                }
-            }
-            // The frame type is a literal. Encode the value directly.
-            _ => self.write_value(frame).await?,
-        }
+        let mut buf = Vec::new();
+        encode_frame(frame, self.resp_version == 3, &mut buf);
+        self.write_all(&buf).await?;
 
-        // Ensure the encoded frame is written to the socket. The calls above
-        // are to the buffered stream and writes. Calling `flush` writes the
-        // remaining contents of the buffer to the socket.
+        // Ensure the encoded frame is written to the socket.
         self.flush().await
     }
+}
 
-    /// Write a frame literal to the stream
-    async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Simple(val) => {
-                self.write_all(b"+").await?;
-                self.write_all(val.as_bytes()).await?;
-                self.write_all(b"\r\n").await?;
-            }
-            Frame::ErrorString(val) => {
-                self.write_all(b"-").await?;
-                self.write_all(val.as_bytes()).await?;
-                self.write_all(b"\r\n").await?;
-            }
-            Frame::ErrorOwned(val) => {
-                self.write_all(b"-").await?;
-                self.write_all(val.as_bytes()).await?;
-                self.write_all(b"\r\n").await?;
-            }
-            Frame::Integer(val) => {
-                self.write_all(b":").await?;
-                self.write_decimal(*val).await?;
-            }
-            Frame::Null => {
-                self.write_all(b"$-1\r\n").await?;
-            }
-            Frame::Bulk(val) => {
-                let len = val.len();
-
-                self.write_all(b"$").await?;
-                self.write_decimal(len as i64).await?;
-                self.write_all(val).await?;
-                self.write_all(b"\r\n").await?;
+/// Encode the frame recursively, the RESP3 types are encoded as their RESP2
+/// counterparts unless `resp3` is set
+fn encode_frame(frame: &Frame, resp3: bool, buf: &mut Vec<u8>) {
+    match frame {
+        Frame::Simple(val) => encode_line(b'+', val, buf),
+        Frame::ErrorString(val) => encode_line(b'-', val, buf),
+        Frame::ErrorOwned(val) => encode_line(b'-', val, buf),
+        Frame::Integer(val) => encode_line(b':', &val.to_string(), buf),
+        Frame::Null if resp3 => buf.extend_from_slice(b"_\r\n"),
+        Frame::Null => buf.extend_from_slice(b"$-1\r\n"),
+        Frame::Bulk(val) => encode_blob(b'$', val, buf),
+        Frame::Array(items) => encode_items(b'*', items, resp3, buf),
+        Frame::Set(items) => encode_items(if resp3 { b'~' } else { b'*' }, items, resp3, buf),
+        Frame::Push(items) => encode_items(if resp3 { b'>' } else { b'*' }, items, resp3, buf),
+        Frame::Map(pairs) if resp3 => encode_pairs(b'%', pairs, resp3, buf),
+        Frame::Map(pairs) => {
+            // flattened as key value key value ... in RESP2
+            encode_line(b'*', &(pairs.len() * 2).to_string(), buf);
+            for (key, value) in pairs {
+                encode_frame(key, resp3, buf);
+                encode_frame(value, resp3, buf);
             }
-            // Encoding an `Array` from within a value cannot be done using a
-            // recursive strategy. In general, async fns do not support
-            // recursion.
-            // We do support at most 3 nested array response in up-level for now.
-            Frame::Array(_val) => unreachable!(),
         }
-
-        Ok(())
+        Frame::Attribute(pairs) if resp3 => encode_pairs(b'|', pairs, resp3, buf),
+        // attributes are never sent to RESP2 clients
+        Frame::Attribute(_) => {}
+        Frame::Double(d) if resp3 => encode_line(b',', &format_double(*d), buf),
+        Frame::Double(d) => encode_blob(b'$', format_double(*d).as_bytes(), buf),
+        Frame::Boolean(b) if resp3 => encode_line(b'#', if *b { "t" } else { "f" }, buf),
+        Frame::Boolean(b) => encode_line(b':', if *b { "1" } else { "0" }, buf),
+        Frame::BigNumber(num) if resp3 => encode_line(b'(', num, buf),
+        Frame::BigNumber(num) => encode_blob(b'$', num.as_bytes(), buf),
+        Frame::Verbatim(format, data) if resp3 => {
+            let mut val = Vec::with_capacity(format.len() + 1 + data.len());
+            val.extend_from_slice(format.as_bytes());
+            val.push(b':');
+            val.extend_from_slice(data);
+            encode_blob(b'=', &val, buf);
+        }
+        Frame::Verbatim(_, data) => encode_blob(b'$', data, buf),
     }
+}
 
-    /// Write a decimal frame to the stream
-    async fn write_decimal(&mut self, val: i64) -> io::Result<()> {
-        use std::io::Write;
+fn encode_line(prefix: u8, line: &str, buf: &mut Vec<u8>) {
+    buf.push(prefix);
+    buf.extend_from_slice(line.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+}
 
-        // Convert the value to a string
-        let mut buf = [0u8; 20];
-        let mut buf = Cursor::new(&mut buf[..]);
-        write!(&mut buf, "{}", val)?;
+fn encode_blob(prefix: u8, val: &[u8], buf: &mut Vec<u8>) {
+    encode_line(prefix, &val.len().to_string(), buf);
+    buf.extend_from_slice(val);
+    buf.extend_from_slice(b"\r\n");
+}
 
-        let pos = buf.position() as usize;
-        self.write_all(&buf.get_ref()[..pos]).await?;
-        self.write_all(b"\r\n").await?;
+fn encode_items(prefix: u8, items: &[Frame], resp3: bool, buf: &mut Vec<u8>) {
+    encode_line(prefix, &items.len().to_string(), buf);
+    for item in items {
+        encode_frame(item, resp3, buf);
+    }
+}
 
-        Ok(())
+fn encode_pairs(prefix: u8, pairs: &[(Frame, Frame)], resp3: bool, buf: &mut Vec<u8>) {
+    encode_line(prefix, &pairs.len().to_string(), buf);
+    for (key, value) in pairs {
+        encode_frame(key, resp3, buf);
+        encode_frame(value, resp3, buf);
     }
 }
@@ -2,6 +2,8 @@ use tokio::sync::{broadcast, Notify};
 use tokio::time::{self, Duration, Instant};
 
 use crate::config::LOGGER;
+use crate::config_script_cache_size_or_default;
+use crate::utils::glob_match;
 use bytes::Bytes;
 use slog::debug;
 use std::collections::{BTreeMap, HashMap};
@@ -64,9 +66,18 @@ struct State {
     /// `std::collections::HashMap` works fine.
     entries: HashMap<String, Entry>,
 
-    scripts: HashMap<String, Bytes>,
+    /// Local LRU cache of scripts persisted in tikv, along with the tick of the
+    /// last access
+    scripts: HashMap<String, (Bytes, u64)>,
+    script_tick: u64,
+    /// Epoch of the scripts in tikv, the cache is dropped when it changes
+    script_epoch: u64,
 
     pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
+    /// Pattern subscriptions, the messages carry the channel they are published to
+    pattern_sub: HashMap<String, broadcast::Sender<(String, Bytes)>>,
+    /// Shard channels, only the ones in the slots owned by this instance get messages
+    shard_sub: HashMap<String, broadcast::Sender<Bytes>>,
 
     /// Tracks key TTLs.
     ///
@@ -137,7 +148,11 @@ impl Db {
             state: Mutex::new(State {
                 entries: HashMap::new(),
                 scripts: HashMap::new(),
+                script_tick: 0,
+                script_epoch: 0,
                 pub_sub: HashMap::new(),
+                pattern_sub: HashMap::new(),
+                shard_sub: HashMap::new(),
                 expirations: BTreeMap::new(),
                 next_id: 0,
                 shutdown: false,
@@ -152,18 +167,46 @@ impl Db {
     }
 
     pub(crate) fn get_script(&self, key: &str) -> Option<Bytes> {
-        let state = self.shared.state.lock().unwrap();
-        state.scripts.get(key).cloned()
+        let mut state = self.shared.state.lock().unwrap();
+        state.script_tick += 1;
+        let tick = state.script_tick;
+        state.scripts.get_mut(key).map(|(script, t)| {
+            *t = tick;
+            script.clone()
+        })
     }
 
     pub(crate) fn set_script(&self, key: String, value: Bytes) {
         let mut state = self.shared.state.lock().unwrap();
-        state.scripts.insert(key, value);
+        state.script_tick += 1;
+        let tick = state.script_tick;
+        state.scripts.insert(key, (value, tick));
+
+        // evict the least recently used one
+        if state.scripts.len() > config_script_cache_size_or_default() {
+            let lru = state
+                .scripts
+                .iter()
+                .min_by_key(|(_, (_, t))| *t)
+                .map(|(k, _)| k.clone());
+            if let Some(k) = lru {
+                state.scripts.remove(&k);
+            }
+        }
     }
 
-    pub(crate) fn flush_script(&self) {
+    /// Drop the cached scripts if they are flushed in tikv by any instance, it is called
+    /// every topology update, the flushed scripts are served from the cache until then
+    pub(crate) fn sync_script_epoch(&self, epoch: u64) {
         let mut state = self.shared.state.lock().unwrap();
-        state.scripts.clear();
+        if state.script_epoch != epoch {
+            debug!(
+                LOGGER,
+                "script epoch changed to {}, flush local cache", epoch
+            );
+            state.script_epoch = epoch;
+            state.scripts.clear();
+        }
     }
 
     #[allow(dead_code)]
@@ -284,23 +327,135 @@ impl Db {
         }
     }
 
+    /// Returns a `Receiver` for the requested glob-style pattern, receiving the
+    /// messages of all the channels matching the pattern.
+    #[allow(clippy::significant_drop_in_scrutinee)]
+    pub(crate) fn psubscribe(&self, pattern: String) -> broadcast::Receiver<(String, Bytes)> {
+        use std::collections::hash_map::Entry;
+
+        let mut state = self.shared.state.lock().unwrap();
+
+        match state.pattern_sub.entry(pattern) {
+            Entry::Occupied(e) => e.get().subscribe(),
+            Entry::Vacant(e) => {
+                let (tx, rx) = broadcast::channel(1024);
+                e.insert(tx);
+                rx
+            }
+        }
+    }
+
     /// Publish a message to the channel. Returns the number of subscribers
-    /// listening on the channel.
+    /// listening on the channel, including the subscribers of the matching patterns.
     pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
         let state = self.shared.state.lock().unwrap();
 
-        state
+        let num_subscribers = state
             .pub_sub
             .get(key)
             // On a successful message send on the broadcast channel, the number
             // of subscribers is returned. An error indicates there are no
             // receivers, in which case, `0` should be returned.
-            .map(|tx| tx.send(value).unwrap_or(0))
+            .map(|tx| tx.send(value.clone()).unwrap_or(0))
             // If there is no entry for the channel key, then there are no
             // subscribers. In this case, return `0`.
+            .unwrap_or(0);
+
+        let num_pattern_subscribers: usize = state
+            .pattern_sub
+            .iter()
+            .filter(|(pattern, _)| glob_match(pattern.as_bytes(), key.as_bytes()))
+            .map(|(_, tx)| tx.send((key.to_owned(), value.clone())).unwrap_or(0))
+            .sum();
+
+        num_subscribers + num_pattern_subscribers
+    }
+
+    /// Returns a `Receiver` for the requested shard channel.
+    pub(crate) fn ssubscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        state
+            .shard_sub
+            .entry(key)
+            .or_insert_with(|| broadcast::channel(1024).0)
+            .subscribe()
+    }
+
+    /// Publish a message to the shard channel. Returns the number of subscribers
+    /// listening on the shard channel, patterns do not match shard channels.
+    pub(crate) fn spublish(&self, key: &str, value: Bytes) -> usize {
+        let state = self.shared.state.lock().unwrap();
+
+        state
+            .shard_sub
+            .get(key)
+            .map(|tx| tx.send(value).unwrap_or(0))
             .unwrap_or(0)
     }
 
+    /// Remove the channel once the receivers of all its subscribers are dropped
+    pub(crate) fn unsubscribe(&self, key: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        remove_if_unsubscribed(&mut state.pub_sub, key);
+    }
+
+    /// Remove the pattern once the receivers of all its subscribers are dropped
+    pub(crate) fn punsubscribe(&self, pattern: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        remove_if_unsubscribed(&mut state.pattern_sub, pattern);
+    }
+
+    /// Remove the shard channel once the receivers of all its subscribers are dropped
+    pub(crate) fn sunsubscribe(&self, key: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        remove_if_unsubscribed(&mut state.shard_sub, key);
+    }
+
+    /// Returns the channels, or the shard channels if `sharded`, with at least one
+    /// subscriber, optionally only the ones matching the glob-style `pattern`.
+    pub(crate) fn pubsub_channels(&self, pattern: Option<&str>, sharded: bool) -> Vec<String> {
+        let state = self.shared.state.lock().unwrap();
+
+        let channels = if sharded {
+            &state.shard_sub
+        } else {
+            &state.pub_sub
+        };
+        channels
+            .iter()
+            .filter(|(channel, tx)| {
+                tx.receiver_count() > 0
+                    && pattern.map_or(true, |p| glob_match(p.as_bytes(), channel.as_bytes()))
+            })
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    /// Returns the number of subscribers of the channel, or the shard channel if
+    /// `sharded`, pattern subscribers are not counted.
+    pub(crate) fn pubsub_numsub(&self, channel: &str, sharded: bool) -> usize {
+        let state = self.shared.state.lock().unwrap();
+
+        let channels = if sharded {
+            &state.shard_sub
+        } else {
+            &state.pub_sub
+        };
+        channels.get(channel).map_or(0, |tx| tx.receiver_count())
+    }
+
+    /// Returns the number of unique patterns subscribed by the clients.
+    pub(crate) fn pubsub_numpat(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+
+        state
+            .pattern_sub
+            .values()
+            .filter(|tx| tx.receiver_count() > 0)
+            .count()
+    }
+
     /// Signals the purge background task to shut down. This is called by the
     /// `DbShutdown`s `Drop` implementation.
     fn shutdown_purge_task(&self) {
@@ -373,6 +528,12 @@ impl State {
     }
 }
 
+fn remove_if_unsubscribed<T>(subs: &mut HashMap<String, broadcast::Sender<T>>, key: &str) {
+    if subs.get(key).map_or(false, |tx| tx.receiver_count() == 0) {
+        subs.remove(key);
+    }
+}
+
 /// Routine executed by the background task.
 ///
 /// Wait to be notified. On notification, purge any expired keys from the shared
@@ -3,6 +3,7 @@
 
 use crate::tikv::errors::RTError;
 use bytes::{Buf, Bytes};
+use std::cell::Cell;
 use std::convert::TryInto;
 use std::fmt;
 use std::io::Cursor;
@@ -19,6 +20,38 @@ pub enum Frame {
     Bulk(Bytes),
     Null,
     Array(Vec<Frame>),
+    // RESP3 types, they are written as their RESP2 counterparts to RESP2 clients
+    Map(Vec<(Frame, Frame)>),
+    Set(Vec<Frame>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    Verbatim(String, Bytes),
+    Push(Vec<Frame>),
+    Attribute(Vec<(Frame, Frame)>),
+}
+
+tokio::task_local! {
+    /// RESP version of the connection selected by HELLO, the replies whose shape differs
+    /// between RESP2 and RESP3 are built according to it.
+    pub static RESP_VERSION: Cell<u8>;
+}
+
+/// Max length of an inline command, the same as redis
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// Returns true if the current connection speaks RESP3
+pub fn is_resp3() -> bool {
+    RESP_VERSION.try_with(|v| v.get() == 3).unwrap_or(false)
+}
+
+/// Format the double as the RESP3 double type and the RESP2 bulk string do
+pub fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_owned()
+    } else {
+        d.to_string()
+    }
 }
 
 #[derive(Debug)]
@@ -26,6 +59,9 @@ pub enum Error {
     /// Not enough data is available to parse a message
     Incomplete,
 
+    /// Malformed request, which is replied before the connection is closed
+    Protocol(String),
+
     /// Invalid message encoding
     Other(crate::Error),
 }
@@ -64,6 +100,28 @@ impl Frame {
         }
     }
 
+    /// Convert the RESP3 types to their RESP2 counterparts recursively, attributes
+    /// are dropped as RESP2 has no such type.
+    pub(crate) fn into_resp2(self) -> Frame {
+        match self {
+            Frame::Array(items) | Frame::Set(items) | Frame::Push(items) => {
+                Frame::Array(items.into_iter().map(Frame::into_resp2).collect())
+            }
+            Frame::Map(pairs) => Frame::Array(
+                pairs
+                    .into_iter()
+                    .flat_map(|(key, value)| vec![key.into_resp2(), value.into_resp2()])
+                    .collect(),
+            ),
+            Frame::Double(d) => Frame::Bulk(Bytes::from(format_double(d))),
+            Frame::Boolean(b) => Frame::Integer(b as i64),
+            Frame::BigNumber(num) => Frame::Bulk(Bytes::from(num)),
+            Frame::Verbatim(_, data) => Frame::Bulk(data),
+            Frame::Attribute(_) => Frame::Null,
+            frame => frame,
+        }
+    }
+
     /// Checks if an entire message can be decoded from `src`
     pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
         match get_u8(src)? {
@@ -91,7 +149,7 @@ impl Frame {
                     skip(src, len + 2)
                 }
             }
-            b'*' => {
+            b'*' | b'~' | b'>' => {
                 let len = get_decimal(src)?;
 
                 for _ in 0..len {
@@ -100,6 +158,23 @@ impl Frame {
 
                 Ok(())
             }
+            b'%' | b'|' => {
+                let len = get_decimal(src)?;
+
+                for _ in 0..len * 2 {
+                    Frame::check(src)?;
+                }
+
+                Ok(())
+            }
+            b'_' | b',' | b'#' | b'(' => {
+                get_line(src)?;
+                Ok(())
+            }
+            b'=' | b'!' => {
+                let len: usize = get_decimal(src)?.try_into()?;
+                skip(src, len + 2)
+            }
             actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
         }
     }
@@ -155,19 +230,107 @@ impl Frame {
                     Ok(Frame::Bulk(data))
                 }
             }
-            b'*' => {
-                let len = get_decimal(src)?.try_into()?;
-                let mut out = Vec::with_capacity(len);
-
-                for _ in 0..len {
-                    out.push(Frame::parse(src)?);
+            b'*' => Ok(Frame::Array(parse_items(src)?)),
+            b'~' => Ok(Frame::Set(parse_items(src)?)),
+            b'>' => Ok(Frame::Push(parse_items(src)?)),
+            b'%' => Ok(Frame::Map(parse_pairs(src)?)),
+            b'|' => Ok(Frame::Attribute(parse_pairs(src)?)),
+            b'_' => {
+                get_line(src)?;
+                Ok(Frame::Null)
+            }
+            b',' => {
+                let line = String::from_utf8(get_line(src)?.to_vec())?;
+                let d = match line.as_str() {
+                    "inf" => f64::INFINITY,
+                    "-inf" => f64::NEG_INFINITY,
+                    "nan" => f64::NAN,
+                    _ => line
+                        .parse()
+                        .map_err(|_| "protocol error; invalid frame format")?,
+                };
+                Ok(Frame::Double(d))
+            }
+            b'#' => match get_line(src)? {
+                [b't'] => Ok(Frame::Boolean(true)),
+                [b'f'] => Ok(Frame::Boolean(false)),
+                _ => Err("protocol error; invalid frame format".into()),
+            },
+            b'(' => {
+                let line = get_line(src)?.to_vec();
+                Ok(Frame::BigNumber(String::from_utf8(line)?))
+            }
+            b'=' => {
+                let data = get_blob(src)?;
+                // the data is prefixed by the 3 bytes format and a colon
+                if data.len() < 4 || data[3] != b':' {
+                    return Err("protocol error; invalid frame format".into());
                 }
-
-                Ok(Frame::Array(out))
+                let format = String::from_utf8(data[..3].to_vec())?;
+                Ok(Frame::Verbatim(format, data.slice(4..)))
+            }
+            b'!' => {
+                let data = get_blob(src)?;
+                Ok(Frame::ErrorOwned(String::from_utf8(data.to_vec())?))
             }
             _ => unimplemented!(),
         }
     }
+
+    /// Returns true if the message starting with `first` is an inline command,
+    /// e.g. `PING\r\n` typed in telnet, rather than a RESP frame.
+    pub fn is_inline(first: u8) -> bool {
+        !matches!(
+            first,
+            b'+' | b'-'
+                | b':'
+                | b'$'
+                | b'*'
+                | b'~'
+                | b'>'
+                | b'%'
+                | b'|'
+                | b'_'
+                | b','
+                | b'#'
+                | b'('
+                | b'='
+                | b'!'
+        )
+    }
+
+    /// Checks if an entire inline command, terminated by a new line, can be decoded from `src`
+    pub fn check_inline(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+        let start = src.position() as usize;
+        match src.get_ref()[start..].iter().position(|b| *b == b'\n') {
+            Some(end) if end > MAX_INLINE_LEN => {
+                Err(Error::Protocol("too big inline request".to_string()))
+            }
+            Some(end) => {
+                src.set_position((start + end + 1) as u64);
+                Ok(())
+            }
+            None if src.remaining() > MAX_INLINE_LEN => {
+                Err(Error::Protocol("too big inline request".to_string()))
+            }
+            None => Err(Error::Incomplete),
+        }
+    }
+
+    /// Parse the inline command validated with `check_inline` into an array of
+    /// bulk strings, the array is empty for a blank line.
+    pub fn parse_inline(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
+        let start = src.position() as usize;
+        let line = &src.get_ref()[start..];
+        let end = line
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or(Error::Incomplete)?;
+        src.set_position((start + end + 1) as u64);
+
+        let args = split_args(&line[..end])?;
+        Ok(Frame::Array(args.into_iter().map(Frame::Bulk).collect()))
+    }
 }
 
 impl PartialEq<&str> for Frame {
@@ -194,7 +357,24 @@ impl fmt::Display for Frame {
                 Err(_) => write!(fmt, "{:?}", msg),
             },
             Frame::Null => "(nil)".fmt(fmt),
-            Frame::Array(parts) => {
+            Frame::Double(d) => format_double(*d).fmt(fmt),
+            Frame::Boolean(b) => b.fmt(fmt),
+            Frame::BigNumber(num) => num.fmt(fmt),
+            Frame::Verbatim(_, data) => match str::from_utf8(data) {
+                Ok(string) => string.fmt(fmt),
+                Err(_) => write!(fmt, "{:?}", data),
+            },
+            Frame::Map(pairs) | Frame::Attribute(pairs) => {
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, " ")?;
+                    }
+                    write!(fmt, "{} {}", key, value)?;
+                }
+
+                Ok(())
+            }
+            Frame::Array(parts) | Frame::Set(parts) | Frame::Push(parts) => {
                 for (i, part) in parts.iter().enumerate() {
                     if i > 0 {
                         write!(fmt, " ")?;
@@ -218,6 +398,45 @@ impl From<RTError> for Frame {
     }
 }
 
+fn parse_items(src: &mut Cursor<&[u8]>) -> Result<Vec<Frame>, Error> {
+    let len = get_decimal(src)?.try_into()?;
+    let mut out = Vec::with_capacity(len);
+
+    for _ in 0..len {
+        out.push(Frame::parse(src)?);
+    }
+
+    Ok(out)
+}
+
+fn parse_pairs(src: &mut Cursor<&[u8]>) -> Result<Vec<(Frame, Frame)>, Error> {
+    let len = get_decimal(src)?.try_into()?;
+    let mut out = Vec::with_capacity(len);
+
+    for _ in 0..len {
+        let key = Frame::parse(src)?;
+        let value = Frame::parse(src)?;
+        out.push((key, value));
+    }
+
+    Ok(out)
+}
+
+/// Read a length prefixed blob, like the bulk string
+fn get_blob(src: &mut Cursor<&[u8]>) -> Result<Bytes, Error> {
+    let len = get_decimal(src)?.try_into()?;
+    let n = len + 2;
+
+    if src.remaining() < n {
+        return Err(Error::Incomplete);
+    }
+
+    let data = Bytes::copy_from_slice(&src.chunk()[..len]);
+    skip(src, n)?;
+
+    Ok(data)
+}
+
 fn peek_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
     if !src.has_remaining() {
         return Err(Error::Incomplete);
@@ -252,6 +471,104 @@ fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
     atoi::<i64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
 }
 
+/// Split the inline command into arguments as `sdssplitargs` of redis does,
+/// arguments are separated by spaces and may be quoted.
+fn split_args(line: &[u8]) -> Result<Vec<Bytes>, Error> {
+    let is_space = |c: u8| matches!(c, b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c);
+    let hex_value = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let at = |i: usize| line.get(i).copied();
+
+    let mut args = vec![];
+    let mut i = 0;
+    loop {
+        while at(i).map_or(false, is_space) {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = vec![];
+        // inside "double quotes" or 'single quotes'
+        let mut in_dq = false;
+        let mut in_sq = false;
+        loop {
+            let c = at(i);
+            if in_dq {
+                match (c, at(i + 1)) {
+                    (Some(b'\\'), Some(b'x')) => {
+                        match (at(i + 2).and_then(hex_value), at(i + 3).and_then(hex_value)) {
+                            (Some(h), Some(l)) => {
+                                arg.push(h * 16 + l);
+                                i += 3;
+                            }
+                            _ => {
+                                arg.push(b'x');
+                                i += 1;
+                            }
+                        }
+                    }
+                    (Some(b'\\'), Some(escaped)) => {
+                        arg.push(match escaped {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            c => c,
+                        });
+                        i += 1;
+                    }
+                    // the closing quote must be followed by a space or nothing
+                    (Some(b'"'), next) => {
+                        if next.map_or(false, |n| !is_space(n)) {
+                            return Err(Error::Protocol(
+                                "unbalanced quotes in request".to_string(),
+                            ));
+                        }
+                        i += 1;
+                        break;
+                    }
+                    (None, _) => {
+                        return Err(Error::Protocol("unbalanced quotes in request".to_string()));
+                    }
+                    (Some(c), _) => arg.push(c),
+                }
+            } else if in_sq {
+                match (c, at(i + 1)) {
+                    (Some(b'\\'), Some(b'\'')) => {
+                        arg.push(b'\'');
+                        i += 1;
+                    }
+                    (Some(b'\''), next) => {
+                        if next.map_or(false, |n| !is_space(n)) {
+                            return Err(Error::Protocol(
+                                "unbalanced quotes in request".to_string(),
+                            ));
+                        }
+                        i += 1;
+                        break;
+                    }
+                    (None, _) => {
+                        return Err(Error::Protocol("unbalanced quotes in request".to_string()));
+                    }
+                    (Some(c), _) => arg.push(c),
+                }
+            } else {
+                match c {
+                    None => break,
+                    Some(c) if is_space(c) => break,
+                    Some(b'"') => in_dq = true,
+                    Some(b'\'') => in_sq = true,
+                    Some(c) => arg.push(c),
+                }
+            }
+            i += 1;
+        }
+        args.push(Bytes::from(arg));
+    }
+}
+
 /// Find a line
 fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     // Scan the bytes directly
@@ -302,6 +619,7 @@ impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Incomplete => "stream ended early".fmt(fmt),
+            Error::Protocol(msg) => write!(fmt, "Protocol error: {}", msg),
             Error::Other(err) => err.fmt(fmt),
         }
     }
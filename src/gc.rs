@@ -1,5 +1,5 @@
 use futures::{FutureExt, StreamExt};
-use slog::{debug, error, info};
+use slog::{debug, error, info, warn};
 use std::collections::HashSet;
 use std::convert::TryInto;
 use std::sync::Arc;
@@ -9,12 +9,14 @@ use tokio::time::{self, Duration, MissedTickBehavior};
 
 use crc::{Crc, CRC_16_XMODEM};
 
-use crate::cluster::Cluster;
+use crate::cluster::{key_slot, Cluster};
 use crate::config::LOGGER;
-use crate::metrics::GC_TASK_QUEUE_COUNTER;
+use crate::metrics::{DROPPED_EXPIRED_KEY_COUNTER, GC_TASK_QUEUE_COUNTER};
 use crate::tikv::encoding::{DataType, KeyDecoder};
 use crate::tikv::errors::{AsyncResult, RTError};
+use crate::tikv::string::StringCommandCtx;
 use crate::tikv::{get_txn_client, KEY_ENCODER};
+use crate::utils::now_timestamp_in_millis;
 use crate::{
     async_deletion_enabled_or_default, async_gc_interval_or_default,
     async_gc_worker_queue_size_or_default,
@@ -22,6 +24,74 @@ use crate::{
 
 const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
 
+// versions being copied to by RENAME and COPY are cleaned up if the copy is not renewed within
+const STAGED_COPY_LEASE_MS: u64 = 60000;
+
+// expired keys found by read only commands, waiting to be deleted by gc
+struct ExpiredKeyQueue {
+    tx: Sender<String>,
+    pending: HashSet<String>,
+    // keys dropped by the full queue, submitted again on the next gc tick
+    dropped: HashSet<String>,
+}
+
+impl ExpiredKeyQueue {
+    fn try_submit(&mut self, key: &str) -> bool {
+        if self.pending.contains(key) {
+            return true;
+        }
+        if self.tx.try_send(key.to_owned()).is_err() {
+            return false;
+        }
+        self.pending.insert(key.to_owned());
+        true
+    }
+}
+
+lazy_static! {
+    static ref EXPIRED_KEY_QUEUE: std::sync::Mutex<Option<ExpiredKeyQueue>> =
+        std::sync::Mutex::new(None);
+}
+
+/// Hand an expired key over to gc, so that read only commands never write.
+///
+/// The key is kept aside if the queue is full and submitted again by the next gc tick.
+pub fn submit_expired_key(key: &str) {
+    if let Some(queue) = EXPIRED_KEY_QUEUE.lock().unwrap().as_mut() {
+        if queue.try_submit(key) {
+            return;
+        }
+        DROPPED_EXPIRED_KEY_COUNTER.inc();
+        debug!(LOGGER, "[GC] expired key queue is full, drop key {}", key);
+        // beyond the capacity the key is left to the next read of it
+        if queue.dropped.len() < async_gc_worker_queue_size_or_default() {
+            queue.dropped.insert(key.to_owned());
+        }
+    }
+}
+
+// submit the expired keys dropped by the full queue again
+fn resubmit_dropped_expired_keys() {
+    if let Some(queue) = EXPIRED_KEY_QUEUE.lock().unwrap().as_mut() {
+        if queue.dropped.is_empty() {
+            return;
+        }
+        let dropped = std::mem::take(&mut queue.dropped);
+        let total = dropped.len();
+        for key in dropped {
+            if !queue.try_submit(&key) {
+                queue.dropped.insert(key);
+            }
+        }
+        warn!(
+            LOGGER,
+            "[GC] {} expired keys were dropped by the full queue, {} of them are submitted again",
+            total,
+            total - queue.dropped.len()
+        );
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GcTask {
     key_type: DataType,
@@ -51,6 +121,7 @@ impl GcTask {
 pub struct GcMaster {
     workers: Vec<GcWorker>,
     topo: Cluster,
+    expired_key_rx: Arc<Mutex<Receiver<String>>>,
 }
 
 impl GcMaster {
@@ -64,7 +135,18 @@ impl GcMaster {
             workers.push(worker);
         }
 
-        GcMaster { workers, topo }
+        let (tx, rx) = mpsc::channel::<String>(async_gc_worker_queue_size_or_default());
+        *EXPIRED_KEY_QUEUE.lock().unwrap() = Some(ExpiredKeyQueue {
+            tx,
+            pending: HashSet::new(),
+            dropped: HashSet::new(),
+        });
+
+        GcMaster {
+            workers,
+            topo,
+            expired_key_rx: Arc::new(Mutex::new(rx)),
+        }
     }
 
     pub async fn start_workers(&self) {
@@ -73,6 +155,24 @@ impl GcMaster {
         for worker in &self.workers {
             worker.clone().run().await;
         }
+
+        let rx = self.expired_key_rx.clone();
+        tokio::spawn(async move {
+            info!(LOGGER, "[GC] start expired key cleaner");
+            while let Some(key) = rx.lock().await.recv().await {
+                // the key is checked again, it may be updated after submitted
+                if let Err(e) = StringCommandCtx::new(None)
+                    .do_async_txnkv_expire_if_needed(&key)
+                    .await
+                {
+                    error!(LOGGER, "[GC] delete expired key {} failed: {:?}", key, e);
+                }
+                if let Some(queue) = EXPIRED_KEY_QUEUE.lock().unwrap().as_mut() {
+                    queue.pending.remove(&key);
+                }
+            }
+            info!(LOGGER, "[GC] expired key cleaner exit");
+        });
     }
 
     // dispatch task to a worker
@@ -93,6 +193,8 @@ impl GcMaster {
         loop {
             interval.tick().await;
 
+            resubmit_dropped_expired_keys();
+
             if !async_deletion_enabled_or_default() {
                 continue;
             }
@@ -123,32 +225,17 @@ impl GcMaster {
             while let Some(kv) = iter.next().await {
                 let (user_key, version) = KeyDecoder::decode_key_gc_userkey_version(kv.0);
 
-                let (slot_range_left, slot_range_right) = self.topo.myself_owned_slots();
-                // crc16 to user key with hashtag `{}` support
-                // check if user key contains valid hashtag
-                let mut left_tag_idx = usize::MAX;
-                let mut right_tag_idx = usize::MAX;
-                for (idx, byte) in user_key.iter().enumerate() {
-                    if byte == &b'{' {
-                        left_tag_idx = idx;
-                    }
-                    if left_tag_idx != usize::MAX && byte == &b'}' {
-                        right_tag_idx = idx;
-                        break;
-                    }
-                }
-                let user_key_hash: usize =
-                    if right_tag_idx != usize::MAX && right_tag_idx - left_tag_idx > 1 {
-                        // we have a valid hashtag, do crc16 to string to the content in hashtag
-                        (CRC16.checksum(&user_key[left_tag_idx + 1..right_tag_idx]) & 0x3FFF).into()
-                    } else {
-                        (CRC16.checksum(&user_key) & 0x3FFF).into()
-                    };
-
                 // skip if user key is not owned by myself
-                if user_key_hash < slot_range_left || user_key_hash > slot_range_right {
+                if !self.topo.owns_slot(key_slot(&user_key)) {
                     continue;
                 }
+                // skip the version of a copy in progress, whose value holds the lease start
+                if kv.1.len() == 9 {
+                    let leased_at = u64::from_be_bytes(kv.1[1..].try_into().unwrap());
+                    if now_timestamp_in_millis() < leased_at + STAGED_COPY_LEASE_MS {
+                        continue;
+                    }
+                }
                 let key_type = match kv.1[0] {
                     0 => DataType::String,
                     1 => DataType::Hash,
@@ -228,6 +315,13 @@ impl GcWorker {
                     let mut txn = txn_rc.lock().await;
                     let user_key = String::from_utf8_lossy(&task.user_key);
                     let version = task.version;
+
+                    // the version may be taken back by a finished copy since it is dispatched
+                    let gc_version_key =
+                        KEY_ENCODER.encode_txnkv_gc_version_key(&user_key, version);
+                    if txn.get(gc_version_key.clone()).await?.is_none() {
+                        return Ok(());
+                    }
                     match task.key_type {
                         DataType::String => {
                             panic!("string not support async deletion");
@@ -323,8 +417,6 @@ impl GcWorker {
                     }
 
                     // delete gc version key
-                    let gc_version_key =
-                        KEY_ENCODER.encode_txnkv_gc_version_key(&user_key, version);
                     txn.delete(gc_version_key).await?;
 
                     Ok(())
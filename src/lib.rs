@@ -39,6 +39,8 @@ pub use tikv::do_async_raw_connect;
 pub use tikv::do_async_txn_connect;
 pub use tikv::set_instance_id;
 
+mod rdb;
+
 pub mod cluster;
 
 pub mod client;
@@ -75,14 +77,21 @@ pub use config::cmd_lrem_length_limit_or_default;
 pub use config::config_cluster_broadcast_addr_or_default;
 pub use config::config_cluster_topology_expire_or_default;
 pub use config::config_cluster_topology_interval_or_default;
+pub use config::config_cluster_tracking_invalidation_or_default;
 pub use config::config_instance_id_or_default;
 pub use config::config_listen_or_default;
 pub use config::config_local_pool_number;
+pub use config::config_lua_memory_limit_or_default;
+pub use config::config_lua_time_limit_or_default;
 pub use config::config_meta_key_number_or_default;
+pub use config::config_notify_keyspace_events_or_default;
+pub use config::config_password;
 pub use config::config_pd_addrs_or_default;
+pub use config::config_pipeline_window_or_default;
 pub use config::config_port_or_default;
 pub use config::config_prometheus_listen_or_default;
 pub use config::config_prometheus_port_or_default;
+pub use config::config_script_cache_size_or_default;
 pub use config::config_tls_auth_client_or_default;
 pub use config::config_tls_ca_cert_file_or_default;
 pub use config::config_tls_cert_file_or_default;
@@ -98,8 +107,12 @@ pub use config::is_use_async_commit;
 pub use config::is_use_pessimistic_txn;
 pub use config::is_use_txn_api;
 pub use config::set_global_config;
+pub use config::txn_gc_life_time_ms_or_default;
+pub use config::txn_gc_safepoint_refresh_ms_or_default;
+pub use config::txn_interactive_timeout_ms_or_default;
 pub use config::txn_lock_backoff_delay_attemps;
 pub use config::txn_lock_backoff_delay_ms;
+pub use config::txn_read_staleness_ms_or_default;
 pub use config::txn_region_backoff_delay_attemps;
 pub use config::txn_region_backoff_delay_ms;
 pub use config::txn_retry_count;
@@ -107,6 +120,10 @@ pub use config::Config;
 
 pub mod gc;
 
+mod notify;
+
+mod tracking;
+
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
 /// Default port that a redis server listens on.
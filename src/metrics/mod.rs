@@ -63,6 +63,11 @@ lazy_static! {
         &["kind"]
     )
     .unwrap();
+    pub static ref DROPPED_EXPIRED_KEY_COUNTER: IntCounter = register_int_counter!(
+        "tikv_redis_dropped_expired_keys_count_total",
+        "The number of expired keys dropped by the full gc queue"
+    )
+    .unwrap();
 
     // Trasactions
     pub static ref SNAPSHOT_COUNTER: IntCounter = register_int_counter!("tikv_redis_snapshot_count_total", "Snapshot count").unwrap();
@@ -104,6 +109,18 @@ lazy_static! {
         exponential_buckets(0.0005, 2.0, 20).unwrap()
     )
     .unwrap();
+    pub static ref STALE_READ_TSO_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_stale_read_tso_count_total",
+        "Timestamps used by stale reads, cached or refreshed from PD",
+        &["kind"]
+    )
+    .unwrap();
+    pub static ref STALE_READ_TSO_AGE: Histogram = register_histogram!(
+        "tikv_redis_stale_read_tso_age_seconds",
+        "Bucketed histogram of the age of cached timestamp served by stale reads",
+        exponential_buckets(0.0005, 2.0, 20).unwrap()
+    )
+    .unwrap();
    pub static ref HANDLE_SNAPSHOT_DURATION: Histogram = register_histogram!(
         "tikv_redis_handle_snapshot_duration_seconds",
         "Bucketed histogram of handling snapshot duration",
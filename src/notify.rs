@@ -0,0 +1,187 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use bytes::Bytes;
+use slog::{error, info};
+use tokio::sync::mpsc;
+
+use crate::cluster::Cluster;
+use crate::config::LOGGER;
+use crate::config_notify_keyspace_events_or_default;
+use crate::tracking::invalidate_key;
+use crate::Db;
+
+// classes of keyspace events, the same as redis
+pub const NOTIFY_KEYSPACE: u32 = 1 << 0; // K
+pub const NOTIFY_KEYEVENT: u32 = 1 << 1; // E
+pub const NOTIFY_GENERIC: u32 = 1 << 2; // g
+pub const NOTIFY_STRING: u32 = 1 << 3; // $
+pub const NOTIFY_LIST: u32 = 1 << 4; // l
+pub const NOTIFY_SET: u32 = 1 << 5; // s
+pub const NOTIFY_HASH: u32 = 1 << 6; // h
+pub const NOTIFY_ZSET: u32 = 1 << 7; // z
+pub const NOTIFY_EXPIRED: u32 = 1 << 8; // x
+pub const NOTIFY_EVICTED: u32 = 1 << 9; // e
+pub const NOTIFY_STREAM: u32 = 1 << 10; // t
+pub const NOTIFY_KEY_MISS: u32 = 1 << 11; // m
+pub const NOTIFY_MODULE: u32 = 1 << 12; // d
+pub const NOTIFY_NEW: u32 = 1 << 13; // n
+pub const NOTIFY_ALL: u32 = NOTIFY_GENERIC
+    | NOTIFY_STRING
+    | NOTIFY_LIST
+    | NOTIFY_SET
+    | NOTIFY_HASH
+    | NOTIFY_ZSET
+    | NOTIFY_EXPIRED
+    | NOTIFY_EVICTED
+    | NOTIFY_STREAM
+    | NOTIFY_MODULE; // A
+
+static NOTIFY_FLAGS: AtomicU32 = AtomicU32::new(0);
+
+lazy_static! {
+    static ref NOTIFIER: RwLock<Option<Notifier>> = RwLock::new(None);
+}
+
+tokio::task_local! {
+    // events of the transaction being executed, they are published once it commits
+    static PENDING_EVENTS: PendingEvents;
+}
+
+/// Keyspace events raised in a transaction which is not committed yet, they are
+/// published by `publish` after the commit, or dropped with the transaction.
+#[derive(Debug, Clone, Default)]
+pub struct PendingEvents(Arc<Mutex<Vec<(u32, String, String)>>>);
+
+impl PendingEvents {
+    /// Run `f`, the keyspace events it raises are kept in `self` until published.
+    pub async fn defer<F: Future>(&self, f: F) -> F::Output {
+        PENDING_EVENTS.scope(self.clone(), f).await
+    }
+
+    /// Publish the events after the transaction commits, they are kept by the
+    /// outer transaction instead if the commit happens inside one.
+    pub fn publish(&self) {
+        let events = std::mem::take(&mut *self.0.lock().unwrap());
+        for (class, event, key) in events {
+            notify_keyspace_event(class, &event, &key);
+        }
+    }
+
+    /// Drop the events of the rolled back transaction
+    pub fn discard(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+struct Notifier {
+    db: Db,
+    // notifications to be published on the other instances
+    peer_tx: mpsc::UnboundedSender<(String, Bytes)>,
+}
+
+/// Parse the `notify-keyspace-events` string into the event flags, returns None
+/// if it contains an unknown class.
+pub fn keyspace_events_from_str(classes: &str) -> Option<u32> {
+    let mut flags = 0;
+    for c in classes.chars() {
+        flags |= match c {
+            'A' => NOTIFY_ALL,
+            'g' => NOTIFY_GENERIC,
+            '$' => NOTIFY_STRING,
+            'l' => NOTIFY_LIST,
+            's' => NOTIFY_SET,
+            'h' => NOTIFY_HASH,
+            'z' => NOTIFY_ZSET,
+            'x' => NOTIFY_EXPIRED,
+            'e' => NOTIFY_EVICTED,
+            'K' => NOTIFY_KEYSPACE,
+            'E' => NOTIFY_KEYEVENT,
+            't' => NOTIFY_STREAM,
+            'm' => NOTIFY_KEY_MISS,
+            'd' => NOTIFY_MODULE,
+            'n' => NOTIFY_NEW,
+            _ => return None,
+        };
+    }
+    Some(flags)
+}
+
+/// Start publishing keyspace notifications to the subscribers of `db` and of the
+/// other instances in the cluster, according to the `notify_keyspace_events` config.
+pub fn init_keyspace_notifier(db: Db, topo: Cluster) {
+    let classes = config_notify_keyspace_events_or_default();
+    let flags = match keyspace_events_from_str(&classes) {
+        Some(flags) => flags,
+        None => {
+            error!(
+                LOGGER,
+                "invalid notify_keyspace_events {}, keyspace notifications disabled", classes
+            );
+            0
+        }
+    };
+    // neither keyspace nor keyevent channel is selected
+    if flags & (NOTIFY_KEYSPACE | NOTIFY_KEYEVENT) == 0 {
+        return;
+    }
+
+    let (peer_tx, mut peer_rx) = mpsc::unbounded_channel::<(String, Bytes)>();
+    *NOTIFIER.write().unwrap() = Some(Notifier { db, peer_tx });
+    NOTIFY_FLAGS.store(flags, Ordering::Relaxed);
+
+    // forward notifications one by one to keep them in order on the other instances
+    tokio::spawn(async move {
+        while let Some((channel, message)) = peer_rx.recv().await {
+            topo.publish_to_peers(&channel, &message).await;
+        }
+        info!(LOGGER, "keyspace notifier exit");
+    });
+}
+
+/// Publish the `event` of `key` on the keyspace and keyevent channels, if the
+/// class of the event is enabled. The clients tracking `key` are always notified.
+///
+/// Events raised in a transaction are published after it commits.
+pub fn notify_keyspace_event(class: u32, event: &str, key: &str) {
+    let deferred = PENDING_EVENTS.try_with(|pending| {
+        pending
+            .0
+            .lock()
+            .unwrap()
+            .push((class, event.to_owned(), key.to_owned()))
+    });
+    if deferred.is_ok() {
+        return;
+    }
+
+    invalidate_key(key);
+
+    let flags = NOTIFY_FLAGS.load(Ordering::Relaxed);
+    if flags & class == 0 {
+        return;
+    }
+
+    if let Some(notifier) = NOTIFIER.read().unwrap().as_ref() {
+        if flags & NOTIFY_KEYSPACE != 0 {
+            notifier.publish(
+                format!("__keyspace@0__:{}", key),
+                Bytes::from(event.to_owned()),
+            );
+        }
+        if flags & NOTIFY_KEYEVENT != 0 {
+            notifier.publish(
+                format!("__keyevent@0__:{}", event),
+                Bytes::from(key.to_owned()),
+            );
+        }
+    }
+}
+
+impl Notifier {
+    fn publish(&self, channel: String, message: Bytes) {
+        self.db.publish(&channel, message.clone());
+        let _ = self.peer_tx.send((channel, message));
+    }
+}
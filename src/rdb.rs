@@ -0,0 +1,588 @@
+use crc::{Algorithm, Crc};
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+use crate::tikv::errors::{AsyncResult, REDIS_BAD_DATA_FORMAT_ERR, REDIS_DUMP_PAYLOAD_ERR};
+
+// crc64 with jones polynomial, the same as crc64.c in redis
+const CRC_64_REDIS: Algorithm<u64> = Algorithm {
+    poly: 0xad93d23594c935a9,
+    init: 0x0000000000000000,
+    refin: true,
+    refout: true,
+    xorout: 0x0000000000000000,
+    check: 0xe9c6d914c4b8d9ca,
+    residue: 0x0000000000000000,
+};
+const CRC64: Crc<u64> = Crc::<u64>::new(&CRC_64_REDIS);
+
+/// rdb version written in dump payload, redis 5.0 and later can restore it
+const RDB_VERSION: u16 = 9;
+/// the newest rdb version the loader understands
+const RDB_LOAD_VERSION_MAX: u16 = 12;
+/// rdb version written in function dump payload, functions are introduced in redis 7.0
+const RDB_FUNCTION_VERSION: u16 = 10;
+
+const RDB_OPCODE_FUNCTION2: u8 = 245;
+
+const RDB_TYPE_STRING: u8 = 0;
+const RDB_TYPE_LIST: u8 = 1;
+const RDB_TYPE_SET: u8 = 2;
+const RDB_TYPE_ZSET: u8 = 3;
+const RDB_TYPE_HASH: u8 = 4;
+const RDB_TYPE_ZSET_2: u8 = 5;
+const RDB_TYPE_LIST_ZIPLIST: u8 = 10;
+const RDB_TYPE_SET_INTSET: u8 = 11;
+const RDB_TYPE_ZSET_ZIPLIST: u8 = 12;
+const RDB_TYPE_HASH_ZIPLIST: u8 = 13;
+const RDB_TYPE_LIST_QUICKLIST: u8 = 14;
+const RDB_TYPE_HASH_LISTPACK: u8 = 16;
+const RDB_TYPE_ZSET_LISTPACK: u8 = 17;
+const RDB_TYPE_LIST_QUICKLIST_2: u8 = 18;
+const RDB_TYPE_SET_LISTPACK: u8 = 20;
+
+const RDB_6BITLEN: u8 = 0;
+const RDB_14BITLEN: u8 = 1;
+const RDB_32BITLEN: u8 = 0x80;
+const RDB_64BITLEN: u8 = 0x81;
+const RDB_ENCVAL: u8 = 3;
+
+const RDB_ENC_INT8: u8 = 0;
+const RDB_ENC_INT16: u8 = 1;
+const RDB_ENC_INT32: u8 = 2;
+const RDB_ENC_LZF: u8 = 3;
+
+const QUICKLIST_NODE_CONTAINER_PLAIN: u64 = 1;
+const QUICKLIST_NODE_CONTAINER_PACKED: u64 = 2;
+
+/// Value of a key in a form independent of the storage layout
+#[derive(Debug, Clone, PartialEq)]
+pub enum RdbValue {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    Zset(Vec<(Vec<u8>, f64)>),
+    Hash(Vec<(Vec<u8>, Vec<u8>)>),
+}
+
+/// Serialize the value to a DUMP payload: object type, object, rdb version and crc64.
+pub fn dump_payload(value: &RdbValue) -> Vec<u8> {
+    let mut buf = vec![];
+    match value {
+        RdbValue::String(v) => {
+            buf.push(RDB_TYPE_STRING);
+            write_string(&mut buf, v);
+        }
+        RdbValue::List(elements) => {
+            buf.push(RDB_TYPE_LIST);
+            write_len(&mut buf, elements.len() as u64);
+            for ele in elements {
+                write_string(&mut buf, ele);
+            }
+        }
+        RdbValue::Set(members) => {
+            buf.push(RDB_TYPE_SET);
+            write_len(&mut buf, members.len() as u64);
+            for member in members {
+                write_string(&mut buf, member);
+            }
+        }
+        RdbValue::Zset(pairs) => {
+            buf.push(RDB_TYPE_ZSET_2);
+            write_len(&mut buf, pairs.len() as u64);
+            for (member, score) in pairs {
+                write_string(&mut buf, member);
+                buf.extend_from_slice(&score.to_le_bytes());
+            }
+        }
+        RdbValue::Hash(pairs) => {
+            buf.push(RDB_TYPE_HASH);
+            write_len(&mut buf, pairs.len() as u64);
+            for (field, value) in pairs {
+                write_string(&mut buf, field);
+                write_string(&mut buf, value);
+            }
+        }
+    }
+    write_footer(&mut buf, RDB_VERSION);
+    buf
+}
+
+/// Verify the footer of a DUMP payload and decode the value in it.
+pub fn restore_payload(payload: &[u8]) -> AsyncResult<RdbValue> {
+    let mut reader = Reader::new(verify_footer(payload)?);
+    let value = reader.read_object()?;
+    // trailing garbage means the payload is corrupted
+    if !reader.is_empty() {
+        return Err(REDIS_BAD_DATA_FORMAT_ERR);
+    }
+    Ok(value)
+}
+
+/// Serialize the code of function libraries to a FUNCTION DUMP payload.
+pub fn dump_functions<T: AsRef<[u8]>>(libs: &[T]) -> Vec<u8> {
+    let mut buf = vec![];
+    for code in libs {
+        buf.push(RDB_OPCODE_FUNCTION2);
+        write_string(&mut buf, code.as_ref());
+    }
+    write_footer(&mut buf, RDB_FUNCTION_VERSION);
+    buf
+}
+
+/// Verify the footer of a FUNCTION DUMP payload and return the library codes in it.
+pub fn restore_functions(payload: &[u8]) -> AsyncResult<Vec<Vec<u8>>> {
+    let mut reader = Reader::new(verify_footer(payload)?);
+    let mut libs = vec![];
+    while !reader.is_empty() {
+        if reader.read_u8()? != RDB_OPCODE_FUNCTION2 {
+            return Err(REDIS_BAD_DATA_FORMAT_ERR);
+        }
+        libs.push(reader.read_string()?);
+    }
+    Ok(libs)
+}
+
+// append rdb version and crc64 of the payload
+fn write_footer(buf: &mut Vec<u8>, version: u16) {
+    buf.extend_from_slice(&version.to_le_bytes());
+    let crc = CRC64.checksum(buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+}
+
+// check rdb version and crc64, returns the payload without footer
+fn verify_footer(payload: &[u8]) -> AsyncResult<&[u8]> {
+    if payload.len() < 10 {
+        return Err(REDIS_DUMP_PAYLOAD_ERR);
+    }
+    let (body, crc) = payload.split_at(payload.len() - 8);
+    let version = u16::from_le_bytes(body[body.len() - 2..].try_into().unwrap());
+    if version > RDB_LOAD_VERSION_MAX {
+        return Err(REDIS_DUMP_PAYLOAD_ERR);
+    }
+    if CRC64.checksum(body) != u64::from_le_bytes(crc.try_into().unwrap()) {
+        return Err(REDIS_DUMP_PAYLOAD_ERR);
+    }
+    Ok(&body[..body.len() - 2])
+}
+
+fn write_len(buf: &mut Vec<u8>, len: u64) {
+    if len < 1 << 6 {
+        buf.push((len as u8) | (RDB_6BITLEN << 6));
+    } else if len < 1 << 14 {
+        buf.push(((len >> 8) as u8) | (RDB_14BITLEN << 6));
+        buf.push(len as u8);
+    } else if len <= u32::MAX as u64 {
+        buf.push(RDB_32BITLEN);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        buf.push(RDB_64BITLEN);
+        buf.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &[u8]) {
+    write_len(buf, s.len() as u64);
+    buf.extend_from_slice(s);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn read_bytes(&mut self, n: usize) -> AsyncResult<&'a [u8]> {
+        if self.data.len() - self.pos < n {
+            return Err(REDIS_BAD_DATA_FORMAT_ERR);
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> AsyncResult<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// return (len, is_encoded), len is the encoding type if is_encoded is true
+    fn read_len_with_encoding(&mut self) -> AsyncResult<(u64, bool)> {
+        let first = self.read_u8()?;
+        match first >> 6 {
+            RDB_6BITLEN => Ok(((first & 0x3f) as u64, false)),
+            RDB_14BITLEN => {
+                let next = self.read_u8()?;
+                Ok(((((first & 0x3f) as u64) << 8) | next as u64, false))
+            }
+            RDB_ENCVAL => Ok(((first & 0x3f) as u64, true)),
+            _ => match first {
+                RDB_32BITLEN => Ok((
+                    u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()) as u64,
+                    false,
+                )),
+                RDB_64BITLEN => Ok((
+                    u64::from_be_bytes(self.read_bytes(8)?.try_into().unwrap()),
+                    false,
+                )),
+                _ => Err(REDIS_BAD_DATA_FORMAT_ERR),
+            },
+        }
+    }
+
+    fn read_len(&mut self) -> AsyncResult<usize> {
+        match self.read_len_with_encoding()? {
+            (len, false) => Ok(len as usize),
+            _ => Err(REDIS_BAD_DATA_FORMAT_ERR),
+        }
+    }
+
+    fn read_string(&mut self) -> AsyncResult<Vec<u8>> {
+        let (len, is_encoded) = self.read_len_with_encoding()?;
+        if !is_encoded {
+            return Ok(self.read_bytes(len as usize)?.to_vec());
+        }
+        match len as u8 {
+            RDB_ENC_INT8 => Ok((self.read_u8()? as i8).to_string().into_bytes()),
+            RDB_ENC_INT16 => {
+                let v = i16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap());
+                Ok(v.to_string().into_bytes())
+            }
+            RDB_ENC_INT32 => {
+                let v = i32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap());
+                Ok(v.to_string().into_bytes())
+            }
+            RDB_ENC_LZF => {
+                let clen = self.read_len()?;
+                let len = self.read_len()?;
+                lzf_decompress(self.read_bytes(clen)?, len)
+            }
+            _ => Err(REDIS_BAD_DATA_FORMAT_ERR),
+        }
+    }
+
+    // double saved as string in old RDB_TYPE_ZSET
+    fn read_double_string(&mut self) -> AsyncResult<f64> {
+        match self.read_u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => parse_double(self.read_bytes(len as usize)?),
+        }
+    }
+
+    fn read_binary_double(&mut self) -> AsyncResult<f64> {
+        Ok(f64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    fn read_object(&mut self) -> AsyncResult<RdbValue> {
+        let value = match self.read_u8()? {
+            RDB_TYPE_STRING => RdbValue::String(self.read_string()?),
+            RDB_TYPE_LIST => {
+                let len = self.read_len()?;
+                let mut elements = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    elements.push(self.read_string()?);
+                }
+                RdbValue::List(elements)
+            }
+            RDB_TYPE_SET => {
+                let len = self.read_len()?;
+                let mut members = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    members.push(self.read_string()?);
+                }
+                RdbValue::Set(members)
+            }
+            t @ (RDB_TYPE_ZSET | RDB_TYPE_ZSET_2) => {
+                let len = self.read_len()?;
+                let mut pairs = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    let member = self.read_string()?;
+                    let score = if t == RDB_TYPE_ZSET {
+                        self.read_double_string()?
+                    } else {
+                        self.read_binary_double()?
+                    };
+                    if score.is_nan() {
+                        return Err(REDIS_BAD_DATA_FORMAT_ERR);
+                    }
+                    pairs.push((member, score));
+                }
+                RdbValue::Zset(pairs)
+            }
+            RDB_TYPE_HASH => {
+                let len = self.read_len()?;
+                let mut pairs = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    let field = self.read_string()?;
+                    let value = self.read_string()?;
+                    pairs.push((field, value));
+                }
+                RdbValue::Hash(pairs)
+            }
+            RDB_TYPE_LIST_ZIPLIST => RdbValue::List(ziplist_entries(&self.read_string()?)?),
+            RDB_TYPE_SET_INTSET => RdbValue::Set(intset_entries(&self.read_string()?)?),
+            RDB_TYPE_SET_LISTPACK => RdbValue::Set(listpack_entries(&self.read_string()?)?),
+            RDB_TYPE_ZSET_ZIPLIST => zset_from_entries(ziplist_entries(&self.read_string()?)?)?,
+            RDB_TYPE_ZSET_LISTPACK => zset_from_entries(listpack_entries(&self.read_string()?)?)?,
+            RDB_TYPE_HASH_ZIPLIST => hash_from_entries(ziplist_entries(&self.read_string()?)?)?,
+            RDB_TYPE_HASH_LISTPACK => hash_from_entries(listpack_entries(&self.read_string()?)?)?,
+            RDB_TYPE_LIST_QUICKLIST => {
+                let len = self.read_len()?;
+                let mut elements = vec![];
+                for _ in 0..len {
+                    elements.append(&mut ziplist_entries(&self.read_string()?)?);
+                }
+                RdbValue::List(elements)
+            }
+            RDB_TYPE_LIST_QUICKLIST_2 => {
+                let len = self.read_len()?;
+                let mut elements = vec![];
+                for _ in 0..len {
+                    let container = self.read_len()? as u64;
+                    let node = self.read_string()?;
+                    match container {
+                        QUICKLIST_NODE_CONTAINER_PLAIN => elements.push(node),
+                        QUICKLIST_NODE_CONTAINER_PACKED => {
+                            elements.append(&mut listpack_entries(&node)?)
+                        }
+                        _ => return Err(REDIS_BAD_DATA_FORMAT_ERR),
+                    }
+                }
+                RdbValue::List(elements)
+            }
+            // modules, streams and the others are not supported
+            _ => return Err(REDIS_BAD_DATA_FORMAT_ERR),
+        };
+        check_object(&value)?;
+        Ok(value)
+    }
+}
+
+// reject empty collections and duplicated members, which can not be created by redis commands
+fn check_object(value: &RdbValue) -> AsyncResult<()> {
+    let (len, unique) = match value {
+        RdbValue::String(_) => return Ok(()),
+        RdbValue::List(elements) => (elements.len(), elements.len()),
+        RdbValue::Set(members) => (members.len(), members.iter().collect::<HashSet<_>>().len()),
+        RdbValue::Zset(pairs) => (
+            pairs.len(),
+            pairs.iter().map(|p| &p.0).collect::<HashSet<_>>().len(),
+        ),
+        RdbValue::Hash(pairs) => (
+            pairs.len(),
+            pairs.iter().map(|p| &p.0).collect::<HashSet<_>>().len(),
+        ),
+    };
+    if len == 0 || len != unique {
+        return Err(REDIS_BAD_DATA_FORMAT_ERR);
+    }
+    Ok(())
+}
+
+fn parse_double(bytes: &[u8]) -> AsyncResult<f64> {
+    let s = std::str::from_utf8(bytes).map_err(|_| REDIS_BAD_DATA_FORMAT_ERR)?;
+    match s {
+        "inf" | "+inf" => Ok(f64::INFINITY),
+        "-inf" => Ok(f64::NEG_INFINITY),
+        _ => s.parse::<f64>().map_err(|_| REDIS_BAD_DATA_FORMAT_ERR),
+    }
+}
+
+fn zset_from_entries(entries: Vec<Vec<u8>>) -> AsyncResult<RdbValue> {
+    if entries.len() % 2 != 0 {
+        return Err(REDIS_BAD_DATA_FORMAT_ERR);
+    }
+    let mut pairs = Vec::with_capacity(entries.len() / 2);
+    let mut iter = entries.into_iter();
+    while let (Some(member), Some(score)) = (iter.next(), iter.next()) {
+        pairs.push((member, parse_double(&score)?));
+    }
+    Ok(RdbValue::Zset(pairs))
+}
+
+fn hash_from_entries(entries: Vec<Vec<u8>>) -> AsyncResult<RdbValue> {
+    if entries.len() % 2 != 0 {
+        return Err(REDIS_BAD_DATA_FORMAT_ERR);
+    }
+    let mut pairs = Vec::with_capacity(entries.len() / 2);
+    let mut iter = entries.into_iter();
+    while let (Some(field), Some(value)) = (iter.next(), iter.next()) {
+        pairs.push((field, value));
+    }
+    Ok(RdbValue::Hash(pairs))
+}
+
+fn intset_entries(blob: &[u8]) -> AsyncResult<Vec<Vec<u8>>> {
+    let mut reader = Reader::new(blob);
+    let width = u32::from_le_bytes(reader.read_bytes(4)?.try_into().unwrap()) as usize;
+    let len = u32::from_le_bytes(reader.read_bytes(4)?.try_into().unwrap()) as usize;
+    let mut members = Vec::with_capacity(len.min(1024));
+    for _ in 0..len {
+        let bytes = reader.read_bytes(width)?;
+        let v = match width {
+            2 => i16::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            4 => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            8 => i64::from_le_bytes(bytes.try_into().unwrap()),
+            _ => return Err(REDIS_BAD_DATA_FORMAT_ERR),
+        };
+        members.push(v.to_string().into_bytes());
+    }
+    Ok(members)
+}
+
+// sign extend the lowest `bits` bits of v
+fn sign_extend(v: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((v << shift) as i64) >> shift
+}
+
+fn read_le_int(reader: &mut Reader, width: usize) -> AsyncResult<i64> {
+    let mut v: u64 = 0;
+    for (i, b) in reader.read_bytes(width)?.iter().enumerate() {
+        v |= (*b as u64) << (8 * i);
+    }
+    Ok(sign_extend(v, width as u32 * 8))
+}
+
+fn ziplist_entries(blob: &[u8]) -> AsyncResult<Vec<Vec<u8>>> {
+    let mut reader = Reader::new(blob);
+    // skip zlbytes and zltail
+    reader.read_bytes(8)?;
+    let len = u16::from_le_bytes(reader.read_bytes(2)?.try_into().unwrap()) as usize;
+    let mut entries = Vec::with_capacity(len.min(1024));
+    loop {
+        let prevlen = reader.read_u8()?;
+        if prevlen == 0xff {
+            break;
+        }
+        if prevlen == 0xfe {
+            reader.read_bytes(4)?;
+        }
+        let enc = reader.read_u8()?;
+        let entry = match enc >> 6 {
+            0 => reader.read_bytes((enc & 0x3f) as usize)?.to_vec(),
+            1 => {
+                let len = (((enc & 0x3f) as usize) << 8) | reader.read_u8()? as usize;
+                reader.read_bytes(len)?.to_vec()
+            }
+            2 => {
+                let len = u32::from_be_bytes(reader.read_bytes(4)?.try_into().unwrap());
+                reader.read_bytes(len as usize)?.to_vec()
+            }
+            _ => {
+                let v = match enc {
+                    0xc0 => read_le_int(&mut reader, 2)?,
+                    0xd0 => read_le_int(&mut reader, 4)?,
+                    0xe0 => read_le_int(&mut reader, 8)?,
+                    0xf0 => read_le_int(&mut reader, 3)?,
+                    0xfe => read_le_int(&mut reader, 1)?,
+                    0xf1..=0xfd => (enc & 0x0f) as i64 - 1,
+                    _ => return Err(REDIS_BAD_DATA_FORMAT_ERR),
+                };
+                v.to_string().into_bytes()
+            }
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn listpack_entries(blob: &[u8]) -> AsyncResult<Vec<Vec<u8>>> {
+    let mut reader = Reader::new(blob);
+    // skip total bytes and number of elements
+    reader.read_bytes(6)?;
+    let mut entries = vec![];
+    loop {
+        let enc = reader.read_u8()?;
+        if enc == 0xff {
+            break;
+        }
+        let (entry, entry_len) = if enc & 0x80 == 0 {
+            ((enc & 0x7f).to_string().into_bytes(), 1)
+        } else if enc & 0xc0 == 0x80 {
+            let len = (enc & 0x3f) as usize;
+            (reader.read_bytes(len)?.to_vec(), 1 + len)
+        } else if enc & 0xe0 == 0xc0 {
+            let v = (((enc & 0x1f) as u64) << 8) | reader.read_u8()? as u64;
+            (sign_extend(v, 13).to_string().into_bytes(), 2)
+        } else if enc & 0xf0 == 0xe0 {
+            let len = (((enc & 0x0f) as usize) << 8) | reader.read_u8()? as usize;
+            (reader.read_bytes(len)?.to_vec(), 2 + len)
+        } else {
+            match enc {
+                0xf0 => {
+                    let len = u32::from_le_bytes(reader.read_bytes(4)?.try_into().unwrap());
+                    (reader.read_bytes(len as usize)?.to_vec(), 5 + len as usize)
+                }
+                0xf1 => (read_le_int(&mut reader, 2)?.to_string().into_bytes(), 3),
+                0xf2 => (read_le_int(&mut reader, 3)?.to_string().into_bytes(), 4),
+                0xf3 => (read_le_int(&mut reader, 4)?.to_string().into_bytes(), 5),
+                0xf4 => (read_le_int(&mut reader, 8)?.to_string().into_bytes(), 9),
+                _ => return Err(REDIS_BAD_DATA_FORMAT_ERR),
+            }
+        };
+        // skip the backlen of the entry
+        let backlen_size = match entry_len {
+            0..=127 => 1,
+            128..=16382 => 2,
+            16383..=2097150 => 3,
+            2097151..=268435454 => 4,
+            _ => 5,
+        };
+        reader.read_bytes(backlen_size)?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn lzf_decompress(input: &[u8], out_len: usize) -> AsyncResult<Vec<u8>> {
+    // out_len comes from the payload, it is only trusted as an upper bound
+    let mut output: Vec<u8> = Vec::with_capacity(out_len.min(1024));
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 32 {
+            // literal run of ctrl + 1 bytes
+            let len = ctrl + 1;
+            if i + len > input.len() || output.len() + len > out_len {
+                return Err(REDIS_BAD_DATA_FORMAT_ERR);
+            }
+            output.extend_from_slice(&input[i..i + len]);
+            i += len;
+        } else {
+            // back reference
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input.get(i).ok_or(REDIS_BAD_DATA_FORMAT_ERR)? as usize;
+                i += 1;
+            }
+            len += 2;
+            let low = *input.get(i).ok_or(REDIS_BAD_DATA_FORMAT_ERR)? as usize;
+            i += 1;
+            let back = ((ctrl & 0x1f) << 8) + low + 1;
+            if back > output.len() || output.len() + len > out_len {
+                return Err(REDIS_BAD_DATA_FORMAT_ERR);
+            }
+            let start = output.len() - back;
+            // the reference may overlap the bytes being copied
+            for k in 0..len {
+                let b = output[start + k];
+                output.push(b);
+            }
+        }
+    }
+    if output.len() != out_len {
+        return Err(REDIS_BAD_DATA_FORMAT_ERR);
+    }
+    Ok(output)
+}
@@ -1,22 +1,36 @@
 use crate::cluster::Cluster;
+use crate::frame::RESP_VERSION;
 use crate::gc::GcMaster;
 use crate::metrics::{
     CURRENT_CONNECTION_COUNTER, CURRENT_TLS_CONNECTION_COUNTER, REQUEST_CMD_COUNTER,
     REQUEST_CMD_ERROR_COUNTER, REQUEST_CMD_FINISH_COUNTER, REQUEST_CMD_HANDLE_TIME,
     REQUEST_COUNTER, TOTAL_CONNECTION_PROCESSED,
 };
+use crate::notify::init_keyspace_notifier;
+use crate::tikv::client::{ReadOptions, READ_OPTIONS};
 use crate::tikv::encoding::KeyDecoder;
+use crate::tikv::ldb::LdbMode;
+use crate::tikv::lualib::{new_lua, set_client_id};
+use crate::tikv::script::ScriptCommandCtx;
 use crate::tikv::{get_txn_client, KEY_ENCODER};
+use crate::tracking::{
+    disable_tracking, init_tracking, register_client, track_command, unregister_client,
+    Invalidation, CLIENT_ID,
+};
 use crate::utils::{self, resp_err, resp_invalid_arguments, resp_ok, resp_queued, sleep};
 use crate::{
     async_gc_worker_number_or_default, config_cluster_broadcast_addr_or_default,
     config_cluster_topology_expire_or_default, config_cluster_topology_interval_or_default,
-    config_local_pool_number, is_auth_enabled, is_auth_matched, Command, Connection, Db,
-    DbDropGuard, Shutdown,
+    config_local_pool_number, config_lua_memory_limit_or_default,
+    config_pipeline_window_or_default, is_auth_enabled, is_auth_matched,
+    txn_read_staleness_ms_or_default, Command, Connection, Db, DbDropGuard, Frame, Shutdown,
 };
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
 
 use async_std::net::{TcpListener, TcpStream};
+use futures::future::LocalBoxFuture;
+use futures::stream::FuturesOrdered;
 use futures::FutureExt;
 use std::future::Future;
 use std::ops::Range;
@@ -30,7 +44,7 @@ use slog::{debug, error, info, warn};
 use tokio::sync::{broadcast, mpsc, Mutex};
 use tokio::time::{self, Duration, Instant, MissedTickBehavior};
 
-use mlua::{HookTriggers, Lua};
+use mlua::Lua;
 
 use crate::config::LOGGER;
 
@@ -38,11 +52,14 @@ use crate::client::Client;
 use tokio_util::task::LocalPoolHandle;
 
 use crate::tikv::errors::{
-    REDIS_AUTH_INVALID_PASSWORD_ERR, REDIS_AUTH_REQUIRED_ERR, REDIS_AUTH_WHEN_DISABLED_ERR,
-    REDIS_DISCARD_WITHOUT_MULTI_ERR, REDIS_EXEC_WITHOUT_MULTI_ERR, REDIS_MULTI_NESTED_ERR,
+    AsyncResult, RTError, REDIS_AUTH_INVALID_PASSWORD_ERR, REDIS_AUTH_REQUIRED_ERR,
+    REDIS_AUTH_WHEN_DISABLED_ERR, REDIS_BUSY_ERR, REDIS_DISCARD_WITHOUT_MULTI_ERR,
+    REDIS_EVALSHA_IN_DEBUG_ERR, REDIS_EXEC_ERR, REDIS_EXEC_WITHOUT_MULTI_ERR,
+    REDIS_MULTI_NESTED_ERR, REDIS_SCRIPT_DEBUG_INSIDE_MULTI_ERR, REDIS_SCRIPT_DEBUG_MODE_ERR,
+    REDIS_TXN_INSIDE_MULTI_ERR, REDIS_WATCH_INSIDE_MULTI_ERR,
 };
 
-use crate::cmd::{script_clear_killed, script_interuptted};
+use crate::cmd::{script_busy, InteractiveTxn, Invalid, ScriptRunGuard, WatchedKeys};
 
 /// Server listener state. Created in the `run` call. It includes a `run` method
 /// which performs the TCP listening and initialization of per-connection state.
@@ -118,6 +135,9 @@ struct TopologyManager {
 
     topo_holder: Cluster,
 
+    /// drop the local script cache if scripts are flushed by any instance
+    db: Db,
+
     interval: u64, // in milliseconds
     expire: u64,   // in milliseconds
 }
@@ -149,6 +169,12 @@ struct Handler {
     /// The txn state of this connection.
     inner_txn: bool,
     queued_commands: Vec<Command>,
+    // keys watched by WATCH
+    watched_keys: WatchedKeys,
+    // set if any command failed to be queued, EXEC will be aborted
+    txn_dirty: bool,
+    // txn started by TXN BEGIN
+    interactive_txn: Option<InteractiveTxn>,
 
     /// Max connection semaphore.
     ///
@@ -174,11 +200,71 @@ struct Handler {
 
     /// Lua vm context, lazy initialized when eval/evalsha called
     lua: Option<Lua>,
+    /// set by SCRIPT DEBUG, the next EVAL is run in a debugging session
+    lua_debug: Option<LdbMode>,
+
+    /// invalidation messages of CLIENT TRACKING sent to this connection
+    invalidations: mpsc::UnboundedReceiver<Invalidation>,
 
     /// Not used directly. Instead, when `Handler` is dropped...?
     _shutdown_complete: mpsc::Sender<()>,
 }
 
+/// Reply of a pipelined command, written to the socket after the replies of the
+/// commands before it.
+struct PipelinedReply {
+    cmd_name: String,
+    start_at: Instant,
+    frames: Vec<u8>,
+    result: crate::Result<()>,
+}
+
+/// Pipelined commands of a connection being applied concurrently, their replies
+/// are yielded in the order of the requests.
+#[derive(Default)]
+struct Pipeline {
+    replies: FuturesOrdered<LocalBoxFuture<'static, PipelinedReply>>,
+    // keys of the commands in flight, along with whether they are written
+    keys: VecDeque<(Vec<String>, bool)>,
+}
+
+impl Pipeline {
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns true if a command in flight accesses any of the `keys`, and either
+    /// of them writes.
+    fn conflicts(&self, keys: &[String], write: bool) -> bool {
+        self.keys.iter().any(|(in_flight, in_flight_write)| {
+            (write || *in_flight_write) && in_flight.iter().any(|key| keys.contains(key))
+        })
+    }
+
+    fn push(
+        &mut self,
+        keys: Vec<String>,
+        write: bool,
+        reply: LocalBoxFuture<'static, PipelinedReply>,
+    ) {
+        self.keys.push_back((keys, write));
+        self.replies.push_back(reply);
+    }
+
+    /// Wait for the reply of the first command in flight
+    async fn next(&mut self) -> Option<PipelinedReply> {
+        let reply = self.replies.next().await;
+        if reply.is_some() {
+            self.keys.pop_front();
+        }
+        reply
+    }
+}
+
 /// Maximum number of concurrent connections the redis server will accept.
 ///
 /// When this limit is reached, the server will stop accepting connections until
@@ -225,10 +311,14 @@ pub async fn run(
     let topo_manager = TopologyManager {
         address: topo_addr,
         topo_holder: topo_holder.clone(),
+        db: db_holder.db(),
         interval: config_cluster_topology_interval_or_default(),
         expire: config_cluster_topology_expire_or_default(),
     };
 
+    init_keyspace_notifier(db_holder.db(), topo_holder.clone());
+    init_tracking(topo_holder.clone());
+
     let mut gc_master = GcMaster::new(async_gc_worker_number_or_default(), topo_holder.clone());
     gc_master.start_workers().await;
 
@@ -460,6 +550,9 @@ impl Listener {
 
                 inner_txn: false,
                 queued_commands: vec![],
+                watched_keys: WatchedKeys::default(),
+                txn_dirty: false,
+                interactive_txn: None,
 
                 // The connection state needs a handle to the max connections
                 // semaphore. When the handler is done processing the
@@ -472,23 +565,35 @@ impl Listener {
                 authorized: !is_auth_enabled(),
 
                 lua: None,
+                lua_debug: None,
+                invalidations: register_client(client_id),
 
                 // Notifies the receiver half once all clones are
                 // dropped.
                 _shutdown_complete: self.shutdown_complete_tx.clone(),
             };
-            local_pool.spawn_pinned(|| async move {
+            local_pool.spawn_pinned(move || async move {
                 // Process the connection. If an error is encountered, log it.
                 CURRENT_CONNECTION_COUNTER.inc();
                 TOTAL_CONNECTION_PROCESSED.inc();
-                if let Err(err) = handler.run().await {
+                // every connection starts with the staleness in config
+                let read_options = ReadOptions::new(txn_read_staleness_ms_or_default());
+                if let Err(err) = READ_OPTIONS
+                    .scope(
+                        read_options,
+                        RESP_VERSION.scope(Cell::new(2), CLIENT_ID.scope(client_id, handler.run())),
+                    )
+                    .await
+                {
                     error!(LOGGER, "connection error {:?}", err);
                 }
+                handler.rollback_interactive_txn().await;
                 handler
                     .clients
                     .lock()
                     .await
                     .remove(&handler.cur_client.lock().await.id());
+                unregister_client(client_id);
                 CURRENT_CONNECTION_COUNTER.dec();
             });
         }
@@ -576,24 +681,39 @@ impl TlsListener {
                 connection: Connection::new_tls(&local_addr, &peer_addr, tls_stream),
                 inner_txn: false,
                 queued_commands: vec![],
+                watched_keys: WatchedKeys::default(),
+                txn_dirty: false,
+                interactive_txn: None,
                 shutdown: Shutdown::new(self.tls_notify_shutdown.subscribe(), kill_rx),
                 authorized: !is_auth_enabled(),
                 lua: None,
+                lua_debug: None,
+                invalidations: register_client(client_id),
                 _shutdown_complete: self.tls_shutdown_complete_tx.clone(),
             };
 
-            local_pool.spawn_pinned(|| async move {
+            local_pool.spawn_pinned(move || async move {
                 // Process the connection. If an error is encountered, log it.
                 CURRENT_TLS_CONNECTION_COUNTER.inc();
                 TOTAL_CONNECTION_PROCESSED.inc();
-                if let Err(err) = handler.run().await {
+                // every connection starts with the staleness in config
+                let read_options = ReadOptions::new(txn_read_staleness_ms_or_default());
+                if let Err(err) = READ_OPTIONS
+                    .scope(
+                        read_options,
+                        RESP_VERSION.scope(Cell::new(2), CLIENT_ID.scope(client_id, handler.run())),
+                    )
+                    .await
+                {
                     error!(LOGGER, "tls connection error {:?}", err);
                 }
+                handler.rollback_interactive_txn().await;
                 handler
                     .clients
                     .lock()
                     .await
                     .remove(&handler.cur_client.lock().await.id());
+                unregister_client(client_id);
                 CURRENT_TLS_CONNECTION_COUNTER.dec();
             });
         }
@@ -669,6 +789,16 @@ impl TopologyManager {
                 }
             }
 
+            match ScriptCommandCtx::new(None)
+                .do_async_txnkv_script_epoch()
+                .await
+            {
+                Ok(epoch) => self.db.sync_script_epoch(epoch),
+                Err(err) => {
+                    warn!(LOGGER, "script epoch sync failed: {}", err);
+                }
+            }
+
             // random sleep a small period for jitter tick
             let jitter = rng.gen::<u8>();
             sleep(jitter as u32).await;
@@ -682,24 +812,48 @@ impl Handler {
     /// Request frames are read from the socket and processed. Responses are
     /// written back to the socket.
     ///
-    /// Currently, pipelining is not implemented. Pipelining is the ability to
-    /// process more than one request concurrently per connection without
-    /// interleaving frames. See for more details:
+    /// Pipelined commands on keys are applied concurrently, up to
+    /// `pipeline_window` of them, without interleaving frames: their replies are
+    /// written in the order of the requests, and a command waits for the ones
+    /// before it on the same keys. See for more details:
     /// https://redis.io/topics/pipelining
     ///
     /// When the shutdown signal is received, the connection is processed until
     /// it reaches a safe state, at which point it is terminated.
     async fn run(&mut self) -> crate::Result<()> {
+        let window = config_pipeline_window_or_default().max(1);
+        let mut pipeline = Pipeline::default();
+
         // As long as the shutdown signal has not been received, try to read a
         // new request frame.
         while !self.shutdown.is_shutdown() {
             // While reading a request frame, also listen for the shutdown
             // signal.
+            let txn_deadline = self.interactive_txn.as_ref().and_then(|t| t.deadline());
             let maybe_frame = tokio::select! {
-                res = self.connection.read_frame() => res?,
+                res = self.connection.read_frame(), if pipeline.len() < window => res?,
+                // the first pipelined command in flight is done
+                Some(reply) = pipeline.next(), if !pipeline.is_empty() => {
+                    self.write_pipelined(reply, &pipeline).await?;
+                    continue;
+                }
+                // rollback the txn started by TXN BEGIN on timeout, its locks are released
+                _ = time::sleep_until(txn_deadline.unwrap_or_else(Instant::now)), if txn_deadline.is_some() => {
+                    self.rollback_timeout_txn().await;
+                    continue;
+                }
+                // keys tracked by this client or redirected to it are modified
+                Some(invalidation) = self.invalidations.recv() => {
+                    let resp3 = self.connection.resp_version() == 3;
+                    if let Some(frame) = invalidation.into_frame(resp3, false) {
+                        self.connection.write_frame(&frame).await?;
+                    }
+                    continue;
+                }
                 _ = self.shutdown.recv() => {
                     // If a shutdown signal is received, return from `run`.
-                    // This will result in the task terminating.
+                    // This will result in the task terminating, the replies of
+                    // the pipelined commands in flight are dropped.
                     return Ok(());
                 }
             };
@@ -709,7 +863,7 @@ impl Handler {
             // terminated.
             let frame = match maybe_frame {
                 Some(frame) => frame,
-                None => return Ok(()),
+                None => return self.finish_pipeline(&mut pipeline).await.map(|_| ()),
             };
 
             // Convert the redis frame into a command struct. This returns an
@@ -718,10 +872,13 @@ impl Handler {
             let cmd = Command::from_frame(frame)?;
             let cmd_name = cmd.get_name().to_owned();
 
-            {
+            let client_id = {
                 let mut w_client = self.cur_client.lock().await;
                 w_client.interact(&cmd_name);
-            }
+                w_client.id()
+            };
+            // registered while the script of this connection is running
+            let mut _running_script = None;
 
             let start_at = Instant::now();
             REQUEST_COUNTER.inc();
@@ -735,6 +892,27 @@ impl Handler {
                 cmd
             );
 
+            // commands on keys are applied concurrently with the pipelined ones
+            // before them, unless they access the same keys
+            if let Some(keys) = self.pipelined_keys(&cmd, client_id, window) {
+                let write = cmd.is_write();
+                if !self
+                    .drain_pipeline(&mut pipeline, |p| !p.conflicts(&keys, write))
+                    .await?
+                {
+                    return Ok(());
+                }
+                track_command(client_id, &cmd, false);
+                let reply = self.apply_pipelined(cmd, cmd_name, start_at);
+                pipeline.push(keys, write, reply);
+                continue;
+            }
+            // the other commands may depend on the results of the pipelined ones
+            // or change the state of the connection, they are applied after them
+            if !self.finish_pipeline(&mut pipeline).await? {
+                return Ok(());
+            }
+
             match cmd {
                 Command::Auth(c) => {
                     if !c.valid() {
@@ -755,38 +933,156 @@ impl Handler {
                             .await?;
                     }
                 }
+                Command::Hello(c) => {
+                    c.apply(&self.cur_client, &mut self.authorized, &mut self.connection)
+                        .await?;
+                }
+                // QUIT is served without authentication, the txn started by TXN BEGIN
+                // is rolled back as the connection is closed
+                Command::Quit(_) => {
+                    self.connection.write_frame(&resp_ok()).await?;
+                    return Ok(());
+                }
                 _ => {
                     if !self.authorized {
                         self.connection
                             .write_frame(&resp_err(REDIS_AUTH_REQUIRED_ERR))
                             .await?;
                     } else {
+                        // the flag of CLIENT CACHING applies to the whole MULTI
+                        let keep_caching = self.inner_txn
+                            && !matches!(cmd, Command::Exec(_) | Command::Discard(_));
+                        track_command(client_id, &cmd, keep_caching);
                         match cmd {
-                            Command::Eval(_) | Command::Evalsha(_) => {
-                                if self.lua.is_none() {
-                                    // initialize the mlua once in same connection
-                                    let lua = Lua::new();
-                                    // set script interupt handler
-                                    lua.set_hook(HookTriggers::every_line(), |_lua, _debug| {
-                                        if script_interuptted() {
-                                            warn!(
-                                                LOGGER,
-                                                "Script kiiled by user with SCRIPT KILL..."
-                                            );
-
-                                            script_clear_killed();
-
-                                            Err(mlua::Error::RuntimeError(
-                                                "Script kiiled by user with SCRIPT KILL..."
-                                                    .to_string(),
-                                            ))
-                                        } else {
-                                            Ok(())
+                            _ if !cmd.is_allowed_when_busy()
+                                && script_busy(client_id, cmd.keys().as_deref()) =>
+                            {
+                                self.connection
+                                    .write_frame(&resp_err(REDIS_BUSY_ERR))
+                                    .await?;
+
+                                let duration = Instant::now() - start_at;
+                                REQUEST_CMD_HANDLE_TIME
+                                    .with_label_values(&[&cmd_name])
+                                    .observe(duration_to_sec(duration));
+                                REQUEST_CMD_FINISH_COUNTER
+                                    .with_label_values(&[&cmd_name])
+                                    .inc();
+                                continue;
+                            }
+                            // RESET is never queued, it also discards the txn
+                            Command::Reset(_) => {
+                                self.reset().await?;
+                            }
+                            // PING is answered out of the txn started by TXN BEGIN
+                            Command::Ping(_) if self.interactive_txn.is_some() => {}
+                            _ if self.interactive_txn.is_some()
+                                && !matches!(cmd, Command::Txn(_)) =>
+                            {
+                                if cmd.is_script() {
+                                    match self.start_script(client_id, std::slice::from_ref(&cmd)) {
+                                        Ok(guard) => _running_script = Some(guard),
+                                        Err(e) => {
+                                            self.connection.write_frame(&resp_err(e)).await?;
+                                            continue;
                                         }
-                                    })
-                                    .unwrap();
+                                    }
+                                }
+                                self.interactive_txn
+                                    .as_mut()
+                                    .unwrap()
+                                    .exec(cmd, &mut self.connection, &self.db, &self.lua)
+                                    .await?;
 
-                                    self.lua = Some(lua);
+                                let duration = Instant::now() - start_at;
+                                REQUEST_CMD_HANDLE_TIME
+                                    .with_label_values(&[&cmd_name])
+                                    .observe(duration_to_sec(duration));
+                                REQUEST_CMD_FINISH_COUNTER
+                                    .with_label_values(&[&cmd_name])
+                                    .inc();
+                                continue;
+                            }
+                            Command::Subscribe(ref c) if !self.inner_txn => {
+                                let reset = c
+                                    .clone()
+                                    .apply(
+                                        &self.db,
+                                        &self.topo,
+                                        &mut self.connection,
+                                        &mut self.shutdown,
+                                        &mut self.invalidations,
+                                    )
+                                    .await?;
+                                if reset {
+                                    self.reset().await?;
+                                }
+                            }
+                            Command::Psubscribe(ref c) if !self.inner_txn => {
+                                let reset = c
+                                    .clone()
+                                    .apply(
+                                        &self.db,
+                                        &self.topo,
+                                        &mut self.connection,
+                                        &mut self.shutdown,
+                                        &mut self.invalidations,
+                                    )
+                                    .await?;
+                                if reset {
+                                    self.reset().await?;
+                                }
+                            }
+                            Command::Ssubscribe(ref c) if !self.inner_txn => {
+                                let reset = c
+                                    .clone()
+                                    .apply_sharded(
+                                        &self.db,
+                                        &self.topo,
+                                        &mut self.connection,
+                                        &mut self.shutdown,
+                                        &mut self.invalidations,
+                                    )
+                                    .await?;
+                                if reset {
+                                    self.reset().await?;
+                                }
+                            }
+                            Command::Script(ref c) if c.is_debug() => {
+                                let response = if self.inner_txn {
+                                    resp_err(REDIS_SCRIPT_DEBUG_INSIDE_MULTI_ERR)
+                                } else if !c.valid() {
+                                    resp_err(REDIS_SCRIPT_DEBUG_MODE_ERR)
+                                } else {
+                                    self.lua_debug = c.debug_mode();
+                                    resp_ok()
+                                };
+                                self.connection.write_frame(&response).await?;
+                            }
+                            Command::Eval(ref c) if self.lua_debug.is_some() && !self.inner_txn => {
+                                let mode = self.lua_debug.take().unwrap();
+                                c.debug(&mut self.connection, mode).await?;
+                                // the connection is closed at the end of the debugging
+                                // session, as redis does
+                                return Ok(());
+                            }
+                            Command::Evalsha(_) if self.lua_debug.is_some() && !self.inner_txn => {
+                                self.connection
+                                    .write_frame(&resp_err(REDIS_EVALSHA_IN_DEBUG_ERR))
+                                    .await?;
+                            }
+                            Command::Eval(_)
+                            | Command::Evalsha(_)
+                            | Command::Fcall(_)
+                            | Command::FcallRo(_)
+                                if !self.inner_txn =>
+                            {
+                                match self.start_script(client_id, std::slice::from_ref(&cmd)) {
+                                    Ok(guard) => _running_script = Some(guard),
+                                    Err(e) => {
+                                        self.connection.write_frame(&resp_err(e)).await?;
+                                        continue;
+                                    }
                                 }
                             }
                             Command::Multi(_) => {
@@ -796,6 +1092,7 @@ impl Handler {
                                         .await?;
                                 } else {
                                     self.inner_txn = true;
+                                    self.txn_dirty = false;
                                     self.queued_commands.clear();
                                     self.connection.write_frame(&resp_ok()).await?;
                                 }
@@ -805,10 +1102,38 @@ impl Handler {
                                     self.connection
                                         .write_frame(&resp_err(REDIS_EXEC_WITHOUT_MULTI_ERR))
                                         .await?;
+                                } else if self.txn_dirty {
+                                    self.inner_txn = false;
+                                    self.txn_dirty = false;
+                                    self.queued_commands.clear();
+                                    self.watched_keys.clear();
+                                    self.connection
+                                        .write_frame(&resp_err(REDIS_EXEC_ERR))
+                                        .await?;
                                 } else {
                                     self.inner_txn = false;
+                                    let watched_keys = std::mem::take(&mut self.watched_keys);
+                                    if self.queued_commands.iter().any(|c| c.is_script()) {
+                                        let cmds = std::mem::take(&mut self.queued_commands);
+                                        let started = self.start_script(client_id, &cmds);
+                                        self.queued_commands = cmds;
+                                        match started {
+                                            Ok(guard) => _running_script = Some(guard),
+                                            Err(e) => {
+                                                self.queued_commands.clear();
+                                                self.connection.write_frame(&resp_err(e)).await?;
+                                                continue;
+                                            }
+                                        }
+                                    }
                                     c.clone()
-                                        .exec(&mut self.connection, self.queued_commands.clone())
+                                        .exec(
+                                            &mut self.connection,
+                                            self.queued_commands.clone(),
+                                            watched_keys,
+                                            &self.db,
+                                            &self.lua,
+                                        )
                                         .await?;
                                 }
 
@@ -824,7 +1149,9 @@ impl Handler {
                             Command::Discard(_) => {
                                 if self.inner_txn {
                                     self.inner_txn = false;
+                                    self.txn_dirty = false;
                                     self.queued_commands.clear();
+                                    self.watched_keys.clear();
                                     self.connection.write_frame(&resp_ok()).await?;
                                 } else {
                                     self.connection
@@ -832,10 +1159,59 @@ impl Handler {
                                         .await?;
                                 }
                             }
+                            Command::Watch(ref c) => {
+                                if self.inner_txn {
+                                    self.connection
+                                        .write_frame(&resp_err(REDIS_WATCH_INSIDE_MULTI_ERR))
+                                        .await?;
+                                } else if !c.valid() {
+                                    self.connection
+                                        .write_frame(&resp_invalid_arguments())
+                                        .await?;
+                                } else {
+                                    match c.watch(&mut self.watched_keys).await {
+                                        Ok(()) => {
+                                            self.connection.write_frame(&resp_ok()).await?;
+                                        }
+                                        Err(e) => {
+                                            self.connection.write_frame(&resp_err(e)).await?;
+                                        }
+                                    }
+                                }
+                            }
+                            Command::Txn(ref c) => {
+                                if self.inner_txn {
+                                    self.txn_dirty = true;
+                                    self.connection
+                                        .write_frame(&resp_err(REDIS_TXN_INSIDE_MULTI_ERR))
+                                        .await?;
+                                } else {
+                                    c.apply(&mut self.connection, &mut self.interactive_txn)
+                                        .await?;
+                                }
+                            }
+                            Command::Unwatch(_) if !self.inner_txn => {
+                                self.watched_keys.clear();
+                                self.connection.write_frame(&resp_ok()).await?;
+                            }
                             _ => {
                                 if self.inner_txn {
-                                    self.queued_commands.push(cmd);
-                                    self.connection.write_frame(&resp_queued()).await?;
+                                    // reject the command and abort the txn later in EXEC
+                                    let response = match cmd {
+                                        Command::Unknown(ref c) => Some(c.response()),
+                                        _ if !cmd.valid() => Some(resp_invalid_arguments()),
+                                        _ => None,
+                                    };
+                                    match response {
+                                        Some(response) => {
+                                            self.txn_dirty = true;
+                                            self.connection.write_frame(&response).await?;
+                                        }
+                                        None => {
+                                            self.queued_commands.push(cmd);
+                                            self.connection.write_frame(&resp_queued()).await?;
+                                        }
+                                    }
                                     continue;
                                 }
                             }
@@ -855,7 +1231,6 @@ impl Handler {
                                 self.cur_client.clone(),
                                 self.clients.clone(),
                                 &mut self.lua,
-                                &mut self.shutdown,
                             )
                             .await
                         {
@@ -882,6 +1257,188 @@ impl Handler {
 
         Ok(())
     }
+
+    /// Returns the keys of `cmd` if it can be applied concurrently with the
+    /// pipelined commands, otherwise it is applied after them.
+    fn pipelined_keys(&self, cmd: &Command, client_id: u64, window: usize) -> Option<Vec<String>> {
+        if window <= 1
+            || !self.authorized
+            || self.inner_txn
+            || self.interactive_txn.is_some()
+            || self.lua_debug.is_some()
+        {
+            return None;
+        }
+        let keys = cmd.keys();
+        if script_busy(client_id, keys.as_deref()) {
+            return None;
+        }
+        keys
+    }
+
+    /// Apply the pipelined `cmd` to a buffered connection, in the task of this
+    /// connection to keep its task locals.
+    fn apply_pipelined(
+        &self,
+        cmd: Command,
+        cmd_name: String,
+        start_at: Instant,
+    ) -> LocalBoxFuture<'static, PipelinedReply> {
+        let db = self.db.clone();
+        let topo = self.topo.clone();
+        let cur_client = self.cur_client.clone();
+        let clients = self.clients.clone();
+        let mut dst = self.connection.buffered();
+        async move {
+            let result = cmd
+                .apply(&db, &topo, &mut dst, cur_client, clients, &mut None)
+                .await;
+            PipelinedReply {
+                cmd_name,
+                start_at,
+                frames: dst.take_pending(),
+                result,
+            }
+        }
+        .boxed_local()
+    }
+
+    /// Write the reply of a pipelined command, the replies are sent to the socket
+    /// together once no more requests are buffered or no command is in flight.
+    async fn write_pipelined(
+        &mut self,
+        reply: PipelinedReply,
+        pipeline: &Pipeline,
+    ) -> crate::Result<()> {
+        self.connection.write_pending(&reply.frames).await?;
+        if reply.result.is_err() || pipeline.is_empty() || !self.connection.has_buffered_input() {
+            self.connection.flush_pending().await?;
+        }
+        if let Err(e) = reply.result {
+            REQUEST_CMD_ERROR_COUNTER
+                .with_label_values(&[&reply.cmd_name])
+                .inc();
+            return Err(e);
+        }
+
+        let duration = Instant::now() - reply.start_at;
+        REQUEST_CMD_HANDLE_TIME
+            .with_label_values(&[&reply.cmd_name])
+            .observe(duration_to_sec(duration));
+        REQUEST_CMD_FINISH_COUNTER
+            .with_label_values(&[&reply.cmd_name])
+            .inc();
+        Ok(())
+    }
+
+    /// Write the replies of the pipelined commands in flight until `done` returns true.
+    ///
+    /// Returns false if the shutdown signal is received meanwhile.
+    async fn drain_pipeline(
+        &mut self,
+        pipeline: &mut Pipeline,
+        done: impl Fn(&Pipeline) -> bool,
+    ) -> crate::Result<bool> {
+        while !done(pipeline) {
+            let txn_deadline = self.interactive_txn.as_ref().and_then(|t| t.deadline());
+            tokio::select! {
+                Some(reply) = pipeline.next() => {
+                    self.write_pipelined(reply, pipeline).await?;
+                }
+                _ = time::sleep_until(txn_deadline.unwrap_or_else(Instant::now)), if txn_deadline.is_some() => {
+                    self.rollback_timeout_txn().await;
+                }
+                _ = self.shutdown.recv() => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Wait for all the pipelined commands in flight and write their replies
+    async fn finish_pipeline(&mut self, pipeline: &mut Pipeline) -> crate::Result<bool> {
+        self.drain_pipeline(pipeline, Pipeline::is_empty).await
+    }
+
+    /// Initialize the lua of the connection for the scripts in `cmds`, which are registered
+    /// as running until the returned guard is dropped.
+    fn start_script(&mut self, client_id: u64, cmds: &[Command]) -> AsyncResult<ScriptRunGuard> {
+        let init_err = |e: mlua::Error| RTError::Owned(format!("ERR failed to init lua, {}", e));
+        if self.lua.is_none() {
+            // initialize the mlua once in same connection
+            let lua = new_lua().map_err(init_err)?;
+            set_client_id(&lua, client_id).map_err(init_err)?;
+            // allocations beyond the limit fail and abort the script
+            let memory_limit = config_lua_memory_limit_or_default();
+            if memory_limit > 0 {
+                lua.set_memory_limit(memory_limit).map_err(init_err)?;
+            }
+
+            self.lua = Some(lua);
+        }
+
+        let function = matches!(
+            cmds.iter().find(|c| c.is_script()),
+            Some(Command::Fcall(_) | Command::FcallRo(_))
+        );
+        // a script declaring no keys may access any key
+        let mut keys = Some(vec![]);
+        for cmd in cmds {
+            keys = match (keys, cmd.declared_keys()) {
+                (Some(mut keys), Some(declared)) if !declared.is_empty() => {
+                    keys.extend(declared);
+                    Some(keys)
+                }
+                _ => None,
+            };
+        }
+        let guard = ScriptRunGuard::new(client_id, function, keys);
+        guard
+            .set_hook(self.lua.as_ref().unwrap())
+            .map_err(init_err)?;
+        Ok(guard)
+    }
+
+    /// Reset the connection state as RESET does, the client leaves MULTI, TXN BEGIN
+    /// and the debugging mode, unwatches all keys, turns off tracking, talks RESP2
+    /// again and has to authenticate again if a password is required.
+    async fn reset(&mut self) -> crate::Result<()> {
+        self.inner_txn = false;
+        self.txn_dirty = false;
+        self.queued_commands.clear();
+        self.watched_keys.clear();
+        self.rollback_interactive_txn().await;
+        self.lua_debug = None;
+        self.authorized = !is_auth_enabled();
+        {
+            let mut w_client = self.cur_client.lock().await;
+            w_client.set_resp(2);
+            disable_tracking(w_client.id());
+        }
+        self.connection.set_resp_version(2);
+        let _ = RESP_VERSION.try_with(|v| v.set(2));
+        self.connection
+            .write_frame(&Frame::Simple("RESET".to_string()))
+            .await?;
+        Ok(())
+    }
+
+    /// Rollback the txn started by TXN BEGIN once its deadline is reached
+    async fn rollback_timeout_txn(&mut self) {
+        if let Some(txn) = self.interactive_txn.as_mut() {
+            if let Err(e) = txn.rollback().await {
+                warn!(LOGGER, "rollback timeout txn failed, {}", e);
+            }
+        }
+    }
+
+    /// Rollback the txn started by TXN BEGIN if the connection is closed before it finishes
+    async fn rollback_interactive_txn(&mut self) {
+        if let Some(mut txn) = self.interactive_txn.take() {
+            if let Err(e) = txn.rollback().await {
+                warn!(LOGGER, "rollback interactive txn failed, {}", e);
+            }
+        }
+    }
 }
 
 #[inline]
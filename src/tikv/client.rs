@@ -1,5 +1,7 @@
+use std::cell::Cell;
 use std::convert::TryInto;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use tokio::sync::Mutex;
 
 use tikv_client::Error::StringError;
@@ -10,29 +12,175 @@ use tikv_client::{
 };
 
 use crate::config::LOGGER;
+use crate::notify::PendingEvents;
 use crate::{
     async_deletion_enabled_or_default, is_try_one_pc_commit, is_use_async_commit,
-    is_use_pessimistic_txn, txn_lock_backoff_delay_attemps, txn_lock_backoff_delay_ms,
+    is_use_pessimistic_txn, txn_gc_life_time_ms_or_default, txn_gc_safepoint_refresh_ms_or_default,
+    txn_lock_backoff_delay_attemps, txn_lock_backoff_delay_ms, txn_read_staleness_ms_or_default,
     txn_region_backoff_delay_attemps, txn_region_backoff_delay_ms, txn_retry_count,
 };
 
-use super::errors::{AsyncResult, RTError, KEY_VERSION_EXHUSTED_ERR};
+use super::errors::{AsyncResult, RTError, KEY_VERSION_EXHUSTED_ERR, REDIS_SNAPSHOT_TOO_OLD_ERR};
 
 use futures::future::BoxFuture;
 
 use slog::{debug, error};
 
 use crate::metrics::{
-    ACQUIRE_LOCK_DURATION, TIKV_CLIENT_RETRIES, TIKV_ERR_COUNTER, TXN_COUNTER, TXN_DURATION,
+    ACQUIRE_LOCK_DURATION, RETRIEVE_TSO_DURATION, SNAPSHOT_COUNTER, STALE_READ_TSO_AGE,
+    STALE_READ_TSO_COUNTER, TIKV_CLIENT_RETRIES, TIKV_ERR_COUNTER, TXN_COUNTER, TXN_DURATION,
     TXN_MECHANISM_COUNTER, TXN_RETRY_COUNTER, TXN_RETRY_ERR, TXN_RETRY_KIND_COUNTER,
 };
 
-use super::{sleep, KEY_ENCODER};
+use super::{get_txn_client, sleep, KEY_ENCODER};
 use crate::server::duration_to_sec;
-use tokio::time::Instant;
+use crate::utils::now_timestamp_in_millis;
+use tokio::time::{Duration, Instant};
 
 const MAX_DELAY_MS: u64 = 500;
 
+// the logical part takes the low 18 bits of a tso
+const TSO_PHYSICAL_SHIFT_BITS: u64 = 18;
+// unix time in milliseconds stays below it for thousands of years, while a real tso is above
+const UNIX_MS_UPPER_BOUND: u64 = 1 << 46;
+
+/// Per connection options for read only commands.
+#[derive(Debug, Default)]
+pub struct ReadOptions {
+    // staleness in milliseconds allowed, 0 means no stale read
+    pub staleness_ms: Cell<u64>,
+    // historical timestamp to read at, set by SNAPSHOT AT
+    pub snapshot_ts: Cell<Option<u64>>,
+}
+
+impl ReadOptions {
+    pub fn new(staleness_ms: u64) -> ReadOptions {
+        ReadOptions {
+            staleness_ms: Cell::new(staleness_ms),
+            ..Default::default()
+        }
+    }
+}
+
+tokio::task_local! {
+    pub static READ_OPTIONS: ReadOptions;
+}
+
+/// Convert unix time in milliseconds to tso, values large enough are taken as tso already.
+pub fn tso_from_unix_ms_or_tso(v: u64) -> u64 {
+    if v < UNIX_MS_UPPER_BOUND {
+        v << TSO_PHYSICAL_SHIFT_BITS
+    } else {
+        v
+    }
+}
+
+/// Whether the transaction failed because a key it writes or locks is committed by others
+/// after it began.
+pub fn is_write_conflict(err: &Error) -> bool {
+    match err {
+        Error::KeyError(e) => e.conflict.is_some(),
+        Error::MultipleKeyErrors(errs) | Error::ExtractedErrors(errs) => {
+            errs.iter().any(is_write_conflict)
+        }
+        _ => false,
+    }
+}
+
+/// Gc safepoint of the cluster, versions older than it may have been collected.
+///
+/// The value is fetched from PD and refreshed every `txn_gc_safepoint_refresh_ms`, before the
+/// first fetch succeeds it is derived from `txn_gc_life_time_ms`.
+pub fn gc_safepoint() -> u64 {
+    let safepoint = GC_SAFEPOINT.load(Ordering::Relaxed);
+    if safepoint > 0 {
+        return safepoint;
+    }
+    now_timestamp_in_millis().saturating_sub(txn_gc_life_time_ms_or_default())
+        << TSO_PHYSICAL_SHIFT_BITS
+}
+
+// gc safepoint last fetched from PD, 0 if not fetched yet
+static GC_SAFEPOINT: AtomicU64 = AtomicU64::new(0);
+
+async fn fetch_gc_safepoint(pd_addr: &str) -> crate::Result<u64> {
+    let uri = if pd_addr.starts_with("http://") {
+        format!("{}/pd/api/v1/gc/safepoint", pd_addr)
+    } else {
+        format!("http://{}/pd/api/v1/gc/safepoint", pd_addr)
+    };
+    let resp = hyper::Client::new().get(uri.parse()?).await?;
+    if !resp.status().is_success() {
+        return Err(format!("pd responded {}", resp.status()).into());
+    }
+    let body = hyper::body::to_bytes(resp.into_body()).await?;
+    let body = String::from_utf8_lossy(&body);
+    // the response looks like {"service_gc_safe_points":[...],"gc_safe_point":434...}
+    let field = "\"gc_safe_point\":";
+    let pos = body
+        .find(field)
+        .ok_or("gc_safe_point missing in pd response")?;
+    let value: String = body[pos + field.len()..]
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    Ok(value.parse()?)
+}
+
+/// Refresh the cached gc safepoint from PD periodically.
+pub fn init_gc_safepoint_refresher(pd_addrs: Vec<String>) {
+    tokio::spawn(async move {
+        let interval = Duration::from_millis(txn_gc_safepoint_refresh_ms_or_default());
+        loop {
+            for addr in &pd_addrs {
+                match fetch_gc_safepoint(addr).await {
+                    Ok(safepoint) => {
+                        // never move it backwards
+                        GC_SAFEPOINT.fetch_max(safepoint, Ordering::Relaxed);
+                        break;
+                    }
+                    Err(e) => {
+                        error!(LOGGER, "fetch gc safepoint from {} failed: {}", addr, e);
+                    }
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Refresh the timestamp shared by stale reads in background, so that reads within
+/// `txn_read_staleness_ms` do not wait for PD.
+pub fn init_tso_refresher() {
+    let staleness = txn_read_staleness_ms_or_default();
+    if staleness == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        // refresh at half the staleness, the cached one never expires in between
+        let interval = Duration::from_millis((staleness / 2).max(1));
+        loop {
+            match get_txn_client() {
+                Ok(client) => {
+                    if let Err(e) = client.refresh_cached_timestamp().await {
+                        error!(LOGGER, "refresh cached timestamp failed: {}", e);
+                    }
+                }
+                Err(e) => {
+                    error!(LOGGER, "refresh cached timestamp failed: {}", e);
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+lazy_static! {
+    // timestamp shared by stale reads, along with the instant it was requested
+    static ref CACHED_TSO: RwLock<Option<(Timestamp, Instant)>> = RwLock::new(None);
+}
+
 pub struct TxnClientWrapper<'a> {
     client: &'a TransactionClient,
     retries: u32,
@@ -46,8 +194,7 @@ impl TxnClientWrapper<'static> {
         }
     }
 
-    pub fn begin_with_latest(&self) -> Transaction {
-        // add retry options
+    fn retry_options(&self) -> RetryOptions {
         let region_backoff = Backoff::no_jitter_backoff(
             txn_region_backoff_delay_ms(),
             MAX_DELAY_MS,
@@ -58,47 +205,104 @@ impl TxnClientWrapper<'static> {
             MAX_DELAY_MS,
             txn_lock_backoff_delay_attemps(),
         );
-        let retry_options = RetryOptions::new(region_backoff, lock_backoff);
+        RetryOptions::new(region_backoff, lock_backoff)
+    }
 
-        let mut txn_options = if is_use_pessimistic_txn() {
-            TransactionOptions::new_pessimistic().retry_options(retry_options)
-        } else {
-            TransactionOptions::new_optimistic().retry_options(retry_options)
-        };
+    pub async fn current_timestamp(&self) -> TiKVResult<Timestamp> {
+        let start_at = Instant::now();
+        let ts = self.client.current_timestamp().await.map_err(|err| {
+            TIKV_ERR_COUNTER
+                .with_label_values(&["get_timestamp_error"])
+                .inc();
+            err
+        })?;
+        RETRIEVE_TSO_DURATION.observe(duration_to_sec(Instant::now() - start_at));
+        Ok(ts)
+    }
 
-        let mut mechanism: (&str, &str) = ("two_pc", "sync");
-        txn_options = if is_try_one_pc_commit() {
-            mechanism.0 = "one_pc";
-            txn_options.try_one_pc()
-        } else {
-            txn_options
-        };
-        txn_options = if is_use_async_commit() {
-            mechanism.1 = "async";
-            txn_options.use_async_commit()
-        } else {
-            txn_options
+    async fn refresh_cached_timestamp(&self) -> TiKVResult<Timestamp> {
+        let requested_at = Instant::now();
+        let ts = self.current_timestamp().await?;
+        let mut cached = CACHED_TSO.write().unwrap();
+        // another connection may have refreshed it meanwhile
+        if !matches!(&*cached, Some((_, at)) if *at > requested_at) {
+            *cached = Some((ts.clone(), requested_at));
+        }
+        Ok(ts)
+    }
+
+    // reuse the cached timestamp if it is not older than staleness, or refresh it from PD
+    async fn cached_timestamp(&self, staleness: u64) -> TiKVResult<Timestamp> {
+        if let Some((ts, requested_at)) = CACHED_TSO.read().unwrap().clone() {
+            let age = Instant::now() - requested_at;
+            if age <= Duration::from_millis(staleness) {
+                STALE_READ_TSO_COUNTER.with_label_values(&["cached"]).inc();
+                STALE_READ_TSO_AGE.observe(duration_to_sec(age));
+                return Ok(ts);
+            }
+        }
+
+        let ts = self.refresh_cached_timestamp().await?;
+        STALE_READ_TSO_COUNTER
+            .with_label_values(&["refreshed"])
+            .inc();
+        Ok(ts)
+    }
+
+    /// Create a read only transaction, it needs no commit.
+    ///
+    /// The timestamp is fresh, unless the connection reads at a historical timestamp by
+    /// `SNAPSHOT AT`, or allows stale reads by `CLIENT STALENESS` or `txn_read_staleness_ms`.
+    pub async fn snapshot(&self) -> AsyncResult<Transaction> {
+        let (staleness, snapshot_ts) = READ_OPTIONS
+            .try_with(|o| (o.staleness_ms.get(), o.snapshot_ts.get()))
+            .unwrap_or((0, None));
+        let ts = match snapshot_ts {
+            Some(version) => {
+                // the safepoint keeps moving after SNAPSHOT AT
+                if version < gc_safepoint() {
+                    return Err(REDIS_SNAPSHOT_TOO_OLD_ERR);
+                }
+                Timestamp::from_version(version)
+            }
+            None if staleness > 0 => self.cached_timestamp(staleness).await?,
+            None => self.current_timestamp().await?,
         };
 
-        self.client
-            .new_transaction(Timestamp::from_version(u64::MAX), txn_options.read_only())
+        SNAPSHOT_COUNTER.inc();
+        let txn_options = TransactionOptions::new_optimistic()
+            .retry_options(self.retry_options())
+            .read_only();
+        Ok(self.client.new_transaction(ts, txn_options))
     }
 
     pub async fn begin(&self) -> TiKVResult<Transaction> {
+        self.begin_with_mode(is_use_pessimistic_txn()).await
+    }
+
+    pub async fn begin_with_mode(&self, pessimistic: bool) -> TiKVResult<Transaction> {
+        self.client
+            .begin_with_options(self.txn_options(pessimistic))
+            .await
+            .map_err(|err| {
+                TIKV_ERR_COUNTER
+                    .with_label_values(&["start_txn_error"])
+                    .inc();
+                err
+            })
+    }
+
+    /// Begin an optimistic transaction at `ts`, it fails to commit if the keys it writes or
+    /// locks are modified by others since `ts`.
+    pub fn begin_at(&self, ts: Timestamp) -> Transaction {
+        self.client.new_transaction(ts, self.txn_options(false))
+    }
+
+    fn txn_options(&self, pessimistic: bool) -> TransactionOptions {
         // add retry options
-        let region_backoff = Backoff::no_jitter_backoff(
-            txn_region_backoff_delay_ms(),
-            MAX_DELAY_MS,
-            txn_region_backoff_delay_attemps(),
-        );
-        let lock_backoff = Backoff::no_jitter_backoff(
-            txn_lock_backoff_delay_ms(),
-            MAX_DELAY_MS,
-            txn_lock_backoff_delay_attemps(),
-        );
-        let retry_options = RetryOptions::new(region_backoff, lock_backoff);
+        let retry_options = self.retry_options();
 
-        let mut txn_options = if is_use_pessimistic_txn() {
+        let mut txn_options = if pessimistic {
             TransactionOptions::new_pessimistic().retry_options(retry_options)
         } else {
             TransactionOptions::new_optimistic().retry_options(retry_options)
@@ -122,16 +326,7 @@ impl TxnClientWrapper<'static> {
         TXN_MECHANISM_COUNTER
             .with_label_values(&[mechanism.0, mechanism.1])
             .inc();
-
-        self.client
-            .begin_with_options(txn_options)
-            .await
-            .map_err(|err| {
-                TIKV_ERR_COUNTER
-                    .with_label_values(&["start_txn_error"])
-                    .inc();
-                err
-            })
+        txn_options
     }
 
     fn error_retryable(&self, err: &Error) -> bool {
@@ -153,6 +348,45 @@ impl TxnClientWrapper<'static> {
         ret
     }
 
+    /// Call f with a read only snapshot for commands that never write.
+    ///
+    /// Unlike `exec_in_txn`, the snapshot is neither committed nor retried on conflict.
+    /// If `txn` is given, such as in MULTI or lua script, f reads in that transaction.
+    pub async fn exec_in_snapshot<T, F>(
+        &mut self,
+        txn: Option<Arc<Mutex<Transaction>>>,
+        f: F,
+    ) -> AsyncResult<T>
+    where
+        F: FnOnce(Arc<Mutex<Transaction>>) -> BoxFuture<'static, AsyncResult<T>>,
+    {
+        let txn = match txn {
+            Some(txn) => txn,
+            None => Arc::new(Mutex::new(self.snapshot().await?)),
+        };
+
+        let start_at = Instant::now();
+        let result = f(txn).await;
+        let duration = Instant::now() - start_at;
+        TXN_DURATION.observe(duration_to_sec(duration));
+        result
+    }
+
+    /// Like `exec_in_snapshot`, but reads the latest data regardless of the staleness or
+    /// `SNAPSHOT AT` of the connection, for the data shared by the cluster such as scripts.
+    pub async fn exec_in_latest_snapshot<T, F>(
+        &mut self,
+        txn: Option<Arc<Mutex<Transaction>>>,
+        f: F,
+    ) -> AsyncResult<T>
+    where
+        F: FnOnce(Arc<Mutex<Transaction>>) -> BoxFuture<'static, AsyncResult<T>>,
+    {
+        READ_OPTIONS
+            .scope(ReadOptions::default(), self.exec_in_snapshot(txn, f))
+            .await
+    }
+
     /// Auto begin new txn, call f with the txn, commit or callback due to the result
     pub async fn exec_in_txn<T, F>(
         &mut self,
@@ -206,9 +440,10 @@ impl TxnClientWrapper<'static> {
 
                     let txn_arc = Arc::new(Mutex::new(txn));
 
-                    // call f
+                    // call f, the keyspace events of a failed attempt are dropped
+                    let events = PendingEvents::default();
                     let start_at = Instant::now();
-                    let result = f(txn_arc.clone()).await;
+                    let result = events.defer(f(txn_arc.clone())).await;
                     let duration = Instant::now() - start_at;
                     TXN_DURATION.observe(duration_to_sec(duration));
 
@@ -219,6 +454,7 @@ impl TxnClientWrapper<'static> {
                     match result {
                         Ok(res) => match txn.commit().await {
                             Ok(_) => {
+                                events.publish();
                                 return Ok(res);
                             }
                             Err(e) => {
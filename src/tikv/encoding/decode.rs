@@ -61,6 +61,11 @@ impl KeyDecoder {
         u64::from_be_bytes(value.try_into().unwrap())
     }
 
+    /// returns whether the function has the no-writes flag, and its library name
+    pub fn decode_function_value(value: &[u8]) -> (bool, &[u8]) {
+        (value[0] != 0, &value[1..])
+    }
+
     pub fn decode_key_string_value(value: &[u8]) -> Value {
         value[11..].to_vec()
     }
@@ -26,6 +26,9 @@ pub const DATA_TYPE_USER_END: u8 = b'v';
 pub const DATA_TYPE_TOPO: u8 = b't';
 pub const DATA_TYPE_GC: u8 = b'g';
 pub const DATA_TYPE_GC_VERSION: u8 = b'v';
+pub const DATA_TYPE_SCRIPT: u8 = b'c';
+pub const DATA_TYPE_SCRIPT_END: u8 = b'd';
+pub const DATA_TYPE_FUNCTION: u8 = b'f';
 
 pub const DATA_TYPE_META: u8 = b'm';
 pub const DATA_TYPE_SCORE: u8 = b'S';
@@ -34,6 +37,9 @@ pub const DATA_TYPE_LIST: u8 = b'l';
 pub const DATA_TYPE_SET: u8 = b's';
 pub const DATA_TYPE_ZSET: u8 = b'z';
 
+pub const FUNCTION_LIBRARY: u8 = b'l';
+pub const FUNCTION_NAME: u8 = b'n';
+
 pub const PLACE_HOLDER: u8 = b'`';
 
 impl KeyEncoder {
@@ -106,6 +112,75 @@ impl KeyEncoder {
         key.into()
     }
 
+    /// encode key of script loaded by SCRIPT LOAD, shared by all instances
+    pub fn encode_txnkv_script(&self, sha1: &str) -> Key {
+        let mut key = Vec::with_capacity(4 + sha1.len());
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_SCRIPT);
+        key.extend_from_slice(sha1.as_bytes());
+        key.into()
+    }
+
+    /// encode key of script cache epoch, which is bumped by SCRIPT FLUSH
+    pub fn encode_txnkv_script_epoch(&self) -> Key {
+        self.encode_txnkv_script("")
+    }
+
+    /// encode range of all scripts along with the epoch key
+    pub fn encode_txnkv_script_range(&self) -> BoundRange {
+        let mut end = Vec::with_capacity(4);
+        end.push(TXN_KEY_PREFIX);
+        end.extend_from_slice(self.instance_id.as_slice());
+        end.push(DATA_TYPE_SCRIPT_END);
+        let range: Range<Key> = self.encode_txnkv_script_epoch()..end.into();
+        range.into()
+    }
+
+    fn encode_txnkv_function_prefix(&self, sub_type: u8) -> Vec<u8> {
+        let mut key = Vec::with_capacity(5);
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_FUNCTION);
+        key.push(sub_type);
+        key
+    }
+
+    /// encode key of function library loaded by FUNCTION LOAD, the value is the library code
+    pub fn encode_txnkv_function_library(&self, lib: &str) -> Key {
+        let mut key = self.encode_txnkv_function_prefix(FUNCTION_LIBRARY);
+        key.extend_from_slice(lib.as_bytes());
+        key.into()
+    }
+
+    pub fn encode_txnkv_function_library_range(&self) -> BoundRange {
+        let start = self.encode_txnkv_function_prefix(FUNCTION_LIBRARY);
+        let end = self.encode_txnkv_function_prefix(FUNCTION_LIBRARY + 1);
+        let range: Range<Key> = start.into()..end.into();
+        range.into()
+    }
+
+    /// encode key of function registered by a library, the value is its flags and library name
+    pub fn encode_txnkv_function(&self, name: &str) -> Key {
+        let mut key = self.encode_txnkv_function_prefix(FUNCTION_NAME);
+        key.extend_from_slice(name.as_bytes());
+        key.into()
+    }
+
+    pub fn encode_txnkv_function_range(&self) -> BoundRange {
+        let start = self.encode_txnkv_function_prefix(FUNCTION_NAME);
+        let end = self.encode_txnkv_function_prefix(FUNCTION_NAME + 1);
+        let range: Range<Key> = start.into()..end.into();
+        range.into()
+    }
+
+    pub fn encode_txnkv_function_value(&self, no_writes: bool, lib: &str) -> Value {
+        let mut val = Vec::with_capacity(1 + lib.len());
+        val.push(no_writes as u8);
+        val.extend_from_slice(lib.as_bytes());
+        val
+    }
+
     pub fn encode_rawkv_string(&self, ukey: &str) -> Key {
         let mut key = Vec::with_capacity(4 + ukey.len());
         key.push(RAW_KEY_PREFIX);
@@ -184,6 +259,39 @@ impl KeyEncoder {
         key.into()
     }
 
+    /// Return the [start, end) bound of meta keys whose user key starts with `prefix`
+    pub fn encode_txnkv_meta_key_prefix_bound(&self, prefix: &[u8]) -> (Key, Key) {
+        let mut start = Vec::with_capacity(5 + prefix.len() * 9 / 8 + 9);
+        self.encode_txnkv_meta_common_prefix(&self.encode_bytes(prefix), &mut start);
+
+        // the smallest user key greater than all keys with the prefix
+        let mut next_prefix = prefix.to_vec();
+        while let Some(b) = next_prefix.pop() {
+            if b < u8::MAX {
+                next_prefix.push(b + 1);
+                let mut end = Vec::with_capacity(start.capacity());
+                self.encode_txnkv_meta_common_prefix(&self.encode_bytes(&next_prefix), &mut end);
+                return (start.into(), end.into());
+            }
+        }
+        (start.into(), self.encode_txnkv_keyspace_end())
+    }
+
+    /// Return the range of all keys belong to the user key, including meta, sub meta and data
+    pub fn encode_txnkv_key_range(&self, ukey: &str) -> BoundRange {
+        let enc_ukey = self.encode_bytes(ukey.as_bytes());
+        let mut start = Vec::with_capacity(4 + enc_ukey.len());
+        start.push(TXN_KEY_PREFIX);
+        start.extend_from_slice(self.instance_id.as_slice());
+        start.push(DATA_TYPE_USER);
+        start.extend_from_slice(&enc_ukey);
+
+        let mut end = start.clone();
+        end.push(u8::MAX);
+        let range: Range<Key> = start.into()..end.into();
+        range.into()
+    }
+
     pub fn encode_txnkv_sub_meta_key(&self, ukey: &str, version: u16, idx: u16) -> Key {
         let enc_ukey = self.encode_bytes(ukey.as_bytes());
         let mut key = Vec::with_capacity(10 + enc_ukey.len());
@@ -320,7 +428,7 @@ impl KeyEncoder {
         range.into()
     }
 
-    fn encode_txnkv_list_data_key_start(&self, ukey: &str, version: u16) -> Key {
+    pub fn encode_txnkv_list_data_key_start(&self, ukey: &str, version: u16) -> Key {
         let enc_ukey = self.encode_bytes(ukey.as_bytes());
         let mut key = Vec::with_capacity(8 + enc_ukey.len());
 
@@ -34,7 +34,14 @@ impl From<TiKVError> for RTError {
 
 impl From<LuaError> for RTError {
     fn from(e: LuaError) -> Self {
-        RTError::Owned(e.to_string())
+        match e {
+            // error reply raised by redis.call is returned as it is
+            LuaError::CallbackError { cause, .. } => match cause.as_ref() {
+                LuaError::RuntimeError(msg) => RTError::Owned(msg.clone()),
+                cause => cause.clone().into(),
+            },
+            e => RTError::Owned(format!("ERR Error running script: {}", e)),
+        }
     }
 }
 
@@ -61,6 +68,23 @@ pub type AsyncResult<T> = std::result::Result<T, RTError>;
 pub const REDIS_WRONG_TYPE_ERR: RTError =
     RTError::String("WRONGTYPE Operation against a key holding the wrong kind of value");
 pub const REDIS_NO_SUCH_KEY_ERR: RTError = RTError::String("ERR no such key");
+pub const REDIS_SAME_OBJECT_ERR: RTError =
+    RTError::String("ERR source and destination objects are the same");
+pub const REDIS_BUSY_KEY_ERR: RTError = RTError::String("BUSYKEY Target key name already exists.");
+pub const REDIS_DUMP_PAYLOAD_ERR: RTError =
+    RTError::String("ERR DUMP payload version or checksum are wrong");
+pub const REDIS_BAD_DATA_FORMAT_ERR: RTError = RTError::String("ERR Bad data format");
+pub const REDIS_INVALID_TTL_ERR: RTError = RTError::String("ERR Invalid TTL value, must be >= 0");
+pub const REDIS_INVALID_CAS_EXPIRE_TIME_ERR: RTError =
+    RTError::String("ERR invalid expire time in 'cas' command");
+pub const REDIS_INVALID_IDLETIME_ERR: RTError =
+    RTError::String("ERR Invalid IDLETIME value, must be >= 0");
+pub const REDIS_INVALID_FREQ_ERR: RTError =
+    RTError::String("ERR Invalid FREQ value, must be >= 0 and <= 255");
+pub const REDIS_SNAPSHOT_TOO_OLD_ERR: RTError =
+    RTError::String("ERR snapshot timestamp is older than the GC safepoint");
+pub const REDIS_SNAPSHOT_IN_FUTURE_ERR: RTError =
+    RTError::String("ERR snapshot timestamp is in the future");
 pub const REDIS_INDEX_OUT_OF_RANGE_ERR: RTError = RTError::String("ERR index out of range");
 pub const REDIS_VALUE_IS_NOT_INTEGER_ERR: RTError =
     RTError::String("ERR value is not an integer or out of range");
@@ -76,11 +100,50 @@ pub const REDIS_AUTH_WHEN_DISABLED_ERR: RTError =
     RTError::String("ERR Client sent AUTH, but no password is set");
 pub const REDIS_AUTH_INVALID_PASSWORD_ERR: RTError = RTError::String("ERR invalid password");
 pub const REDIS_AUTH_REQUIRED_ERR: RTError = RTError::String("NOAUTH Authentication required.");
+pub const REDIS_HELLO_AUTH_REQUIRED_ERR: RTError = RTError::String(
+    "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time",
+);
+pub const REDIS_WRONG_PASS_ERR: RTError =
+    RTError::String("WRONGPASS invalid username-password pair or user is disabled.");
+pub const REDIS_PROTOCOL_VERSION_ERR: RTError =
+    RTError::String("ERR Protocol version is not an integer or out of range");
+pub const REDIS_NO_PROTO_ERR: RTError = RTError::String("NOPROTO unsupported protocol version");
 pub const REDIS_NO_MATCHING_SCRIPT_ERR: RTError =
     RTError::String("NOSCRIPT No matching script. Please use EVAL.");
 pub const REDIS_LUA_CONTEXT_IS_NOT_INITIALIZED_ERR: RTError =
     RTError::String("ERR lua context is not initialized");
-pub const REDIS_LUA_PANIC: RTError = RTError::String("ERR lua panic");
+pub const REDIS_FUNCTION_NOT_FOUND_ERR: RTError = RTError::String("ERR Function not found");
+pub const REDIS_FUNCTION_LIBRARY_NOT_FOUND_ERR: RTError = RTError::String("ERR Library not found");
+pub const REDIS_FUNCTION_WRITE_ON_RO_ERR: RTError =
+    RTError::String("ERR Can not execute a script with write flag using *_ro command.");
+pub const REDIS_FUNCTION_NO_FUNCTIONS_ERR: RTError = RTError::String("ERR No functions registered");
+pub const REDIS_FUNCTION_MISSING_METADATA_ERR: RTError =
+    RTError::String("ERR Missing library metadata");
+pub const REDIS_FUNCTION_LIBRARY_NAME_ERR: RTError = RTError::String(
+    "ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long",
+);
+pub const REDIS_WRITE_IN_READ_ONLY_SCRIPT_ERR: RTError =
+    RTError::String("ERR Write commands are not allowed from read-only scripts.");
+pub const REDIS_BUSY_ERR: RTError = RTError::String(
+    "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE.",
+);
+pub const REDIS_NOTBUSY_ERR: RTError =
+    RTError::String("NOTBUSY No scripts in execution right now.");
+pub const REDIS_UNKILLABLE_ERR: RTError = RTError::String(
+    "UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.",
+);
+pub const REDIS_SCRIPT_DEBUG_MODE_ERR: RTError =
+    RTError::String("ERR Use SCRIPT DEBUG YES/SYNC/NO");
+pub const REDIS_SCRIPT_DEBUG_INSIDE_MULTI_ERR: RTError =
+    RTError::String("ERR SCRIPT DEBUG must be called outside MULTI");
+pub const REDIS_EVALSHA_IN_DEBUG_ERR: RTError =
+    RTError::String("ERR Please use EVAL instead of EVALSHA for debugging");
+pub const REDIS_LUA_NO_ARGUMENT_ERR: RTError =
+    RTError::String("ERR Please specify at least one argument for this redis lib call");
+pub const REDIS_LUA_ARGUMENT_TYPE_ERR: RTError =
+    RTError::String("ERR Lua redis lib command arguments must be strings or integers");
+pub const REDIS_CROSSSLOT_ERR: RTError =
+    RTError::String("CROSSSLOT Keys in request don't hash to the same slot");
 pub const REDIS_UNKNOWN_SUBCOMMAND: RTError =
     RTError::String("Unknown subcommand or wrong number of arguments");
 pub const DECREMENT_OVERFLOW: RTError = RTError::String("Decrement would overflow");
@@ -89,8 +152,34 @@ pub const KEY_VERSION_EXHUSTED_ERR: RTError = RTError::String("ERR key version e
 pub const REDIS_MULTI_NESTED_ERR: RTError = RTError::String("ERR MULTI calls can not be nested");
 pub const REDIS_DISCARD_WITHOUT_MULTI_ERR: RTError = RTError::String("ERR DISCARD without MULTI");
 pub const REDIS_EXEC_WITHOUT_MULTI_ERR: RTError = RTError::String("ERR EXEC without MULTI");
+pub const REDIS_WATCH_INSIDE_MULTI_ERR: RTError =
+    RTError::String("ERR WATCH inside MULTI is not allowed");
+pub const REDIS_TXN_NESTED_ERR: RTError = RTError::String("ERR TXN BEGIN calls can not be nested");
+pub const REDIS_TXN_WITHOUT_BEGIN_ERR: RTError = RTError::String("ERR TXN without BEGIN");
+pub const REDIS_TXN_INSIDE_MULTI_ERR: RTError =
+    RTError::String("ERR TXN inside MULTI is not allowed");
+pub const REDIS_TXN_TIMEOUT_ERR: RTError =
+    RTError::String("ERR TXN rolled back because of timeout, use TXN ROLLBACK to end it");
 pub const REDIS_EXEC_ERR: RTError =
     RTError::String("EXECABORT Transaction discarded because of previous errors.");
 
 pub const REDIS_INVALID_CLIENT_ID_ERR: RTError = RTError::String("ERR Invalid client ID");
 pub const REDIS_NO_SUCH_CLIENT_ERR: RTError = RTError::String("ERR No such client");
+pub const REDIS_TRACKING_REDIRECT_ERR: RTError =
+    RTError::String("ERR The client ID you want redirect to does not exist");
+pub const REDIS_TRACKING_PREFIX_ERR: RTError =
+    RTError::String("ERR PREFIX option requires BCAST mode to be enabled");
+pub const REDIS_TRACKING_OPTIN_OPTOUT_ERR: RTError =
+    RTError::String("ERR You can't use both OPTIN and OPTOUT");
+pub const REDIS_TRACKING_BCAST_OPTIN_ERR: RTError =
+    RTError::String("ERR OPTIN and OPTOUT are not compatible with BCAST");
+pub const REDIS_TRACKING_SWITCH_BCAST_ERR: RTError = RTError::String(
+    "ERR You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.",
+);
+pub const REDIS_TRACKING_CACHING_ERR: RTError = RTError::String(
+    "ERR CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled",
+);
+pub const REDIS_TRACKING_CACHING_YES_ERR: RTError =
+    RTError::String("ERR CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode.");
+pub const REDIS_TRACKING_CACHING_NO_ERR: RTError =
+    RTError::String("ERR CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode.");
@@ -0,0 +1,205 @@
+use super::encoding::KeyDecoder;
+use super::errors::{AsyncResult, RTError, REDIS_FUNCTION_LIBRARY_NOT_FOUND_ERR};
+use super::{get_txn_client, KEY_ENCODER};
+use ::futures::future::FutureExt;
+use bytes::Bytes;
+use std::sync::Arc;
+use tikv_client::{BoundRange, Key, Transaction};
+use tokio::sync::Mutex;
+
+/// Function registered by `redis.register_function` in a library
+#[derive(Debug, Clone)]
+pub struct FunctionMeta {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+impl FunctionMeta {
+    pub fn no_writes(&self) -> bool {
+        self.flags.iter().any(|f| f == "no-writes")
+    }
+}
+
+/// Library loaded by FUNCTION LOAD, along with the functions it registers
+#[derive(Debug, Clone)]
+pub struct FunctionLibrary {
+    pub name: String,
+    pub code: Bytes,
+    pub functions: Vec<FunctionMeta>,
+}
+
+/// Function libraries are persisted in tikv, so that FCALL works on every instance
+/// of the cluster and after restart
+#[derive(Clone)]
+pub struct FunctionCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl FunctionCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        FunctionCommandCtx { txn }
+    }
+
+    async fn delete_range(txn: &mut Transaction, range: BoundRange) -> AsyncResult<()> {
+        let keys: Vec<Key> = txn.scan_keys(range, u32::MAX).await?.collect();
+        for key in keys {
+            txn.delete(key).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_library_functions(txn: &mut Transaction, lib: &str) -> AsyncResult<()> {
+        let range = KEY_ENCODER.encode_txnkv_function_range();
+        let keys: Vec<Key> = txn
+            .scan(range, u32::MAX)
+            .await?
+            .filter(|kv| KeyDecoder::decode_function_value(&kv.1).1 == lib.as_bytes())
+            .map(|kv| kv.0)
+            .collect();
+        for key in keys {
+            txn.delete(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Store the libraries, existing libraries with the same name are replaced if `replace`
+    /// is set, or all libraries are deleted first if `flush` is set
+    pub async fn do_async_txnkv_function_load(
+        mut self,
+        libs: Vec<FunctionLibrary>,
+        replace: bool,
+        flush: bool,
+    ) -> AsyncResult<()> {
+        let mut client = get_txn_client()?;
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    if flush {
+                        let range = KEY_ENCODER.encode_txnkv_function_library_range();
+                        Self::delete_range(&mut txn, range).await?;
+                        let range = KEY_ENCODER.encode_txnkv_function_range();
+                        Self::delete_range(&mut txn, range).await?;
+                    }
+
+                    for lib in libs {
+                        let lib_key = KEY_ENCODER.encode_txnkv_function_library(&lib.name);
+                        if txn.get(lib_key.clone()).await?.is_some() {
+                            if !replace {
+                                return Err(RTError::Owned(format!(
+                                    "ERR Library '{}' already exists",
+                                    lib.name
+                                )));
+                            }
+                            Self::delete_library_functions(&mut txn, &lib.name).await?;
+                        }
+
+                        for func in &lib.functions {
+                            let key = KEY_ENCODER.encode_txnkv_function(&func.name);
+                            // function names are unique across libraries
+                            if txn.get(key.clone()).await?.is_some() {
+                                return Err(RTError::Owned(format!(
+                                    "ERR Function {} already exists",
+                                    func.name
+                                )));
+                            }
+                            let value = KEY_ENCODER
+                                .encode_txnkv_function_value(func.no_writes(), &lib.name);
+                            txn.put(key, value).await?;
+                        }
+                        txn.put(lib_key, lib.code.to_vec()).await?;
+                    }
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    pub async fn do_async_txnkv_function_delete(mut self, lib: &str) -> AsyncResult<()> {
+        let mut client = get_txn_client()?;
+        let lib = lib.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let lib_key = KEY_ENCODER.encode_txnkv_function_library(&lib);
+                    if txn.get(lib_key.clone()).await?.is_none() {
+                        return Err(REDIS_FUNCTION_LIBRARY_NOT_FOUND_ERR);
+                    }
+                    txn.delete(lib_key).await?;
+                    Self::delete_library_functions(&mut txn, &lib).await
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    pub async fn do_async_txnkv_function_flush(self) -> AsyncResult<()> {
+        self.do_async_txnkv_function_load(vec![], false, true).await
+    }
+
+    /// Returns the code of all libraries, ordered by library name
+    pub async fn do_async_txnkv_function_libraries(mut self) -> AsyncResult<Vec<Bytes>> {
+        let mut client = get_txn_client()?;
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let range = KEY_ENCODER.encode_txnkv_function_library_range();
+                    let libs = txn
+                        .scan(range, u32::MAX)
+                        .await?
+                        .map(|kv| Bytes::from(kv.1))
+                        .collect();
+                    Ok(libs)
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Returns the code of the library which registers the function, and whether
+    /// the function has the no-writes flag
+    pub async fn do_async_txnkv_function_get(
+        mut self,
+        name: &str,
+    ) -> AsyncResult<Option<(Bytes, bool)>> {
+        let mut client = get_txn_client()?;
+        let key = KEY_ENCODER.encode_txnkv_function(name);
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let value = match txn.get(key).await? {
+                        Some(v) => v,
+                        None => return Ok(None),
+                    };
+                    let (no_writes, lib) = KeyDecoder::decode_function_value(&value);
+                    let lib_key =
+                        KEY_ENCODER.encode_txnkv_function_library(&String::from_utf8_lossy(lib));
+                    let code = txn.get(lib_key).await?;
+                    Ok(code.map(|c| (Bytes::from(c), no_writes)))
+                }
+                .boxed()
+            })
+            .await
+    }
+}
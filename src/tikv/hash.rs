@@ -22,7 +22,9 @@ use tokio::sync::Mutex;
 use super::errors::*;
 use crate::utils::{resp_array, resp_bulk, resp_err, resp_int, resp_nil};
 
+use crate::gc::submit_expired_key;
 use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED, NOTIFY_HASH};
 
 #[derive(Clone)]
 pub struct HashCommandCtx {
@@ -90,6 +92,7 @@ impl<'a> HashCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -233,6 +236,9 @@ impl<'a> HashCommandCtx {
             .await;
         match resp {
             Ok(num) => {
+                if num > 0 {
+                    notify_keyspace_event(NOTIFY_HASH, "hset", &key);
+                }
                 if is_hmset {
                     Ok(resp_ok())
                 } else {
@@ -250,7 +256,7 @@ impl<'a> HashCommandCtx {
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -270,10 +276,7 @@ impl<'a> HashCommandCtx {
                             debug!(LOGGER, "hget key {} with version: {}", key, version);
 
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_hash_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_nil());
                             }
 
@@ -292,6 +295,69 @@ impl<'a> HashCommandCtx {
             .await
     }
 
+    /// Overwrite the field with `value` only if its current value equals `expected`.
+    /// Returns 1 if swapped, otherwise 0
+    pub async fn do_async_txnkv_hcas(
+        mut self,
+        key: &str,
+        field: &str,
+        expected: &[u8],
+        value: &[u8],
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let field = field.to_owned();
+        let expected = expected.to_vec();
+        let value = value.to_vec();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    match txn.get(meta_key).await? {
+                        Some(meta_value) => {
+                            // check key type and ttl
+                            if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Hash) {
+                                return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                            }
+
+                            let (ttl, version, _meta_size) =
+                                KeyDecoder::decode_key_meta(&meta_value);
+                            if key_is_expired(ttl) {
+                                submit_expired_key(&key);
+                                return Ok(resp_int(0));
+                            }
+
+                            // the field must exist, its size is not changed
+                            let data_key =
+                                KEY_ENCODER.encode_txnkv_hash_data_key(&key, &field, version);
+                            match txn.get(data_key.clone()).await? {
+                                Some(data) if data == expected => {
+                                    txn.put(data_key, value).await?;
+                                    Ok(resp_int(1))
+                                }
+                                _ => Ok(resp_int(0)),
+                            }
+                        }
+                        None => Ok(resp_int(0)),
+                    }
+                }
+                .boxed()
+            })
+            .await;
+
+        if let Ok(Frame::Integer(1)) = resp {
+            notify_keyspace_event(NOTIFY_HASH, "hset", &key);
+        }
+        resp
+    }
+
     pub async fn do_async_txnkv_hstrlen(mut self, key: &str, field: &str) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
@@ -299,7 +365,7 @@ impl<'a> HashCommandCtx {
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -317,10 +383,7 @@ impl<'a> HashCommandCtx {
                             let (ttl, version, _meta_size) =
                                 KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_hash_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_int(0));
                             }
 
@@ -347,7 +410,7 @@ impl<'a> HashCommandCtx {
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -365,10 +428,7 @@ impl<'a> HashCommandCtx {
                             let (ttl, version, _meta_size) =
                                 KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_hash_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_int(0));
                             }
 
@@ -402,7 +462,7 @@ impl<'a> HashCommandCtx {
         let mut resp = Vec::with_capacity(fields.len());
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -419,10 +479,7 @@ impl<'a> HashCommandCtx {
                             let (ttl, version, _meta_size) =
                                 KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_hash_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_array(vec![]));
                             }
 
@@ -468,7 +525,7 @@ impl<'a> HashCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -485,9 +542,7 @@ impl<'a> HashCommandCtx {
                             drop(txn);
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                self.clone()
-                                    .do_async_txnkv_hash_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_int(0));
                             }
                             let meta_size = self.txnkv_sum_key_size(&key, version).await?;
@@ -512,7 +567,7 @@ impl<'a> HashCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -528,10 +583,7 @@ impl<'a> HashCommandCtx {
 
                         let (ttl, version, _meta_size) = KeyDecoder::decode_key_meta(&meta_value);
                         if key_is_expired(ttl) {
-                            drop(txn);
-                            self.clone()
-                                .do_async_txnkv_hash_expire_if_needed(&key)
-                                .await?;
+                            submit_expired_key(&key);
                             return Ok(resp_nil());
                         }
 
@@ -544,16 +596,18 @@ impl<'a> HashCommandCtx {
 
                         let resp: Vec<Frame>;
                         if with_field && with_value {
-                            resp = iter
-                                .flat_map(|kv| {
+                            // flattened into field and value pairs for the RESP2 clients
+                            let pairs: Vec<(Frame, Frame)> = iter
+                                .map(|kv| {
                                     let field: Vec<u8> =
                                         KeyDecoder::decode_key_hash_userkey_from_datakey(
                                             &key, kv.0,
                                         );
-                                    stream::iter([resp_bulk(field), resp_bulk(kv.1)])
+                                    (resp_bulk(field), resp_bulk(kv.1))
                                 })
                                 .collect()
                                 .await;
+                            return Ok(Frame::Map(pairs));
                         } else if with_field {
                             resp = iter
                                 .flat_map(|kv| {
@@ -573,6 +627,8 @@ impl<'a> HashCommandCtx {
                         }
 
                         Ok(resp_array(resp))
+                    } else if with_field && with_value {
+                        Ok(Frame::Map(vec![]))
                     } else {
                         Ok(resp_array(vec![]))
                     }
@@ -590,6 +646,7 @@ impl<'a> HashCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -668,7 +725,12 @@ impl<'a> HashCommandCtx {
             .await;
 
         match resp {
-            Ok(n) => Ok(resp_int(n)),
+            Ok(n) => {
+                if n > 0 {
+                    notify_keyspace_event(NOTIFY_HASH, "hdel", &key);
+                }
+                Ok(resp_int(n))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -687,6 +749,7 @@ impl<'a> HashCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     let prev_int;
                     let data_key;
@@ -786,7 +849,10 @@ impl<'a> HashCommandCtx {
             .await;
 
         match resp {
-            Ok(n) => Ok(resp_int(n)),
+            Ok(n) => {
+                notify_keyspace_event(NOTIFY_HASH, "hincrby", &key);
+                Ok(resp_int(n))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -915,6 +981,7 @@ impl<'a> HashCommandCtx {
                             REMOVED_EXPIRED_KEY_COUNTER
                                 .with_label_values(&["hash"])
                                 .inc();
+                            notify_keyspace_event(NOTIFY_EXPIRED, "expired", &key);
                             Ok(1)
                         }
                         None => Ok(0),
@@ -0,0 +1,751 @@
+//! Lua debugger of SCRIPT DEBUG, which speaks the protocol of the redis lua debugger
+//! (LDB), so `redis-cli --ldb` works against tidis.
+//!
+//! The script being debugged runs in a lua state of its own on a dedicated thread, where
+//! the line hook blocks while waiting for the debugger commands, which are read from the
+//! connection and forwarded by `run_session`.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use mlua::{prelude::*, Debug as LuaDebug, HookTriggers, Lua, Value as LuaValue};
+use slog::{debug, info};
+use tikv_client::Transaction;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Mutex;
+
+use crate::cmd::Multi;
+use crate::config::LOGGER;
+use crate::frame::format_double;
+use crate::notify::PendingEvents;
+use crate::tikv::errors::{AsyncResult, RTError};
+use crate::tikv::get_txn_client;
+use crate::tikv::lua::LuaCommandCtx;
+use crate::tikv::lualib::{debug_lib, new_debug_lua};
+use crate::utils::{format_number_g, resp_err};
+use crate::{Command, Connection, Frame, Parse};
+
+/// Mode set by SCRIPT DEBUG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LdbMode {
+    /// SCRIPT DEBUG YES, changes of the script are rolled back at the end of the session
+    Async,
+    /// SCRIPT DEBUG SYNC, changes of the script are kept
+    Sync,
+}
+
+// logged replies and values are trimmed to 256 bytes by default
+const DEFAULT_MAXLEN: usize = 256;
+// tables nested deeper are not printed
+const MAX_VALUE_DEPTH: usize = 18;
+// level of the paused frame for the debug library functions called in the hook
+const FRAME_LEVEL: i64 = 1;
+
+const HELP: &[&str] = &[
+    "Redis Lua debugger help:",
+    "[h]elp               Show this help.",
+    "[s]tep               Run current line and stop again.",
+    "[n]ext               Alias for step.",
+    "[c]ontinue           Run till next breakpoint.",
+    "[l]ist               List source code around current line.",
+    "[l]ist [line]        List source code around [line].",
+    "                     line = 0 means: current position.",
+    "[l]ist [line] [ctx]  In this form [ctx] specifies how many lines",
+    "                     to show before/after [line].",
+    "[w]hole              List all source code. Alias for 'list 1 1000000'.",
+    "[p]rint              Show all the local variables.",
+    "[p]rint <var>        Show the value of the specified variable.",
+    "                     Can also show global vars KEYS and ARGV.",
+    "[b]reak              Show all breakpoints.",
+    "[b]reak <line>       Add a breakpoint to the specified line.",
+    "[b]reak -<line>      Remove breakpoint from the specified line.",
+    "[b]reak 0            Remove all breakpoints.",
+    "[t]race              Show a backtrace.",
+    "[e]val <code>        Execute some Lua code in the current callframe,",
+    "                     assignments to its local variables are kept.",
+    "[r]edis <cmd>        Execute a Redis command.",
+    "[m]axlen [len]       Trim logged Redis replies and Lua var dumps to len.",
+    "                     Specifying zero as <len> means unlimited.",
+    "[a]bort              Stop the execution of the script. The dataset",
+    "                     changes of the script are rolled back.",
+    "",
+    "Debugger functions you can call from Lua scripts:",
+    "redis.debug()        Produce logs in the debugger console.",
+    "redis.breakpoint()   Stop execution like if there was a breakpoint in the",
+    "                     next line of code.",
+];
+
+enum LdbEvent {
+    /// logs replied to the client as an array of status replies
+    Logs(Vec<String>),
+    /// reply of EVAL, which ends the session
+    Reply(Frame),
+}
+
+struct Session {
+    lines: Vec<String>,
+    breakpoints: BTreeSet<i32>,
+    step: bool,
+    /// set by redis.breakpoint()
+    luabp: bool,
+    current_line: i32,
+    maxlen: usize,
+    maxlen_hint_sent: bool,
+    logs: Vec<String>,
+    commands: Receiver<Vec<Bytes>>,
+    events: UnboundedSender<LdbEvent>,
+    txn: Arc<Mutex<Transaction>>,
+}
+
+thread_local! {
+    // session of the script debugged on this thread
+    static SESSION: RefCell<Option<Session>> = RefCell::new(None);
+}
+
+// the session is borrowed by the hook while the script is paused, so the logs of the
+// lua code run by the debugger itself are dropped
+fn with_session<T>(f: impl FnOnce(&mut Session) -> T) -> Option<T> {
+    SESSION.with(|session| match session.try_borrow_mut() {
+        Ok(mut session) => session.as_mut().map(f),
+        Err(_) => None,
+    })
+}
+
+/// Log the command called by the script while stepping through it
+pub fn trace_command(argv: &[Bytes]) {
+    with_session(|session| {
+        if session.step {
+            session.log_command(argv);
+        }
+    });
+}
+
+/// Log the reply of the command called by the script while stepping through it
+pub fn trace_reply(resp: &Frame) {
+    with_session(|session| {
+        if session.step {
+            session.log_reply(resp);
+        }
+    });
+}
+
+/// redis.debug(...)
+pub fn log_debug(args: &[LuaValue]) {
+    with_session(|session| {
+        let values: Vec<String> = args.iter().map(|v| repr_value(v, 0)).collect();
+        let entry = format!(
+            "<debug> line {}: {}",
+            session.current_line,
+            values.join(", ")
+        );
+        session.log_with_maxlen(entry);
+    });
+}
+
+/// redis.breakpoint(), returns false if the script is not being debugged
+pub fn breakpoint() -> bool {
+    with_session(|session| session.luabp = true).is_some()
+}
+
+/// Quote the bytes like `sdscatrepr` of redis
+fn repr_bytes(s: &[u8]) -> String {
+    let mut repr = String::from("\"");
+    for &c in s {
+        match c {
+            b'\\' => repr.push_str("\\\\"),
+            b'"' => repr.push_str("\\\""),
+            b'\n' => repr.push_str("\\n"),
+            b'\r' => repr.push_str("\\r"),
+            b'\t' => repr.push_str("\\t"),
+            0x07 => repr.push_str("\\a"),
+            0x08 => repr.push_str("\\b"),
+            c if c == b' ' || c.is_ascii_graphic() => repr.push(c as char),
+            c => repr.push_str(&format!("\\x{:02x}", c)),
+        }
+    }
+    repr.push('"');
+    repr
+}
+
+fn repr_value(value: &LuaValue, depth: usize) -> String {
+    match value {
+        LuaValue::Nil => "nil".to_owned(),
+        LuaValue::Boolean(b) => b.to_string(),
+        LuaValue::Integer(i) => format_number_g(*i as f64, 6),
+        LuaValue::Number(n) => format_number_g(*n, 6),
+        LuaValue::String(s) => repr_bytes(s.as_bytes()),
+        LuaValue::Table(_) if depth == MAX_VALUE_DEPTH => {
+            "<max recursion level reached! Nested table?>".to_owned()
+        }
+        LuaValue::Table(t) => repr_table(t, depth),
+        v => format!("\"{}\"", v.type_name()),
+    }
+}
+
+// tables with the keys 1..n are printed as {v1; v2}, the others as {[k1]=v1; [k2]=v2}
+fn repr_table(table: &LuaTable, depth: usize) -> String {
+    let mut is_array = true;
+    let (mut array, mut map) = (vec![], vec![]);
+    for (idx, pair) in table.clone().pairs::<LuaValue, LuaValue>().enumerate() {
+        let (key, value) = match pair {
+            Ok(pair) => pair,
+            Err(_) => break,
+        };
+        is_array = is_array
+            && match key {
+                LuaValue::Integer(i) => i == idx as i64 + 1,
+                LuaValue::Number(n) => n == (idx + 1) as f64,
+                _ => false,
+            };
+        let value = repr_value(&value, depth + 1);
+        map.push(format!("[{}]={}", repr_value(&key, depth + 1), value));
+        array.push(value);
+    }
+    if is_array {
+        format!("{{{}}}", array.join("; "))
+    } else {
+        format!("{{{}}}", map.join("; "))
+    }
+}
+
+/// Format the reply like `redis-cli` does
+fn repr_reply(resp: &Frame) -> String {
+    match resp {
+        Frame::Simple(s) => format!("+{}", s),
+        Frame::ErrorOwned(e) => format!("-{}", e),
+        Frame::ErrorString(e) => format!("-{}", e),
+        Frame::Integer(i) => format!(":{}", i),
+        Frame::Bulk(b) => repr_bytes(b),
+        Frame::Null => "NULL".to_owned(),
+        Frame::Array(items) | Frame::Push(items) => {
+            let items: Vec<String> = items.iter().map(repr_reply).collect();
+            format!("[{}]", items.join(","))
+        }
+        Frame::Set(items) => {
+            let items: Vec<String> = items.iter().map(repr_reply).collect();
+            format!("~({})", items.join(","))
+        }
+        Frame::Map(pairs) | Frame::Attribute(pairs) => {
+            let pairs: Vec<String> = pairs
+                .iter()
+                .map(|(key, value)| format!("{} => {}", repr_reply(key), repr_reply(value)))
+                .collect();
+            format!("{{{}}}", pairs.join(","))
+        }
+        Frame::Double(d) => format!("(double) {}", format_double(*d)),
+        Frame::Boolean(b) => format!("#{}", b),
+        Frame::BigNumber(num) => format!("(big number) {}", num),
+        Frame::Verbatim(_, data) => repr_bytes(data),
+    }
+}
+
+// locals of the frame at the level, or None if there is no such frame
+fn frame_locals(lua: &Lua, level: i64) -> LuaResult<Option<Vec<(i64, String, LuaValue)>>> {
+    let getlocal: LuaFunction = debug_lib(lua)?.get("getlocal")?;
+    let mut locals = vec![];
+    for idx in 1i64.. {
+        // the debug library raises an error if the level is out of range
+        let (name, value) = match getlocal.call::<_, (Option<String>, LuaValue)>((level, idx)) {
+            Ok(local) => local,
+            Err(_) if idx == 1 => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match name {
+            // internal locals of the lua vm are named like (*temporary)
+            Some(name) if name.starts_with('(') => {}
+            Some(name) => locals.push((idx, name, value)),
+            None => break,
+        }
+    }
+    Ok(Some(locals))
+}
+
+impl Session {
+    fn log(&mut self, entry: impl Into<String>) {
+        self.logs.push(entry.into());
+    }
+
+    fn log_with_maxlen(&mut self, mut entry: String) {
+        let trimmed = self.maxlen > 0 && entry.len() > self.maxlen;
+        if trimmed {
+            let mut end = self.maxlen;
+            while !entry.is_char_boundary(end) {
+                end -= 1;
+            }
+            entry.truncate(end);
+            entry.push_str(" ...");
+        }
+        self.log(entry);
+        if trimmed && !self.maxlen_hint_sent {
+            self.maxlen_hint_sent = true;
+            self.log("<hint> The above reply was trimmed. Use 'maxlen 0' to disable trimming.");
+        }
+    }
+
+    fn log_command(&mut self, argv: &[Bytes]) {
+        let mut entry = "<redis>".to_owned();
+        for (idx, arg) in argv.iter().enumerate() {
+            // only the first 10 arguments are logged
+            if idx == 10 {
+                entry.push_str(&format!(" ... ({} more)", argv.len() - idx));
+                break;
+            }
+            entry.push(' ');
+            entry.push_str(&String::from_utf8_lossy(arg));
+        }
+        self.log(entry);
+    }
+
+    fn log_reply(&mut self, resp: &Frame) {
+        self.log_with_maxlen(format!("<reply> {}", repr_reply(resp)));
+    }
+
+    fn log_source_line(&mut self, line: i32) {
+        let prefix = match (line == self.current_line, self.breakpoints.contains(&line)) {
+            (true, true) => "->#",
+            (true, false) => "-> ",
+            (false, true) => "  #",
+            (false, false) => "   ",
+        };
+        let source = usize::try_from(line - 1)
+            .ok()
+            .and_then(|idx| self.lines.get(idx))
+            .map_or("<out of range source code line>", |s| s.as_str());
+        let entry = format!("{}{:<3} {}", prefix, line, source);
+        self.log(entry);
+    }
+
+    fn send_logs(&mut self) {
+        let logs = std::mem::take(&mut self.logs);
+        let _ = self.events.send(LdbEvent::Logs(logs));
+    }
+
+    fn on_line(&mut self, lua: &Lua, line: i32) -> LuaResult<()> {
+        self.current_line = line;
+        let breakpoint = self.luabp || self.breakpoints.contains(&line);
+        if !self.step && !breakpoint {
+            return Ok(());
+        }
+        let reason = if !breakpoint {
+            "step over"
+        } else if self.luabp {
+            "redis.breakpoint() called"
+        } else {
+            "break point"
+        };
+        self.step = false;
+        self.luabp = false;
+        self.log(format!("* Stopped at {}, stop reason = {}", line, reason));
+        self.log_source_line(line);
+        self.repl(lua)
+    }
+
+    // serve the debugger commands until the script is resumed
+    fn repl(&mut self, lua: &Lua) -> LuaResult<()> {
+        loop {
+            self.send_logs();
+            let argv = match self.commands.recv() {
+                Ok(argv) => argv,
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Lua debugging session aborted, the client is disconnected".to_owned(),
+                    ))
+                }
+            };
+            let args: Vec<String> = argv
+                .iter()
+                .map(|arg| String::from_utf8_lossy(arg).to_string())
+                .collect();
+            let name = args.first().map(|a| a.to_lowercase()).unwrap_or_default();
+
+            let result = match (name.as_str(), args.len()) {
+                ("h" | "help", 1) => {
+                    HELP.iter().for_each(|line| self.log(*line));
+                    Ok(())
+                }
+                ("s" | "step" | "n" | "next", 1) => {
+                    self.step = true;
+                    return Ok(());
+                }
+                ("c" | "continue", 1) => return Ok(()),
+                ("a" | "abort", 1) => {
+                    return Err(LuaError::RuntimeError(
+                        "script aborted for user request".to_owned(),
+                    ))
+                }
+                ("l" | "list", 1..=3) => {
+                    let around = args
+                        .get(1)
+                        .and_then(|a| a.parse().ok())
+                        .filter(|line| *line != 0)
+                        .unwrap_or(self.current_line);
+                    let context = args.get(2).and_then(|a| a.parse().ok()).unwrap_or(5);
+                    self.list(around, context);
+                    Ok(())
+                }
+                ("w" | "whole", 1) => {
+                    self.list(1, 1000000);
+                    Ok(())
+                }
+                ("b" | "break", _) => {
+                    self.breakpoint(&args[1..]);
+                    Ok(())
+                }
+                ("m" | "maxlen", 1 | 2) => {
+                    self.maxlen(args.get(1));
+                    Ok(())
+                }
+                ("r" | "redis", n) if n > 1 => {
+                    self.redis(&argv[1..]);
+                    Ok(())
+                }
+                ("p" | "print", 1) => self.print_all(lua),
+                ("p" | "print", 2) => self.print(lua, &args[1]),
+                ("t" | "trace", 1) => self.trace(lua),
+                ("e" | "eval", n) if n > 1 => self.eval(lua, &args[1..].join(" ")),
+                _ => {
+                    self.log(
+                        "<error> Unknown Redis Lua debugger command or wrong number of arguments.",
+                    );
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                self.log(format!("<error> {}", e));
+            }
+        }
+    }
+
+    fn list(&mut self, around: i32, context: i32) {
+        for line in 1..=self.lines.len() as i32 {
+            if (around - line).abs() <= context {
+                self.log_source_line(line);
+            }
+        }
+    }
+
+    fn breakpoint(&mut self, args: &[String]) {
+        if args.is_empty() {
+            if self.breakpoints.is_empty() {
+                self.log("No breakpoints set. Use 'b <line>' to add one.");
+            } else {
+                self.log(format!("{} breakpoints set:", self.breakpoints.len()));
+                for line in self.breakpoints.clone() {
+                    self.log_source_line(line);
+                }
+            }
+            return;
+        }
+        for arg in args {
+            match arg.parse::<i32>() {
+                Ok(0) => {
+                    self.breakpoints.clear();
+                    self.log("All breakpoints removed.");
+                }
+                Ok(line) if line > 0 => {
+                    if line as usize <= self.lines.len() {
+                        self.breakpoints.insert(line);
+                        self.list(line, 1);
+                    } else {
+                        self.log("<error> Wrong line number.");
+                    }
+                }
+                Ok(line) => {
+                    if self.breakpoints.remove(&-line) {
+                        self.log("Breakpoint removed.");
+                    } else {
+                        self.log("<error> No breakpoint in the specified line.");
+                    }
+                }
+                Err(_) => self.log(format!("<error> Invalid argument:'{}'", arg)),
+            }
+        }
+    }
+
+    fn maxlen(&mut self, arg: Option<&String>) {
+        if let Some(arg) = arg {
+            let maxlen = arg.parse::<usize>().unwrap_or(0);
+            self.maxlen_hint_sent = true;
+            // replies are not trimmed to less than 60 bytes
+            self.maxlen = if maxlen != 0 && maxlen <= 60 {
+                60
+            } else {
+                maxlen
+            };
+        }
+        if self.maxlen > 0 {
+            let entry = format!("<value> replies are truncated at {} bytes.", self.maxlen);
+            self.log(entry);
+        } else {
+            self.log("<value> replies are unlimited.");
+        }
+    }
+
+    // the command runs in the txn of the script, the hook can not yield so it is run to
+    // the end on this thread
+    fn redis(&mut self, argv: &[Bytes]) {
+        self.log_command(argv);
+        let cmd_name = String::from_utf8_lossy(&argv[0]).to_string();
+        let resp = match Command::from_argv(&cmd_name, &argv[1..].to_vec()) {
+            Ok(Command::Unknown(c)) => c.response(),
+            Ok(cmd) => async_std::task::block_on(Multi::exec_cmd(cmd, Some(self.txn.clone())))
+                .unwrap_or_else(resp_err),
+            Err(e) => resp_err(RTError::Owned(format!("ERR {}", e))),
+        };
+        self.log_reply(&resp);
+    }
+
+    fn print_all(&mut self, lua: &Lua) -> LuaResult<()> {
+        let locals = frame_locals(lua, FRAME_LEVEL)?.unwrap_or_default();
+        if locals.is_empty() {
+            self.log("No local variables in the current context.");
+        }
+        for (_, name, value) in locals {
+            self.log_with_maxlen(format!("<value> {} = {}", name, repr_value(&value, 0)));
+        }
+        Ok(())
+    }
+
+    fn print(&mut self, lua: &Lua, name: &str) -> LuaResult<()> {
+        // search the paused frame first, then the frames calling it
+        let mut level = FRAME_LEVEL;
+        while let Some(locals) = frame_locals(lua, level)? {
+            if let Some((_, _, value)) = locals.iter().rev().find(|(_, n, _)| n == name) {
+                self.log_with_maxlen(format!("<value> {}", repr_value(value, 0)));
+                return Ok(());
+            }
+            level += 1;
+        }
+        if name == "KEYS" || name == "ARGV" {
+            let value: LuaValue = lua.globals().raw_get(name)?;
+            self.log_with_maxlen(format!("<value> {}", repr_value(&value, 0)));
+        } else {
+            self.log("No such variable.");
+        }
+        Ok(())
+    }
+
+    fn trace(&mut self, lua: &Lua) -> LuaResult<()> {
+        let getinfo: LuaFunction = debug_lib(lua)?.get("getinfo")?;
+        let mut level = FRAME_LEVEL;
+        let mut frames = 0;
+        while let Some(info) = getinfo.call::<_, Option<LuaTable>>((level, "Snl"))? {
+            level += 1;
+            if info.get::<_, String>("what")? == "C" {
+                continue;
+            }
+            let name: Option<String> = info.get("name")?;
+            self.log(format!(
+                "{} {}:",
+                if frames == 0 { "In" } else { "From" },
+                name.as_deref().unwrap_or("top level")
+            ));
+            self.log_source_line(info.get("currentline")?);
+            frames += 1;
+        }
+        if frames == 0 {
+            self.log("<error> Can't retrieve Lua stack.");
+        }
+        Ok(())
+    }
+
+    // the code sees the locals of the paused frame, and the values assigned to them are
+    // written back to the frame
+    fn eval(&mut self, lua: &Lua, code: &str) -> LuaResult<()> {
+        let locals = frame_locals(lua, FRAME_LEVEL)?.unwrap_or_default();
+        let env = lua.create_table()?;
+        for (_, name, value) in &locals {
+            env.raw_set(name.as_str(), value.clone())?;
+        }
+        let meta = lua.create_table()?;
+        meta.raw_set("__index", lua.globals())?;
+        env.set_metatable(Some(meta));
+
+        // the code is tried as an expression first
+        let expr = format!("return {}", code);
+        let func = match lua
+            .load(&expr)
+            .set_environment(env.clone())?
+            .into_function()
+        {
+            Ok(func) => func,
+            Err(_) => lua
+                .load(code)
+                .set_environment(env.clone())?
+                .into_function()?,
+        };
+        let value: LuaValue = func.call(())?;
+        self.log_with_maxlen(format!("<retval> {}", repr_value(&value, 0)));
+
+        // shadowed locals are left as they are
+        let setlocal: LuaFunction = debug_lib(lua)?.get("setlocal")?;
+        let mut written = HashSet::new();
+        for (idx, name, _) in locals.iter().rev() {
+            if written.insert(name) {
+                let value: LuaValue = env.raw_get(name.as_str())?;
+                setlocal.call::<_, ()>((FRAME_LEVEL, *idx, value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn line_hook(lua: &Lua, debug: LuaDebug) -> LuaResult<()> {
+    let line = debug.curr_line();
+    SESSION.with(|session| match session.borrow_mut().as_mut() {
+        Some(session) => session.on_line(lua, line),
+        None => Ok(()),
+    })
+}
+
+async fn debug_eval(
+    script: &str,
+    keys: &[String],
+    args: &[Bytes],
+    mode: LdbMode,
+    commands: Receiver<Vec<Bytes>>,
+    events: UnboundedSender<LdbEvent>,
+) -> AsyncResult<Frame> {
+    let lua = Some(new_debug_lua()?);
+    let client = get_txn_client()?;
+    let txn = client.begin().await?;
+    let txn_rc = Arc::new(Mutex::new(txn));
+
+    let session = Session {
+        lines: script.lines().map(String::from).collect(),
+        breakpoints: BTreeSet::new(),
+        // stop at the first line
+        step: true,
+        luabp: false,
+        current_line: 0,
+        maxlen: DEFAULT_MAXLEN,
+        maxlen_hint_sent: false,
+        logs: vec![],
+        commands,
+        events,
+        txn: txn_rc.clone(),
+    };
+    SESSION.with(|s| *s.borrow_mut() = Some(session));
+    if let Some(lua) = &lua {
+        lua.set_hook(HookTriggers::every_line(), line_hook)?;
+    }
+
+    // keyspace events are published only if the txn commits
+    let events = PendingEvents::default();
+    let resp = events
+        .defer(
+            LuaCommandCtx::new(Some(txn_rc.clone()), &lua).do_async_eval_inner(script, keys, args),
+        )
+        .await;
+
+    if let Some(mut session) = SESSION.with(|s| s.borrow_mut().take()) {
+        session.log("<endsession>");
+        session.send_logs();
+    }
+
+    // changes are kept in sync mode only, like the forked sessions of redis
+    let mut txn = txn_rc.lock().await;
+    match resp {
+        Ok(resp) if mode == LdbMode::Sync => {
+            txn.commit().await?;
+            events.publish();
+            Ok(resp)
+        }
+        Ok(resp) => {
+            txn.rollback().await?;
+            Ok(resp)
+        }
+        Err(e) => {
+            txn.rollback().await?;
+            Ok(resp_err(e.into()))
+        }
+    }
+}
+
+fn argv_from_frame(frame: Frame) -> Vec<Bytes> {
+    let mut argv = vec![];
+    if let Ok(mut parse) = Parse::new(frame) {
+        while let Ok(arg) = parse.next_bytes() {
+            argv.push(arg);
+        }
+    }
+    argv
+}
+
+/// Run the script of EVAL in a debugging session, the commands read from the connection
+/// are served by the debugger until the script finishes, then the reply of EVAL is sent
+pub async fn run_session(
+    dst: &mut Connection,
+    script: &str,
+    keys: &[String],
+    args: &[Bytes],
+    mode: LdbMode,
+) -> crate::Result<()> {
+    let (commands_tx, commands_rx) = channel();
+    let (events_tx, mut events_rx) = unbounded_channel();
+    let runtime = tokio::runtime::Handle::current();
+    let (script, keys, args) = (script.to_owned(), keys.to_vec(), args.to_vec());
+
+    info!(
+        LOGGER,
+        "Lua debugging session started, {} -> {}, {:?}",
+        dst.peer_addr(),
+        dst.local_addr(),
+        mode
+    );
+    // the hook blocks while the script is paused, so the script can not run on the
+    // worker of the connection
+    std::thread::Builder::new()
+        .name("tidis-ldb".to_owned())
+        .spawn(move || {
+            let _guard = runtime.enter();
+            let reply = async_std::task::block_on(debug_eval(
+                &script,
+                &keys,
+                &args,
+                mode,
+                commands_rx,
+                events_tx.clone(),
+            ))
+            .unwrap_or_else(resp_err);
+            let _ = events_tx.send(LdbEvent::Reply(reply));
+        })?;
+
+    let mut commands_tx = Some(commands_tx);
+    loop {
+        tokio::select! {
+            event = events_rx.recv() => match event {
+                Some(LdbEvent::Logs(logs)) => {
+                    let logs = logs
+                        .into_iter()
+                        .map(|log| Frame::Simple(log.replace(|c| c == '\r' || c == '\n', " ")))
+                        .collect();
+                    dst.write_frame(&Frame::Array(logs)).await?;
+                }
+                Some(LdbEvent::Reply(reply)) => {
+                    debug!(
+                        LOGGER,
+                        "res, {} -> {}, {:?}",
+                        dst.local_addr(),
+                        dst.peer_addr(),
+                        reply
+                    );
+                    dst.write_frame(&reply).await?;
+                    info!(LOGGER, "Lua debugging session ended, {}", dst.peer_addr());
+                    return Ok(());
+                }
+                None => return Ok(()),
+            },
+            frame = dst.read_frame(), if commands_tx.is_some() => match frame? {
+                Some(frame) => {
+                    let _ = commands_tx.as_ref().unwrap().send(argv_from_frame(frame));
+                }
+                // the paused script is aborted once the client is gone
+                None => commands_tx = None,
+            },
+        }
+    }
+}
@@ -9,7 +9,9 @@ use super::{
 use crate::async_del_list_threshold_or_default;
 use crate::cmd_linsert_length_limit_or_default;
 use crate::cmd_lrem_length_limit_or_default;
+use crate::gc::submit_expired_key;
 use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED, NOTIFY_LIST};
 use crate::utils::{resp_array, resp_bulk, resp_err, resp_int, resp_nil, resp_ok};
 use crate::{utils::key_is_expired, Frame};
 use bytes::Bytes;
@@ -22,7 +24,7 @@ use std::sync::Arc;
 use tikv_client::{BoundRange, Key, Transaction};
 use tokio::sync::Mutex;
 
-const INIT_INDEX: u64 = 1 << 32;
+pub const INIT_INDEX: u64 = 1 << 32;
 
 #[derive(Clone)]
 pub struct ListCommandCtx {
@@ -48,6 +50,7 @@ impl<'a> ListCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -134,7 +137,11 @@ impl<'a> ListCommandCtx {
             .await;
 
         match resp {
-            Ok(n) => Ok(resp_int(n as i64)),
+            Ok(n) => {
+                let event = if op_left { "lpush" } else { "rpush" };
+                notify_keyspace_event(NOTIFY_LIST, event, &key);
+                Ok(resp_int(n as i64))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -152,6 +159,7 @@ impl<'a> ListCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -249,6 +257,10 @@ impl<'a> ListCommandCtx {
 
         match resp {
             Ok(values) => {
+                if !values.is_empty() {
+                    let event = if op_left { "lpop" } else { "rpop" };
+                    notify_keyspace_event(NOTIFY_LIST, event, &key);
+                }
                 if values.is_empty() {
                     Ok(resp_nil())
                 } else if values.len() == 1 {
@@ -274,6 +286,7 @@ impl<'a> ListCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -366,7 +379,10 @@ impl<'a> ListCommandCtx {
             .await;
 
         match resp {
-            Ok(_) => Ok(resp_ok()),
+            Ok(_) => {
+                notify_keyspace_event(NOTIFY_LIST, "ltrim", &key);
+                Ok(resp_ok())
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -382,7 +398,7 @@ impl<'a> ListCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -398,10 +414,7 @@ impl<'a> ListCommandCtx {
                             let (ttl, version, left, right) =
                                 KeyDecoder::decode_key_list_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_list_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_array(vec![]));
                             }
 
@@ -453,7 +466,7 @@ impl<'a> ListCommandCtx {
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -469,10 +482,7 @@ impl<'a> ListCommandCtx {
                             let (ttl, _, left, right) =
                                 KeyDecoder::decode_key_list_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_list_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_int(0));
                             }
 
@@ -493,7 +503,7 @@ impl<'a> ListCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -509,10 +519,7 @@ impl<'a> ListCommandCtx {
                             let (ttl, version, left, right) =
                                 KeyDecoder::decode_key_list_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_list_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_nil());
                             }
 
@@ -557,6 +564,7 @@ impl<'a> ListCommandCtx {
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -605,7 +613,10 @@ impl<'a> ListCommandCtx {
             .await;
 
         match resp {
-            Ok(_) => Ok(resp_ok()),
+            Ok(_) => {
+                notify_keyspace_event(NOTIFY_LIST, "lset", &key);
+                Ok(resp_ok())
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -625,6 +636,7 @@ impl<'a> ListCommandCtx {
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -764,7 +776,12 @@ impl<'a> ListCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_int(v)),
+            Ok(v) => {
+                if v > 0 {
+                    notify_keyspace_event(NOTIFY_LIST, "linsert", &key);
+                }
+                Ok(resp_int(v))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -784,6 +801,7 @@ impl<'a> ListCommandCtx {
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -950,7 +968,12 @@ impl<'a> ListCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_int(v)),
+            Ok(v) => {
+                if v > 0 {
+                    notify_keyspace_event(NOTIFY_LIST, "lrem", &key);
+                }
+                Ok(resp_int(v))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -1058,6 +1081,7 @@ impl<'a> ListCommandCtx {
                             REMOVED_EXPIRED_KEY_COUNTER
                                 .with_label_values(&["list"])
                                 .inc();
+                            notify_keyspace_event(NOTIFY_EXPIRED, "expired", &key);
                             Ok(1)
                         }
                         None => Ok(0),
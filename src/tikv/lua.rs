@@ -1,19 +1,192 @@
 use std::sync::Arc;
 
-use super::errors::AsyncResult;
+use super::errors::{AsyncResult, RTError};
+use crate::cmd::{script_set_written, Multi};
 use crate::db::Db;
-use crate::utils::{lua_resp_to_redis_resp, redis_resp_to_lua_resp, resp_err, sha1hex};
+use crate::frame::RESP_VERSION;
+use crate::tikv::function::{FunctionLibrary, FunctionMeta};
+use crate::tikv::ldb;
+use crate::tikv::lualib::{client_id, is_resp3, new_lua, set_redis_helpers};
+use crate::tracking::track_command;
+use crate::utils::{
+    format_lua_number, lua_resp_to_redis_resp, redis_resp_to_lua_resp, resp_err, sha1hex,
+};
 use crate::{utils::resp_invalid_arguments, Command, Frame};
 use bytes::Bytes;
+use std::cell::Cell;
 use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 use crate::config::LOGGER;
 use slog::{debug, error};
 
-use crate::tikv::errors::{REDIS_LUA_CONTEXT_IS_NOT_INITIALIZED_ERR, REDIS_NO_MATCHING_SCRIPT_ERR};
+use crate::tikv::errors::{
+    REDIS_FUNCTION_LIBRARY_NAME_ERR, REDIS_FUNCTION_MISSING_METADATA_ERR,
+    REDIS_FUNCTION_NOT_FOUND_ERR, REDIS_FUNCTION_NO_FUNCTIONS_ERR, REDIS_LUA_ARGUMENT_TYPE_ERR,
+    REDIS_LUA_CONTEXT_IS_NOT_INITIALIZED_ERR, REDIS_LUA_NO_ARGUMENT_ERR,
+    REDIS_NO_MATCHING_SCRIPT_ERR, REDIS_WRITE_IN_READ_ONLY_SCRIPT_ERR,
+};
+use crate::tikv::script::ScriptCommandCtx;
 use mlua::{prelude::*, Lua, Value as LuaValue, Variadic};
 
+// registry table of the functions registered by the library being loaded
+const FUNCTION_REGISTRY: &str = "__tidis_functions";
+const FUNCTION_FLAGS: [&str; 5] = [
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+fn is_valid_function_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Split the `#!lua name=<library>` shebang from the library code, the shebang line
+/// is left empty so that line numbers in errors are kept
+fn parse_shebang(code: &str) -> AsyncResult<(String, &str)> {
+    let (shebang, body) = match code.strip_prefix("#!") {
+        Some(rest) => rest.split_at(rest.find('\n').unwrap_or(rest.len())),
+        None => return Err(REDIS_FUNCTION_MISSING_METADATA_ERR),
+    };
+
+    let mut parts = shebang.split_whitespace();
+    let engine = parts.next().unwrap_or("");
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(RTError::Owned(format!("ERR Engine '{}' not found", engine)));
+    }
+    let mut name = None;
+    for part in parts {
+        match part.strip_prefix("name=") {
+            Some(n) => name = Some(n.to_owned()),
+            None => {
+                return Err(RTError::Owned(format!(
+                    "ERR Invalid metadata value given: {}",
+                    part
+                )))
+            }
+        }
+    }
+    match name {
+        Some(name) if is_valid_function_name(&name) => Ok((name, body)),
+        Some(_) => Err(REDIS_FUNCTION_LIBRARY_NAME_ERR),
+        None => Err(REDIS_FUNCTION_MISSING_METADATA_ERR),
+    }
+}
+
+// redis.register_function(name, callback)
+// redis.register_function{function_name=name, callback=callback, flags={...}, description=...}
+fn create_register_function(lua: &Lua) -> LuaResult<LuaFunction> {
+    lua.create_function(|lua, args: Variadic<LuaValue>| {
+        let (name, callback, flags, description) = match args.as_slice() {
+            [LuaValue::String(name), LuaValue::Function(callback)] => {
+                (name.to_str()?.to_owned(), callback.clone(), vec![], None)
+            }
+            [LuaValue::Table(t)] => (
+                t.get::<_, String>("function_name")?,
+                t.get::<_, LuaFunction>("callback")?,
+                t.get::<_, Option<Vec<String>>>("flags")?.unwrap_or_default(),
+                t.get::<_, Option<String>>("description")?,
+            ),
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "wrong arguments given to redis.register_function".to_owned(),
+                ))
+            }
+        };
+        if !is_valid_function_name(&name) {
+            return Err(LuaError::RuntimeError(
+                "Function names can only contain letters, numbers, or underscores(_) and must be at least one character long".to_owned(),
+            ));
+        }
+        if let Some(flag) = flags.iter().find(|f| !FUNCTION_FLAGS.contains(&f.as_str())) {
+            return Err(LuaError::RuntimeError(format!("unknown flag given: {}", flag)));
+        }
+
+        let registry: LuaTable = lua.named_registry_value(FUNCTION_REGISTRY)?;
+        if !matches!(registry.raw_get::<_, LuaValue>(name.clone())?, LuaValue::Nil) {
+            return Err(LuaError::RuntimeError(format!(
+                "Function {} already exists",
+                name
+            )));
+        }
+        let func = lua.create_table()?;
+        func.raw_set("callback", callback)?;
+        func.raw_set("flags", flags)?;
+        if let Some(description) = description {
+            func.raw_set("description", description)?;
+        }
+        registry.raw_set(name, func)
+    })
+}
+
+// run the library body, returns the registry of the functions it registers
+fn load_library<'lua>(lua: &'lua Lua, body: &str) -> LuaResult<LuaTable<'lua>> {
+    lua.set_named_registry_value(FUNCTION_REGISTRY, lua.create_table()?)?;
+    lua.load(body).exec()?;
+    lua.named_registry_value(FUNCTION_REGISTRY)
+}
+
+/// Parse the library code of FUNCTION LOAD, the code is run in a new lua state where
+/// `redis.register_function` is available but redis.call is not
+pub fn parse_library(code: &Bytes) -> AsyncResult<FunctionLibrary> {
+    let code_str = String::from_utf8_lossy(code);
+    let (name, body) = parse_shebang(&code_str)?;
+
+    let functions = (|| -> LuaResult<Vec<FunctionMeta>> {
+        let lua = new_lua()?;
+        let redis = lua.create_table()?;
+        set_redis_helpers(&lua, &redis)?;
+        redis.set("register_function", create_register_function(&lua)?)?;
+        lua.globals().raw_set("redis", redis)?;
+
+        let mut functions = vec![];
+        for pair in load_library(&lua, body)?.pairs::<String, LuaTable>() {
+            let (name, func) = pair?;
+            functions.push(FunctionMeta {
+                name,
+                description: func.get("description")?,
+                flags: func.get("flags")?,
+            });
+        }
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(functions)
+    })()
+    .map_err(|e| RTError::Owned(format!("ERR Error registering functions: {}", e)))?;
+
+    if functions.is_empty() {
+        return Err(REDIS_FUNCTION_NO_FUNCTIONS_ERR);
+    }
+    Ok(FunctionLibrary {
+        name,
+        code: code.clone(),
+        functions,
+    })
+}
+
+// build the command called by redis.call, numbers are formatted like lua does, and
+// the other types are rejected as redis does
+fn command_from_lua_args(args: &[LuaValue]) -> AsyncResult<Command> {
+    let mut argv = Vec::with_capacity(args.len());
+    for arg in args {
+        let bytes = match arg {
+            LuaValue::String(s) => Bytes::copy_from_slice(s.as_bytes()),
+            LuaValue::Integer(i) => Bytes::from(i.to_string()),
+            LuaValue::Number(n) => Bytes::from(format_lua_number(*n)),
+            _ => return Err(REDIS_LUA_ARGUMENT_TYPE_ERR),
+        };
+        argv.push(bytes);
+    }
+    if argv.is_empty() {
+        return Err(REDIS_LUA_NO_ARGUMENT_ERR);
+    }
+    ldb::trace_command(&argv);
+
+    let cmd_name = String::from_utf8_lossy(&argv.remove(0)).to_string();
+    Command::from_argv(&cmd_name, &argv).map_err(|e| RTError::Owned(format!("ERR {}", e)))
+}
+
 #[derive(Clone)]
 pub struct LuaCommandCtx<'a> {
     txn: Option<Arc<Mutex<Transaction>>>,
@@ -25,6 +198,91 @@ impl<'a> LuaCommandCtx<'a> {
         LuaCommandCtx { txn, lua }
     }
 
+    // redis.call() raises the error reply as a lua error, which aborts the script,
+    // redis.pcall() returns the error reply as a table like {err = "..."}
+    fn create_redis_call(&self, lua: &Lua, raise: bool, read_only: bool) -> LuaResult<LuaFunction> {
+        let txn_rc = self.txn.clone();
+        lua.create_async_function(move |lua, args: Variadic<LuaValue>| {
+            let txn_rc = txn_rc.clone();
+            async move {
+                let result = match command_from_lua_args(&args) {
+                    Ok(cmd) if read_only && cmd.is_write() => {
+                        Err(REDIS_WRITE_IN_READ_ONLY_SCRIPT_ERR)
+                    }
+                    Ok(Command::Unknown(_)) => Ok(resp_invalid_arguments()),
+                    Ok(cmd) => {
+                        debug!(LOGGER, "command call from lua {:?}", cmd);
+                        if let Some(id) = client_id(lua) {
+                            // scripts which called write commands can not be killed
+                            if cmd.is_write() {
+                                script_set_written(id);
+                            }
+                            // keys read by scripts are tracked as well
+                            track_command(id, &cmd, true);
+                        }
+                        // commands reply to the script in the RESP version it selected
+                        let version = if is_resp3(lua) { 3 } else { 2 };
+                        RESP_VERSION
+                            .scope(Cell::new(version), Multi::exec_cmd(cmd, txn_rc))
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+                let resp = result.unwrap_or_else(|e| {
+                    error!(LOGGER, "response call from lua failed {}", e);
+                    resp_err(e)
+                });
+                debug!(LOGGER, "response call from lua {:?}", resp);
+                ldb::trace_reply(&resp);
+
+                match resp {
+                    Frame::ErrorOwned(e) if raise => Err(LuaError::RuntimeError(e)),
+                    Frame::ErrorString(e) if raise => Err(LuaError::RuntimeError(e.to_owned())),
+                    resp => redis_resp_to_lua_resp(resp, lua, is_resp3(lua)),
+                }
+            }
+        })
+    }
+
+    /// Register the `redis` table, write commands are rejected by redis.call if `read_only`
+    fn set_redis_globals(&self, lua: &Lua, read_only: bool) -> LuaResult<()> {
+        // Regist redis.call etc to handle redis.* command call in lua
+        // create redis.* commands table
+        let redis = lua.create_table()?;
+
+        redis.set("call", self.create_redis_call(lua, true, read_only)?)?;
+        redis.set("pcall", self.create_redis_call(lua, false, read_only)?)?;
+
+        // redis.sha1hex()
+        let redis_sha1hex = lua.create_function(|lua, str: String| {
+            let hex = sha1hex(&str);
+            Ok(LuaValue::String(lua.create_string(&hex)?))
+        })?;
+        redis.set("sha1hex", redis_sha1hex)?;
+
+        // redis.error_reply()
+        let redis_error_reply = lua.create_function(|lua, err_str: String| {
+            let table = lua.create_table()?;
+            table.raw_set("err", err_str)?;
+            Ok(LuaValue::Table(table))
+        })?;
+        redis.set("error_reply", redis_error_reply)?;
+
+        // redis.status_reply()
+        let redis_status_reply = lua.create_function(|lua, status_str: String| {
+            let table = lua.create_table()?;
+            table.raw_set("ok", status_str)?;
+            Ok(LuaValue::Table(table))
+        })?;
+        redis.set("status_reply", redis_status_reply)?;
+
+        // redis.log, redis.setresp etc
+        set_redis_helpers(lua, &redis)?;
+
+        // register to global table, which is protected against writes from scripts
+        lua.globals().raw_set("redis", redis)
+    }
+
     pub async fn do_async_eval_inner(
         self,
         script: &str,
@@ -50,162 +308,10 @@ impl<'a> LuaCommandCtx<'a> {
             args_table.set(idx + 1, str)?;
         }
 
-        globals.set("KEYS", keys_table)?;
-        globals.set("ARGV", args_table)?;
-
-        // Regist redis.call etc to handle redis.* command call in lua
-        // create redis.* commands table
-        let redis = lua.create_table()?;
-        let txn_rc = self.txn;
-
-        // redis.call()
-        // redis.pcall()
-        let redis_call = lua.create_async_function(move |_lua, args: Variadic<LuaValue>| {
-            let txn_rc = txn_rc.clone();
-            // package arguments(without cmd) to argv
-            async move {
-                if args.len() == 0 {
-                    let table = _lua.create_table().unwrap();
-                    table.raw_set("err", "Invalid arguments, please specify at least one argument for redis.call()").unwrap();
-                    return Ok(LuaValue::Table(table));
-                }
-                let cmd_name: String = if let LuaValue::String(cmd) = args[0].clone() {
-                    cmd.to_str().unwrap().to_owned()
-                } else {
-                    "".to_owned()
-                };
-
-                let mut argv = vec![];
-                for arg in &args.as_slice()[1..] {
-                    if let LuaValue::Integer(i) = arg {
-                        argv.push(Bytes::from(i.to_string().as_bytes().to_vec()));
-                    } else if let LuaValue::String(s) = arg{
-                        argv.push(Bytes::from(s.as_bytes().to_vec()));
-                    }
-                }
-
-                let cmd = Command::from_argv(&cmd_name, &argv).unwrap();
-                let txn_rc1 = txn_rc.clone().unwrap();
-                let txn = txn_rc1.lock().await;
-                let txn_ts = txn.start_timestamp();
-                drop(txn);
-                debug!(LOGGER, "command call from lua {:?}, txn {:?}", cmd, txn_ts);
-                let result = match cmd {
-                    Command::Incr(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
-                    Command::IncrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
-                    Command::Decr(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
-                    Command::DecrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
-                    Command::Strlen(cmd) => cmd.strlen(txn_rc.clone()).await,
-                    Command::Del(cmd) => cmd.del(txn_rc.clone()).await,
-                    Command::Exists(cmd) => cmd.exists(txn_rc.clone()).await,
-                    Command::Get(cmd) => cmd.get(txn_rc.clone()).await,
-                    Command::Set(cmd) => cmd.set(txn_rc.clone()).await,
-                    Command::SetNX(cmd) => cmd.put_not_exists(txn_rc.clone()).await,
-                    Command::SetEX(cmd) => cmd.setex(txn_rc.clone()).await,
-                    Command::Mget(cmd) => cmd.batch_get(txn_rc.clone()).await,
-                    Command::Mset(cmd) => cmd.batch_put(txn_rc.clone()).await,
-                    Command::Type(cmd) => cmd.cmd_type(txn_rc.clone()).await,
-                    Command::TTL(cmd) => cmd.ttl(false, txn_rc.clone()).await,
-                    Command::PTTL(cmd) => cmd.ttl(true, txn_rc.clone()).await,
-                    Command::Expire(cmd) => cmd.expire(false, false, txn_rc.clone()).await,
-                    Command::ExpireAt(cmd) => cmd.expire(false, true, txn_rc.clone()).await,
-                    Command::Pexpire(cmd) => cmd.expire(true, false, txn_rc.clone()).await,
-                    Command::PexpireAt(cmd) => cmd.expire(true, true, txn_rc.clone()).await,
-                    Command::Persist(cmd) => cmd.persist(txn_rc.clone()).await,
-                    Command::Hset(cmd) => cmd.hset(txn_rc.clone(), false, false).await,
-                    Command::Hmset(cmd) => cmd.hset(txn_rc.clone(), true, false).await,
-                    Command::Hsetnx(cmd) => cmd.hset(txn_rc.clone(), false, true).await,
-                    Command::Hget(cmd) => cmd.hget(txn_rc.clone()).await,
-                    Command::Hmget(cmd) => cmd.hmget(txn_rc.clone()).await,
-                    Command::Hlen(cmd) => cmd.hlen(txn_rc.clone()).await,
-                    Command::Hgetall(cmd) => cmd.hgetall(txn_rc.clone()).await,
-                    Command::Hdel(cmd) => cmd.hdel(txn_rc.clone()).await,
-                    Command::Hkeys(cmd) => cmd.hkeys(txn_rc.clone()).await,
-                    Command::Hvals(cmd) => cmd.hvals(txn_rc.clone()).await,
-                    Command::Hincrby(cmd) => cmd.hincrby(txn_rc.clone()).await,
-                    Command::Hexists(cmd) => cmd.hexists(txn_rc.clone()).await,
-                    Command::Hstrlen(cmd) => cmd.hstrlen(txn_rc.clone()).await,
-                    Command::Lpush(cmd) => cmd.push(txn_rc.clone(), true).await,
-                    Command::Rpush(cmd) => cmd.push(txn_rc.clone(), false).await,
-                    Command::Lpop(cmd) => cmd.pop(txn_rc.clone(), true).await,
-                    Command::Rpop(cmd) => cmd.pop(txn_rc.clone(), false).await,
-                    Command::Lrange(cmd) => cmd.lrange(txn_rc.clone()).await,
-                    Command::Llen(cmd) => cmd.llen(txn_rc.clone()).await,
-                    Command::Lindex(cmd) => cmd.lindex(txn_rc.clone()).await,
-                    Command::Lset(cmd) => cmd.lset(txn_rc.clone()).await,
-                    Command::Ltrim(cmd) => cmd.ltrim(txn_rc.clone()).await,
-                    Command::Lrem(cmd) => cmd.lrem(txn_rc.clone()).await,
-                    Command::Linsert(cmd) => cmd.linsert(txn_rc.clone()).await,
-                    Command::Sadd(cmd) => cmd.sadd(txn_rc.clone()).await,
-                    Command::Scard(cmd) => cmd.scard(txn_rc.clone()).await,
-                    Command::Sismember(cmd) => cmd.sismember(txn_rc.clone()).await,
-                    Command::Smismember(cmd) => cmd.smismember(txn_rc.clone()).await,
-                    Command::Smembers(cmd) => cmd.smembers(txn_rc.clone()).await,
-                    Command::Srandmember(cmd) => cmd.srandmember(txn_rc.clone()).await,
-                    Command::Spop(cmd) => cmd.spop(txn_rc.clone()).await,
-                    Command::Srem(cmd) => cmd.srem(txn_rc.clone()).await,
-                    Command::Zadd(cmd) => cmd.zadd(txn_rc.clone()).await,
-                    Command::Zcard(cmd) => cmd.zcard(txn_rc.clone()).await,
-                    Command::Zscore(cmd) => cmd.zscore(txn_rc.clone()).await,
-                    Command::Zrem(cmd) => cmd.zrem(txn_rc.clone()).await,
-                    Command::Zremrangebyscore(cmd) => cmd.zremrangebyscore(txn_rc.clone()).await,
-                    Command::Zremrangebyrank(cmd) => cmd.zremrangebyrank(txn_rc.clone()).await,
-                    Command::Zrange(cmd) => cmd.zrange(txn_rc.clone()).await,
-                    Command::Zrevrange(cmd) => cmd.zrevrange(txn_rc.clone()).await,
-                    Command::Zrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), false).await,
-                    Command::Zrevrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), true).await,
-                    Command::Zcount(cmd) => cmd.zcount(txn_rc.clone()).await,
-                    Command::Zpopmin(cmd) => cmd.zpop(txn_rc.clone(), true).await,
-                    Command::Zpopmax(cmd) => cmd.zpop(txn_rc.clone(), false).await,
-                    Command::Zrank(cmd) => cmd.zrank(txn_rc.clone()).await,
-                    Command::Zincryby(cmd) => cmd.zincrby(txn_rc.clone()).await,
-                    Command::Scan(cmd) => cmd.scan(txn_rc.clone()).await,
-                    Command::Xscan(cmd) => cmd.scan(txn_rc.clone()).await,
-                    _ => Ok(resp_invalid_arguments()),
-                };
-                match result {
-                    Ok(resp) => {
-                        debug!(LOGGER, "response call from lua {:?}", resp);
-                        let lua_resp = redis_resp_to_lua_resp(resp, _lua);
-                        Ok(lua_resp)
-                    }
-                    Err(e) => {
-                        error!(LOGGER, "response call from lua failed {}", e);
-                        let table = _lua.create_table().unwrap();
-                        table.raw_set("err", e.to_string()).unwrap();
-                        Ok(LuaValue::Table(table))
-                    }
-                }
-            }
-        })?;
-        redis.set("call", redis_call.clone())?;
-        redis.set("pcall", redis_call)?;
+        globals.raw_set("KEYS", keys_table)?;
+        globals.raw_set("ARGV", args_table)?;
 
-        // redis.sha1hex()
-        let redis_sha1hex = lua.create_function(|_lua, str: String| {
-            let hex = sha1hex(&str);
-            Ok(LuaValue::String(_lua.create_string(&hex).unwrap()))
-        })?;
-        redis.set("sha1hex", redis_sha1hex)?;
-
-        // redis.error_reply()
-        let redis_error_reply = lua.create_function(|_lua, err_str: String| {
-            let table = _lua.create_table().unwrap();
-            table.raw_set("err", err_str).unwrap();
-            Ok(LuaValue::Table(table))
-        })?;
-        redis.set("error_reply", redis_error_reply)?;
-
-        // redis.status_reply()
-        let redis_status_reply = lua.create_function(|_lua, status_str: String| {
-            let table = _lua.create_table().unwrap();
-            table.raw_set("ok", status_str).unwrap();
-            Ok(LuaValue::Table(table))
-        })?;
-        redis.set("status_reply", redis_status_reply)?;
-
-        // register to global table
-        globals.set("redis", redis)?;
+        self.set_redis_globals(lua, false)?;
 
         let chunk = lua.load(script);
         let resp: LuaValue = chunk.eval_async().await?;
@@ -215,12 +321,52 @@ impl<'a> LuaCommandCtx<'a> {
         }
 
         // convert lua value to redis value
-        let redis_resp = lua_resp_to_redis_resp(resp);
+        let redis_resp = lua_resp_to_redis_resp(resp, is_resp3(lua));
 
         // lua clean up
         Ok(redis_resp)
     }
 
+    /// Load the library in the lua state of connection and call the function in it,
+    /// functions get KEYS and ARGV as arguments instead of globals
+    pub async fn do_async_fcall(
+        self,
+        code: &Bytes,
+        name: &str,
+        keys: &[String],
+        args: &[Bytes],
+        read_only: bool,
+    ) -> AsyncResult<Frame> {
+        let lua = match self.lua {
+            Some(lua) => lua,
+            None => return Ok(resp_err(REDIS_LUA_CONTEXT_IS_NOT_INITIALIZED_ERR)),
+        };
+        let code_str = String::from_utf8_lossy(code);
+        let (_, body) = parse_shebang(&code_str)?;
+
+        self.set_redis_globals(lua, read_only)?;
+        let redis: LuaTable = lua.globals().get("redis")?;
+        redis.set("register_function", create_register_function(lua)?)?;
+
+        let func = match load_library(lua, body)?.raw_get::<_, Option<LuaTable>>(name)? {
+            Some(func) => func,
+            None => return Ok(resp_err(REDIS_FUNCTION_NOT_FOUND_ERR)),
+        };
+        let callback: LuaFunction = func.get("callback")?;
+
+        let keys_table = lua.create_sequence_from(keys.iter().cloned())?;
+        let args_table = lua.create_table()?;
+        for (idx, arg) in args.iter().enumerate() {
+            args_table.set(idx + 1, lua.create_string(arg)?)?;
+        }
+
+        let resp: LuaValue = callback.call_async((keys_table, args_table)).await?;
+        if let LuaValue::Error(e) = resp {
+            return Err(e.into());
+        }
+        Ok(lua_resp_to_redis_resp(resp, is_resp3(lua)))
+    }
+
     pub async fn do_async_eval(
         self,
         script: &str,
@@ -238,8 +384,20 @@ impl<'a> LuaCommandCtx<'a> {
         keys: &[String],
         args: &[Bytes],
     ) -> AsyncResult<Frame> {
-        // get script from cache with sha1 key
-        let script = db.get_script(&sha1.to_lowercase());
+        // get script from local cache first, then the scripts persisted in tikv
+        let sha1 = sha1.to_lowercase();
+        let script = match db.get_script(&sha1) {
+            Some(script) => Some(script),
+            None => {
+                let script = ScriptCommandCtx::new(None)
+                    .do_async_txnkv_script_get(&sha1)
+                    .await?;
+                if let Some(ref s) = script {
+                    db.set_script(sha1.clone(), s.clone());
+                }
+                script
+            }
+        };
         match script {
             Some(script) => Ok(self
                 .clone()
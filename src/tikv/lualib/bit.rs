@@ -0,0 +1,78 @@
+use mlua::{prelude::*, Lua, Variadic};
+
+// normalize the number to a 32 bit integer as LuaBitOp does, by adding 2^52 + 2^51
+// to the number and taking the lower 32 bits of the double
+fn tobit(n: f64) -> i32 {
+    (n + 6755399441055744.0).to_bits() as u32 as i32
+}
+
+fn fold(x: f64, rest: Variadic<f64>, op: fn(i32, i32) -> i32) -> i32 {
+    rest.iter().fold(tobit(x), |acc, n| op(acc, tobit(*n)))
+}
+
+pub fn create(lua: &Lua) -> LuaResult<LuaTable> {
+    let bit = lua.create_table()?;
+
+    bit.raw_set("tobit", lua.create_function(|_, x: f64| Ok(tobit(x)))?)?;
+    bit.raw_set("bnot", lua.create_function(|_, x: f64| Ok(!tobit(x)))?)?;
+    bit.raw_set(
+        "band",
+        lua.create_function(|_, (x, rest): (f64, Variadic<f64>)| Ok(fold(x, rest, |a, b| a & b)))?,
+    )?;
+    bit.raw_set(
+        "bor",
+        lua.create_function(|_, (x, rest): (f64, Variadic<f64>)| Ok(fold(x, rest, |a, b| a | b)))?,
+    )?;
+    bit.raw_set(
+        "bxor",
+        lua.create_function(|_, (x, rest): (f64, Variadic<f64>)| Ok(fold(x, rest, |a, b| a ^ b)))?,
+    )?;
+
+    bit.raw_set(
+        "lshift",
+        lua.create_function(|_, (x, n): (f64, f64)| {
+            Ok(((tobit(x) as u32) << (tobit(n) & 31)) as i32)
+        })?,
+    )?;
+    bit.raw_set(
+        "rshift",
+        lua.create_function(|_, (x, n): (f64, f64)| {
+            Ok(((tobit(x) as u32) >> (tobit(n) & 31)) as i32)
+        })?,
+    )?;
+    bit.raw_set(
+        "arshift",
+        lua.create_function(|_, (x, n): (f64, f64)| Ok(tobit(x) >> (tobit(n) & 31)))?,
+    )?;
+    bit.raw_set(
+        "rol",
+        lua.create_function(|_, (x, n): (f64, f64)| {
+            Ok((tobit(x) as u32).rotate_left((tobit(n) & 31) as u32) as i32)
+        })?,
+    )?;
+    bit.raw_set(
+        "ror",
+        lua.create_function(|_, (x, n): (f64, f64)| {
+            Ok((tobit(x) as u32).rotate_right((tobit(n) & 31) as u32) as i32)
+        })?,
+    )?;
+    bit.raw_set(
+        "bswap",
+        lua.create_function(|_, x: f64| Ok(tobit(x).swap_bytes()))?,
+    )?;
+
+    // bit.tohex(x [,n]), n digits of lower case hex, or upper case if n is negative
+    let tohex = lua.create_function(|_, (x, n): (f64, Option<f64>)| {
+        let n = n.map(tobit).unwrap_or(8);
+        let digits = n.unsigned_abs().min(8) as usize;
+        let hex = if n < 0 {
+            format!("{:08X}", tobit(x) as u32)
+        } else {
+            format!("{:08x}", tobit(x) as u32)
+        };
+        Ok(hex[8 - digits..].to_owned())
+    })?;
+    bit.raw_set("tohex", tohex)?;
+
+    Ok(bit)
+}
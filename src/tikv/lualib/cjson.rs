@@ -0,0 +1,420 @@
+use super::script_error;
+use crate::utils::format_number_g;
+use mlua::{prelude::*, LightUserData, Lua, Value as LuaValue};
+
+// same limits as the lua-cjson bundled in redis
+const MAX_DEPTH: usize = 1000;
+const SPARSE_RATIO: i64 = 2;
+const SPARSE_SAFE: i64 = 10;
+const NUMBER_PRECISION: i32 = 14;
+
+/// `cjson.null`, a NULL lightuserdata as lua-cjson uses
+fn null<'lua>() -> LuaValue<'lua> {
+    LuaValue::LightUserData(LightUserData(std::ptr::null_mut()))
+}
+
+pub fn create(lua: &Lua) -> LuaResult<LuaTable> {
+    let cjson = lua.create_table()?;
+
+    let encode = lua.create_function(|lua, value: LuaValue| {
+        let mut buf = vec![];
+        encode_value(&value, 0, &mut buf)?;
+        lua.create_string(&buf)
+    })?;
+    cjson.raw_set("encode", encode)?;
+
+    let decode = lua.create_function(|lua, json: LuaString| {
+        let mut decoder = Decoder {
+            lua,
+            data: json.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        decoder.decode()
+    })?;
+    cjson.raw_set("decode", decode)?;
+
+    cjson.raw_set("null", null())?;
+    Ok(cjson)
+}
+
+fn encode_value(value: &LuaValue, depth: usize, buf: &mut Vec<u8>) -> LuaResult<()> {
+    match value {
+        LuaValue::Nil => buf.extend_from_slice(b"null"),
+        LuaValue::LightUserData(p) if p.0.is_null() => buf.extend_from_slice(b"null"),
+        LuaValue::Boolean(true) => buf.extend_from_slice(b"true"),
+        LuaValue::Boolean(false) => buf.extend_from_slice(b"false"),
+        LuaValue::Integer(i) => buf.extend_from_slice(i.to_string().as_bytes()),
+        LuaValue::Number(n) => encode_number(*n, buf)?,
+        LuaValue::String(s) => encode_string(s.as_bytes(), buf),
+        LuaValue::Table(t) => encode_table(t, depth + 1, buf)?,
+        v => {
+            return Err(script_error(format!(
+                "Cannot serialise {}: type not supported",
+                v.type_name()
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn encode_number(n: f64, buf: &mut Vec<u8>) -> LuaResult<()> {
+    if !n.is_finite() {
+        return Err(script_error(
+            "Cannot serialise number: must not be NaN or Inf",
+        ));
+    }
+    buf.extend_from_slice(format_number_g(n, NUMBER_PRECISION).as_bytes());
+    Ok(())
+}
+
+fn encode_string(s: &[u8], buf: &mut Vec<u8>) {
+    buf.push(b'"');
+    for &c in s {
+        match c {
+            b'"' => buf.extend_from_slice(b"\\\""),
+            b'\\' => buf.extend_from_slice(b"\\\\"),
+            b'/' => buf.extend_from_slice(b"\\/"),
+            b'\x08' => buf.extend_from_slice(b"\\b"),
+            b'\x0c' => buf.extend_from_slice(b"\\f"),
+            b'\n' => buf.extend_from_slice(b"\\n"),
+            b'\r' => buf.extend_from_slice(b"\\r"),
+            b'\t' => buf.extend_from_slice(b"\\t"),
+            c if c < 0x20 || c == 0x7f => buf.extend_from_slice(format!("\\u{:04x}", c).as_bytes()),
+            c => buf.push(c),
+        }
+    }
+    buf.push(b'"');
+}
+
+// returns the length if the table is a non empty array, empty tables are objects
+fn array_length(table: &LuaTable) -> LuaResult<Option<i64>> {
+    let (mut max, mut items) = (0, 0);
+    for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+        let idx = match pair?.0 {
+            LuaValue::Integer(i) if i >= 1 => i,
+            LuaValue::Number(n) if n >= 1.0 && n.floor() == n => n as i64,
+            _ => return Ok(None),
+        };
+        max = max.max(idx);
+        items += 1;
+    }
+    if max > SPARSE_SAFE && max > items * SPARSE_RATIO {
+        return Err(script_error(
+            "Cannot serialise table: excessively sparse array",
+        ));
+    }
+    Ok(if max > 0 { Some(max) } else { None })
+}
+
+fn encode_table(table: &LuaTable, depth: usize, buf: &mut Vec<u8>) -> LuaResult<()> {
+    if depth > MAX_DEPTH {
+        return Err(script_error(format!(
+            "Cannot serialise, excessive nesting ({})",
+            depth
+        )));
+    }
+
+    if let Some(len) = array_length(table)? {
+        buf.push(b'[');
+        for idx in 1..=len {
+            if idx > 1 {
+                buf.push(b',');
+            }
+            encode_value(&table.raw_get::<_, LuaValue>(idx)?, depth, buf)?;
+        }
+        buf.push(b']');
+        return Ok(());
+    }
+
+    buf.push(b'{');
+    for (idx, pair) in table.clone().pairs::<LuaValue, LuaValue>().enumerate() {
+        let (key, value) = pair?;
+        if idx > 0 {
+            buf.push(b',');
+        }
+        match key {
+            LuaValue::String(s) => encode_string(s.as_bytes(), buf),
+            LuaValue::Integer(i) => buf.extend_from_slice(format!("\"{}\"", i).as_bytes()),
+            LuaValue::Number(n) => {
+                buf.push(b'"');
+                encode_number(n, buf)?;
+                buf.push(b'"');
+            }
+            _ => {
+                return Err(script_error(
+                    "Cannot serialise table: table key must be a number or string",
+                ))
+            }
+        }
+        buf.push(b':');
+        encode_value(&value, depth, buf)?;
+    }
+    buf.push(b'}');
+    Ok(())
+}
+
+enum Token {
+    ObjBegin,
+    ObjEnd,
+    ArrBegin,
+    ArrEnd,
+    Str(Vec<u8>),
+    Number(f64),
+    Boolean(bool),
+    Null,
+    Colon,
+    Comma,
+    End,
+    Error(&'static str),
+}
+
+impl Token {
+    fn name(&self) -> &'static str {
+        match self {
+            Token::ObjBegin => "T_OBJ_BEGIN",
+            Token::ObjEnd => "T_OBJ_END",
+            Token::ArrBegin => "T_ARR_BEGIN",
+            Token::ArrEnd => "T_ARR_END",
+            Token::Str(_) => "T_STRING",
+            Token::Number(_) => "T_NUMBER",
+            Token::Boolean(_) => "T_BOOLEAN",
+            Token::Null => "T_NULL",
+            Token::Colon => "T_COLON",
+            Token::Comma => "T_COMMA",
+            Token::End => "T_END",
+            Token::Error(e) => e,
+        }
+    }
+}
+
+struct Decoder<'a, 'lua> {
+    lua: &'lua Lua,
+    data: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a, 'lua> Decoder<'a, 'lua> {
+    fn decode(&mut self) -> LuaResult<LuaValue<'lua>> {
+        let (token, idx) = self.next_token();
+        let value = self.decode_value(token, idx)?;
+        match self.next_token() {
+            (Token::End, _) => Ok(value),
+            (token, idx) => Err(Self::parse_error("the end", &token, idx)),
+        }
+    }
+
+    fn parse_error(expected: &str, found: &Token, idx: usize) -> LuaError {
+        script_error(format!(
+            "Expected {} but found {} at character {}",
+            expected,
+            found.name(),
+            idx + 1
+        ))
+    }
+
+    fn decode_value(&mut self, token: Token, idx: usize) -> LuaResult<LuaValue<'lua>> {
+        match token {
+            Token::Str(s) => Ok(LuaValue::String(self.lua.create_string(&s)?)),
+            Token::Number(n) => Ok(LuaValue::Number(n)),
+            Token::Boolean(b) => Ok(LuaValue::Boolean(b)),
+            Token::Null => Ok(null()),
+            Token::ObjBegin => self.decode_object(idx),
+            Token::ArrBegin => self.decode_array(idx),
+            token => Err(Self::parse_error("value", &token, idx)),
+        }
+    }
+
+    fn enter(&mut self, idx: usize) -> LuaResult<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(script_error(format!(
+                "Found too many nested data structures ({}) at character {}",
+                self.depth,
+                idx + 1
+            )));
+        }
+        Ok(())
+    }
+
+    fn decode_object(&mut self, idx: usize) -> LuaResult<LuaValue<'lua>> {
+        self.enter(idx)?;
+        let table = self.lua.create_table()?;
+        let (mut token, mut idx) = self.next_token();
+        if let Token::ObjEnd = token {
+            self.depth -= 1;
+            return Ok(LuaValue::Table(table));
+        }
+        loop {
+            let key = match token {
+                Token::Str(s) => self.lua.create_string(&s)?,
+                token => return Err(Self::parse_error("object key string", &token, idx)),
+            };
+            match self.next_token() {
+                (Token::Colon, _) => {}
+                (token, idx) => return Err(Self::parse_error("colon", &token, idx)),
+            }
+            let (value_token, value_idx) = self.next_token();
+            table.raw_set(key, self.decode_value(value_token, value_idx)?)?;
+
+            match self.next_token() {
+                (Token::ObjEnd, _) => break,
+                (Token::Comma, _) => {}
+                (token, idx) => return Err(Self::parse_error("comma or object end", &token, idx)),
+            }
+            let next = self.next_token();
+            token = next.0;
+            idx = next.1;
+        }
+        self.depth -= 1;
+        Ok(LuaValue::Table(table))
+    }
+
+    fn decode_array(&mut self, idx: usize) -> LuaResult<LuaValue<'lua>> {
+        self.enter(idx)?;
+        let table = self.lua.create_table()?;
+        let (mut token, mut idx) = self.next_token();
+        if let Token::ArrEnd = token {
+            self.depth -= 1;
+            return Ok(LuaValue::Table(table));
+        }
+        for i in 1.. {
+            table.raw_set(i, self.decode_value(token, idx)?)?;
+            match self.next_token() {
+                (Token::ArrEnd, _) => break,
+                (Token::Comma, _) => {}
+                (token, idx) => return Err(Self::parse_error("comma or array end", &token, idx)),
+            }
+            let next = self.next_token();
+            token = next.0;
+            idx = next.1;
+        }
+        self.depth -= 1;
+        Ok(LuaValue::Table(table))
+    }
+
+    fn next_token(&mut self) -> (Token, usize) {
+        while self.pos < self.data.len()
+            && matches!(self.data[self.pos], b' ' | b'\t' | b'\n' | b'\r')
+        {
+            self.pos += 1;
+        }
+        let idx = self.pos;
+        let c = match self.data.get(self.pos) {
+            Some(c) => *c,
+            None => return (Token::End, idx),
+        };
+
+        let token = match c {
+            b'{' => Token::ObjBegin,
+            b'}' => Token::ObjEnd,
+            b'[' => Token::ArrBegin,
+            b']' => Token::ArrEnd,
+            b':' => Token::Colon,
+            b',' => Token::Comma,
+            b'"' => return (self.next_string(), idx),
+            b'-' | b'0'..=b'9' => return (self.next_number(), idx),
+            _ => {
+                let rest = &self.data[self.pos..];
+                let (token, len) = if rest.starts_with(b"true") {
+                    (Token::Boolean(true), 4)
+                } else if rest.starts_with(b"false") {
+                    (Token::Boolean(false), 5)
+                } else if rest.starts_with(b"null") {
+                    (Token::Null, 4)
+                } else {
+                    return (Token::Error("invalid token"), idx);
+                };
+                self.pos += len;
+                return (token, idx);
+            }
+        };
+        self.pos += 1;
+        (token, idx)
+    }
+
+    fn next_number(&mut self) -> Token {
+        let start = self.pos;
+        while self.pos < self.data.len()
+            && matches!(
+                self.data[self.pos],
+                b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'
+            )
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .map(Token::Number)
+            .unwrap_or(Token::Error("invalid number"))
+    }
+
+    fn next_hex4(&mut self) -> Option<u32> {
+        let hex = self.data.get(self.pos..self.pos + 4)?;
+        let code = u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+        self.pos += 4;
+        Some(code)
+    }
+
+    // decode \uXXXX and the following low surrogate if any, the leading \u is consumed
+    fn next_unicode(&mut self) -> Option<char> {
+        let high = self.next_hex4()?;
+        if (0xdc00..0xe000).contains(&high) {
+            return None;
+        }
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if self.data.get(self.pos..self.pos + 2) != Some(b"\\u") {
+            return None;
+        }
+        self.pos += 2;
+        let low = self.next_hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+    }
+
+    fn next_string(&mut self) -> Token {
+        // skip the opening quote
+        self.pos += 1;
+        let mut s = vec![];
+        loop {
+            let c = match self.data.get(self.pos) {
+                Some(c) => *c,
+                None => return Token::Error("unexpected end of string"),
+            };
+            self.pos += 1;
+            match c {
+                b'"' => return Token::Str(s),
+                b'\\' => {
+                    let escaped = match self.data.get(self.pos) {
+                        Some(c) => *c,
+                        None => return Token::Error("unexpected end of string"),
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'"' | b'\\' | b'/' => s.push(escaped),
+                        b'b' => s.push(b'\x08'),
+                        b'f' => s.push(b'\x0c'),
+                        b'n' => s.push(b'\n'),
+                        b'r' => s.push(b'\r'),
+                        b't' => s.push(b'\t'),
+                        b'u' => match self.next_unicode() {
+                            Some(ch) => {
+                                let mut utf8 = [0; 4];
+                                s.extend_from_slice(ch.encode_utf8(&mut utf8).as_bytes());
+                            }
+                            None => return Token::Error("invalid unicode escape code"),
+                        },
+                        _ => return Token::Error("invalid escape code"),
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+    }
+}
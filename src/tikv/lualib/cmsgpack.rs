@@ -0,0 +1,288 @@
+use super::script_error;
+use mlua::{prelude::*, Lua, Value as LuaValue, Variadic};
+use std::convert::TryInto;
+
+// tables nested deeper are packed as nil, as lua-cmsgpack bundled in redis does
+const MAX_NESTING: usize = 16;
+// arrays and maps nested deeper are rejected when unpacking
+const MAX_DEPTH: usize = 1000;
+
+pub fn create(lua: &Lua) -> LuaResult<LuaTable> {
+    let cmsgpack = lua.create_table()?;
+
+    let pack = lua.create_function(|lua, values: Variadic<LuaValue>| {
+        if values.is_empty() {
+            return Err(script_error("MessagePack pack needs input."));
+        }
+        let mut buf = vec![];
+        for value in values.iter() {
+            encode_value(value, 0, &mut buf)?;
+        }
+        lua.create_string(&buf)
+    })?;
+    cmsgpack.raw_set("pack", pack)?;
+
+    let unpack = lua.create_function(|lua, data: LuaString| {
+        let mut decoder = Decoder {
+            lua,
+            data: data.as_bytes(),
+            pos: 0,
+        };
+        let mut values = vec![];
+        while decoder.pos < decoder.data.len() {
+            values.push(decoder.decode_value(0)?);
+        }
+        Ok(values.into_iter().collect::<Variadic<_>>())
+    })?;
+    cmsgpack.raw_set("unpack", unpack)?;
+
+    Ok(cmsgpack)
+}
+
+fn encode_value(value: &LuaValue, level: usize, buf: &mut Vec<u8>) -> LuaResult<()> {
+    match value {
+        LuaValue::Table(_) if level == MAX_NESTING => buf.push(0xc0),
+        LuaValue::Table(t) => encode_table(t, level, buf)?,
+        LuaValue::Boolean(b) => buf.push(if *b { 0xc3 } else { 0xc2 }),
+        LuaValue::Integer(i) => encode_integer(*i, buf),
+        LuaValue::Number(n) => encode_number(*n, buf),
+        LuaValue::String(s) => encode_bytes(s.as_bytes(), buf),
+        _ => buf.push(0xc0),
+    }
+    Ok(())
+}
+
+fn encode_integer(i: i64, buf: &mut Vec<u8>) {
+    if i >= 0 {
+        match i {
+            0..=0x7f => buf.push(i as u8),
+            0x80..=0xff => buf.extend_from_slice(&[0xcc, i as u8]),
+            0x100..=0xffff => {
+                buf.push(0xcd);
+                buf.extend_from_slice(&(i as u16).to_be_bytes());
+            }
+            0x10000..=0xffffffff => {
+                buf.push(0xce);
+                buf.extend_from_slice(&(i as u32).to_be_bytes());
+            }
+            _ => {
+                buf.push(0xcf);
+                buf.extend_from_slice(&(i as u64).to_be_bytes());
+            }
+        }
+    } else if i >= -32 {
+        buf.push(i as i8 as u8);
+    } else if i >= i8::MIN as i64 {
+        buf.extend_from_slice(&[0xd0, i as i8 as u8]);
+    } else if i >= i16::MIN as i64 {
+        buf.push(0xd1);
+        buf.extend_from_slice(&(i as i16).to_be_bytes());
+    } else if i >= i32::MIN as i64 {
+        buf.push(0xd2);
+        buf.extend_from_slice(&(i as i32).to_be_bytes());
+    } else {
+        buf.push(0xd3);
+        buf.extend_from_slice(&i.to_be_bytes());
+    }
+}
+
+fn encode_number(n: f64, buf: &mut Vec<u8>) {
+    if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+        encode_integer(n as i64, buf);
+    } else if n as f32 as f64 == n {
+        buf.push(0xca);
+        buf.extend_from_slice(&(n as f32).to_be_bytes());
+    } else {
+        buf.push(0xcb);
+        buf.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn encode_bytes(s: &[u8], buf: &mut Vec<u8>) {
+    let len = s.len();
+    if len < 32 {
+        buf.push(0xa0 | len as u8);
+    } else if len <= 0xff {
+        buf.extend_from_slice(&[0xd9, len as u8]);
+    } else if len <= 0xffff {
+        buf.push(0xda);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(0xdb);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+    buf.extend_from_slice(s);
+}
+
+fn encode_header(len: usize, fix: u8, code16: u8, code32: u8, buf: &mut Vec<u8>) {
+    if len <= 15 {
+        buf.push(fix | len as u8);
+    } else if len <= 0xffff {
+        buf.push(code16);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(code32);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+// tables with only the keys 1..n are packed as array, empty tables included
+fn is_array(table: &LuaTable) -> LuaResult<bool> {
+    let (mut max, mut count) = (0, 0);
+    for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+        let idx = match pair?.0 {
+            LuaValue::Integer(i) if i > 0 => i,
+            LuaValue::Number(n) if n > 0.0 && n.floor() == n => n as i64,
+            _ => return Ok(false),
+        };
+        max = max.max(idx);
+        count += 1;
+    }
+    Ok(max == count)
+}
+
+fn encode_table(table: &LuaTable, level: usize, buf: &mut Vec<u8>) -> LuaResult<()> {
+    if is_array(table)? {
+        let len = table.raw_len() as usize;
+        encode_header(len, 0x90, 0xdc, 0xdd, buf);
+        for idx in 1..=len {
+            encode_value(&table.raw_get::<_, LuaValue>(idx)?, level + 1, buf)?;
+        }
+        return Ok(());
+    }
+
+    let pairs = table
+        .clone()
+        .pairs::<LuaValue, LuaValue>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    encode_header(pairs.len(), 0x80, 0xde, 0xdf, buf);
+    for (key, value) in pairs {
+        encode_value(&key, level + 1, buf)?;
+        encode_value(&value, level + 1, buf)?;
+    }
+    Ok(())
+}
+
+struct Decoder<'a, 'lua> {
+    lua: &'lua Lua,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a, 'lua> Decoder<'a, 'lua> {
+    fn take(&mut self, len: usize) -> LuaResult<&'a [u8]> {
+        match self.data.get(self.pos..self.pos + len) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(script_error("Missing bytes in input.")),
+        }
+    }
+
+    fn take_u8(&mut self) -> LuaResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take_u16(&mut self) -> LuaResult<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn take_u32(&mut self) -> LuaResult<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn take_u64(&mut self) -> LuaResult<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn decode_bytes(&mut self, len: usize) -> LuaResult<LuaValue<'lua>> {
+        let bytes = self.take(len)?;
+        Ok(LuaValue::String(self.lua.create_string(bytes)?))
+    }
+
+    fn decode_array(&mut self, len: usize, depth: usize) -> LuaResult<LuaValue<'lua>> {
+        check_depth(depth)?;
+        let table = self.lua.create_table()?;
+        for idx in 1..=len {
+            table.raw_set(idx, self.decode_value(depth)?)?;
+        }
+        Ok(LuaValue::Table(table))
+    }
+
+    fn decode_map(&mut self, len: usize, depth: usize) -> LuaResult<LuaValue<'lua>> {
+        check_depth(depth)?;
+        let table = self.lua.create_table()?;
+        for _ in 0..len {
+            let key = self.decode_value(depth)?;
+            let value = self.decode_value(depth)?;
+            table.raw_set(key, value)?;
+        }
+        Ok(LuaValue::Table(table))
+    }
+
+    // `depth` is the number of arrays and maps containing the value
+    fn decode_value(&mut self, depth: usize) -> LuaResult<LuaValue<'lua>> {
+        let c = self.take_u8()?;
+        let value = match c {
+            0x00..=0x7f => LuaValue::Integer(c as i64),
+            0x80..=0x8f => self.decode_map((c & 0x0f) as usize, depth + 1)?,
+            0x90..=0x9f => self.decode_array((c & 0x0f) as usize, depth + 1)?,
+            0xa0..=0xbf => self.decode_bytes((c & 0x1f) as usize)?,
+            0xc0 => LuaValue::Nil,
+            0xc2 => LuaValue::Boolean(false),
+            0xc3 => LuaValue::Boolean(true),
+            0xc4 | 0xd9 => {
+                let len = self.take_u8()? as usize;
+                self.decode_bytes(len)?
+            }
+            0xc5 | 0xda => {
+                let len = self.take_u16()? as usize;
+                self.decode_bytes(len)?
+            }
+            0xc6 | 0xdb => {
+                let len = self.take_u32()? as usize;
+                self.decode_bytes(len)?
+            }
+            0xca => LuaValue::Number(f32::from_bits(self.take_u32()?) as f64),
+            0xcb => LuaValue::Number(f64::from_bits(self.take_u64()?)),
+            0xcc => LuaValue::Integer(self.take_u8()? as i64),
+            0xcd => LuaValue::Integer(self.take_u16()? as i64),
+            0xce => LuaValue::Integer(self.take_u32()? as i64),
+            0xcf => LuaValue::Number(self.take_u64()? as f64),
+            0xd0 => LuaValue::Integer(self.take_u8()? as i8 as i64),
+            0xd1 => LuaValue::Integer(self.take_u16()? as i16 as i64),
+            0xd2 => LuaValue::Integer(self.take_u32()? as i32 as i64),
+            0xd3 => LuaValue::Integer(self.take_u64()? as i64),
+            0xdc => {
+                let len = self.take_u16()? as usize;
+                self.decode_array(len, depth + 1)?
+            }
+            0xdd => {
+                let len = self.take_u32()? as usize;
+                self.decode_array(len, depth + 1)?
+            }
+            0xde => {
+                let len = self.take_u16()? as usize;
+                self.decode_map(len, depth + 1)?
+            }
+            0xdf => {
+                let len = self.take_u32()? as usize;
+                self.decode_map(len, depth + 1)?
+            }
+            0xe0..=0xff => LuaValue::Integer(c as i8 as i64),
+            _ => return Err(script_error("Bad data format in input.")),
+        };
+        Ok(value)
+    }
+}
+
+fn check_depth(depth: usize) -> LuaResult<()> {
+    if depth > MAX_DEPTH {
+        return Err(script_error(format!(
+            "Found too many nested data structures ({})",
+            depth
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+mod bit;
+mod cjson;
+mod cmsgpack;
+mod structlib;
+
+use crate::config::LOGGER;
+use crate::tikv::ldb;
+use crate::utils::format_number_g;
+use mlua::{prelude::*, Lua, LuaOptions, StdLib, Value as LuaValue, Variadic};
+use slog::{debug, info, warn};
+
+/// Version of redis whose scripting behavior is provided, exposed as `redis.REDIS_VERSION`
+const REDIS_VERSION: &str = "7.0.0";
+const REDIS_VERSION_NUM: i64 = 0x00070000;
+
+const LOG_DEBUG: i64 = 0;
+const LOG_VERBOSE: i64 = 1;
+const LOG_NOTICE: i64 = 2;
+const LOG_WARNING: i64 = 3;
+
+const REPL_NONE: i64 = 0;
+const REPL_AOF: i64 = 1;
+const REPL_REPLICA: i64 = 2;
+const REPL_ALL: i64 = 3;
+
+// registry value of the resp version selected by redis.setresp
+const RESP_VERSION: &str = "__tidis_resp";
+// registry value of the id of the client which the lua state belongs to
+const CLIENT_ID: &str = "__tidis_client_id";
+// registry value of the debug library, only loaded in the lua state of the debugger
+const DEBUG_LIB: &str = "__tidis_debug";
+
+// coroutine is required by the async functions of mlua
+fn lua_libs() -> StdLib {
+    StdLib::COROUTINE | StdLib::TABLE | StdLib::STRING | StdLib::MATH
+}
+
+/// Error raised by the libraries, reported as `ERR Error running script: <msg>`
+fn script_error(msg: impl ToString) -> LuaError {
+    LuaError::external(msg.to_string())
+}
+
+fn lua_integer(value: &LuaValue) -> Option<i64> {
+    match value {
+        LuaValue::Integer(i) => Some(*i),
+        LuaValue::Number(n) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+/// Create the lua state sandboxed as redis does. Only the base, table, string and math
+/// libraries are loaded along with cjson, cmsgpack, bit and struct, files can not be
+/// loaded, and scripts are not allowed to create or access undefined globals.
+pub fn new_lua() -> LuaResult<Lua> {
+    let lua = Lua::new_with(lua_libs(), LuaOptions::new())?;
+    sandbox(&lua)?;
+    Ok(lua)
+}
+
+/// Create the sandboxed lua state of SCRIPT DEBUG, the debug library is kept in the
+/// registry for the debugger and is not visible to scripts
+pub fn new_debug_lua() -> LuaResult<Lua> {
+    // mlua only loads the debug library into the lua states created unsafely
+    let lua = unsafe { Lua::unsafe_new_with(lua_libs() | StdLib::DEBUG, LuaOptions::new()) };
+    let debug: LuaTable = lua.globals().raw_get("debug")?;
+    lua.set_named_registry_value(DEBUG_LIB, debug)?;
+    lua.globals().raw_set("debug", LuaValue::Nil)?;
+    sandbox(&lua)?;
+    Ok(lua)
+}
+
+pub fn debug_lib(lua: &Lua) -> LuaResult<LuaTable> {
+    lua.named_registry_value(DEBUG_LIB)
+}
+
+fn sandbox(lua: &Lua) -> LuaResult<()> {
+    let globals = lua.globals();
+    globals.raw_set("loadfile", LuaValue::Nil)?;
+    globals.raw_set("dofile", LuaValue::Nil)?;
+
+    globals.raw_set("cjson", cjson::create(lua)?)?;
+    globals.raw_set("cmsgpack", cmsgpack::create(lua)?)?;
+    globals.raw_set("bit", bit::create(lua)?)?;
+    globals.raw_set("struct", structlib::create(lua)?)?;
+
+    let meta = lua.create_table()?;
+    let newindex = lua.create_function(|_, (_, name): (LuaValue, String)| -> LuaResult<()> {
+        Err(script_error(format!(
+            "Script attempted to create global variable '{}'",
+            name
+        )))
+    })?;
+    let index = lua.create_function(|_, (_, name): (LuaValue, String)| -> LuaResult<()> {
+        Err(script_error(format!(
+            "Script attempted to access nonexistent global variable '{}'",
+            name
+        )))
+    })?;
+    meta.raw_set("__newindex", newindex)?;
+    meta.raw_set("__index", index)?;
+    globals.set_metatable(Some(meta));
+    Ok(())
+}
+
+/// Bind the lua state to the client, so that the running script of the client can be found
+pub fn set_client_id(lua: &Lua, client_id: u64) -> LuaResult<()> {
+    lua.set_named_registry_value(CLIENT_ID, client_id as i64)
+}
+
+pub fn client_id(lua: &Lua) -> Option<u64> {
+    let id: LuaResult<Option<i64>> = lua.named_registry_value(CLIENT_ID);
+    id.ok().flatten().map(|id| id as u64)
+}
+
+/// Returns true if the script selected RESP3 by `redis.setresp(3)`
+pub fn is_resp3(lua: &Lua) -> bool {
+    let version: LuaResult<i64> = lua.named_registry_value(RESP_VERSION);
+    matches!(version, Ok(3))
+}
+
+/// Register the helpers of the `redis` table which do not touch the database,
+/// globals are protected so the `redis` table itself must be set with `raw_set`
+pub fn set_redis_helpers(lua: &Lua, redis: &LuaTable) -> LuaResult<()> {
+    redis.set("REDIS_VERSION", REDIS_VERSION)?;
+    redis.set("REDIS_VERSION_NUM", REDIS_VERSION_NUM)?;
+
+    redis.set("LOG_DEBUG", LOG_DEBUG)?;
+    redis.set("LOG_VERBOSE", LOG_VERBOSE)?;
+    redis.set("LOG_NOTICE", LOG_NOTICE)?;
+    redis.set("LOG_WARNING", LOG_WARNING)?;
+
+    redis.set("REPL_NONE", REPL_NONE)?;
+    redis.set("REPL_AOF", REPL_AOF)?;
+    redis.set("REPL_SLAVE", REPL_REPLICA)?;
+    redis.set("REPL_REPLICA", REPL_REPLICA)?;
+    redis.set("REPL_ALL", REPL_ALL)?;
+
+    // redis.log(level, message, ...)
+    let redis_log = lua.create_function(|_, args: Variadic<LuaValue>| {
+        if args.len() < 2 {
+            return Err(script_error("redis.log() requires two arguments or more."));
+        }
+        let level = match lua_integer(&args[0]) {
+            Some(level) => level,
+            None => return Err(script_error("First argument must be a number (log level).")),
+        };
+        let msg = args[1..]
+            .iter()
+            .filter_map(|arg| match arg {
+                LuaValue::String(s) => Some(String::from_utf8_lossy(s.as_bytes()).to_string()),
+                LuaValue::Integer(i) => Some(i.to_string()),
+                LuaValue::Number(n) => Some(format_number_g(*n, 14)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        match level {
+            LOG_DEBUG => debug!(LOGGER, "{}", msg),
+            LOG_VERBOSE | LOG_NOTICE => info!(LOGGER, "{}", msg),
+            LOG_WARNING => warn!(LOGGER, "{}", msg),
+            _ => return Err(script_error("Invalid debug level.")),
+        }
+        Ok(())
+    })?;
+    redis.set("log", redis_log)?;
+
+    // redis.setresp(2|3), scripts always start with resp2
+    lua.set_named_registry_value(RESP_VERSION, 2)?;
+    let redis_setresp = lua.create_function(|lua, args: Variadic<LuaValue>| {
+        if args.len() != 1 {
+            return Err(script_error("redis.setresp() requires one argument."));
+        }
+        match lua_integer(&args[0]) {
+            Some(version @ (2 | 3)) => lua.set_named_registry_value(RESP_VERSION, version),
+            _ => Err(script_error("RESP version must be 2 or 3.")),
+        }
+    })?;
+    redis.set("setresp", redis_setresp)?;
+
+    // redis.set_repl(flags), commands are not replicated in tidis, the flags are only checked
+    let redis_set_repl = lua.create_function(|_, args: Variadic<LuaValue>| {
+        if args.len() != 1 {
+            return Err(script_error("redis.set_repl() requires one argument."));
+        }
+        match lua_integer(&args[0]) {
+            Some(REPL_NONE..=REPL_ALL) => Ok(()),
+            _ => Err(script_error(
+                "Invalid replication flags. Use REPL_AOF, REPL_REPLICA, REPL_ALL or REPL_NONE.",
+            )),
+        }
+    })?;
+    redis.set("set_repl", redis_set_repl)?;
+
+    // redis.replicate_commands(), effects replication is always on since redis 7.0
+    let redis_replicate_commands = lua.create_function(|_, ()| Ok(true))?;
+    redis.set("replicate_commands", redis_replicate_commands)?;
+
+    // redis.debug(...) and redis.breakpoint() only work in the sessions of SCRIPT DEBUG
+    let redis_debug = lua.create_function(|_, args: Variadic<LuaValue>| {
+        ldb::log_debug(&args);
+        Ok(())
+    })?;
+    redis.set("debug", redis_debug)?;
+    let redis_breakpoint = lua.create_function(|_, ()| Ok(ldb::breakpoint()))?;
+    redis.set("breakpoint", redis_breakpoint)
+}
@@ -0,0 +1,323 @@
+use super::script_error;
+use crate::utils::format_number_g;
+use mlua::{prelude::*, Lua, Value as LuaValue, Variadic};
+
+// limits of the lua struct library bundled in redis
+const MAX_INT_SIZE: usize = 32;
+const MAX_ALIGN: usize = 8;
+
+struct Header {
+    little: bool,
+    align: usize,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Header {
+            little: cfg!(target_endian = "little"),
+            align: 1,
+        }
+    }
+}
+
+fn arg_error(arg: usize, func: &str, msg: &str) -> LuaError {
+    script_error(format!("bad argument #{} to '{}' ({})", arg, func, msg))
+}
+
+fn get_num(fmt: &[u8], pos: &mut usize, default: usize) -> usize {
+    if !fmt.get(*pos).map_or(false, |c| c.is_ascii_digit()) {
+        return default;
+    }
+    let mut n = 0usize;
+    while let Some(c) = fmt.get(*pos).filter(|c| c.is_ascii_digit()) {
+        n = n.saturating_mul(10).saturating_add((c - b'0') as usize);
+        *pos += 1;
+    }
+    n
+}
+
+fn opt_size(opt: u8, fmt: &[u8], pos: &mut usize) -> LuaResult<usize> {
+    Ok(match opt {
+        b'B' | b'b' | b'x' => 1,
+        b'H' | b'h' => 2,
+        b'L' | b'l' | b'T' | b'd' => 8,
+        b'f' => 4,
+        b'c' => get_num(fmt, pos, 1),
+        b'i' | b'I' => {
+            let size = get_num(fmt, pos, 4);
+            if size > MAX_INT_SIZE {
+                return Err(script_error(format!(
+                    "integral size {} is larger than limit of {}",
+                    size, MAX_INT_SIZE
+                )));
+            }
+            size
+        }
+        _ => 0,
+    })
+}
+
+fn to_align(len: usize, h: &Header, opt: u8, size: usize) -> usize {
+    if size == 0 || opt == b'c' {
+        return 0;
+    }
+    let size = size.min(h.align);
+    (size - (len & (size - 1))) & (size - 1)
+}
+
+fn control_options(opt: u8, fmt: &[u8], pos: &mut usize, h: &mut Header) -> LuaResult<()> {
+    match opt {
+        b' ' => {}
+        b'>' => h.little = false,
+        b'<' => h.little = true,
+        b'=' => h.little = cfg!(target_endian = "little"),
+        b'!' => {
+            let align = get_num(fmt, pos, MAX_ALIGN);
+            if !align.is_power_of_two() {
+                return Err(script_error(format!(
+                    "alignment {} is not a power of 2",
+                    align
+                )));
+            }
+            h.align = align;
+        }
+        _ => {
+            return Err(script_error(format!(
+                "invalid format option '{}'",
+                opt as char
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn lua_number(value: Option<&LuaValue>, arg: usize) -> LuaResult<f64> {
+    match value {
+        Some(LuaValue::Integer(i)) => Ok(*i as f64),
+        Some(LuaValue::Number(n)) => Ok(*n),
+        Some(LuaValue::String(s)) => s
+            .to_str()
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .ok_or_else(|| arg_error(arg, "pack", "number expected")),
+        _ => Err(arg_error(arg, "pack", "number expected")),
+    }
+}
+
+fn lua_bytes(value: Option<&LuaValue>, arg: usize) -> LuaResult<Vec<u8>> {
+    match value {
+        Some(LuaValue::String(s)) => Ok(s.as_bytes().to_vec()),
+        Some(LuaValue::Integer(i)) => Ok(i.to_string().into_bytes()),
+        Some(LuaValue::Number(n)) => Ok(format_number_g(*n, 14).into_bytes()),
+        _ => Err(arg_error(arg, "pack", "string expected")),
+    }
+}
+
+fn put_integer(n: f64, little: bool, size: usize, buf: &mut Vec<u8>) {
+    let mut value = if n < 0.0 { n as i64 as u64 } else { n as u64 };
+    let mut bytes: Vec<u8> = (0..size)
+        .map(|_| {
+            let byte = (value & 0xff) as u8;
+            value >>= 8;
+            byte
+        })
+        .collect();
+    if !little {
+        bytes.reverse();
+    }
+    buf.extend_from_slice(&bytes);
+}
+
+fn get_integer(data: &[u8], little: bool, signed: bool) -> f64 {
+    let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+    let mut value = if little {
+        data.iter().rev().fold(0, fold)
+    } else {
+        data.iter().fold(0, fold)
+    };
+    if !signed {
+        return value as f64;
+    }
+    // sign extend the integers shorter than 64 bits
+    let bits = data.len() * 8;
+    if bits > 0 && bits < 64 {
+        let mask = !0u64 << (bits - 1);
+        if value & mask != 0 {
+            value |= mask;
+        }
+    }
+    value as i64 as f64
+}
+
+// struct.pack(fmt, d1, d2, ...)
+fn pack(args: Variadic<LuaValue>) -> LuaResult<Vec<u8>> {
+    let fmt = lua_bytes(args.first(), 1)?;
+    let mut h = Header::default();
+    let mut buf = vec![];
+    let mut arg = 1;
+    let mut pos = 0;
+    while pos < fmt.len() {
+        let opt = fmt[pos];
+        pos += 1;
+        let mut size = opt_size(opt, &fmt, &mut pos)?;
+        let align = to_align(buf.len(), &h, opt, size);
+        buf.resize(buf.len() + align, 0);
+        match opt {
+            b'b' | b'B' | b'h' | b'H' | b'l' | b'L' | b'T' | b'i' | b'I' => {
+                let n = lua_number(args.get(arg), arg + 1)?;
+                put_integer(n, h.little, size, &mut buf);
+                arg += 1;
+            }
+            b'x' => buf.push(0),
+            b'f' => {
+                let f = lua_number(args.get(arg), arg + 1)? as f32;
+                if h.little {
+                    buf.extend_from_slice(&f.to_le_bytes());
+                } else {
+                    buf.extend_from_slice(&f.to_be_bytes());
+                }
+                arg += 1;
+            }
+            b'd' => {
+                let d = lua_number(args.get(arg), arg + 1)?;
+                if h.little {
+                    buf.extend_from_slice(&d.to_le_bytes());
+                } else {
+                    buf.extend_from_slice(&d.to_be_bytes());
+                }
+                arg += 1;
+            }
+            b'c' | b's' => {
+                let s = lua_bytes(args.get(arg), arg + 1)?;
+                if size == 0 {
+                    size = s.len();
+                }
+                if s.len() < size {
+                    return Err(arg_error(arg + 1, "pack", "string too short"));
+                }
+                buf.extend_from_slice(&s[..size]);
+                if opt == b's' {
+                    buf.push(0);
+                }
+                arg += 1;
+            }
+            _ => control_options(opt, &fmt, &mut pos, &mut h)?,
+        }
+    }
+    Ok(buf)
+}
+
+// struct.unpack(fmt, s [, i]), returns the values and the position after them
+fn unpack<'lua>(
+    lua: &'lua Lua,
+    (fmt, data, init): (LuaString<'lua>, LuaString<'lua>, Option<i64>),
+) -> LuaResult<Variadic<LuaValue<'lua>>> {
+    let fmt = fmt.as_bytes();
+    let data = data.as_bytes();
+    let mut pos = match init.unwrap_or(1) {
+        init if init > 0 => init as usize - 1,
+        _ => return Err(arg_error(3, "unpack", "offset must be 1 or greater")),
+    };
+    let too_short = || arg_error(2, "unpack", "data string too short");
+
+    let mut h = Header::default();
+    let mut values = Variadic::new();
+    let mut fmt_pos = 0;
+    while fmt_pos < fmt.len() {
+        let opt = fmt[fmt_pos];
+        fmt_pos += 1;
+        let mut size = opt_size(opt, fmt, &mut fmt_pos)?;
+        pos += to_align(pos, &h, opt, size);
+        if size > data.len() || pos > data.len() - size {
+            return Err(too_short());
+        }
+        match opt {
+            b'b' | b'B' | b'h' | b'H' | b'l' | b'L' | b'T' | b'i' | b'I' => {
+                let n = get_integer(&data[pos..pos + size], h.little, opt.is_ascii_lowercase());
+                values.push(LuaValue::Number(n));
+            }
+            b'x' => {}
+            b'f' => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(&data[pos..pos + 4]);
+                let f = if h.little {
+                    f32::from_le_bytes(bytes)
+                } else {
+                    f32::from_be_bytes(bytes)
+                };
+                values.push(LuaValue::Number(f as f64));
+            }
+            b'd' => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&data[pos..pos + 8]);
+                let d = if h.little {
+                    f64::from_le_bytes(bytes)
+                } else {
+                    f64::from_be_bytes(bytes)
+                };
+                values.push(LuaValue::Number(d));
+            }
+            b'c' => {
+                if size == 0 {
+                    // c0 takes the length from the previous value
+                    size = match values.pop() {
+                        Some(LuaValue::Number(n)) => n as usize,
+                        Some(LuaValue::Integer(i)) => i as usize,
+                        _ => return Err(script_error("format 'c0' needs a previous size")),
+                    };
+                    if size > data.len() || pos > data.len() - size {
+                        return Err(too_short());
+                    }
+                }
+                values.push(LuaValue::String(lua.create_string(&data[pos..pos + size])?));
+            }
+            b's' => {
+                let end = match data[pos..].iter().position(|c| *c == 0) {
+                    Some(end) => end,
+                    None => return Err(script_error("unfinished string in data")),
+                };
+                values.push(LuaValue::String(lua.create_string(&data[pos..pos + end])?));
+                size = end + 1;
+            }
+            _ => control_options(opt, fmt, &mut fmt_pos, &mut h)?,
+        }
+        pos += size;
+    }
+    values.push(LuaValue::Integer(pos as i64 + 1));
+    Ok(values)
+}
+
+// struct.size(fmt)
+fn size(fmt: &[u8]) -> LuaResult<usize> {
+    let mut h = Header::default();
+    let mut total = 0;
+    let mut pos = 0;
+    while pos < fmt.len() {
+        let opt = fmt[pos];
+        pos += 1;
+        let size = opt_size(opt, fmt, &mut pos)?;
+        total += to_align(total, &h, opt, size);
+        match opt {
+            b's' => return Err(arg_error(1, "size", "option 's' has no fixed size")),
+            b'c' if size == 0 => return Err(arg_error(1, "size", "option 'c0' has no fixed size")),
+            b'b' | b'B' | b'h' | b'H' | b'l' | b'L' | b'T' | b'i' | b'I' | b'x' | b'f' | b'd'
+            | b'c' => {}
+            _ => control_options(opt, fmt, &mut pos, &mut h)?,
+        }
+        total += size;
+    }
+    Ok(total)
+}
+
+pub fn create(lua: &Lua) -> LuaResult<LuaTable> {
+    let structlib = lua.create_table()?;
+
+    let struct_pack =
+        lua.create_function(|lua, args: Variadic<LuaValue>| lua.create_string(&pack(args)?))?;
+    structlib.raw_set("pack", struct_pack)?;
+    structlib.raw_set("unpack", lua.create_function(unpack)?)?;
+    let struct_size = lua.create_function(|_, fmt: LuaString| size(fmt.as_bytes()))?;
+    structlib.raw_set("size", struct_size)?;
+
+    Ok(structlib)
+}
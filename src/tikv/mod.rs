@@ -24,16 +24,20 @@ use crate::{
 };
 
 use self::client::RawClientWrapper;
-use self::client::TxnClientWrapper;
+use self::client::{init_gc_safepoint_refresher, init_tso_refresher, TxnClientWrapper};
 
 use self::errors::{AsyncResult, RTError};
 
 pub mod client;
 pub mod encoding;
 pub mod errors;
+pub mod function;
 pub mod hash;
+pub mod ldb;
 pub mod list;
 pub mod lua;
+pub mod lualib;
+pub mod script;
 pub mod set;
 pub mod string;
 pub mod zset;
@@ -160,6 +164,8 @@ pub async fn do_async_txn_connect(addrs: Vec<String>) -> AsyncResult<()> {
     unsafe {
         TIKV_TXN_CLIENTS.replace(clients);
     }
+    init_gc_safepoint_refresher(addrs);
+    init_tso_refresher();
 
     Ok(())
 }
@@ -0,0 +1,144 @@
+use super::errors::AsyncResult;
+use super::{get_txn_client, KEY_ENCODER};
+use ::futures::future::FutureExt;
+use bytes::Bytes;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::sync::Arc;
+use tikv_client::{Key, Transaction};
+use tokio::sync::Mutex;
+
+/// Scripts loaded by SCRIPT LOAD are persisted in tikv, so that EVALSHA works on
+/// every instance of the cluster and after restart
+#[derive(Clone)]
+pub struct ScriptCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl ScriptCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        ScriptCommandCtx { txn }
+    }
+
+    pub async fn do_async_txnkv_script_load(
+        mut self,
+        sha1: &str,
+        script: &Bytes,
+    ) -> AsyncResult<()> {
+        let mut client = get_txn_client()?;
+        let key = KEY_ENCODER.encode_txnkv_script(sha1);
+        let value = script.to_vec();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    txn.put(key, value).await?;
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    // scripts are read in the latest snapshot, not affected by the staleness or snapshot of
+    // connection
+    pub async fn do_async_txnkv_script_get(mut self, sha1: &str) -> AsyncResult<Option<Bytes>> {
+        let mut client = get_txn_client()?;
+        let key = KEY_ENCODER.encode_txnkv_script(sha1);
+
+        client
+            .exec_in_latest_snapshot(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    Ok(txn.get(key).await?.map(Bytes::from))
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    pub async fn do_async_txnkv_script_exists(
+        mut self,
+        sha1s: &[String],
+    ) -> AsyncResult<Vec<bool>> {
+        let mut client = get_txn_client()?;
+        let keys: Vec<Key> = sha1s
+            .iter()
+            .map(|sha1| KEY_ENCODER.encode_txnkv_script(sha1))
+            .collect();
+
+        client
+            .exec_in_latest_snapshot(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let found: HashSet<Key> =
+                        txn.batch_get(keys.clone()).await?.map(|kv| kv.0).collect();
+                    Ok(keys.iter().map(|k| found.contains(k)).collect())
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Delete all scripts and bump the epoch, instances drop their local cache
+    /// once they see the new epoch
+    pub async fn do_async_txnkv_script_flush(mut self) -> AsyncResult<u64> {
+        let mut client = get_txn_client()?;
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let epoch_key = KEY_ENCODER.encode_txnkv_script_epoch();
+                    let epoch = match txn.get(epoch_key.clone()).await? {
+                        Some(v) => u64::from_be_bytes(v[..].try_into().unwrap()) + 1,
+                        None => 1,
+                    };
+
+                    let range = KEY_ENCODER.encode_txnkv_script_range();
+                    let keys: Vec<Key> = txn.scan_keys(range, u32::MAX).await?.collect();
+                    for key in keys {
+                        txn.delete(key).await?;
+                    }
+                    txn.put(epoch_key, epoch.to_be_bytes().to_vec()).await?;
+                    Ok(epoch)
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    pub async fn do_async_txnkv_script_epoch(mut self) -> AsyncResult<u64> {
+        let mut client = get_txn_client()?;
+        let epoch_key = KEY_ENCODER.encode_txnkv_script_epoch();
+
+        client
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    match txn.get(epoch_key).await? {
+                        Some(v) => Ok(u64::from_be_bytes(v[..].try_into().unwrap())),
+                        None => Ok(0),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+}
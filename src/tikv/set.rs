@@ -25,7 +25,9 @@ use tokio::sync::Mutex;
 
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
+use crate::gc::submit_expired_key;
 use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED, NOTIFY_SET};
 
 const RANDOM_BASE: i64 = 100;
 
@@ -93,6 +95,7 @@ impl SetCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -191,7 +194,12 @@ impl SetCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_int(v)),
+            Ok(v) => {
+                if v > 0 {
+                    notify_keyspace_event(NOTIFY_SET, "sadd", &key);
+                }
+                Ok(resp_int(v))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -202,7 +210,7 @@ impl SetCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -220,9 +228,7 @@ impl SetCommandCtx {
                             drop(txn);
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                self.clone()
-                                    .do_async_txnkv_set_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_int(0));
                             }
 
@@ -253,7 +259,7 @@ impl SetCommandCtx {
         let members = members.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -270,10 +276,7 @@ impl SetCommandCtx {
 
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_set_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 if !resp_in_arr {
                                     return Ok(resp_int(0));
                                 } else {
@@ -349,7 +352,7 @@ impl SetCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -366,10 +369,7 @@ impl SetCommandCtx {
 
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_set_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_array(vec![]));
                             }
 
@@ -444,7 +444,7 @@ impl SetCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -460,10 +460,7 @@ impl SetCommandCtx {
 
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_set_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_array(vec![]));
                             }
 
@@ -506,6 +503,7 @@ impl SetCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -580,7 +578,12 @@ impl SetCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_int(v as i64)),
+            Ok(v) => {
+                if v > 0 {
+                    notify_keyspace_event(NOTIFY_SET, "srem", &key);
+                }
+                Ok(resp_int(v as i64))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -594,6 +597,7 @@ impl SetCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -680,6 +684,9 @@ impl SetCommandCtx {
 
         match resp {
             Ok(mut v) => {
+                if !v.is_empty() {
+                    notify_keyspace_event(NOTIFY_SET, "spop", &key);
+                }
                 if count == 1 {
                     if v.is_empty() {
                         Ok(resp_nil())
@@ -818,6 +825,7 @@ impl SetCommandCtx {
                             REMOVED_EXPIRED_KEY_COUNTER
                                 .with_label_values(&["set"])
                                 .inc();
+                            notify_keyspace_event(NOTIFY_EXPIRED, "expired", &key);
 
                             Ok(1)
                         }
@@ -1,8 +1,9 @@
 use super::{
+    client::get_version_for_new,
     encoding::{DataType, KeyDecoder},
     errors::AsyncResult,
     errors::RTError,
-    KEY_ENCODER,
+    gen_next_meta_index, KEY_ENCODER,
 };
 use crate::{
     utils::{resp_array, resp_bulk, resp_nil, resp_ok},
@@ -10,8 +11,8 @@ use crate::{
 };
 use ::futures::future::FutureExt;
 use futures::StreamExt;
-use regex::bytes::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::str;
 use std::sync::Arc;
 use tikv_client::{BoundRange, Key, KvPair, Transaction, Value};
@@ -20,12 +21,22 @@ use tokio::sync::Mutex;
 use super::errors::*;
 use super::{get_client, get_txn_client};
 use super::{hash::HashCommandCtx, list::ListCommandCtx, set::SetCommandCtx, zset::ZsetCommandCtx};
+use crate::rdb::{self, RdbValue};
+use crate::tikv::list::INIT_INDEX;
 use crate::utils::{
-    key_is_expired, resp_err, resp_int, resp_ok_ignore, resp_str, sleep, ttl_from_timestamp,
+    count_unique_keys, glob_literal_prefix, glob_match, key_is_expired, now_timestamp_in_millis,
+    resp_err, resp_int, resp_ok_ignore, resp_str, sleep, ttl_from_timestamp,
 };
+use crate::{async_deletion_enabled_or_default, config_meta_key_number_or_default};
 use bytes::Bytes;
 
+use crate::gc::submit_expired_key;
 use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED, NOTIFY_GENERIC, NOTIFY_STRING};
+
+// collections with more elements are copied in batches before RENAME and COPY, whose
+// transaction then only writes the elements modified meanwhile
+const COPY_BATCH_SIZE: u32 = 1000;
 
 #[derive(Clone)]
 pub struct StringCommandCtx {
@@ -51,14 +62,8 @@ impl StringCommandCtx {
         let ekey = KEY_ENCODER.encode_txnkv_string(key);
         let key = key.to_owned();
 
-        // if get is executed from a new transaction, we can do get with latest commit
-        if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
-            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
-        }
-
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -77,8 +82,7 @@ impl StringCommandCtx {
                             let ttl = KeyDecoder::decode_key_ttl(&val);
                             if key_is_expired(ttl) {
                                 // delete key
-                                drop(txn);
-                                self.do_async_txnkv_string_expire_if_needed(&key).await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_nil());
                             }
 
@@ -108,14 +112,8 @@ impl StringCommandCtx {
         let ekey = KEY_ENCODER.encode_txnkv_string(key);
         let key = key.to_owned();
 
-        // if get is executed from a new transaction, we can do get with latest commit
-        if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
-            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
-        }
-
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -127,8 +125,7 @@ impl StringCommandCtx {
                             let ttl = KeyDecoder::decode_key_ttl(&val);
                             if key_is_expired(ttl) {
                                 // delete key
-                                drop(txn);
-                                self.do_async_txnkv_string_expire_if_needed(&key).await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_str(&DataType::Null.to_string()));
                             }
 
@@ -157,14 +154,8 @@ impl StringCommandCtx {
         let ekey = KEY_ENCODER.encode_txnkv_string(key);
         let key = key.to_owned();
 
-        // if get is executed from a new transaction, we can do get with latest commit
-        if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
-            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
-        }
-
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -183,8 +174,7 @@ impl StringCommandCtx {
                             let ttl = KeyDecoder::decode_key_ttl(&val);
                             if key_is_expired(ttl) {
                                 // delete key
-                                drop(txn);
-                                self.do_async_txnkv_string_expire_if_needed(&key).await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_int(0));
                             }
 
@@ -228,6 +218,12 @@ impl StringCommandCtx {
                 .boxed()
             })
             .await;
+        if resp.is_ok() {
+            notify_keyspace_event(NOTIFY_STRING, "set", key);
+            if timestamp > 0 {
+                notify_keyspace_event(NOTIFY_GENERIC, "expire", key);
+            }
+        }
         resp.map(resp_ok_ignore)
     }
 
@@ -254,14 +250,8 @@ impl StringCommandCtx {
         let mut client = get_txn_client()?;
         let ekeys = KEY_ENCODER.encode_txnkv_strings(keys);
 
-        // if get is executed from a new transaction, we can do get with latest commit
-        if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
-            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
-        }
-
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -304,6 +294,10 @@ impl StringCommandCtx {
 
     pub async fn do_async_txnkv_batch_put(mut self, kvs: Vec<KvPair>) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
+        let keys: Vec<Vec<u8>> = kvs
+            .iter()
+            .map(|kv| KeyDecoder::decode_key_userkey_from_metakey(kv.key()).0)
+            .collect();
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
                 async move {
@@ -320,7 +314,12 @@ impl StringCommandCtx {
             })
             .await;
         match resp {
-            Ok(_) => Ok(resp_ok()),
+            Ok(_) => {
+                for key in keys {
+                    notify_keyspace_event(NOTIFY_STRING, "set", &String::from_utf8_lossy(&key));
+                }
+                Ok(resp_ok())
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -378,6 +377,9 @@ impl StringCommandCtx {
 
         match resp {
             Ok(n) => {
+                if n == 1 {
+                    notify_keyspace_event(NOTIFY_STRING, "set", &key);
+                }
                 if return_number {
                     return Ok(resp_int(n as i64));
                 }
@@ -391,6 +393,60 @@ impl StringCommandCtx {
         }
     }
 
+    /// Overwrite the value with `value` or delete the key if `value` is None, only if the
+    /// current value equals `expected`. Returns 1 if swapped, otherwise 0
+    pub async fn do_async_txnkv_compare_and_swap(
+        mut self,
+        key: &str,
+        expected: &Bytes,
+        value: Option<&Bytes>,
+        timestamp: u64,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let expected = expected.to_vec();
+        let eval = value.map(|v| KEY_ENCODER.encode_txnkv_string_value(&mut v.to_vec(), timestamp));
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let old_value = match txn.get(ekey.clone()).await? {
+                        Some(v) => v,
+                        None => return Ok(resp_int(0)),
+                    };
+                    if !matches!(KeyDecoder::decode_key_type(&old_value), DataType::String) {
+                        return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                    }
+                    let ttl = KeyDecoder::decode_key_ttl(&old_value);
+                    if key_is_expired(ttl)
+                        || KeyDecoder::decode_key_string_value(&old_value) != expected
+                    {
+                        return Ok(resp_int(0));
+                    }
+
+                    match eval {
+                        Some(eval) => txn.put(ekey, eval).await?,
+                        None => txn.delete(ekey).await?,
+                    }
+                    Ok(resp_int(1))
+                }
+                .boxed()
+            })
+            .await;
+
+        if let Ok(Frame::Integer(1)) = resp {
+            match value {
+                Some(_) => notify_keyspace_event(NOTIFY_STRING, "set", key),
+                None => notify_keyspace_event(NOTIFY_GENERIC, "del", key),
+            }
+        }
+        resp
+    }
+
     pub async fn do_async_rawkv_exists(self, keys: &[String]) -> AsyncResult<Frame> {
         let client = get_client()?;
         let ekeys = KEY_ENCODER.encode_rawkv_strings(keys);
@@ -399,18 +455,37 @@ impl StringCommandCtx {
         Ok(resp_int(num_items as i64))
     }
 
+    /// Count the keys stored for the user key, meta, sub meta and data keys of all versions.
+    pub async fn do_async_txnkv_raw_key_count(mut self, key: &str) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let range = KEY_ENCODER.encode_txnkv_key_range(key);
+
+        client
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let cnt = txn_rc
+                        .lock()
+                        .await
+                        .scan_keys(range, u32::MAX)
+                        .await?
+                        .count();
+                    Ok(resp_int(cnt as i64))
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_exists(mut self, keys: &Vec<String>) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
         let keys = keys.to_owned();
 
-        // if get is executed from a new transaction, we can do get with latest commit
-        if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
-            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
-        }
-
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -432,9 +507,7 @@ impl StringCommandCtx {
                         if let Some(v) = kv_map.get(&ekeys[idx]) {
                             let ttl = KeyDecoder::decode_key_ttl(v);
                             if key_is_expired(ttl) {
-                                self.clone()
-                                    .do_async_txnkv_string_expire_if_needed(&keys[idx])
-                                    .await?;
+                                submit_expired_key(&keys[idx]);
                             } else {
                                 cnt += 1;
                             }
@@ -492,6 +565,7 @@ impl StringCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone())
@@ -532,7 +606,10 @@ impl StringCommandCtx {
             .await;
 
         match resp {
-            Ok(n) => Ok(resp_int(n)),
+            Ok(n) => {
+                notify_keyspace_event(NOTIFY_STRING, "incrby", &key);
+                Ok(resp_int(n))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -579,6 +656,7 @@ impl StringCommandCtx {
                             REMOVED_EXPIRED_KEY_COUNTER
                                 .with_label_values(&["string"])
                                 .inc();
+                            notify_keyspace_event(NOTIFY_EXPIRED, "expired", &key);
                             return Ok(1);
                         }
                     }
@@ -589,6 +667,56 @@ impl StringCommandCtx {
             .await
     }
 
+    /// Delete the key of any type if it is expired, return 1 if deleted.
+    pub async fn do_async_txnkv_expire_if_needed(mut self, key: &str) -> AsyncResult<i64> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let meta_value = txn_rc.lock().await.get(meta_key).await?;
+                    let dt = match meta_value {
+                        Some(v) if key_is_expired(KeyDecoder::decode_key_ttl(&v)) => {
+                            KeyDecoder::decode_key_type(&v)
+                        }
+                        _ => return Ok(0),
+                    };
+                    match dt {
+                        DataType::String => self.do_async_txnkv_string_expire_if_needed(&key).await,
+                        DataType::Hash => {
+                            HashCommandCtx::new(self.txn)
+                                .do_async_txnkv_hash_expire_if_needed(&key)
+                                .await
+                        }
+                        DataType::List => {
+                            ListCommandCtx::new(self.txn)
+                                .do_async_txnkv_list_expire_if_needed(&key)
+                                .await
+                        }
+                        DataType::Set => {
+                            SetCommandCtx::new(self.txn)
+                                .do_async_txnkv_set_expire_if_needed(&key)
+                                .await
+                        }
+                        DataType::Zset => {
+                            ZsetCommandCtx::new(self.txn)
+                                .do_async_txnkv_zset_expire_if_needed(&key)
+                                .await
+                        }
+                        DataType::Null => Ok(0),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_expire(mut self, key: &str, timestamp: u64) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
@@ -596,6 +724,7 @@ impl StringCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -691,7 +820,13 @@ impl StringCommandCtx {
             })
             .await;
         match resp {
-            Ok(v) => Ok(resp_int(v)),
+            Ok(v) => {
+                if v == 1 {
+                    let event = if timestamp == 0 { "persist" } else { "expire" };
+                    notify_keyspace_event(NOTIFY_GENERIC, event, &key);
+                }
+                Ok(resp_int(v))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -702,7 +837,7 @@ impl StringCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -712,36 +847,9 @@ impl StringCommandCtx {
 
                     match txn.get(ekey).await? {
                         Some(meta_value) => {
-                            let dt = KeyDecoder::decode_key_type(&meta_value);
                             let ttl = KeyDecoder::decode_key_ttl(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                match dt {
-                                    DataType::String => {
-                                        self.do_async_txnkv_string_expire_if_needed(&key).await?;
-                                    }
-                                    DataType::Hash => {
-                                        HashCommandCtx::new(self.txn.clone())
-                                            .do_async_txnkv_hash_expire_if_needed(&key)
-                                            .await?;
-                                    }
-                                    DataType::Set => {
-                                        SetCommandCtx::new(self.txn.clone())
-                                            .do_async_txnkv_set_expire_if_needed(&key)
-                                            .await?;
-                                    }
-                                    DataType::List => {
-                                        ListCommandCtx::new(self.txn.clone())
-                                            .do_async_txnkv_list_expire_if_needed(&key)
-                                            .await?;
-                                    }
-                                    DataType::Zset => {
-                                        ZsetCommandCtx::new(self.txn.clone())
-                                            .do_async_txnkv_zset_expire_if_needed(&key)
-                                            .await?;
-                                    }
-                                    _ => {}
-                                }
+                                submit_expired_key(&key);
                                 return Ok(resp_int(-2));
                             }
 
@@ -795,63 +903,722 @@ impl StringCommandCtx {
                         }
                     }
 
-                    let mut resp = 0;
+                    let mut deleted = Vec::with_capacity(keys_len);
                     for idx in 0..keys_len {
                         match dts[idx] {
                             DataType::String => {
                                 self.clone().do_async_txnkv_string_del(&keys[idx]).await?;
-                                resp += 1;
+                                deleted.push(keys[idx].clone());
                             }
                             DataType::Hash => {
                                 HashCommandCtx::new(self.txn.clone())
                                     .do_async_txnkv_hash_del(&keys[idx])
                                     .await?;
-                                resp += 1;
+                                deleted.push(keys[idx].clone());
                             }
                             DataType::List => {
                                 ListCommandCtx::new(self.txn.clone())
                                     .do_async_txnkv_list_del(&keys[idx])
                                     .await?;
-                                resp += 1;
+                                deleted.push(keys[idx].clone());
                             }
                             DataType::Set => {
                                 SetCommandCtx::new(self.txn.clone())
                                     .do_async_txnkv_set_del(&keys[idx])
                                     .await?;
-                                resp += 1;
+                                deleted.push(keys[idx].clone());
                             }
                             DataType::Zset => {
                                 ZsetCommandCtx::new(self.txn.clone())
                                     .do_async_txnk_zset_del(&keys[idx])
                                     .await?;
-                                resp += 1;
+                                deleted.push(keys[idx].clone());
                             }
                             DataType::Null => {}
                         }
                     }
-                    Ok(resp)
+                    Ok(deleted)
                 }
                 .boxed()
             })
             .await;
         match resp {
-            Ok(v) => Ok(resp_int(v)),
+            Ok(deleted) => {
+                for key in &deleted {
+                    notify_keyspace_event(NOTIFY_GENERIC, "del", key);
+                }
+                Ok(resp_int(deleted.len() as i64))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
 
-    pub async fn do_async_txnkv_scan(
+    async fn txnkv_del_by_type(self, key: &str, dt: DataType) -> AsyncResult<i64> {
+        match dt {
+            DataType::String => self.do_async_txnkv_string_del(key).await,
+            DataType::Hash => {
+                HashCommandCtx::new(self.txn)
+                    .do_async_txnkv_hash_del(key)
+                    .await
+            }
+            DataType::List => {
+                ListCommandCtx::new(self.txn)
+                    .do_async_txnkv_list_del(key)
+                    .await
+            }
+            DataType::Set => {
+                SetCommandCtx::new(self.txn)
+                    .do_async_txnkv_set_del(key)
+                    .await
+            }
+            DataType::Zset => {
+                ZsetCommandCtx::new(self.txn)
+                    .do_async_txnk_zset_del(key)
+                    .await
+            }
+            DataType::Null => Ok(0),
+        }
+    }
+
+    /// Copy the key `src` described by `meta_value` to `dst`, `dst` must not exist.
+    ///
+    /// Data keys embed the user key, so the elements of a collection are written again under a
+    /// new version of `dst`. If they are copied already by `txnkv_stage_copy` and `src` is not
+    /// modified since then, only the elements modified meanwhile are written in this transaction.
+    async fn txnkv_copy_key(
+        mut self,
+        src: &str,
+        dst: &str,
+        meta_value: Value,
+        staged: Option<StagedCopy>,
+    ) -> AsyncResult<()> {
+        let mut client = get_txn_client()?;
+        let src = src.to_owned();
+        let dst = dst.to_owned();
+        let dst_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&dst);
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let dt = KeyDecoder::decode_key_type(&meta_value);
+                    if let DataType::String = dt {
+                        // string value lives in the meta key, ttl is copied along with it
+                        txn_rc.lock().await.put(dst_meta_key, meta_value).await?;
+                        return Ok(());
+                    }
+
+                    let ttl = KeyDecoder::decode_key_ttl(&meta_value);
+                    let src_version = KeyDecoder::decode_key_version(&meta_value);
+
+                    // the staged version is taken back from gc, unless gc has cleaned it up
+                    let mut staged_version = None;
+                    if let Some(staged) = staged.filter(|s| s.src_meta_value == meta_value) {
+                        let gc_version_key =
+                            KEY_ENCODER.encode_txnkv_gc_version_key(&dst, staged.version);
+                        let mut txn = txn_rc.lock().await;
+                        if txn.get(gc_version_key.clone()).await?.is_some() {
+                            txn.delete(gc_version_key).await?;
+                            staged_version = Some(staged.version);
+                        }
+                    }
+                    let version = match staged_version {
+                        Some(version) => version,
+                        None => get_version_for_new(&dst, txn_rc.clone()).await?,
+                    };
+
+                    let mut txn = txn_rc.lock().await;
+                    let src_starts = data_key_starts(&dt, &src, src_version);
+                    let dst_starts = data_key_starts(&dt, &dst, version);
+                    let mut size: i64 = 0;
+                    for (idx, (src_start, dst_start)) in
+                        src_starts.iter().zip(&dst_starts).enumerate()
+                    {
+                        let mut from = Some(src_start.clone());
+                        while let Some(start) = from {
+                            let (count, next) = copy_data_batch(
+                                &mut txn,
+                                src_start,
+                                dst_start,
+                                start,
+                                staged_version.is_some(),
+                            )
+                            .await?;
+                            // the first range holds one key per element
+                            if idx == 0 {
+                                size += count;
+                            }
+                            from = next;
+                        }
+                        if staged_version.is_some() {
+                            let mut from = Some(dst_start.clone());
+                            while let Some(start) = from {
+                                from = remove_stale_copies(&mut txn, src_start, dst_start, start)
+                                    .await?;
+                            }
+                        }
+                    }
+
+                    let new_meta_value = match dt {
+                        DataType::Hash => KEY_ENCODER.encode_txnkv_hash_meta_value(ttl, version, 0),
+                        DataType::List => {
+                            let (_, _, left, right) = KeyDecoder::decode_key_list_meta(&meta_value);
+                            KEY_ENCODER.encode_txnkv_list_meta_value(ttl, version, left, right)
+                        }
+                        DataType::Set => KEY_ENCODER.encode_txnkv_set_meta_value(ttl, version, 0),
+                        DataType::Zset => KEY_ENCODER.encode_txnkv_zset_meta_value(ttl, version, 0),
+                        _ => return Ok(()),
+                    };
+
+                    // list keeps its size in meta value, others need a sub meta key
+                    if !matches!(dt, DataType::List) {
+                        let sub_meta_key = KEY_ENCODER.encode_txnkv_sub_meta_key(
+                            &dst,
+                            version,
+                            gen_next_meta_index(),
+                        );
+                        txn.put(sub_meta_key, size.to_be_bytes().to_vec()).await?;
+                    }
+                    txn.put(dst_meta_key, new_meta_value).await?;
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Copy the elements of the collection `src` in batches to a new version of `dst`, which is
+    /// invisible until `txnkv_copy_key` writes the meta key of `dst`. The version is registered
+    /// to gc with a lease renewed by every batch, so an abandoned copy is cleaned up by gc.
+    ///
+    /// Returns None if `src` is not a collection larger than a batch, if `dst` exists and is not
+    /// to be overwritten, or if the copy is abandoned because `src` is modified.
+    async fn txnkv_stage_copy(
+        src: &str,
+        dst: &str,
+        overwrite: bool,
+    ) -> AsyncResult<Option<StagedCopy>> {
+        // the copy relies on gc to clean up the version if it is abandoned
+        if !async_deletion_enabled_or_default() {
+            return Ok(None);
+        }
+        let src = src.to_owned();
+        let dst = dst.to_owned();
+        let src_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&src);
+        let dst_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&dst);
+
+        let staged = get_txn_client()?
+            .exec_in_txn(None, |txn_rc| {
+                let src = src.clone();
+                let dst = dst.clone();
+                let src_meta_key = src_meta_key.clone();
+                async move {
+                    let mut txn = txn_rc.lock().await;
+                    let src_meta_value = match txn.get(src_meta_key).await? {
+                        Some(v) if !key_is_expired(KeyDecoder::decode_key_ttl(&v)) => v,
+                        _ => return Ok(None),
+                    };
+                    let dt = KeyDecoder::decode_key_type(&src_meta_value);
+                    let src_version = KeyDecoder::decode_key_version(&src_meta_value);
+                    let first = match data_key_starts(&dt, &src, src_version).into_iter().next() {
+                        Some(start) => start,
+                        None => return Ok(None),
+                    };
+                    let count = txn
+                        .scan_keys(data_key_range(&first, first.clone()), COPY_BATCH_SIZE + 1)
+                        .await?
+                        .count();
+                    if count <= COPY_BATCH_SIZE as usize {
+                        return Ok(None);
+                    }
+
+                    // the existing version of dst is deleted only when the copy is done
+                    let mut dst_version = None;
+                    if let Some(v) = txn.get(dst_meta_key).await? {
+                        if !overwrite && !key_is_expired(KeyDecoder::decode_key_ttl(&v)) {
+                            return Ok(None);
+                        }
+                        if !matches!(KeyDecoder::decode_key_type(&v), DataType::String) {
+                            dst_version = Some(KeyDecoder::decode_key_version(&v));
+                        }
+                    }
+                    drop(txn);
+
+                    let mut version = get_version_for_new(&dst, txn_rc.clone()).await?;
+                    let mut txn = txn_rc.lock().await;
+                    if dst_version == Some(version) {
+                        version = version.wrapping_add(1);
+                        let gc_version_key = KEY_ENCODER.encode_txnkv_gc_version_key(&dst, version);
+                        if txn.get(gc_version_key).await?.is_some() {
+                            return Ok(None);
+                        }
+                    }
+
+                    // new versions of dst are allocated after this one
+                    let gc_key = KEY_ENCODER.encode_txnkv_gc_key(&dst);
+                    txn.put(gc_key, version.to_be_bytes().to_vec()).await?;
+                    let gc_version_key = KEY_ENCODER.encode_txnkv_gc_version_key(&dst, version);
+                    txn.put(gc_version_key, staged_gc_version_value(dt)).await?;
+                    Ok(Some(StagedCopy {
+                        src_meta_value,
+                        version,
+                    }))
+                }
+                .boxed()
+            })
+            .await?;
+
+        let staged = match staged {
+            Some(staged) => staged,
+            None => return Ok(None),
+        };
+        let dt = KeyDecoder::decode_key_type(&staged.src_meta_value);
+        let src_version = KeyDecoder::decode_key_version(&staged.src_meta_value);
+        let src_starts = data_key_starts(&dt, &src, src_version);
+        let dst_starts = data_key_starts(&dt, &dst, staged.version);
+        for (src_start, dst_start) in src_starts.into_iter().zip(dst_starts) {
+            let mut from = Some(src_start.clone());
+            while let Some(start) = from {
+                // every batch is a small transaction, each retried on its own
+                let (valid, next) = get_txn_client()?
+                    .exec_in_txn(None, |txn_rc| {
+                        let staged = staged.clone();
+                        let dst = dst.clone();
+                        let src_meta_key = src_meta_key.clone();
+                        let src_start = src_start.clone();
+                        let dst_start = dst_start.clone();
+                        async move {
+                            let mut txn = txn_rc.lock().await;
+                            // give up if src is modified or gc has cleaned up the version
+                            let gc_version_key =
+                                KEY_ENCODER.encode_txnkv_gc_version_key(&dst, staged.version);
+                            if txn.get(src_meta_key).await? != Some(staged.src_meta_value.clone())
+                                || txn.get(gc_version_key.clone()).await?.is_none()
+                            {
+                                return Ok((false, None));
+                            }
+                            let dt = KeyDecoder::decode_key_type(&staged.src_meta_value);
+                            txn.put(gc_version_key, staged_gc_version_value(dt)).await?;
+                            let (_, next) =
+                                copy_data_batch(&mut txn, &src_start, &dst_start, start, false)
+                                    .await?;
+                            Ok((true, next))
+                        }
+                        .boxed()
+                    })
+                    .await?;
+                if !valid {
+                    return Ok(None);
+                }
+                from = next;
+            }
+        }
+        Ok(Some(staged))
+    }
+
+    /// Rename `src` to `dst`, overwriting `dst` unless `is_nx` is set.
+    ///
+    /// Large collections are copied in batches before the transaction of the command, unless it
+    /// runs in MULTI or a script. The old version of `src` is removed the same way as `DEL`.
+    pub async fn do_async_txnkv_rename(
+        mut self,
+        src: &str,
+        dst: &str,
+        is_nx: bool,
+    ) -> AsyncResult<Frame> {
+        let staged = if self.txn.is_none() && src != dst {
+            // an abandoned staged version is cleaned up by gc
+            match Self::txnkv_stage_copy(src, dst, !is_nx).await {
+                Ok(staged) => staged,
+                Err(e) => return Ok(resp_err(e)),
+            }
+        } else {
+            None
+        };
+
+        let mut client = get_txn_client()?;
+        let src = src.to_owned();
+        let dst = dst.to_owned();
+        let src_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&src);
+        let dst_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&dst);
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let src = src.clone();
+                let dst = dst.clone();
+                let staged = staged.clone();
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    let src_meta_value = match txn.get(src_meta_key).await? {
+                        Some(v) if !key_is_expired(KeyDecoder::decode_key_ttl(&v)) => v,
+                        _ => return Err(REDIS_NO_SUCH_KEY_ERR),
+                    };
+                    if src == dst {
+                        return Ok(if is_nx { 0 } else { 1 });
+                    }
+                    let dst_meta_value = txn.get(dst_meta_key).await?;
+                    drop(txn);
+
+                    if let Some(v) = dst_meta_value {
+                        if is_nx && !key_is_expired(KeyDecoder::decode_key_ttl(&v)) {
+                            return Ok(0);
+                        }
+                        self.clone()
+                            .txnkv_del_by_type(&dst, KeyDecoder::decode_key_type(&v))
+                            .await?;
+                    }
+
+                    let src_dt = KeyDecoder::decode_key_type(&src_meta_value);
+                    self.clone()
+                        .txnkv_copy_key(&src, &dst, src_meta_value, staged)
+                        .await?;
+                    self.txnkv_del_by_type(&src, src_dt).await?;
+                    Ok(1)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => {
+                if n == 1 && src != dst {
+                    notify_keyspace_event(NOTIFY_GENERIC, "rename_from", &src);
+                    notify_keyspace_event(NOTIFY_GENERIC, "rename_to", &dst);
+                }
+                if is_nx {
+                    Ok(resp_int(n))
+                } else {
+                    Ok(resp_ok())
+                }
+            }
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    pub async fn do_async_txnkv_copy(
+        mut self,
+        src: &str,
+        dst: &str,
+        replace: bool,
+    ) -> AsyncResult<Frame> {
+        if src == dst {
+            return Ok(resp_err(REDIS_SAME_OBJECT_ERR));
+        }
+        let staged = if self.txn.is_none() {
+            match Self::txnkv_stage_copy(src, dst, replace).await {
+                Ok(staged) => staged,
+                Err(e) => return Ok(resp_err(e)),
+            }
+        } else {
+            None
+        };
+
+        let mut client = get_txn_client()?;
+        let src = src.to_owned();
+        let dst = dst.to_owned();
+        let src_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&src);
+        let dst_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&dst);
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let dst = dst.clone();
+                let staged = staged.clone();
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    let src_meta_value = match txn.get(src_meta_key).await? {
+                        Some(v) if !key_is_expired(KeyDecoder::decode_key_ttl(&v)) => v,
+                        _ => return Ok(0),
+                    };
+                    let dst_meta_value = txn.get(dst_meta_key).await?;
+                    drop(txn);
+
+                    if let Some(v) = dst_meta_value {
+                        if !replace && !key_is_expired(KeyDecoder::decode_key_ttl(&v)) {
+                            return Ok(0);
+                        }
+                        self.clone()
+                            .txnkv_del_by_type(&dst, KeyDecoder::decode_key_type(&v))
+                            .await?;
+                    }
+
+                    self.txnkv_copy_key(&src, &dst, src_meta_value, staged)
+                        .await?;
+                    Ok(1)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => {
+                if n == 1 {
+                    notify_keyspace_event(NOTIFY_GENERIC, "copy_to", &dst);
+                }
+                Ok(resp_int(n))
+            }
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Serialize the value of `key` in redis rdb format, return nil if key not exists.
+    pub async fn do_async_txnkv_dump(mut self, key: &str) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        let resp = client
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    let meta_value = match txn.get(meta_key).await? {
+                        Some(v) if !key_is_expired(KeyDecoder::decode_key_ttl(&v)) => v,
+                        _ => return Ok(None),
+                    };
+                    let version = KeyDecoder::decode_key_version(&meta_value);
+
+                    let value = match KeyDecoder::decode_key_type(&meta_value) {
+                        DataType::String => RdbValue::String(
+                            KeyDecoder::decode_key_string_slice(&meta_value).to_vec(),
+                        ),
+                        DataType::Hash => {
+                            let bound_range =
+                                KEY_ENCODER.encode_txnkv_hash_data_key_range(&key, version);
+                            let iter = txn.scan(bound_range, u32::MAX).await?;
+                            RdbValue::Hash(
+                                iter.map(|kv| {
+                                    let field = KeyDecoder::decode_key_hash_userkey_from_datakey(
+                                        &key, kv.0,
+                                    );
+                                    (field, kv.1)
+                                })
+                                .collect(),
+                            )
+                        }
+                        DataType::List => {
+                            let bound_range =
+                                KEY_ENCODER.encode_txnkv_list_data_key_range(&key, version);
+                            let iter = txn.scan(bound_range, u32::MAX).await?;
+                            RdbValue::List(iter.map(|kv| kv.1).collect())
+                        }
+                        DataType::Set => {
+                            let bound_range =
+                                KEY_ENCODER.encode_txnkv_set_data_key_range(&key, version);
+                            let iter = txn.scan_keys(bound_range, u32::MAX).await?;
+                            RdbValue::Set(
+                                iter.map(|k| {
+                                    KeyDecoder::decode_key_set_member_from_datakey(&key, k)
+                                })
+                                .collect(),
+                            )
+                        }
+                        DataType::Zset => {
+                            let bound_range =
+                                KEY_ENCODER.encode_txnkv_zset_data_key_range(&key, version);
+                            let iter = txn.scan(bound_range, u32::MAX).await?;
+                            RdbValue::Zset(
+                                iter.map(|kv| {
+                                    let member =
+                                        KeyDecoder::decode_key_zset_member_from_datakey(&key, kv.0);
+                                    (member, KeyDecoder::decode_key_zset_data_value(&kv.1))
+                                })
+                                .collect(),
+                            )
+                        }
+                        DataType::Null => return Ok(None),
+                    };
+                    Ok(Some(rdb::dump_payload(&value)))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(Some(payload)) => Ok(resp_bulk(payload)),
+            Ok(None) => Ok(resp_nil()),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Create `key` from a DUMP payload, `ttl` is the absolute expire timestamp in millis or 0.
+    pub async fn do_async_txnkv_restore(
+        mut self,
+        key: &str,
+        payload: &[u8],
+        ttl: u64,
+        replace: bool,
+    ) -> AsyncResult<Frame> {
+        let value = match rdb::restore_payload(payload) {
+            Ok(v) => v,
+            Err(e) => return Ok(resp_err(e)),
+        };
+
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let meta_value = txn_rc.lock().await.get(meta_key.clone()).await?;
+                    if let Some(v) = meta_value {
+                        if !replace && !key_is_expired(KeyDecoder::decode_key_ttl(&v)) {
+                            return Err(REDIS_BUSY_KEY_ERR);
+                        }
+                        self.clone()
+                            .txnkv_del_by_type(&key, KeyDecoder::decode_key_type(&v))
+                            .await?;
+                    }
+
+                    // the key is expired as soon as it is created
+                    if key_is_expired(ttl) {
+                        return Ok(());
+                    }
+
+                    if let RdbValue::String(v) = value {
+                        let val = KEY_ENCODER.encode_txnkv_string_slice(&v, ttl);
+                        txn_rc.lock().await.put(meta_key, val).await?;
+                        return Ok(());
+                    }
+
+                    let version = get_version_for_new(&key, txn_rc.clone()).await?;
+                    let mut txn = txn_rc.lock().await;
+                    let (size, new_meta_value) = match value {
+                        RdbValue::Hash(pairs) => {
+                            let fields: Vec<&Vec<u8>> = pairs.iter().map(|(f, _)| f).collect();
+                            if count_unique_keys(&fields) != pairs.len() {
+                                return Err(REDIS_BAD_DATA_FORMAT_ERR);
+                            }
+                            for (field, value) in &pairs {
+                                let field =
+                                    str::from_utf8(field).map_err(|_| REDIS_BAD_DATA_FORMAT_ERR)?;
+                                let data_key =
+                                    KEY_ENCODER.encode_txnkv_hash_data_key(&key, field, version);
+                                txn.put(data_key, value.to_vec()).await?;
+                            }
+                            (
+                                pairs.len(),
+                                KEY_ENCODER.encode_txnkv_hash_meta_value(ttl, version, 0),
+                            )
+                        }
+                        RdbValue::List(elements) => {
+                            let left = INIT_INDEX;
+                            let right = left + elements.len() as u64;
+                            for (idx, ele) in (left..right).zip(elements) {
+                                let data_key =
+                                    KEY_ENCODER.encode_txnkv_list_data_key(&key, idx, version);
+                                txn.put(data_key, ele).await?;
+                            }
+                            (
+                                0,
+                                KEY_ENCODER.encode_txnkv_list_meta_value(ttl, version, left, right),
+                            )
+                        }
+                        RdbValue::Set(members) => {
+                            if count_unique_keys(&members) != members.len() {
+                                return Err(REDIS_BAD_DATA_FORMAT_ERR);
+                            }
+                            for member in &members {
+                                let member = str::from_utf8(member)
+                                    .map_err(|_| REDIS_BAD_DATA_FORMAT_ERR)?;
+                                let data_key =
+                                    KEY_ENCODER.encode_txnkv_set_data_key(&key, member, version);
+                                txn.put(data_key, vec![0]).await?;
+                            }
+                            (
+                                members.len(),
+                                KEY_ENCODER.encode_txnkv_set_meta_value(ttl, version, 0),
+                            )
+                        }
+                        RdbValue::Zset(pairs) => {
+                            let members: Vec<&Vec<u8>> = pairs.iter().map(|(m, _)| m).collect();
+                            if count_unique_keys(&members) != pairs.len() {
+                                return Err(REDIS_BAD_DATA_FORMAT_ERR);
+                            }
+                            for (member, score) in &pairs {
+                                let member = str::from_utf8(member)
+                                    .map_err(|_| REDIS_BAD_DATA_FORMAT_ERR)?;
+                                let data_key =
+                                    KEY_ENCODER.encode_txnkv_zset_data_key(&key, member, version);
+                                let score_key = KEY_ENCODER
+                                    .encode_txnkv_zset_score_key(&key, *score, member, version);
+                                txn.put(data_key, KEY_ENCODER.encode_txnkv_zset_data_value(*score))
+                                    .await?;
+                                txn.put(score_key, member.as_bytes().to_vec()).await?;
+                            }
+                            (
+                                pairs.len(),
+                                KEY_ENCODER.encode_txnkv_zset_meta_value(ttl, version, 0),
+                            )
+                        }
+                        RdbValue::String(_) => unreachable!(),
+                    };
+
+                    // list keeps its size in meta value, others need a sub meta key
+                    if size > 0 {
+                        let sub_meta_key = KEY_ENCODER.encode_txnkv_sub_meta_key(
+                            &key,
+                            version,
+                            gen_next_meta_index(),
+                        );
+                        txn.put(sub_meta_key, (size as i64).to_be_bytes().to_vec())
+                            .await?;
+                    }
+                    txn.put(meta_key, new_meta_value).await?;
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(_) => {
+                notify_keyspace_event(NOTIFY_GENERIC, "restore", &key);
+                Ok(resp_ok())
+            }
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Collect keys after the cursor `start` which match the glob `pattern` and `key_type`.
+    ///
+    /// At most `count` meta keys are visited, the returned cursor is empty when all keys are
+    /// visited. The literal prefix of the pattern narrows the range to be scanned.
+    async fn txnkv_scan_keys(
         mut self,
         start: &str,
         count: u32,
-        regex: &str,
-    ) -> AsyncResult<Frame> {
+        pattern: &[u8],
+        key_type: Option<&str>,
+    ) -> AsyncResult<(Vec<Vec<u8>>, Vec<u8>)> {
         let mut client = get_txn_client()?;
         let ekey = KEY_ENCODER.encode_txnkv_string(start);
-        let re = Regex::new(regex).unwrap();
+        let (prefix_start, prefix_end) =
+            KEY_ENCODER.encode_txnkv_meta_key_prefix_bound(&glob_literal_prefix(pattern));
+        let pattern = pattern.to_vec();
+        let key_type = key_type.map(|t| t.to_lowercase());
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -862,17 +1629,23 @@ impl StringCommandCtx {
                     let mut next_key = vec![];
                     let mut txn = txn_rc.lock().await;
 
-                    let mut left_bound = ekey.clone();
+                    // a cursor key is returned by the last call already, it is skipped, while
+                    // the start of the pattern prefix is inclusive
+                    let (mut left_bound, mut skip_key) = if ekey > prefix_start {
+                        (ekey.clone(), Some(ekey))
+                    } else {
+                        (prefix_start, None)
+                    };
 
                     // set to a non-zore value before loop
                     let mut last_round_iter_count = 1;
                     while retrieved_key_count < count as usize {
-                        if last_round_iter_count == 0 {
+                        if last_round_iter_count == 0 || left_bound >= prefix_end {
                             next_key = vec![];
                             break;
                         }
 
-                        let range = left_bound.clone()..KEY_ENCODER.encode_txnkv_keyspace_end();
+                        let range = left_bound.clone()..prefix_end.clone();
                         let bound_range: BoundRange = range.into();
 
                         // the iterator will scan all keyspace include sub metakey and datakey
@@ -881,12 +1654,12 @@ impl StringCommandCtx {
                         // reset count to zero
                         last_round_iter_count = 0;
                         while let Some(kv) = iter.next().await {
-                            // skip the left bound key, this should be exclusive
-                            if kv.0 == left_bound {
+                            // skip the last key of the previous round, it is handled already
+                            if skip_key.as_ref() == Some(&kv.0) {
                                 continue;
                             }
                             left_bound = kv.0.clone();
-                            // left bound key is exclusive
+                            skip_key = Some(kv.0.clone());
                             last_round_iter_count += 1;
 
                             let (userkey, is_meta_key) =
@@ -898,37 +1671,227 @@ impl StringCommandCtx {
                             }
 
                             let ttl = KeyDecoder::decode_key_ttl(&kv.1);
-                            // delete it if it is expired
-                            if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_del(&vec![
-                                        String::from_utf8_lossy(&userkey).to_string()
-                                    ])
-                                    .await?;
-                                txn = txn_rc.lock().await;
+                            let expired = key_is_expired(ttl);
+                            if expired {
+                                submit_expired_key(&String::from_utf8_lossy(&userkey));
                             }
-                            if retrieved_key_count == (count - 1) as usize {
+
+                            let type_matched = match &key_type {
+                                Some(t) => KeyDecoder::decode_key_type(&kv.1).to_string() == *t,
+                                None => true,
+                            };
+                            let matched =
+                                !expired && type_matched && glob_match(&pattern, &userkey);
+
+                            retrieved_key_count += 1;
+                            if retrieved_key_count == count as usize {
                                 next_key = userkey.clone();
-                                retrieved_key_count += 1;
-                                if re.is_match(&userkey) {
-                                    keys.push(resp_bulk(userkey));
-                                }
-                                break;
                             }
-                            retrieved_key_count += 1;
-                            if re.is_match(&userkey) {
-                                keys.push(resp_bulk(userkey));
+                            if matched {
+                                keys.push(userkey);
+                            }
+                            if retrieved_key_count == count as usize {
+                                break;
                             }
                         }
                     }
-                    let resp_next_key = resp_bulk(next_key);
-                    let resp_keys = resp_array(keys);
 
-                    Ok(resp_array(vec![resp_next_key, resp_keys]))
+                    Ok((keys, next_key))
                 }
                 .boxed()
             })
             .await
     }
+
+    pub async fn do_async_txnkv_scan(
+        self,
+        start: &str,
+        count: u32,
+        pattern: &[u8],
+        key_type: Option<&str>,
+    ) -> AsyncResult<Frame> {
+        let (keys, next_key) = self
+            .txnkv_scan_keys(start, count, pattern, key_type)
+            .await?;
+
+        let resp_next_key = resp_bulk(next_key);
+        let resp_keys = resp_array(keys.into_iter().map(resp_bulk).collect());
+        Ok(resp_array(vec![resp_next_key, resp_keys]))
+    }
+
+    pub async fn do_async_txnkv_keys(self, pattern: &[u8]) -> AsyncResult<Frame> {
+        let (keys, _) = self.txnkv_scan_keys("", u32::MAX, pattern, None).await?;
+
+        Ok(resp_array(keys.into_iter().map(resp_bulk).collect()))
+    }
+
+    /// Lock the watched keys in the transaction of EXEC, which begins at the first WATCH. The
+    /// meta key, sub meta keys and data keys are locked, any write to the key since WATCH
+    /// conflicts with the transaction when it commits.
+    pub async fn do_async_txnkv_lock_keys(mut self, keys: &[String]) -> AsyncResult<()> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    for key in &keys {
+                        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+                        let mut lock_keys = vec![meta_key.clone()];
+                        if let Some(meta_value) = txn.get(meta_key).await? {
+                            let dt = KeyDecoder::decode_key_type(&meta_value);
+                            if !matches!(dt, DataType::String) {
+                                // elements are modified by writing their data keys only
+                                let version = KeyDecoder::decode_key_version(&meta_value);
+                                if let Some(start) =
+                                    data_key_starts(&dt, key, version).into_iter().next()
+                                {
+                                    let range = data_key_range(&start, start.clone());
+                                    lock_keys.extend(txn.scan_keys(range, u32::MAX).await?);
+                                }
+                                // new members are counted in a random sub meta key
+                                if !matches!(dt, DataType::List) {
+                                    for idx in 0..config_meta_key_number_or_default() {
+                                        lock_keys.push(
+                                            KEY_ENCODER
+                                                .encode_txnkv_sub_meta_key(key, version, idx),
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                        txn.lock_keys(lock_keys).await?;
+                    }
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await
+    }
+}
+
+/// A collection copied to a version of the destination key which is not visible yet
+#[derive(Clone)]
+struct StagedCopy {
+    // meta value of the source when the copy started, the copy is stale once it changes
+    src_meta_value: Value,
+    version: u16,
+}
+
+// value of the gc version key of a staged copy, gc skips it until the lease expires
+fn staged_gc_version_value(dt: DataType) -> Vec<u8> {
+    let mut value = vec![KEY_ENCODER.get_type_bytes(dt)];
+    value.extend_from_slice(&now_timestamp_in_millis().to_be_bytes());
+    value
+}
+
+// start keys of the data ranges of a collection, every data key starts with one of them
+fn data_key_starts(dt: &DataType, key: &str, version: u16) -> Vec<Vec<u8>> {
+    let starts = match dt {
+        DataType::Hash => vec![KEY_ENCODER.encode_txnkv_hash_data_key_start(key, version)],
+        DataType::List => vec![KEY_ENCODER.encode_txnkv_list_data_key_start(key, version)],
+        DataType::Set => vec![KEY_ENCODER.encode_txnkv_set_data_key_start(key, version)],
+        DataType::Zset => vec![
+            KEY_ENCODER.encode_txnkv_zset_data_key_start(key, version),
+            KEY_ENCODER.encode_txnkv_zset_score_key_start(key, version),
+        ],
+        DataType::String | DataType::Null => vec![],
+    };
+    starts.into_iter().map(Into::into).collect()
+}
+
+// the data keys starting with `start`, from the key `from` on
+fn data_key_range(start: &[u8], from: Vec<u8>) -> BoundRange {
+    // start ends with the placeholder, the range ends before the byte following it
+    let mut end = start.to_vec();
+    if let Some(last) = end.last_mut() {
+        *last += 1;
+    }
+    let range: Range<Key> = Key::from(from)..Key::from(end);
+    range.into()
+}
+
+// the key following the last one of a full batch, None if the batch is the last one
+fn next_batch_start(last: Option<&Vec<u8>>, len: usize) -> Option<Vec<u8>> {
+    if len < COPY_BATCH_SIZE as usize {
+        return None;
+    }
+    last.map(|key| {
+        let mut next = key.clone();
+        next.push(0);
+        next
+    })
+}
+
+// Copy a batch of the data keys starting with `src_start` from `from` on, to the keys starting
+// with `dst_start`, the copies holding the same value are not written again if `staged`.
+// Returns the number of data keys in the batch and the key to continue from.
+async fn copy_data_batch(
+    txn: &mut Transaction,
+    src_start: &[u8],
+    dst_start: &[u8],
+    from: Vec<u8>,
+    staged: bool,
+) -> AsyncResult<(i64, Option<Vec<u8>>)> {
+    let pairs: Vec<(Vec<u8>, Value)> = txn
+        .scan(data_key_range(src_start, from), COPY_BATCH_SIZE)
+        .await?
+        .map(|kv| (kv.0.into(), kv.1))
+        .collect();
+    let copies: Vec<Vec<u8>> = pairs
+        .iter()
+        .map(|(key, _)| [dst_start, &key[src_start.len()..]].concat())
+        .collect();
+    let mut copied = HashMap::new();
+    if staged {
+        for kv in txn.batch_get(copies.clone()).await? {
+            copied.insert(Vec::<u8>::from(kv.0), kv.1);
+        }
+    }
+
+    let next = next_batch_start(pairs.last().map(|(key, _)| key), pairs.len());
+    let count = pairs.len() as i64;
+    for ((_, value), copy) in pairs.into_iter().zip(copies) {
+        if copied.get(&copy) != Some(&value) {
+            txn.put(copy, value).await?;
+        }
+    }
+    Ok((count, next))
+}
+
+// Delete a batch of the keys starting with `dst_start` from `from` on, whose data key starting
+// with `src_start` is removed after it was copied. Returns the key to continue from.
+async fn remove_stale_copies(
+    txn: &mut Transaction,
+    src_start: &[u8],
+    dst_start: &[u8],
+    from: Vec<u8>,
+) -> AsyncResult<Option<Vec<u8>>> {
+    let copies: Vec<Vec<u8>> = txn
+        .scan_keys(data_key_range(dst_start, from), COPY_BATCH_SIZE)
+        .await?
+        .map(Into::into)
+        .collect();
+    let sources: Vec<Vec<u8>> = copies
+        .iter()
+        .map(|key| [src_start, &key[dst_start.len()..]].concat())
+        .collect();
+    let existing: HashSet<Vec<u8>> = txn
+        .batch_get(sources.clone())
+        .await?
+        .map(|kv| kv.0.into())
+        .collect();
+
+    let next = next_batch_start(copies.last(), copies.len());
+    for (copy, source) in copies.into_iter().zip(sources) {
+        if !existing.contains(&source) {
+            txn.delete(copy).await?;
+        }
+    }
+    Ok(next)
 }
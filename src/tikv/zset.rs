@@ -9,6 +9,7 @@ use super::{
 };
 use crate::async_del_zset_threshold_or_default;
 use crate::async_expire_zset_threshold_or_default;
+use crate::frame::is_resp3;
 use crate::utils::{key_is_expired, resp_array, resp_bulk, resp_err, resp_int, resp_nil};
 use crate::Frame;
 use ::futures::future::FutureExt;
@@ -19,7 +20,9 @@ use std::sync::Arc;
 use tikv_client::{BoundRange, Key, Transaction, Value};
 use tokio::sync::Mutex;
 
+use crate::gc::submit_expired_key;
 use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED, NOTIFY_ZSET};
 
 #[derive(Clone)]
 pub struct ZsetCommandCtx {
@@ -91,6 +94,7 @@ impl ZsetCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -318,7 +322,10 @@ impl ZsetCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_int(v as i64)),
+            Ok(v) => {
+                notify_keyspace_event(NOTIFY_ZSET, "zadd", &key);
+                Ok(resp_int(v as i64))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -329,7 +336,7 @@ impl ZsetCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -347,9 +354,7 @@ impl ZsetCommandCtx {
                             drop(txn);
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                self.clone()
-                                    .do_async_txnkv_zset_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_int(0));
                             }
 
@@ -371,7 +376,7 @@ impl ZsetCommandCtx {
         let member = member.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -388,10 +393,7 @@ impl ZsetCommandCtx {
 
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_zset_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_nil());
                             }
 
@@ -400,7 +402,7 @@ impl ZsetCommandCtx {
                             match txn.get(data_key).await? {
                                 Some(data_value) => {
                                     let score = KeyDecoder::decode_key_zset_data_value(&data_value);
-                                    Ok(resp_bulk(score.to_string().as_bytes().to_vec()))
+                                    Ok(Frame::Double(score))
                                 }
                                 None => Ok(resp_nil()),
                             }
@@ -426,7 +428,7 @@ impl ZsetCommandCtx {
         let key = key.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -443,10 +445,7 @@ impl ZsetCommandCtx {
 
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_zset_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_array(vec![]));
                             }
 
@@ -491,9 +490,10 @@ impl ZsetCommandCtx {
         let mut client = get_txn_client()?;
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
         let key = key.to_owned();
+        let resp3 = is_resp3();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -512,9 +512,7 @@ impl ZsetCommandCtx {
                             drop(txn);
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                self.clone()
-                                    .do_async_txnkv_zset_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_array(vec![]));
                             }
                             let size = self.txnkv_sum_key_size(&key, version).await?;
@@ -553,24 +551,18 @@ impl ZsetCommandCtx {
 
                                 // decode member key from data key
                                 let member = kv.1;
-                                if reverse {
-                                    resp.insert(0, resp_bulk(member));
-                                } else {
-                                    resp.push(resp_bulk(member));
-                                }
-                                if with_scores {
+                                let score = if with_scores {
                                     // decode vec[u8] to f64
-                                    let score =
-                                        KeyDecoder::decode_key_zset_score_from_scorekey(&key, kv.0);
-                                    if reverse {
-                                        resp.insert(
-                                            1,
-                                            resp_bulk(score.to_string().as_bytes().to_vec()),
-                                        );
-                                    } else {
-                                        resp.push(resp_bulk(score.to_string().as_bytes().to_vec()));
-                                    }
-                                }
+                                    Some(KeyDecoder::decode_key_zset_score_from_scorekey(
+                                        &key, kv.0,
+                                    ))
+                                } else {
+                                    None
+                                };
+                                push_range_member(&mut resp, member, score, reverse, resp3);
+                            }
+                            if reverse {
+                                resp.reverse();
                             }
                             Ok(resp_array(resp))
                         }
@@ -596,9 +588,10 @@ impl ZsetCommandCtx {
         let mut client = get_txn_client()?;
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
         let key = key.to_owned();
+        let resp3 = is_resp3();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -617,9 +610,7 @@ impl ZsetCommandCtx {
                             drop(txn);
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                self.clone()
-                                    .do_async_txnkv_zset_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_array(vec![]));
                             }
 
@@ -655,24 +646,18 @@ impl ZsetCommandCtx {
 
                             while let Some(kv) = iter.next().await {
                                 let member = kv.1;
-                                if reverse {
-                                    resp.insert(0, resp_bulk(member));
-                                } else {
-                                    resp.push(resp_bulk(member));
-                                }
-                                if with_scores {
+                                let score = if with_scores {
                                     // decode score from score key
-                                    let score =
-                                        KeyDecoder::decode_key_zset_score_from_scorekey(&key, kv.0);
-                                    if reverse {
-                                        resp.insert(
-                                            1,
-                                            resp_bulk(score.to_string().as_bytes().to_vec()),
-                                        );
-                                    } else {
-                                        resp.push(resp_bulk(score.to_string().as_bytes().to_vec()));
-                                    }
-                                }
+                                    Some(KeyDecoder::decode_key_zset_score_from_scorekey(
+                                        &key, kv.0,
+                                    ))
+                                } else {
+                                    None
+                                };
+                                push_range_member(&mut resp, member, score, reverse, resp3);
+                            }
+                            if reverse {
+                                resp.reverse();
                             }
                             Ok(resp_array(resp))
                         }
@@ -701,6 +686,7 @@ impl ZsetCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -747,7 +733,7 @@ impl ZsetCommandCtx {
                                         &key,
                                         k.clone(),
                                     );
-                                    resp.push(resp_bulk(score.to_string().as_bytes().to_vec()));
+                                    resp.push(Frame::Double(score));
 
                                     txn.delete(data_key).await?;
                                     txn.delete(k).await?;
@@ -775,7 +761,7 @@ impl ZsetCommandCtx {
                                         &key,
                                         k.clone(),
                                     );
-                                    resp.push(resp_bulk(score.to_string().as_bytes().to_vec()));
+                                    resp.push(Frame::Double(score));
 
                                     txn.delete(data_key).await?;
                                     txn.delete(k).await?;
@@ -824,7 +810,13 @@ impl ZsetCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_array(v)),
+            Ok(v) => {
+                if !v.is_empty() {
+                    let event = if from_min { "zpopmin" } else { "zpopmax" };
+                    notify_keyspace_event(NOTIFY_ZSET, event, &key);
+                }
+                Ok(resp_array(v))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -836,7 +828,7 @@ impl ZsetCommandCtx {
         let member = member.to_owned();
 
         client
-            .exec_in_txn(self.txn.clone(), |txn_rc| {
+            .exec_in_snapshot(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -853,10 +845,7 @@ impl ZsetCommandCtx {
 
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if key_is_expired(ttl) {
-                                drop(txn);
-                                self.clone()
-                                    .do_async_txnkv_zset_expire_if_needed(&key)
-                                    .await?;
+                                submit_expired_key(&key);
                                 return Ok(resp_nil());
                             }
 
@@ -910,6 +899,7 @@ impl ZsetCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -1017,7 +1007,10 @@ impl ZsetCommandCtx {
             .await;
 
         match resp {
-            Ok(new_score) => Ok(resp_bulk(new_score.to_string().as_bytes().to_vec())),
+            Ok(new_score) => {
+                notify_keyspace_event(NOTIFY_ZSET, "zincr", &key);
+                Ok(Frame::Double(new_score))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -1035,6 +1028,7 @@ impl ZsetCommandCtx {
         let rand_idx = gen_next_meta_index();
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -1124,7 +1118,12 @@ impl ZsetCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_int(v as i64)),
+            Ok(v) => {
+                if v > 0 {
+                    notify_keyspace_event(NOTIFY_ZSET, "zrem", &key);
+                }
+                Ok(resp_int(v as i64))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -1146,6 +1145,7 @@ impl ZsetCommandCtx {
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -1244,7 +1244,12 @@ impl ZsetCommandCtx {
             })
             .await;
         match resp {
-            Ok(v) => Ok(resp_int(v)),
+            Ok(v) => {
+                if v > 0 {
+                    notify_keyspace_event(NOTIFY_ZSET, "zremrangebyrank", &key);
+                }
+                Ok(resp_int(v))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -1262,6 +1267,7 @@ impl ZsetCommandCtx {
         let rand_idx = gen_next_meta_index();
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
+                let key = key.clone();
                 async move {
                     if self.txn.is_none() {
                         self.txn = Some(txn_rc.clone());
@@ -1356,7 +1362,12 @@ impl ZsetCommandCtx {
             .await;
 
         match resp {
-            Ok(v) => Ok(resp_int(v as i64)),
+            Ok(v) => {
+                if v > 0 {
+                    notify_keyspace_event(NOTIFY_ZSET, "zremrangebyscore", &key);
+                }
+                Ok(resp_int(v as i64))
+            }
             Err(e) => Ok(resp_err(e)),
         }
     }
@@ -1518,6 +1529,7 @@ impl ZsetCommandCtx {
                             REMOVED_EXPIRED_KEY_COUNTER
                                 .with_label_values(&["zset"])
                                 .inc();
+                            notify_keyspace_event(NOTIFY_EXPIRED, "expired", &key);
                             Ok(1)
                         }
                         None => Ok(0),
@@ -1528,3 +1540,25 @@ impl ZsetCommandCtx {
             .await
     }
 }
+
+/// Push a member of ZRANGE into the reply, its score follows the member in RESP2
+/// while RESP3 replies with the pair of them. Reverse ranges push the items
+/// backwards and reverse the whole reply once it is complete.
+fn push_range_member(
+    resp: &mut Vec<Frame>,
+    member: Value,
+    score: Option<f64>,
+    reverse: bool,
+    resp3: bool,
+) {
+    let items = match score {
+        Some(score) if resp3 => vec![resp_array(vec![resp_bulk(member), Frame::Double(score)])],
+        Some(score) => vec![resp_bulk(member), Frame::Double(score)],
+        None => vec![resp_bulk(member)],
+    };
+    if reverse {
+        resp.extend(items.into_iter().rev());
+    } else {
+        resp.extend(items);
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, RwLock};
+
+use bytes::Bytes;
+use slog::info;
+use tokio::sync::mpsc;
+
+use crate::cluster::Cluster;
+use crate::cmd::Command;
+use crate::config::LOGGER;
+use crate::tikv::errors::{
+    RTError, REDIS_TRACKING_BCAST_OPTIN_ERR, REDIS_TRACKING_CACHING_ERR,
+    REDIS_TRACKING_CACHING_NO_ERR, REDIS_TRACKING_CACHING_YES_ERR, REDIS_TRACKING_OPTIN_OPTOUT_ERR,
+    REDIS_TRACKING_PREFIX_ERR, REDIS_TRACKING_REDIRECT_ERR, REDIS_TRACKING_SWITCH_BCAST_ERR,
+};
+use crate::{config_cluster_tracking_invalidation_or_default, Frame};
+
+/// Channel of the invalidation messages redirected to the RESP2 clients
+pub const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+tokio::task_local! {
+    /// id of the client served by the current task, keys it modifies are not
+    /// invalidated for itself in the NOLOOP mode
+    pub static CLIENT_ID: u64;
+}
+
+/// Sent to a connection when the keys cached by itself, or by the clients which
+/// redirect to it, are modified.
+#[derive(Debug)]
+pub enum Invalidation {
+    Keys(Vec<String>),
+    // the client to redirect to is gone
+    RedirectBroken(u64),
+}
+
+impl Invalidation {
+    /// Returns the frame to write, RESP2 clients only receive the invalidations
+    /// redirected to them when they subscribe to `__redis__:invalidate`.
+    pub fn into_frame(self, resp3: bool, subscribed: bool) -> Option<Frame> {
+        let bulk = |s: &'static str| Frame::Bulk(Bytes::from_static(s.as_bytes()));
+        match self {
+            Invalidation::Keys(keys) => {
+                let keys = Frame::Array(
+                    keys.into_iter()
+                        .map(|key| Frame::Bulk(Bytes::from(key)))
+                        .collect(),
+                );
+                if resp3 {
+                    Some(Frame::Push(vec![bulk("invalidate"), keys]))
+                } else if subscribed {
+                    Some(Frame::Push(vec![
+                        bulk("message"),
+                        bulk(INVALIDATE_CHANNEL),
+                        keys,
+                    ]))
+                } else {
+                    None
+                }
+            }
+            Invalidation::RedirectBroken(id) if resp3 => Some(Frame::Push(vec![
+                bulk("tracking-redir-broken"),
+                Frame::Integer(id as i64),
+            ])),
+            Invalidation::RedirectBroken(_) => None,
+        }
+    }
+}
+
+/// Options of `CLIENT TRACKING ON`
+#[derive(Debug, Clone, Default)]
+pub struct TrackingOptions {
+    pub redirect: Option<u64>,
+    pub bcast: bool,
+    pub prefixes: Vec<String>,
+    pub optin: bool,
+    pub optout: bool,
+    pub noloop: bool,
+}
+
+struct TrackedClient {
+    tx: mpsc::UnboundedSender<Invalidation>,
+    // None if tracking is off
+    options: Option<TrackingOptions>,
+    // set by CLIENT CACHING for the next command
+    caching: Option<bool>,
+}
+
+#[derive(Default)]
+struct TrackingTable {
+    // every connection is registered, it may be the target of redirection
+    clients: HashMap<u64, TrackedClient>,
+    // clients which may have cached the key, tracked in the default mode
+    keys: HashMap<String, HashSet<u64>>,
+    bcast_clients: HashSet<u64>,
+}
+
+lazy_static! {
+    static ref TRACKING: Mutex<TrackingTable> = Mutex::new(TrackingTable::default());
+    // modified keys to be invalidated on the other instances
+    static ref PEER_TX: RwLock<Option<mpsc::UnboundedSender<String>>> = RwLock::new(None);
+}
+
+/// Start forwarding the modified keys to the other instances in the cluster, so
+/// the clients tracking them there are notified as well, if it is enabled.
+pub fn init_tracking(topo: Cluster) {
+    if !config_cluster_tracking_invalidation_or_default() {
+        return;
+    }
+    let (peer_tx, mut peer_rx) = mpsc::unbounded_channel::<String>();
+    *PEER_TX.write().unwrap() = Some(peer_tx);
+
+    tokio::spawn(async move {
+        while let Some(key) = peer_rx.recv().await {
+            // keys modified while the last request is in flight are sent together
+            let mut keys = vec![key];
+            while let Ok(key) = peer_rx.try_recv() {
+                keys.push(key);
+            }
+            topo.invalidate_on_peers(&keys).await;
+        }
+        info!(LOGGER, "tracking invalidation forwarder exit");
+    });
+}
+
+/// Register the connection of client `id`, the invalidation messages sent to it
+/// are received from the returned receiver.
+pub fn register_client(id: u64) -> mpsc::UnboundedReceiver<Invalidation> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    TRACKING.lock().unwrap().clients.insert(
+        id,
+        TrackedClient {
+            tx,
+            options: None,
+            caching: None,
+        },
+    );
+    rx
+}
+
+pub fn unregister_client(id: u64) {
+    let mut table = TRACKING.lock().unwrap();
+    table.clients.remove(&id);
+    table.forget(id);
+}
+
+pub fn enable_tracking(id: u64, options: TrackingOptions) -> Result<(), RTError> {
+    if !options.bcast && !options.prefixes.is_empty() {
+        return Err(REDIS_TRACKING_PREFIX_ERR);
+    }
+    if options.optin && options.optout {
+        return Err(REDIS_TRACKING_OPTIN_OPTOUT_ERR);
+    }
+    if options.bcast && (options.optin || options.optout) {
+        return Err(REDIS_TRACKING_BCAST_OPTIN_ERR);
+    }
+
+    let mut table = TRACKING.lock().unwrap();
+    if let Some(redirect) = options.redirect {
+        if !table.clients.contains_key(&redirect) {
+            return Err(REDIS_TRACKING_REDIRECT_ERR);
+        }
+    }
+    let client = match table.clients.get_mut(&id) {
+        Some(client) => client,
+        None => return Ok(()),
+    };
+    if let Some(current) = &client.options {
+        if current.bcast != options.bcast {
+            return Err(REDIS_TRACKING_SWITCH_BCAST_ERR);
+        }
+    }
+    let bcast = options.bcast;
+    client.options = Some(options);
+    client.caching = None;
+    if bcast {
+        table.bcast_clients.insert(id);
+    }
+    Ok(())
+}
+
+pub fn disable_tracking(id: u64) {
+    let mut table = TRACKING.lock().unwrap();
+    if let Some(client) = table.clients.get_mut(&id) {
+        client.options = None;
+        client.caching = None;
+    }
+    table.forget(id);
+}
+
+/// Handle `CLIENT CACHING YES|NO`, which applies to the next command.
+pub fn set_caching(id: u64, yes: bool) -> Result<(), RTError> {
+    let mut table = TRACKING.lock().unwrap();
+    let client = match table.clients.get_mut(&id) {
+        Some(client) => client,
+        None => return Ok(()),
+    };
+    match &client.options {
+        Some(options) if options.optin || options.optout => {
+            if yes && !options.optin {
+                return Err(REDIS_TRACKING_CACHING_YES_ERR);
+            }
+            if !yes && !options.optout {
+                return Err(REDIS_TRACKING_CACHING_NO_ERR);
+            }
+        }
+        _ => return Err(REDIS_TRACKING_CACHING_ERR),
+    }
+    client.caching = Some(yes);
+    Ok(())
+}
+
+/// Returns the client id invalidations are redirected to for `CLIENT GETREDIR`,
+/// 0 if they are not redirected and -1 if tracking is off.
+pub fn get_redirect(id: u64) -> i64 {
+    let table = TRACKING.lock().unwrap();
+    match table.clients.get(&id).and_then(|c| c.options.as_ref()) {
+        Some(options) => options.redirect.map_or(0, |redirect| redirect as i64),
+        None => -1,
+    }
+}
+
+/// Remember the keys read by `cmd` for client `id` if it tracks them. The flag
+/// of CLIENT CACHING is kept in MULTI and scripts until the command finishes.
+pub fn track_command(id: u64, cmd: &Command, keep_caching: bool) {
+    let mut table = TRACKING.lock().unwrap();
+    let client = match table.clients.get_mut(&id) {
+        Some(client) => client,
+        None => return,
+    };
+    let tracked = match &client.options {
+        Some(options) if options.bcast => false,
+        Some(options) if options.optin => client.caching == Some(true),
+        Some(options) if options.optout => client.caching != Some(false),
+        Some(_) => true,
+        None => false,
+    };
+    if !keep_caching && !matches!(cmd, Command::Multi(_)) && !is_client_caching(cmd) {
+        client.caching = None;
+    }
+    if tracked {
+        for key in cmd.read_keys() {
+            table.keys.entry(key).or_default().insert(id);
+        }
+    }
+}
+
+/// Invalidate the modified key for the clients of this and the other instances.
+pub fn invalidate_key(key: &str) {
+    let by = CLIENT_ID.try_with(|id| *id).ok();
+    invalidate_keys(&[key.to_owned()], by);
+    if let Some(peer_tx) = PEER_TX.read().unwrap().as_ref() {
+        let _ = peer_tx.send(key.to_owned());
+    }
+}
+
+/// Notify the clients of this instance tracking the `keys`, which are modified by
+/// client `by` or by the other instances.
+pub fn invalidate_keys(keys: &[String], by: Option<u64>) -> usize {
+    let mut table = TRACKING.lock().unwrap();
+    // keys to invalidate of each tracking client
+    let mut invalidated: HashMap<u64, Vec<String>> = HashMap::new();
+    for key in keys {
+        if let Some(ids) = table.keys.remove(key) {
+            for id in ids {
+                invalidated.entry(id).or_default().push(key.clone());
+            }
+        }
+        for id in &table.bcast_clients {
+            let matched = table.clients[id].options.as_ref().map_or(false, |options| {
+                options.prefixes.is_empty() || options.prefixes.iter().any(|p| key.starts_with(p))
+            });
+            if matched {
+                invalidated.entry(*id).or_default().push(key.clone());
+            }
+        }
+    }
+
+    let mut sent = 0;
+    for (id, keys) in invalidated {
+        if table.send(id, keys, by) {
+            sent += 1;
+        }
+    }
+    sent
+}
+
+fn is_client_caching(cmd: &Command) -> bool {
+    match cmd {
+        Command::Client(c) => c.subcommand().eq_ignore_ascii_case("CACHING"),
+        _ => false,
+    }
+}
+
+impl TrackingTable {
+    /// Send the invalidated keys of client `id` to the client or the one it
+    /// redirects to.
+    fn send(&self, id: u64, keys: Vec<String>, by: Option<u64>) -> bool {
+        let client = match self.clients.get(&id) {
+            Some(client) => client,
+            None => return false,
+        };
+        let options = match &client.options {
+            Some(options) => options,
+            None => return false,
+        };
+        if options.noloop && by == Some(id) {
+            return false;
+        }
+        let target = match options.redirect {
+            Some(redirect) => match self.clients.get(&redirect) {
+                Some(target) => target,
+                None => {
+                    let _ = client.tx.send(Invalidation::RedirectBroken(redirect));
+                    return false;
+                }
+            },
+            None => client,
+        };
+        target.tx.send(Invalidation::Keys(keys)).is_ok()
+    }
+
+    /// Drop the keys and prefixes tracked for client `id`.
+    fn forget(&mut self, id: u64) {
+        self.bcast_clients.remove(&id);
+        self.keys.retain(|_, ids| {
+            ids.remove(&id);
+            !ids.is_empty()
+        });
+    }
+}
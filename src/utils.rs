@@ -1,16 +1,15 @@
 use crate::frame::Frame;
 use hex::ToHex;
-use mlua::{Lua, Value as LuaValue};
+use mlua::{Lua, Result as LuaResult, Value as LuaValue};
 use sha1::{Digest, Sha1};
 use std::io;
 use std::{
     collections::HashSet,
-    convert::TryInto,
     time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::time::Duration;
 
-use crate::tikv::errors::{RTError, REDIS_LUA_PANIC};
+use crate::tikv::errors::RTError;
 use rustls::{
     internal::pemfile::{certs, rsa_private_keys},
     AllowAnyAuthenticatedClient, RootCertStore,
@@ -70,81 +69,184 @@ pub async fn sleep(ms: u32) {
     tokio::time::sleep(Duration::from_millis(ms as u64)).await;
 }
 
-pub fn lua_resp_to_redis_resp(resp: LuaValue) -> Frame {
+/// Format the lua number as redis does with `%.17g`
+pub fn format_lua_number(n: f64) -> String {
+    format_number_g(n, 17)
+}
+
+/// Format the number like `%.<precision>g` of C
+pub fn format_number_g(n: f64, precision: i32) -> String {
+    if n.is_nan() {
+        return "nan".to_owned();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "inf" } else { "-inf" }.to_owned();
+    }
+
+    let trim_zeros = |s: &str| -> String {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_owned()
+        } else {
+            s.to_owned()
+        }
+    };
+    // exponent of the number in scientific notation with `precision` significant digits
+    let sci = format!("{:.*e}", (precision - 1) as usize, n);
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    if (-4..precision).contains(&exp) {
+        trim_zeros(&format!("{:.*}", (precision - 1 - exp) as usize, n))
+    } else {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_zeros(mantissa), sign, exp.abs())
+    }
+}
+
+/// Convert the value returned by lua to the reply, booleans and nil are converted to
+/// RESP3 types if the script selected RESP3 by `redis.setresp(3)`
+pub fn lua_resp_to_redis_resp(resp: LuaValue, resp3: bool) -> Frame {
     match resp {
-        LuaValue::Nil => Frame::Null,
-        LuaValue::String(r) => resp_bulk(r.to_str().unwrap().as_bytes().to_vec()),
+        LuaValue::String(r) => resp_bulk(r.as_bytes().to_vec()),
         LuaValue::Integer(r) => resp_int(r),
-        LuaValue::Boolean(r) => {
-            if !r {
-                resp_nil()
-            } else {
-                resp_int(1)
-            }
-        }
         // just return integer part of the float in redis
-        //LuaValue::Number(r) => resp_bulk(r.to_string().as_bytes().to_vec()),
-        LuaValue::Number(r) => resp_int(r.trunc() as i64),
+        LuaValue::Number(r) => resp_int(r as i64),
+        LuaValue::Boolean(b) if resp3 => Frame::Boolean(b),
+        LuaValue::Boolean(true) => resp_int(1),
         LuaValue::Table(r) => {
             // handle error reply
-            if let Ok(err_msg) = r.raw_get::<&str, String>("err") {
-                return resp_err(RTError::Owned(err_msg));
+            if let Ok(LuaValue::String(err_msg)) = r.raw_get("err") {
+                return resp_err(RTError::Owned(
+                    String::from_utf8_lossy(err_msg.as_bytes()).to_string(),
+                ));
             }
 
             // handle status reply
-            if let Ok(status_msg) = r.raw_get::<&str, String>("ok") {
-                return resp_str(&status_msg);
+            if let Ok(LuaValue::String(status_msg)) = r.raw_get("ok") {
+                return resp_str(&String::from_utf8_lossy(status_msg.as_bytes()));
             }
 
-            // handle array reply
-            let len: i64 = r.len().unwrap();
-            let mut arr = Vec::with_capacity(len.try_into().unwrap());
-            for idx in 0..len {
-                // key is start from 1
-                let key = idx + 1;
-                let v: LuaValue = r.raw_get(key).unwrap();
-                arr.push(lua_resp_to_redis_resp(v));
+            // handle RESP3 replies, they are converted back to RESP2 for RESP2 clients
+            match r.raw_get("double") {
+                Ok(LuaValue::Number(d)) => return Frame::Double(d),
+                Ok(LuaValue::Integer(i)) => return Frame::Double(i as f64),
+                _ => {}
+            }
+            if let Ok(LuaValue::String(num)) = r.raw_get("big_number") {
+                return Frame::BigNumber(String::from_utf8_lossy(num.as_bytes()).to_string());
+            }
+            if let Ok(LuaValue::Table(map)) = r.raw_get("map") {
+                let pairs = map
+                    .pairs::<LuaValue, LuaValue>()
+                    .filter_map(|pair| pair.ok())
+                    .map(|(key, value)| {
+                        (
+                            lua_resp_to_redis_resp(key, resp3),
+                            lua_resp_to_redis_resp(value, resp3),
+                        )
+                    })
+                    .collect();
+                return Frame::Map(pairs);
+            }
+            if let Ok(LuaValue::Table(set)) = r.raw_get("set") {
+                let members = set
+                    .pairs::<LuaValue, LuaValue>()
+                    .filter_map(|pair| pair.ok())
+                    .map(|(member, _)| lua_resp_to_redis_resp(member, resp3))
+                    .collect();
+                return Frame::Set(members);
+            }
+
+            // handle array reply, which stops at the first nil
+            let mut arr = vec![];
+            for v in r.sequence_values::<LuaValue>() {
+                match v {
+                    Ok(v) => arr.push(lua_resp_to_redis_resp(v, resp3)),
+                    Err(_) => break,
+                }
             }
             resp_array(arr)
         }
         LuaValue::Error(r) => resp_err(r.into()),
-        _ => resp_err(REDIS_LUA_PANIC),
+        // nil, false and the types can not be converted are nil reply
+        _ => resp_nil(),
     }
 }
 
-pub fn redis_resp_to_lua_resp(resp: Frame, lua: &Lua) -> LuaValue {
-    match resp {
+/// Convert the reply to lua value, the RESP3 types are converted to RESP2 first unless
+/// the script selected RESP3 by `redis.setresp(3)`
+pub fn redis_resp_to_lua_resp(resp: Frame, lua: &Lua, resp3: bool) -> LuaResult<LuaValue> {
+    let value = match resp {
         Frame::Simple(v) => {
-            let table = lua.create_table().unwrap();
-            table.raw_set("ok", v).unwrap();
+            let table = lua.create_table()?;
+            table.raw_set("ok", v)?;
             LuaValue::Table(table)
         }
-        Frame::Bulk(v) => {
-            let str = String::from_utf8_lossy(&v).to_string();
-            LuaValue::String(lua.create_string(&str).unwrap())
-        }
+        Frame::Bulk(v) => LuaValue::String(lua.create_string(&v)?),
         Frame::ErrorOwned(e) => {
-            let table = lua.create_table().unwrap();
-            table.raw_set("err", e).unwrap();
+            let table = lua.create_table()?;
+            table.raw_set("err", e)?;
             LuaValue::Table(table)
         }
         Frame::ErrorString(e) => {
-            let table = lua.create_table().unwrap();
-            table.raw_set("err", e).unwrap();
+            let table = lua.create_table()?;
+            table.raw_set("err", e)?;
             LuaValue::Table(table)
         }
         Frame::Integer(i) => LuaValue::Integer(i),
+        Frame::Null if resp3 => LuaValue::Nil,
+        // nil reply is converted to false, also inside array
         Frame::Null => LuaValue::Boolean(false),
-        Frame::Array(arr) => {
-            let table = lua.create_table().unwrap();
-            for (idx, value) in arr.iter().enumerate() {
-                let v = redis_resp_to_lua_resp(value.clone(), lua);
-                // nil will be convert to boolean false
-                table.raw_set(idx + 1, v).unwrap();
+        Frame::Array(arr) | Frame::Push(arr) => {
+            let table = lua.create_table()?;
+            for (idx, value) in arr.into_iter().enumerate() {
+                table.raw_set(idx + 1, redis_resp_to_lua_resp(value, lua, resp3)?)?;
             }
             LuaValue::Table(table)
         }
-    }
+        frame if !resp3 => return redis_resp_to_lua_resp(frame.into_resp2(), lua, resp3),
+        Frame::Map(pairs) => {
+            let map = lua.create_table()?;
+            for (key, value) in pairs {
+                map.raw_set(
+                    redis_resp_to_lua_resp(key, lua, resp3)?,
+                    redis_resp_to_lua_resp(value, lua, resp3)?,
+                )?;
+            }
+            let table = lua.create_table()?;
+            table.raw_set("map", map)?;
+            LuaValue::Table(table)
+        }
+        Frame::Set(members) => {
+            let set = lua.create_table()?;
+            for member in members {
+                set.raw_set(redis_resp_to_lua_resp(member, lua, resp3)?, true)?;
+            }
+            let table = lua.create_table()?;
+            table.raw_set("set", set)?;
+            LuaValue::Table(table)
+        }
+        Frame::Double(d) => {
+            let table = lua.create_table()?;
+            table.raw_set("double", d)?;
+            LuaValue::Table(table)
+        }
+        Frame::Boolean(b) => LuaValue::Boolean(b),
+        Frame::BigNumber(num) => {
+            let table = lua.create_table()?;
+            table.raw_set("big_number", num)?;
+            LuaValue::Table(table)
+        }
+        Frame::Verbatim(format, data) => {
+            let verbatim = lua.create_table()?;
+            verbatim.raw_set("format", format)?;
+            verbatim.raw_set("string", lua.create_string(&data)?)?;
+            let table = lua.create_table()?;
+            table.raw_set("verbatim_string", verbatim)?;
+            LuaValue::Table(table)
+        }
+        Frame::Attribute(_) => LuaValue::Nil,
+    };
+    Ok(value)
 }
 
 pub fn key_is_expired(ttl: u64) -> bool {
@@ -231,6 +333,106 @@ pub fn count_unique_keys<T: std::hash::Hash + std::cmp::Eq>(keys: &[T]) -> usize
     keys.iter().collect::<HashSet<&T>>().len()
 }
 
+/// Match `string` against a redis glob-style `pattern`, supporting `*`, `?`, `[...]` and `\\`.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // position after the last `*` and the string position it is trying to consume to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            while p < pattern.len() && pattern[p] == b'*' {
+                p += 1;
+            }
+            if p == pattern.len() {
+                return true;
+            }
+            backtrack = Some((p, s));
+            continue;
+        }
+        if p < pattern.len() {
+            if let Some(next) = glob_match_one(pattern, p, string[s]) {
+                p = next;
+                s += 1;
+                continue;
+            }
+        }
+        // let the last `*` consume one more byte and retry
+        match backtrack {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                backtrack = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+    while p < pattern.len() && pattern[p] == b'*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+// match a single byte with the pattern token at `p`, return the position of the next token
+fn glob_match_one(pattern: &[u8], mut p: usize, c: u8) -> Option<usize> {
+    match pattern[p] {
+        b'?' => Some(p + 1),
+        b'[' => {
+            p += 1;
+            let not = pattern.get(p) == Some(&b'^');
+            if not {
+                p += 1;
+            }
+            let mut matched = false;
+            // an unterminated class ends with the pattern
+            while p < pattern.len() && pattern[p] != b']' {
+                if pattern[p] == b'\\' && p + 1 < pattern.len() {
+                    p += 1;
+                    matched |= pattern[p] == c;
+                } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' {
+                    let (start, end) = if pattern[p] <= pattern[p + 2] {
+                        (pattern[p], pattern[p + 2])
+                    } else {
+                        (pattern[p + 2], pattern[p])
+                    };
+                    matched |= start <= c && c <= end;
+                    p += 2;
+                } else {
+                    matched |= pattern[p] == c;
+                }
+                p += 1;
+            }
+            if matched != not {
+                Some((p + 1).min(pattern.len()))
+            } else {
+                None
+            }
+        }
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then(|| p + 2),
+        literal => (literal == c).then(|| p + 1),
+    }
+}
+
+/// Return the literal prefix of a glob-style `pattern`, all matched strings start with it.
+pub fn glob_literal_prefix(pattern: &[u8]) -> Vec<u8> {
+    let mut prefix = vec![];
+    let mut p = 0;
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' | b'?' | b'[' => break,
+            b'\\' if p + 1 < pattern.len() => {
+                prefix.push(pattern[p + 1]);
+                p += 2;
+            }
+            c => {
+                prefix.push(c);
+                p += 1;
+            }
+        }
+    }
+    prefix
+}
+
 pub fn timestamp_local(io: &mut dyn io::Write) -> io::Result<()> {
     let now = chrono::Local::now().format(TIMESTAMP_FORMAT);
     write!(io, "{}", now)
@@ -1,5 +1,8 @@
+import socket
+import time
 import unittest
 
+import redis
 from redis import exceptions
 
 from rediswrap import RedisWrapper
@@ -58,6 +61,133 @@ class GenericTest(unittest.TestCase):
         err = cm.exception
         self.assertEqual(str(err), 'DISCARD without MULTI')
 
+    def test_multi_execabort(self):
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k1, 'value1')
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('unknowncmd', self.k1)
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('exec')
+        err = cm.exception
+        self.assertEqual(str(err), 'EXECABORT Transaction discarded because of previous errors.')
+        self.assertIsNone(self.r.get(self.k1))
+
+    def test_multi_runtime_error(self):
+        self.r.set(self.k1, 'value1')
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('incr', self.k1)
+        self.r.execute_command('set', self.k2, 'value2')
+        res = self.r.execute_command('exec')
+        self.assertEqual(len(res), 2)
+        self.assertIsInstance(res[0], Exception)
+        self.assertEqual(res[1], 'OK')
+        self.assertEqual(self.r.get(self.k1), 'value1')
+        self.assertEqual(self.r.get(self.k2), 'value2')
+
+    def test_watch(self):
+        other = RedisWrapper.clone()
+        self.r.set(self.k1, 'v1')
+        self.assertTrue(self.r.execute_command('watch', self.k1, self.k2))
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k1, 'v2')
+        self.assertListEqual(self.r.execute_command('exec'), ['OK'])
+        self.assertEqual(self.r.get(self.k1), 'v2')
+        # modified by another client after watch
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        other.set(self.k1, 'v3')
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k1, 'v4')
+        self.assertIsNone(self.r.execute_command('exec'))
+        self.assertEqual(self.r.get(self.k1), 'v3')
+        # modified and set back to the same value
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        other.set(self.k1, 'v4')
+        other.set(self.k1, 'v3')
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k1, 'v5')
+        self.assertIsNone(self.r.execute_command('exec'))
+        self.assertEqual(self.r.get(self.k1), 'v3')
+        # collections are watched with their fields
+        self.r.hset(self.k2, 'f1', 'v1')
+        self.assertTrue(self.r.execute_command('watch', self.k2))
+        other.hset(self.k2, 'f1', 'v2')
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('hset', self.k2, 'f2', 'v2')
+        self.assertIsNone(self.r.execute_command('exec'))
+        self.assertEqual(self.r.hlen(self.k2), 1)
+        # keys not watched are read at the time of exec
+        self.r.set(self.k1, '1')
+        self.assertTrue(self.r.execute_command('watch', self.k2))
+        other.set(self.k1, '10')
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('incr', self.k1)
+        self.assertListEqual(self.r.execute_command('exec'), [11])
+        # exec and unwatch forget the watched keys
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertTrue(self.r.execute_command('unwatch'))
+        other.set(self.k1, 'v5')
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('get', self.k1)
+        self.assertListEqual(self.r.execute_command('exec'), ['v5'])
+
+    def test_watch_error(self):
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'watch')
+        self.assertTrue(self.r.execute_command('multi'))
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('watch', self.k1)
+        self.assertEqual(str(cm.exception), 'WATCH inside MULTI is not allowed')
+        self.assertEqual(self.r.execute_command('discard'), 'OK')
+
+    def test_txn(self):
+        other = RedisWrapper.clone()
+        self.r.set(self.k1, '1')
+        self.assertTrue(self.r.execute_command('txn', 'begin'))
+        self.assertEqual(self.r.get(self.k1), '1')
+        self.assertEqual(self.r.incr(self.k1), 2)
+        self.r.set(self.k2, 'v2')
+        self.assertEqual(self.r.get(self.k2), 'v2')
+        # not visible to others before commit
+        self.assertEqual(other.get(self.k1), '1')
+        self.assertIsNone(other.get(self.k2))
+        self.assertTrue(self.r.execute_command('txn', 'commit'))
+        self.assertEqual(other.get(self.k1), '2')
+        self.assertEqual(other.get(self.k2), 'v2')
+
+    def test_txn_ping_quit(self):
+        client = RedisWrapper.clone()
+        self.assertTrue(client.execute_command('txn', 'begin'))
+        client.set(self.k1, 'v1')
+        self.assertTrue(client.ping())
+        # the txn is rolled back as the connection is closed by QUIT
+        self.assertTrue(client.execute_command('quit'))
+        time.sleep(0.1)
+        self.assertIsNone(self.r.get(self.k1))
+
+    def test_txn_rollback(self):
+        self.r.set(self.k1, 'v1')
+        self.assertTrue(self.r.execute_command('txn', 'begin', 'pessimistic'))
+        self.r.set(self.k1, 'v2')
+        self.r.set(self.k2, 'v2')
+        self.assertTrue(self.r.execute_command('txn', 'rollback'))
+        self.assertEqual(self.r.get(self.k1), 'v1')
+        self.assertIsNone(self.r.get(self.k2))
+
+    def test_txn_error(self):
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'txn', 'begin', 'xx')
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('txn', 'commit')
+        self.assertEqual(str(cm.exception), 'TXN without BEGIN')
+        self.assertTrue(self.r.execute_command('txn', 'begin'))
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('txn', 'begin')
+        self.assertEqual(str(cm.exception), 'TXN BEGIN calls can not be nested')
+        self.assertTrue(self.r.execute_command('txn', 'rollback'))
+        self.assertTrue(self.r.execute_command('multi'))
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('txn', 'begin')
+        self.assertEqual(str(cm.exception), 'TXN inside MULTI is not allowed')
+        self.assertEqual(self.r.execute_command('discard'), 'OK')
+
     def test_client(self):
         client1 = self.r
         client1_id = client1.execute_command("client id")
@@ -74,6 +204,132 @@ class GenericTest(unittest.TestCase):
         self.assertEqual(client2.execute_command("client kill id", client1_id), 1)
         self.assertEqual(client2.execute_command("client list id", client1_id), "")
 
+    def test_hello(self):
+        kwargs = self.r.connection_pool.connection_kwargs
+        conn = socket.create_connection((kwargs['host'], kwargs['port']))
+
+        def call(*args):
+            cmd = '*%d\r\n' % len(args) + ''.join('$%d\r\n%s\r\n' % (len(a), a) for a in args)
+            conn.sendall(cmd.encode())
+            time.sleep(0.1)
+            return conn.recv(65536).decode()
+
+        auth = ['auth', 'default', kwargs['password']] if kwargs.get('password') else []
+        self.assertTrue(call('hello', '4', *auth).startswith('-NOPROTO'))
+        self.assertTrue(call('hello', '3', *auth).startswith('%7\r\n$6\r\nserver\r\n'))
+        self.r.hset(self.k1, 'f', 'v')
+        self.assertEqual(call('hgetall', self.k1), '%1\r\n$1\r\nf\r\n$1\r\nv\r\n')
+        self.r.zadd(self.k2, {'m': 1.5})
+        self.assertEqual(call('zscore', self.k2, 'm'), ',1.5\r\n')
+        self.assertEqual(call('zrange', self.k2, '0', '-1', 'withscores'), '*1\r\n*2\r\n$1\r\nm\r\n,1.5\r\n')
+        self.assertEqual(call('get', self.k1 + 'nx'), '_\r\n')
+        # back to RESP2
+        call('hello', '2')
+        self.assertEqual(call('zscore', self.k2, 'm'), '$3\r\n1.5\r\n')
+        self.assertEqual(call('zrange', self.k2, '0', '-1', 'withscores'), '*2\r\n$1\r\nm\r\n$3\r\n1.5\r\n')
+        conn.close()
+
+    def test_inline_command(self):
+        kwargs = self.r.connection_pool.connection_kwargs
+        conn = socket.create_connection((kwargs['host'], kwargs['port']))
+
+        def call(line):
+            conn.sendall(line.encode())
+            time.sleep(0.1)
+            return conn.recv(65536).decode()
+
+        if kwargs.get('password'):
+            self.assertEqual(call('auth %s\r\n' % kwargs['password']), '+OK\r\n')
+        self.assertEqual(call('PING\r\n'), '+PONG\r\n')
+        # blank lines are skipped and a single \n ends the line as well
+        self.assertEqual(call('\r\nping\n'), '+PONG\r\n')
+        self.assertEqual(call('set %s "a b\\x41\\n"\r\n' % self.k1), '+OK\r\n')
+        self.assertEqual(self.r.get(self.k1), 'a bA\n')
+        self.assertEqual(call("set %s 'it\\'s'\r\n" % self.k1), '+OK\r\n')
+        self.assertEqual(self.r.get(self.k1), "it's")
+        # inline and RESP requests can be mixed on the connection
+        self.assertEqual(call('get %s\r\n*1\r\n$4\r\nping\r\n' % self.k1), "$4\r\nit's\r\n+PONG\r\n")
+        # the connection is closed after the error reply on unbalanced quotes
+        self.assertEqual(call('get "%s\r\n' % self.k1), '-ERR Protocol error: unbalanced quotes in request\r\n')
+        self.assertEqual(conn.recv(65536), b'')
+        conn.close()
+
+    def test_pipeline(self):
+        p = self.r.pipeline(transaction=False)
+        for i in range(50):
+            p.set(self.k1, i)
+            p.incr(self.k1)
+            p.get(self.k1)
+            p.rpush(self.k2, i)
+        # a command not on keys waits for the pipelined commands before it
+        p.ping()
+        p.llen(self.k2)
+        replies = p.execute()
+        for i in range(50):
+            # replies are in the order of the requests, and the commands on the
+            # same key are applied in order
+            self.assertEqual(replies[i * 4:i * 4 + 4], [True, i + 1, str(i + 1), i + 1])
+        self.assertEqual(replies[-2:], [True, 50])
+        self.assertEqual(self.r.lrange(self.k2, 0, -1), [str(i) for i in range(50)])
+
+    def test_client_tracking(self):
+        redir = redis.Connection(**self.r.connection_pool.connection_kwargs)
+        redir.send_command('client', 'id')
+        redir_id = redir.read_response()
+        redir.send_command('subscribe', '__redis__:invalidate')
+        self.assertEqual(redir.read_response(), ['subscribe', '__redis__:invalidate', 1])
+
+        client = RedisWrapper.clone()
+        self.assertEqual(client.execute_command('client getredir'), -1)
+        self.assertTrue(client.execute_command('client tracking on redirect', redir_id))
+        self.assertEqual(client.execute_command('client getredir'), redir_id)
+        self.assertRaises(exceptions.ResponseError, client.execute_command, 'client caching yes')
+        client.get(self.k1)
+        self.r.set(self.k1, 'v1')
+        self.assertEqual(redir.read_response(), ['message', '__redis__:invalidate', [self.k1]])
+
+        self.assertTrue(client.execute_command('client tracking off'))
+        self.assertEqual(client.execute_command('client getredir'), -1)
+        redir.disconnect()
+
+    def test_client_staleness(self):
+        client = RedisWrapper.clone()
+        self.assertEqual(client.execute_command("client staleness"), 0)
+        self.assertTrue(client.execute_command("client staleness", 500))
+        self.assertEqual(client.execute_command("client staleness"), 500)
+        self.assertRaises(exceptions.ResponseError, client.execute_command, "client staleness", "-1")
+        # the timestamp taken by the first stale read is reused within the staleness window
+        self.r.set(self.k1, 'v1')
+        time.sleep(0.6)
+        self.assertEqual(client.get(self.k1), 'v1')
+        self.r.set(self.k1, 'v2')
+        self.assertEqual(client.get(self.k1), 'v1')
+        # and refreshed after it
+        time.sleep(0.6)
+        self.assertEqual(client.get(self.k1), 'v2')
+        # the staleness is kept per connection
+        self.assertEqual(self.r.execute_command("client staleness"), 0)
+        self.assertEqual(self.r.get(self.k1), 'v2')
+
+    def test_snapshot(self):
+        client = RedisWrapper.clone()
+        self.r.set(self.k1, 'v1')
+        time.sleep(0.1)
+        ts = int(time.time() * 1000)
+        time.sleep(0.1)
+        self.r.set(self.k1, 'v2')
+        self.assertTrue(client.execute_command('snapshot at', ts))
+        self.assertEqual(client.get(self.k1), 'v1')
+        # tso is accepted as well
+        self.assertTrue(client.execute_command('snapshot at', ts << 18))
+        self.assertEqual(client.get(self.k1), 'v1')
+        self.assertTrue(client.execute_command('snapshot reset'))
+        self.assertEqual(client.get(self.k1), 'v2')
+        self.assertRaises(exceptions.ResponseError, client.execute_command, 'snapshot at', 1)
+        self.assertRaises(exceptions.ResponseError, client.execute_command, 'snapshot at', ts + 3600000)
+        self.assertRaises(exceptions.ResponseError, client.execute_command, 'snapshot at', 'now')
+        self.assertRaises(exceptions.ResponseError, client.execute_command, 'snapshot foo')
+
     def test_scan(self):
         # add some keys for scan test
         for i in range(0, 10):
@@ -94,9 +350,24 @@ class GenericTest(unittest.TestCase):
         part2_scan = self.r.execute_command('xscan', part1_scan[0], 'count', 10)
         self.assertEqual(part2_scan[0], 'list:9')
         self.assertEqual(len(part2_scan[1]), 10)
-        match_scan = self.r.execute_command('xscan', '', 'count', 100, 'match', '^hash:*')
+        match_scan = self.r.execute_command('xscan', '', 'count', 100, 'match', 'hash:*')
         self.assertEqual(match_scan[0], '')
         self.assertEqual(len(match_scan[1]), 10)
+        match_scan = self.r.execute_command('xscan', '', 'count', 100, 'match', '*:[1-3]')
+        self.assertEqual(len(match_scan[1]), 15)
+        match_scan = self.r.execute_command('xscan', '', 'count', 100, 'match', '?et:1')
+        self.assertListEqual(match_scan[1], ['set:1'])
+        type_scan = self.r.execute_command('xscan', '', 'count', 100, 'type', 'zset')
+        self.assertListEqual(sorted(type_scan[1]), ['zset:' + str(i) for i in range(0, 10)])
+        type_scan = self.r.execute_command('xscan', '', 'count', 100, 'match', 's*', 'type', 'set')
+        self.assertEqual(len(type_scan[1]), 10)
+        # malformed pattern is matched literally instead of failing
+        self.assertListEqual(self.r.execute_command('xscan', '', 'match', 'hash:[')[1], [])
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'xscan', '', 'count', 0)
+
+        self.assertListEqual(sorted(self.r.keys('list:*')), ['list:' + str(i) for i in range(0, 10)])
+        self.assertListEqual(self.r.keys('string:\\*'), [])
+        self.assertEqual(len(self.r.keys('*')), 50)
 
         # clean up the keys
         keys = []
@@ -108,6 +379,161 @@ class GenericTest(unittest.TestCase):
             keys.append('zset:' + str(i))
         self.r.delete(*keys)
 
+    def test_keys_literal_prefix(self):
+        # the key equal to the literal prefix of the pattern is matched as well
+        self.r.set('user', 'v')
+        self.r.set('user:1', 'v')
+        self.r.set('users', 'v')
+        self.assertListEqual(self.r.keys('user'), ['user'])
+        self.assertListEqual(sorted(self.r.keys('user*')), ['user', 'user:1', 'users'])
+        self.assertListEqual(self.r.execute_command('xscan', '', 'match', 'user')[1], ['user'])
+        self.r.delete('user', 'user:1', 'users')
+
+    def test_read_expired(self):
+        self.r.set(self.k1, 'v', px=100)
+        self.r.hset(self.k2, 'f', 'v')
+        self.r.pexpire(self.k2, 100)
+        time.sleep(0.2)
+        # the expired keys are still stored before gc runs
+        self.assertEqual(self.r.execute_command('debug', 'rawkeys', self.k1), 1)
+        self.assertGreater(self.r.execute_command('debug', 'rawkeys', self.k2), 1)
+        # reads never delete the key themselves, but still see it as absent
+        self.assertIsNone(self.r.get(self.k1))
+        self.assertEqual(self.r.ttl(self.k1), -2)
+        self.assertEqual(self.r.pttl(self.k2), -2)
+        self.assertEqual(self.r.exists(self.k1, self.k2), 0)
+        self.assertEqual(self.r.hgetall(self.k2), {})
+        self.assertListEqual(self.r.keys(self.k1), [])
+        # expired keys are cleaned in background
+        time.sleep(1)
+        self.assertEqual(self.r.execute_command('debug', 'rawkeys', self.k1), 0)
+        self.assertEqual(self.r.execute_command('debug', 'rawkeys', self.k2), 0)
+        self.assertEqual(self.r.delete(self.k1, self.k2), 0)
+
+    def test_rename(self):
+        self.assertTrue(self.r.set(self.k1, 'value1'))
+        self.assertTrue(self.r.rename(self.k1, self.k2))
+        self.assertIsNone(self.r.get(self.k1))
+        self.assertEqual(self.r.get(self.k2), 'value1')
+        # rename overwrites the destination of another type
+        self.r.hset(self.k1, 'f1', 'v1')
+        self.r.hset(self.k1, 'f2', 'v2')
+        self.assertTrue(self.r.rename(self.k1, self.k2))
+        self.assertEqual(self.r.type(self.k2), 'hash')
+        self.assertDictEqual(self.r.hgetall(self.k2), {'f1': 'v1', 'f2': 'v2'})
+        self.assertEqual(self.r.hlen(self.k2), 2)
+        self.assertEqual(self.r.exists(self.k1), 0)
+        with self.assertRaises(Exception) as cm:
+            self.r.rename(self.k1, self.k2)
+        self.assertEqual(str(cm.exception), 'no such key')
+
+    def test_rename_collections(self):
+        self.r.rpush(self.k1, 'a', 'b', 'c')
+        self.assertTrue(self.r.rename(self.k1, self.k2))
+        self.assertListEqual(self.r.lrange(self.k2, 0, -1), ['a', 'b', 'c'])
+        self.r.sadd(self.k2 + 's', 'a', 'b')
+        self.assertTrue(self.r.rename(self.k2 + 's', self.k1))
+        self.assertSetEqual(self.r.smembers(self.k1), {'a', 'b'})
+        self.assertEqual(self.r.scard(self.k1), 2)
+        self.r.delete(self.k1)
+        self.r.zadd(self.k1, {'a': 1, 'b': 2})
+        self.assertTrue(self.r.rename(self.k1, self.k2))
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1, withscores=True), [('a', 1), ('b', 2)])
+        self.assertEqual(self.r.zcard(self.k2), 2)
+
+    def test_rename_large_collections(self):
+        # collections larger than a batch are copied before the transaction of the command
+        fields = {'f' + str(i): 'v' + str(i) for i in range(2500)}
+        self.r.hset(self.k1, mapping=fields)
+        self.r.set(self.k2, 'value2')
+        self.assertTrue(self.r.rename(self.k1, self.k2))
+        self.assertDictEqual(self.r.hgetall(self.k2), fields)
+        self.assertEqual(self.r.hlen(self.k2), 2500)
+        self.assertEqual(self.r.exists(self.k1), 0)
+        members = {'m' + str(i): i for i in range(2500)}
+        self.r.zadd(self.k1, members)
+        self.assertEqual(self.r.execute_command('copy', self.k1, self.k2, 'replace'), 1)
+        self.assertEqual(self.r.zcard(self.k2), 2500)
+        self.assertListEqual(self.r.zrange(self.k2, 0, 2, withscores=True), [('m0', 0), ('m1', 1), ('m2', 2)])
+        self.assertEqual(self.r.zscore(self.k2, 'm2499'), 2499)
+        self.assertEqual(self.r.zcard(self.k1), 2500)
+
+    def test_renamenx(self):
+        self.r.set(self.k1, 'value1')
+        self.r.set(self.k2, 'value2')
+        self.assertFalse(self.r.renamenx(self.k1, self.k2))
+        self.assertEqual(self.r.get(self.k2), 'value2')
+        self.r.delete(self.k2)
+        self.assertTrue(self.r.renamenx(self.k1, self.k2))
+        self.assertEqual(self.r.get(self.k2), 'value1')
+
+    def test_copy(self):
+        self.r.set(self.k1, 'value1', ex=100)
+        self.assertEqual(self.r.execute_command('copy', self.k1, self.k2), 1)
+        self.assertEqual(self.r.get(self.k1), 'value1')
+        self.assertEqual(self.r.get(self.k2), 'value1')
+        self.assertGreater(self.r.ttl(self.k2), 0)
+        self.r.hset(self.k1 + 'h', 'f1', 'v1')
+        self.assertEqual(self.r.execute_command('copy', self.k1 + 'h', self.k2), 0)
+        self.assertEqual(self.r.execute_command('copy', self.k1 + 'h', self.k2, 'replace'), 1)
+        self.assertDictEqual(self.r.hgetall(self.k2), {'f1': 'v1'})
+        self.assertDictEqual(self.r.hgetall(self.k1 + 'h'), {'f1': 'v1'})
+        self.r.delete(self.k1 + 'h')
+
+    def test_dump_restore(self):
+        raw = redis.StrictRedis(**dict(self.r.connection_pool.connection_kwargs, decode_responses=False))
+        self.assertIsNone(raw.dump(self.k1))
+
+        self.r.set(self.k1, 'value1')
+        payload = raw.dump(self.k1)
+        self.assertTrue(raw.restore(self.k2, 0, payload))
+        self.assertEqual(self.r.get(self.k2), 'value1')
+        self.assertEqual(self.r.ttl(self.k2), -1)
+        self.assertRaises(exceptions.ResponseError, raw.restore, self.k2, 0, payload)
+        self.assertTrue(raw.restore(self.k2, 100000, payload, replace=True))
+        self.assertGreater(self.r.ttl(self.k2), 0)
+
+        # payload generated by redis for integer value 10
+        self.assertTrue(raw.restore(self.k2, 0, b'\x00\xc0\n\t\x00\xbem\x06\x89Z(\x00\n', replace=True))
+        self.assertEqual(self.r.get(self.k2), '10')
+
+        self.assertRaises(exceptions.ResponseError, raw.restore, self.k2, 0, payload[:-1] + b'x', replace=True)
+        self.assertRaises(exceptions.ResponseError, raw.restore, self.k2, -1, payload, replace=True)
+
+    def test_dump_restore_collections(self):
+        raw = redis.StrictRedis(**dict(self.r.connection_pool.connection_kwargs, decode_responses=False))
+        k = self.k1 + random_string(5)
+        self.r.rpush(k, 'a', 'b', 'c')
+        self.assertTrue(raw.restore(self.k2, 0, raw.dump(k), replace=True))
+        self.assertListEqual(self.r.lrange(self.k2, 0, -1), ['a', 'b', 'c'])
+        self.r.delete(k)
+
+        self.r.hset(k, mapping={'f1': 'v1', 'f2': 'v2'})
+        self.assertTrue(raw.restore(self.k2, 0, raw.dump(k), replace=True))
+        self.assertDictEqual(self.r.hgetall(self.k2), {'f1': 'v1', 'f2': 'v2'})
+        self.r.delete(k)
+
+        self.r.sadd(k, 'm1', 'm2')
+        self.assertTrue(raw.restore(self.k2, 0, raw.dump(k), replace=True))
+        self.assertSetEqual(self.r.smembers(self.k2), {'m1', 'm2'})
+        self.r.delete(k)
+
+        self.r.zadd(k, {'m1': 1.5, 'm2': 2})
+        self.assertTrue(raw.restore(self.k2, 0, raw.dump(k), replace=True))
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1, withscores=True), [('m1', 1.5), ('m2', 2)])
+        self.assertEqual(self.r.zcard(self.k2), 2)
+        self.r.delete(k)
+
+    def test_restore_options(self):
+        raw = redis.StrictRedis(**dict(self.r.connection_pool.connection_kwargs, decode_responses=False))
+        self.r.set(self.k1, 'value1')
+        payload = raw.dump(self.k1)
+        # absolute timestamp in the past, key is not created
+        self.assertTrue(raw.restore(self.k2, 1000, payload, absttl=True))
+        self.assertIsNone(self.r.get(self.k2))
+        self.assertTrue(raw.restore(self.k2, 0, payload, idletime=10))
+        self.assertRaises(exceptions.ResponseError, raw.restore, self.k2, 0, payload, replace=True, frequency=256)
+
     def tearDown(self):
         pass
 
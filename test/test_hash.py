@@ -48,6 +48,16 @@ class HashTest(unittest.TestCase):
         self.assertEqual(self.r.hsetnx(self.k1, self.f1, self.v2), 0)
         self.assertNotEqual(self.v2, self.r.hget(self.k1, self.f1))
 
+    def test_hcas(self):
+        self.assertEqual(self.r.execute_command('hcas', self.k1, self.f1, self.v1, self.v2), 0)
+        self.assertEqual(self.r.hset(self.k1, self.f1, self.v1), 1)
+        self.assertEqual(self.r.execute_command('hcas', self.k1, self.f1, self.v2, self.v3), 0)
+        self.assertEqual(self.r.hget(self.k1, self.f1), self.v1)
+        self.assertEqual(self.r.execute_command('hcas', self.k1, self.f1, self.v1, self.v2), 1)
+        self.assertEqual(self.r.hget(self.k1, self.f1), self.v2)
+        self.assertEqual(self.r.execute_command('hcas', self.k1, self.f2, self.v1, self.v2), 0)
+        self.assertEqual(self.r.hlen(self.k1), 1)
+
     def test_type(self):
         self.assertEqual(self.r.type(self.k1), CmdType.NULL.value)
         self.assertEqual(self.r.hset(self.k1, self.f1, self.v1), 1)
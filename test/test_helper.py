@@ -6,6 +6,7 @@ from test_hash import HashTest
 from test_invalid import InvalidTest
 from test_list import ListTest
 from test_lua import LuaTest
+from test_pubsub import PubsubTest
 from test_set import SetTest
 from test_string import StringTest
 from test_zset import ZsetTest
@@ -20,6 +21,7 @@ if __name__ == '__main__':
     suite.addTest(unittest.TestLoader().loadTestsFromTestCase(SetTest))
     suite.addTest(unittest.TestLoader().loadTestsFromTestCase(ZsetTest))
     suite.addTest(unittest.TestLoader().loadTestsFromTestCase(LuaTest))
+    suite.addTest(unittest.TestLoader().loadTestsFromTestCase(PubsubTest))
 
     runner = unittest.TextTestRunner(verbosity=2)
     errors = runner.run(suite).errors
@@ -1,5 +1,6 @@
 import operator
 import random
+import threading
 import time
 import unittest
 import redis
@@ -60,6 +61,169 @@ class LuaTest(unittest.TestCase):
         with self.assertRaisesRegex(Exception, "No matching script"):
             self.r.execute_command('evalsha', 'not-exist-sha', 0)
 
+    def test_script_exists_flush(self):
+        sha = self.r.execute_command('script', 'load', 'return 2')
+        self.assertListEqual(self.r.execute_command('script', 'exists', sha, sha.upper(), 'not-exist-sha'), [1, 1, 0])
+        # scripts are persisted in tikv and shared by all connections
+        self.assertEqual(RedisWrapper.clone().execute_command('evalsha', sha, 0), 2)
+        self.assertEqual(self.r.execute_command('script', 'flush'), 'OK')
+        self.assertListEqual(self.r.execute_command('script', 'exists', sha), [0])
+        with self.assertRaisesRegex(Exception, "No matching script"):
+            self.r.execute_command('evalsha', sha, 0)
+
+    def test_function(self):
+        lib = """#!lua name=__lualib__
+        redis.register_function('lib_set', function(keys, args) return redis.call('set', keys[1], args[1]) end)
+        redis.register_function{function_name='lib_get', callback=function(keys, args) return redis.call('get', keys[1]) end, flags={'no-writes'}}
+        redis.register_function{function_name='lib_bad', callback=function(keys, args) return redis.call('set', keys[1], args[1]) end, flags={'no-writes'}}
+        """
+        self.r.execute_command('function', 'flush')
+        self.assertEqual(self.r.execute_command('function', 'load', lib), '__lualib__')
+        with self.assertRaisesRegex(Exception, "already exists"):
+            self.r.execute_command('function', 'load', lib)
+        self.assertEqual(self.r.execute_command('function', 'load', 'replace', lib), '__lualib__')
+
+        self.assertEqual(self.r.execute_command('fcall', 'lib_set', 1, self.k1, self.v1), 'OK')
+        # functions are persisted in tikv and shared by all connections
+        self.assertEqual(RedisWrapper.clone().execute_command('fcall_ro', 'lib_get', 1, self.k1), self.v1)
+        with self.assertRaisesRegex(Exception, "write flag"):
+            self.r.execute_command('fcall_ro', 'lib_set', 1, self.k1, self.v2)
+        with self.assertRaisesRegex(Exception, "Write commands are not allowed"):
+            self.r.execute_command('fcall', 'lib_bad', 1, self.k1, self.v2)
+        self.assertEqual(self.r.execute_command('get', self.k1), self.v1)
+
+        libs = self.r.execute_command('function', 'list', 'libraryname', '__lua*')
+        self.assertEqual(len(libs), 1)
+        self.assertEqual(libs[0][1], '__lualib__')
+        self.assertListEqual([f[1] for f in libs[0][5]], ['lib_bad', 'lib_get', 'lib_set'])
+
+        self.assertEqual(self.r.execute_command('function', 'delete', '__lualib__'), 'OK')
+        with self.assertRaisesRegex(Exception, "Function not found"):
+            self.r.execute_command('fcall', 'lib_get', 1, self.k1)
+        with self.assertRaisesRegex(Exception, "Library not found"):
+            self.r.execute_command('function', 'delete', '__lualib__')
+
+    def test_function_in_multi(self):
+        self.r.execute_command('function', 'flush')
+        lib = """#!lua name=__lualib__
+        redis.register_function('lib_incr', function(keys, args) return redis.call('incr', keys[1]) end)
+        """
+        self.r.execute_command('function', 'load', lib)
+        self.r.set(self.k1, 1)
+        self.assertTrue(self.r.execute_command('multi'))
+        self.assertEqual(self.r.execute_command('fcall', 'lib_incr', 1, self.k1), 'QUEUED')
+        self.assertEqual(self.r.execute_command('eval', "return redis.call('get', KEYS[1])", 1, self.k1), 'QUEUED')
+        self.assertEqual(self.r.execute_command('fcall', 'lib_incr', 1, self.k1), 'QUEUED')
+        # the functions and scripts see the writes before them in the txn
+        self.assertListEqual(self.r.execute_command('exec'), [2, '2', 3])
+        self.assertEqual(self.r.get(self.k1), '3')
+        # discarded with the txn
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('fcall', 'lib_incr', 1, self.k1)
+        self.assertEqual(self.r.execute_command('discard'), 'OK')
+        self.assertEqual(self.r.get(self.k1), '3')
+
+    def test_function_dump_restore(self):
+        self.r.execute_command('function', 'flush')
+        self.r.execute_command('function', 'load', "#!lua name=__lualib__\nredis.register_function('lib_one', function() return 1 end)")
+        raw = redis.StrictRedis(**dict(self.r.connection_pool.connection_kwargs, decode_responses=False))
+        payload = raw.execute_command('function', 'dump')
+        with self.assertRaisesRegex(Exception, "already exists"):
+            self.r.execute_command('function', 'restore', payload)
+        self.assertEqual(self.r.execute_command('function', 'restore', payload, 'replace'), 'OK')
+        self.assertEqual(self.r.execute_command('function', 'flush'), 'OK')
+        self.assertListEqual(self.r.execute_command('function', 'list'), [])
+        self.assertEqual(self.r.execute_command('function', 'restore', payload), 'OK')
+        self.assertEqual(self.r.execute_command('fcall', 'lib_one', 0), 1)
+
+    def test_libraries(self):
+        self.assertEqual(self.run_script("return cjson.encode({1, 'a', {b=true}})"), '[1,"a",{"b":true}]')
+        self.assertEqual(self.run_script("return cjson.encode({})"), '{}')
+        self.assertEqual(self.run_script("local t = cjson.decode(ARGV[1]) return {t.a[2], tostring(t.b == cjson.null)}", 0, '{"a":[1,"x"],"b":null}'), ['x', 'true'])
+        with self.assertRaisesRegex(Exception, "Expected value"):
+            self.run_script("return cjson.decode('{\"a\":}')")
+        self.assertListEqual(self.run_script("return {cmsgpack.unpack(cmsgpack.pack(1, 'abc', {1, 2}))}"), [1, 'abc', [1, 2]])
+        with self.assertRaisesRegex(Exception, "too many nested"):
+            self.run_script("return cmsgpack.unpack(string.rep('\\145', 2000) .. '\\1')")
+        self.assertEqual(self.run_script("return bit.tohex(bit.band(0xff, 0x0f) + bit.lshift(1, 8))"), '0000010f')
+        self.assertEqual(self.run_script("return bit.bxor(5, 3)"), 6)
+        self.assertListEqual(self.run_script("return {struct.unpack('>HI2', struct.pack('>HI2', 258, 7))}"), [258, 7, 5])
+        self.assertEqual(self.run_script("return struct.size('>iB')"), 5)
+
+    def test_redis_helpers(self):
+        self.assertIsNone(self.run_script("redis.log(redis.LOG_WARNING, 'from lua', 1)"))
+        with self.assertRaisesRegex(Exception, "Invalid debug level"):
+            self.run_script("redis.log(10, 'from lua')")
+        self.assertIsNone(self.run_script("redis.setresp(3) redis.set_repl(redis.REPL_ALL)"))
+        with self.assertRaisesRegex(Exception, "RESP version must be 2 or 3"):
+            self.run_script("redis.setresp(4)")
+        self.assertEqual(self.run_script("return type(redis.REDIS_VERSION)"), 'string')
+
+    def test_sandbox(self):
+        with self.assertRaisesRegex(Exception, "create global variable 'foo'"):
+            self.run_script("foo = 1")
+        with self.assertRaisesRegex(Exception, "nonexistent global variable 'os'"):
+            self.run_script("return os.time()")
+        self.assertEqual(self.run_script("return rawget(_G, 'io') == nil and rawget(_G, 'loadfile') == nil"), 1)
+        self.assertEqual(self.run_script("local foo = 1 return foo"), 1)
+
+    def test_script_kill(self):
+        with self.assertRaisesRegex(Exception, "NOTBUSY"):
+            self.r.execute_command('script', 'kill')
+
+        errors = []
+
+        def run_busy_script():
+            try:
+                RedisWrapper.clone().execute_command('eval', 'while true do end', 1, self.k2)
+            except Exception as e:
+                errors.append(str(e))
+
+        t = threading.Thread(target=run_busy_script)
+        t.start()
+        # other clients get BUSY on the keys of the script once it runs longer than lua_time_limit
+        time.sleep(5.5)
+        with self.assertRaisesRegex(Exception, "BUSY"):
+            self.r.execute_command('get', self.k2)
+        with self.assertRaisesRegex(Exception, "BUSY"):
+            self.r.execute_command('ping')
+        self.assertIsNone(self.r.execute_command('get', self.k1))
+        self.assertEqual(self.r.execute_command('script', 'kill'), 'OK')
+        t.join()
+        self.assertEqual(len(errors), 1)
+        self.assertTrue('killed by user' in errors[0])
+        self.assertIsNone(self.r.execute_command('get', self.k1))
+
+    def test_script_debug(self):
+        conn = redis.Connection(**self.r.connection_pool.connection_kwargs)
+        conn.send_command('script', 'debug', 'yes')
+        self.assertEqual(conn.read_response(), 'OK')
+
+        script = "local a = 1\nredis.call('set', KEYS[1], a)\nreturn redis.call('get', KEYS[1])"
+        conn.send_command('eval', script, 1, self.k1)
+        self.assertEqual(conn.read_response(), ['* Stopped at 1, stop reason = step over', '-> 1   local a = 1'])
+
+        conn.send_command('break', 3)
+        self.assertEqual(len(conn.read_response()), 2)
+        conn.send_command('step')
+        self.assertEqual(conn.read_response()[0], '* Stopped at 2, stop reason = step over')
+        conn.send_command('print')
+        self.assertEqual(conn.read_response(), ['<value> a = 1'])
+        conn.send_command('eval', 'a + 1')
+        self.assertEqual(conn.read_response(), ['<retval> 2'])
+        conn.send_command('step')
+        logs = conn.read_response()
+        self.assertEqual(logs[:3], ['<redis> set __lua1__ 1', '<reply> +OK', '* Stopped at 3, stop reason = break point'])
+        conn.send_command('redis', 'get', self.k1)
+        self.assertEqual(conn.read_response(), ['<redis> get __lua1__', '<reply> "1"'])
+        conn.send_command('continue')
+        self.assertEqual(conn.read_response(), ['<endsession>'])
+        self.assertEqual(conn.read_response(), '1')
+        conn.disconnect()
+
+        # changes of the debugging session are rolled back
+        self.assertIsNone(self.r.execute_command('get', self.k1))
+
     def test_integer_conversion(self):
         script = '''
         redis.call('set', KEYS[1], 0)
@@ -92,6 +256,34 @@ class LuaTest(unittest.TestCase):
         '''
         self.assertEqual(self.run_script(script, 1, self.k1), ['table', 'ERR value is not an integer or out of range'])
 
+    def test_call_error_aborts(self):
+        script = '''
+        redis.call('set', KEYS[1], 'a')
+        redis.call('incr', KEYS[1])
+        return 'unreachable'
+        '''
+        with self.assertRaisesRegex(Exception, 'not an integer'):
+            self.run_script(script, 1, self.k1)
+        # the script is rolled back
+        self.assertIsNone(self.r.execute_command('get', self.k1))
+        with self.assertRaisesRegex(Exception, 'must be strings or integers'):
+            self.run_script("return redis.call('set', KEYS[1], true)", 1, self.k1)
+
+    def test_float_conversion(self):
+        self.assertEqual(self.run_script("redis.call('set', KEYS[1], 3.5) return redis.call('get', KEYS[1])", 1, self.k1), '3.5')
+        self.assertEqual(self.run_script("redis.call('set', KEYS[1], 1e100) return redis.call('get', KEYS[1])", 1, self.k1), '1e+100')
+        self.assertEqual(self.run_script("return 3.99"), 3)
+        self.assertEqual(self.run_script("return -3.99"), -3)
+
+    def test_nested_table_conversion(self):
+        # array reply stops at the first nil
+        self.assertListEqual(self.run_script("return {1, {2, {'a', true}}, false, nil, 4}"), [1, [2, ['a', 1]], None])
+        script = '''
+        redis.call('del', KEYS[1])
+        return redis.call('mget', KEYS[1], KEYS[1])
+        '''
+        self.assertListEqual(self.run_script(script, 1, self.k1), [None, None])
+
     def test_nil_conversion(self):
         script = '''
         local foo = redis.call('get', KEYS[1])
@@ -973,6 +1165,7 @@ class LuaTest(unittest.TestCase):
     @classmethod
     def tearDownClass(cls):
         cls.r.execute_command('script', 'flush')
+        cls.r.execute_command('function', 'flush')
         cls.r.execute_command('del', cls.k1)
         cls.r.execute_command('del', cls.k2)
         print('test scripts flush')
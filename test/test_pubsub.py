@@ -0,0 +1,147 @@
+import time
+import unittest
+import redis
+
+from rediswrap import RedisWrapper
+
+
+class PubsubTest(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        cls.r = RedisWrapper.get_instance()
+
+        cls.c1 = '__pubsub1__'
+        cls.c2 = '__pubsub2__'
+
+        cls.p1 = '__pubsub*'
+
+        cls.m1 = 'message1'
+        cls.m2 = 'message2'
+
+    def setUp(self):
+        self.p = self.r.pubsub(ignore_subscribe_messages=True)
+
+    def get_message(self):
+        for _ in range(50):
+            msg = self.p.get_message()
+            if msg is not None:
+                return msg
+            time.sleep(0.02)
+        return None
+
+    def test_publish(self):
+        self.assertEqual(self.r.execute_command('publish', self.c1, self.m1), 0)
+        self.p.subscribe(self.c1)
+        time.sleep(0.1)
+        self.assertGreaterEqual(self.r.execute_command('publish', self.c1, self.m1), 1)
+        msg = self.get_message()
+        self.assertEqual(msg['channel'], self.c1)
+        self.assertEqual(msg['data'], self.m1)
+
+    def test_cluster_publish(self):
+        # CLUSTER PUBLISH only delivers to the subscribers of the instance itself
+        self.p.subscribe(self.c2)
+        time.sleep(0.1)
+        self.assertEqual(self.r.execute_command('cluster', 'publish', self.c2, self.m2), 1)
+        msg = self.get_message()
+        self.assertEqual(msg['data'], self.m2)
+        self.assertEqual(self.r.execute_command('cluster', 'publish', self.c1, self.m2), 0)
+
+    def test_psubscribe(self):
+        self.p.psubscribe(self.p1)
+        time.sleep(0.1)
+        self.assertGreaterEqual(self.r.execute_command('publish', self.c1, self.m1), 1)
+        msg = self.get_message()
+        self.assertEqual(msg['type'], 'pmessage')
+        self.assertEqual(msg['pattern'], self.p1)
+        self.assertEqual(msg['channel'], self.c1)
+        self.assertEqual(msg['data'], self.m1)
+
+        self.p.punsubscribe(self.p1)
+        time.sleep(0.1)
+        self.assertEqual(self.r.execute_command('publish', self.c1, self.m1), 0)
+
+    def test_pubsub_introspection(self):
+        self.p.subscribe(self.c1, self.c2)
+        self.p.psubscribe(self.p1)
+        time.sleep(0.1)
+        self.assertTrue({self.c1, self.c2}.issubset(self.r.execute_command('pubsub', 'channels')))
+        self.assertEqual(self.r.execute_command('pubsub', 'channels', '__pubsub1*'), [self.c1])
+        self.assertEqual(self.r.execute_command('pubsub', 'numsub', self.c1, '__nosub__'),
+                         [self.c1, 1, '__nosub__', 0])
+        self.assertGreaterEqual(self.r.execute_command('pubsub', 'numpat'), 1)
+
+    def test_subscribed_state(self):
+        conn = redis.Connection(**self.r.connection_pool.connection_kwargs)
+        conn.send_command('subscribe', self.c1)
+        self.assertEqual(conn.read_response(), ['subscribe', self.c1, 1])
+        conn.send_command('psubscribe', self.p1)
+        self.assertEqual(conn.read_response(), ['psubscribe', self.p1, 2])
+        conn.send_command('ping')
+        self.assertEqual(conn.read_response(), ['pong', ''])
+        conn.send_command('ping', 'hello')
+        self.assertEqual(conn.read_response(), ['pong', 'hello'])
+        conn.send_command('get', self.c1)
+        self.assertRaises(redis.ResponseError, conn.read_response)
+
+        conn.send_command('reset')
+        self.assertEqual(conn.read_response(), 'RESET')
+        self.assertEqual(self.r.execute_command('publish', self.c1, self.m1), 0)
+        conn.send_command('ping')
+        self.assertEqual(conn.read_response(), 'PONG')
+
+        # the client leaves the subscribed state without subscriptions
+        conn.send_command('subscribe', self.c2)
+        self.assertEqual(conn.read_response(), ['subscribe', self.c2, 1])
+        conn.send_command('unsubscribe')
+        self.assertEqual(conn.read_response(), ['unsubscribe', self.c2, 0])
+        conn.send_command('ping')
+        self.assertEqual(conn.read_response(), 'PONG')
+        conn.disconnect()
+
+    def test_sharded_pubsub(self):
+        conn = redis.Connection(**self.r.connection_pool.connection_kwargs)
+        conn.send_command('ssubscribe', '{shard}1', '{shard}2')
+        self.assertEqual(conn.read_response(), ['ssubscribe', '{shard}1', 1])
+        self.assertEqual(conn.read_response(), ['ssubscribe', '{shard}2', 2])
+        conn.send_command('ssubscribe', self.c1, self.c2)
+        self.assertRaises(redis.ResponseError, conn.read_response)
+
+        self.assertEqual(self.r.execute_command('spublish', '{shard}1', self.m1), 1)
+        self.assertEqual(conn.read_response(), ['smessage', '{shard}1', self.m1])
+        # shard channels are not matched by patterns or seen as channels
+        self.assertEqual(self.r.execute_command('publish', '{shard}1', self.m1), 0)
+        self.assertEqual(self.r.execute_command('pubsub', 'channels', '{shard}*'), [])
+        self.assertEqual(sorted(self.r.execute_command('pubsub', 'shardchannels', '{shard}*')),
+                         ['{shard}1', '{shard}2'])
+        self.assertEqual(self.r.execute_command('pubsub', 'shardnumsub', '{shard}1', '{shard}3'),
+                         ['{shard}1', 1, '{shard}3', 0])
+
+        conn.send_command('sunsubscribe')
+        self.assertEqual(sorted(conn.read_response()[1] for _ in range(2)), ['{shard}1', '{shard}2'])
+        conn.send_command('ping')
+        self.assertEqual(conn.read_response(), 'PONG')
+        self.assertEqual(self.r.execute_command('spublish', '{shard}1', self.m1), 0)
+        conn.disconnect()
+
+    def test_keyspace_notification(self):
+        k = '__pubsub_key__'
+        self.p.psubscribe('__keyspace@0__:' + k, '__keyevent@0__:del')
+        time.sleep(0.1)
+        self.r.set(k, self.m1)
+        msg = self.get_message()
+        if msg is None:
+            self.skipTest('notify_keyspace_events is not enabled')
+        self.assertEqual(msg['channel'], '__keyspace@0__:' + k)
+        self.assertEqual(msg['data'], 'set')
+
+        self.r.delete(k)
+        events = {(m['channel'], m['data']) for m in (self.get_message(), self.get_message()) if m}
+        self.assertEqual(events, {('__keyspace@0__:' + k, 'del'), ('__keyevent@0__:del', k)})
+
+    def tearDown(self):
+        self.p.close()
+
+    @classmethod
+    def tearDownClass(cls):
+        pass
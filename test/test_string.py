@@ -72,6 +72,31 @@ class StringTest(unittest.TestCase):
         self.assertEqual(self.r.setnx(self.k1, self.v2), 0)
         self.assertEqual(self.r.get(self.k1), self.v1)
 
+    def test_cas(self):
+        self.assertEqual(self.r.execute_command('cas', self.k1, self.v1, self.v2), 0)
+        self.assertIsNone(self.r.get(self.k1))
+        self.assertTrue(self.r.set(self.k1, self.v1))
+        self.assertEqual(self.r.execute_command('cas', self.k1, self.v2, self.v2), 0)
+        self.assertEqual(self.r.get(self.k1), self.v1)
+        self.assertEqual(self.r.execute_command('cas', self.k1, self.v1, self.v2, 'ex', 5), 1)
+        self.assertEqual(self.r.get(self.k1), self.v2)
+        ttl = self.r.ttl(self.k1)
+        self.assertLessEqual(ttl, 5)
+        self.assertGreater(ttl, 0)
+        # the expire time must be positive and must not overflow
+        for flag, v in [('ex', 0), ('px', -1), ('ex', 2 ** 62)]:
+            with self.assertRaisesRegex(Exception, "invalid expire time in 'cas' command"):
+                self.r.execute_command('cas', self.k1, self.v2, self.v1, flag, v)
+        self.assertEqual(self.r.get(self.k1), self.v2)
+
+    def test_cad(self):
+        self.assertTrue(self.r.set(self.k1, self.v1))
+        self.assertEqual(self.r.execute_command('cad', self.k1, self.v2), 0)
+        self.assertEqual(self.r.get(self.k1), self.v1)
+        self.assertEqual(self.r.execute_command('cad', self.k1, self.v1), 1)
+        self.assertIsNone(self.r.get(self.k1))
+        self.assertEqual(self.r.execute_command('cad', self.k1, self.v1), 0)
+
     def test_set_expire(self):
         self.assertTrue(self.r.set(self.k2, self.v2, px=5000))
         v2 = self.r.get(self.k2)